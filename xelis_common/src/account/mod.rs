@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
 
-use crate::serializer::{Serializer, ReaderError, Reader, Writer};
+use crate::{
+    serializer::{Serializer, ReaderError, Reader, Writer},
+    transaction::UnlockCondition
+};
 
 #[derive(Clone, Copy, Serialize, Deserialize)]
 pub struct VersionedBalance {
@@ -116,4 +119,45 @@ impl Serializer for VersionedNonce {
             previous_topoheight
         })
     }
-}
\ No newline at end of file
+}
+
+// amount received through transfers locked with the same unlock condition
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LockedBalance {
+    unlock: UnlockCondition,
+    amount: u64
+}
+
+impl LockedBalance {
+    pub fn new(unlock: UnlockCondition, amount: u64) -> Self {
+        Self {
+            unlock,
+            amount
+        }
+    }
+
+    pub fn get_unlock(&self) -> &UnlockCondition {
+        &self.unlock
+    }
+
+    pub fn get_amount(&self) -> u64 {
+        self.amount
+    }
+}
+
+impl Serializer for LockedBalance {
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let unlock = UnlockCondition::read(reader)?;
+        let amount = reader.read_u64()?;
+
+        Ok(Self {
+            unlock,
+            amount
+        })
+    }
+
+    fn write(&self, writer: &mut Writer) {
+        self.unlock.write(writer);
+        writer.write_u64(&self.amount);
+    }
+}
@@ -2,7 +2,7 @@ use std::{borrow::Cow, collections::{HashSet, HashMap}, net::SocketAddr};
 
 use serde::{Deserialize, Serialize};
 
-use crate::{crypto::{hash::Hash, address::Address}, account::{VersionedBalance, VersionedNonce}, network::Network, block::Difficulty, transaction::Transaction};
+use crate::{crypto::{hash::Hash, address::Address, merkle::StateProof}, account::{VersionedBalance, VersionedNonce, LockedBalance}, network::Network, block::Difficulty, transaction::Transaction};
 
 use super::DataHash;
 
@@ -64,7 +64,9 @@ pub struct GetBlockTemplateParams<'a> {
 pub struct GetBlockTemplateResult {
     pub template: String, // template is BlockMiner in hex format
     pub height: u64, // block height
-    pub difficulty: Difficulty // difficulty required for valid block
+    pub difficulty: Difficulty, // difficulty required for valid block
+    #[serde(default)]
+    pub version: u8 // block version, used to select the PoW algorithm
 }
 
 #[derive(Serialize, Deserialize)]
@@ -117,6 +119,14 @@ pub struct GetLastBalanceResult {
     pub topoheight: u64
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct GetLockedBalanceResult {
+    pub topoheight: u64,
+    // total amount that can't be spent yet, it is already included in the balance
+    pub locked: u64,
+    pub entries: Vec<LockedBalance>
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct GetInfoResult {
     pub height: u64,
@@ -137,10 +147,40 @@ pub struct GetInfoResult {
     pub block_reward: u64,
     // count how many transactions are present in mempool
     pub mempool_size: usize,
+    // total size in bytes of the transactions in mempool
+    #[serde(default)]
+    pub mempool_bytes: usize,
+    // lowest fee per byte paid by a transaction in mempool
+    #[serde(default)]
+    pub mempool_min_fee_rate: Option<f64>,
+    // minimum fee increase in percent to replace a pending transaction
+    #[serde(default = "default_rbf_min_fee_bump")]
+    pub rbf_min_fee_bump: u64,
     // software version on which the daemon is running
     pub version: String,
     // Network state (mainnet, testnet, devnet)
-    pub network: Network
+    pub network: Network,
+    // Block version required for the next block
+    #[serde(default)]
+    pub block_version: u8,
+    // Network upgrades scheduled, including the already activated ones
+    #[serde(default)]
+    pub hard_forks: Vec<HardForkInfo>
+}
+
+fn default_rbf_min_fee_bump() -> u64 {
+    crate::config::DEFAULT_RBF_MIN_FEE_BUMP_PERCENT
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct HardForkInfo {
+    pub height: u64,
+    pub version: u8,
+    pub tx_version: u8,
+    pub features: Vec<String>,
+    pub changelog: String,
+    // is it already active for the next block
+    pub activated: bool
 }
 
 #[derive(Serialize, Deserialize)]
@@ -292,6 +332,33 @@ pub struct AccountHistoryEntry {
     pub block_timestamp: u128
 }
 
+// Direction of a transaction for an account in the history index
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountHistoryDirection {
+    // account is the sender of the transaction
+    Outgoing,
+    // account receives funds from the transaction
+    Incoming
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetAccountTransactionsParams {
+    pub address: Address,
+    pub minimum_topoheight: Option<u64>,
+    pub maximum_topoheight: Option<u64>,
+    pub skip: Option<usize>,
+    pub maximum: Option<usize>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AccountTransactionEntry {
+    // topoheight at which the transaction was executed
+    pub topoheight: u64,
+    pub hash: Hash,
+    pub direction: AccountHistoryDirection
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct GetAccountAssetsParams {
     pub address: Address
@@ -302,6 +369,40 @@ pub struct GetAssetParams {
     pub asset: Hash
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct GetAssetSupplyParams {
+    pub asset: Hash,
+    // top topoheight by default
+    #[serde(default)]
+    pub topoheight: Option<u64>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetAssetSupplyResult {
+    pub topoheight: u64,
+    // atomic units in circulation
+    pub supply: u64,
+    pub max_supply: Option<u64>,
+    // key allowed to mint more supply
+    pub mint_authority: Option<Address>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetMultisigParams<'a> {
+    pub address: Cow<'a, Address>,
+    // top topoheight by default
+    #[serde(default)]
+    pub topoheight: Option<u64>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetMultisigResult {
+    pub topoheight: u64,
+    // participants in the order of their signature index
+    pub participants: Vec<Address>,
+    pub threshold: u8
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct GetAssetsParams {
     pub skip: Option<usize>,
@@ -325,7 +426,7 @@ pub struct IsTxExecutedInBlockParams<'a> {
 }
 
 // Struct to define dev fee threshold
-#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
 pub struct DevFeeThreshold {
     // block height to start dev fee
     pub height: u64,
@@ -340,6 +441,65 @@ pub struct SizeOnDiskResult {
     pub size_formatted: String
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct GetStateRootParams {
+    // top topoheight by default
+    #[serde(default)]
+    pub topoheight: Option<u64>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetStateRootResult {
+    pub topoheight: u64,
+    // root of the state tree over all balances and nonces
+    pub root: Hash
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetBalanceProofParams<'a> {
+    pub address: Cow<'a, Address>,
+    pub asset: Cow<'a, Hash>,
+    // top topoheight by default
+    #[serde(default)]
+    pub topoheight: Option<u64>,
+    // prove against the state root committed in this block header instead of a topoheight
+    #[serde(default)]
+    pub block_hash: Option<Cow<'a, Hash>>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetBalanceProofResult {
+    // topoheight of the state root
+    pub topoheight: u64,
+    pub root: Hash,
+    // topoheight at which this version of the balance was set
+    pub version_topoheight: u64,
+    pub balance: VersionedBalance,
+    pub proof: StateProof
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetNonceProofParams<'a> {
+    pub address: Cow<'a, Address>,
+    // top topoheight by default
+    #[serde(default)]
+    pub topoheight: Option<u64>,
+    // prove against the state root committed in this block header instead of a topoheight
+    #[serde(default)]
+    pub block_hash: Option<Cow<'a, Hash>>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetNonceProofResult {
+    // topoheight of the state root
+    pub topoheight: u64,
+    pub root: Hash,
+    // topoheight at which this version of the nonce was set
+    pub version_topoheight: u64,
+    pub nonce: VersionedNonce,
+    pub proof: StateProof
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum NotifyEvent {
     // When a new block is accepted by chain
@@ -354,6 +514,9 @@ pub enum NotifyEvent {
     // When a new transaction is added in mempool
     // it contains Transaction struct as value
     TransactionAddedInMempool,
+    // When a transaction in mempool has been replaced by another one with the same nonce and higher fees
+    // it contains TransactionReplacedEvent struct as value
+    TransactionReplaced,
     // When a transaction has been included in a valid block & executed on chain
     // it contains TransactionExecutedEvent struct as value
     TransactionExecuted,
@@ -361,7 +524,7 @@ pub enum NotifyEvent {
     // TODO: Smart Contracts
     TransactionSCResult,
     // When a new asset has been registered
-    // TODO: Smart Contracts
+    // it contains AssetWithData struct as value
     NewAsset,
     // When a new peer has connected to us
     // It contains PeerEntry struct as value
@@ -403,6 +566,34 @@ pub struct TransactionExecutedEvent<'a> {
     pub topoheight: u64,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct FeeRateEstimate {
+    // TX should be included within this count of blocks
+    pub blocks: u64,
+    // fee in atomic units to pay per byte of the TX
+    pub fee_rate: f64
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EstimateFeeResult {
+    // topoheight at which the estimation was made
+    pub topoheight: u64,
+    // sorted by blocks in ascending order
+    pub estimates: Vec<FeeRateEstimate>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TransactionReplacedEvent<'a> {
+    // hash of the TX deleted from mempool
+    pub replaced_tx_hash: Cow<'a, Hash>,
+    // hash of the TX replacing it
+    pub tx_hash: Cow<'a, Hash>,
+    pub owner: Cow<'a, Address>,
+    pub nonce: u64,
+    pub replaced_fee: u64,
+    pub fee: u64
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct PeerPeerListUpdatedEvent {
     // Peer ID of the peer that sent us the new peer list
@@ -10,7 +10,8 @@ use super::{DataHash, DataElement, DataValue, Query};
 #[derive(Serialize, Deserialize)]
 pub enum FeeBuilder {
     Multiplier(f64), // calculate tx fees based on its size and multiply by this value
-    Value(u64) // set a direct value of how much fees you want to pay
+    Value(u64), // set a direct value of how much fees you want to pay
+    Target(u64) // pay the fees estimated by the daemon to be included within this count of blocks
 }
 
 #[derive(Serialize, Deserialize)]
@@ -22,7 +23,10 @@ pub struct BuildTransactionParams {
     pub broadcast: bool,
     // Returns the TX in HEX format also
     #[serde(default = "default_false_value")]
-    pub tx_as_hex: bool
+    pub tx_as_hex: bool,
+    // TX can't be included in a block above this topoheight
+    #[serde(default)]
+    pub valid_until_topoheight: Option<u64>
 }
 
 // :(
@@ -1,6 +1,69 @@
 use std::hash::{Hash as StdHash, Hasher};
 
-use crate::{serializer::{Serializer, Writer, Reader, ReaderError}, crypto::hash::Hash};
+use crate::{serializer::{Serializer, Writer, Reader, ReaderError}, crypto::{hash::Hash, key::PublicKey}};
+
+// Details of an asset registered through a CreateAsset transaction
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AssetMetadata {
+    name: String,
+    ticker: String,
+    // Maximum atomic units that can be in circulation
+    max_supply: u64,
+    // Key allowed to mint new supply, if none the whole supply was created at registration
+    mint_authority: Option<PublicKey>
+}
+
+impl AssetMetadata {
+    pub fn new(name: String, ticker: String, max_supply: u64, mint_authority: Option<PublicKey>) -> Self {
+        Self {
+            name,
+            ticker,
+            max_supply,
+            mint_authority
+        }
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_ticker(&self) -> &String {
+        &self.ticker
+    }
+
+    pub fn get_max_supply(&self) -> u64 {
+        self.max_supply
+    }
+
+    pub fn get_mint_authority(&self) -> Option<&PublicKey> {
+        self.mint_authority.as_ref()
+    }
+}
+
+impl Serializer for AssetMetadata {
+    fn write(&self, writer: &mut Writer) {
+        writer.write_string(&self.name);
+        writer.write_string(&self.ticker);
+        writer.write_u64(&self.max_supply);
+        writer.write_bool(self.mint_authority.is_some());
+        if let Some(key) = &self.mint_authority {
+            key.write(writer);
+        }
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let name = reader.read_string()?;
+        let ticker = reader.read_string()?;
+        let max_supply = reader.read_u64()?;
+        let mint_authority = if reader.read_bool()? {
+            Some(PublicKey::read(reader)?)
+        } else {
+            None
+        };
+
+        Ok(Self::new(name, ticker, max_supply, mint_authority))
+    }
+}
 
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
 pub struct AssetData {
@@ -8,13 +71,25 @@ pub struct AssetData {
     topoheight: u64,
     // How many atomic units is needed for a full coin
     decimals: u8,
+    // Native and genesis assets don't have any metadata
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metadata: Option<AssetMetadata>
 }
 
 impl AssetData {
     pub fn new(topoheight: u64, decimals: u8) -> Self {
         Self {
             topoheight,
-            decimals
+            decimals,
+            metadata: None
+        }
+    }
+
+    pub fn with_metadata(topoheight: u64, decimals: u8, metadata: AssetMetadata) -> Self {
+        Self {
+            topoheight,
+            decimals,
+            metadata: Some(metadata)
         }
     }
 
@@ -25,18 +100,36 @@ impl AssetData {
     pub fn get_decimals(&self) -> u8 {
         self.decimals
     }
+
+    pub fn get_metadata(&self) -> Option<&AssetMetadata> {
+        self.metadata.as_ref()
+    }
 }
 
 impl Serializer for AssetData {
     fn write(&self, writer: &mut Writer) {
         writer.write_u64(&self.topoheight);
         writer.write_u8(self.decimals);
+        writer.write_bool(self.metadata.is_some());
+        if let Some(metadata) = &self.metadata {
+            metadata.write(writer);
+        }
     }
 
     fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
-        Ok(
-            Self::new(reader.read_u64()?, reader.read_u8()?)
-        )
+        let topoheight = reader.read_u64()?;
+        let decimals = reader.read_u8()?;
+        let metadata = if reader.read_bool()? {
+            Some(AssetMetadata::read(reader)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            topoheight,
+            decimals,
+            metadata
+        })
     }
 }
 
@@ -1,8 +1,8 @@
 use std::borrow::Cow;
 
-use crate::{crypto::{hash::{Hash, Hashable, hash}, key::PublicKey}, serializer::{Serializer, Writer, Reader, ReaderError}};
+use crate::{crypto::{hash::{Hash, Hashable}, key::PublicKey}, serializer::{Serializer, Writer, Reader, ReaderError}};
 
-use super::{EXTRA_NONCE_SIZE, BLOCK_WORK_SIZE};
+use super::{EXTRA_NONCE_SIZE, BLOCK_WORK_SIZE, PowAlgorithm};
 
 // This structure is used by xelis-miner which allow to compute a valid block POW hash
 #[derive(Clone, Debug)]
@@ -26,9 +26,8 @@ impl<'a> BlockMiner<'a> {
     }
 
     #[inline(always)]
-    pub fn get_pow_hash(&self) -> Hash {
-        // TODO replace with real POW algorithm
-        hash(&self.to_bytes())
+    pub fn get_pow_hash(&self, algorithm: &dyn PowAlgorithm) -> Hash {
+        algorithm.hash(&self.to_bytes())
     }
 }
 
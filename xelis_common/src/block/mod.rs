@@ -1,10 +1,12 @@
 mod miner;
+mod pow;
 
 pub use miner::BlockMiner;
+pub use pow::{PowAlgorithm, KeccakPow, get_pow_algorithm};
 
 use serde::Deserialize;
 
-use crate::crypto::hash::{Hash, Hashable, hash};
+use crate::crypto::hash::{Hash, Hashable, hash, HASH_SIZE};
 use crate::crypto::key::PublicKey;
 use crate::immutable::Immutable;
 use crate::transaction::Transaction;
@@ -13,6 +15,8 @@ use crate::serializer::{Serializer, Writer, Reader, ReaderError};
 pub const EXTRA_NONCE_SIZE: usize = 32;
 pub const HEADER_WORK_SIZE: usize = 73;
 pub const BLOCK_WORK_SIZE: usize = 120; // 32 + 16 + 8 + 32 + 32 = 120
+// From this version, the header commits the state root at the common base of its tips
+pub const STATE_ROOT_BLOCK_VERSION: u8 = 1;
 
 // This type is used to easily switch between u64 and u128 as example
 // And its easier to see where we use the block difficulty
@@ -52,7 +56,10 @@ pub struct BlockHeader {
     #[serde(deserialize_with = "deserialize_extra_nonce")]
     pub extra_nonce: [u8; EXTRA_NONCE_SIZE],
     pub miner: PublicKey,
-    pub txs_hashes: Vec<Hash>
+    pub txs_hashes: Vec<Hash>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state_root: Option<Hash>
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
@@ -72,7 +79,8 @@ impl BlockHeader {
             nonce: 0,
             extra_nonce,
             miner,
-            txs_hashes
+            txs_hashes,
+            state_root: None
         }
     }
 
@@ -80,6 +88,15 @@ impl BlockHeader {
         self.version
     }
 
+    // only set from STATE_ROOT_BLOCK_VERSION
+    pub fn set_state_root(&mut self, state_root: Option<Hash>) {
+        self.state_root = state_root;
+    }
+
+    pub fn get_state_root(&self) -> Option<&Hash> {
+        self.state_root.as_ref()
+    }
+
     pub fn set_miner(&mut self, key: PublicKey) {
         self.miner = key;
     }
@@ -145,15 +162,19 @@ impl BlockHeader {
     }
 
     pub fn get_work(&self) -> Vec<u8> {
-        let mut bytes: Vec<u8> = Vec::with_capacity(HEADER_WORK_SIZE);
+        let size = if self.state_root.is_some() { HEADER_WORK_SIZE + HASH_SIZE } else { HEADER_WORK_SIZE };
+        let mut bytes: Vec<u8> = Vec::with_capacity(size);
 
         bytes.push(self.version); // 1
         bytes.extend(&self.height.to_be_bytes()); // 1 + 8 = 9
         bytes.extend(self.get_tips_hash().as_bytes()); // 9 + 32 = 41
         bytes.extend(self.get_txs_hash().as_bytes()); // 41 + 32 = 73
+        if let Some(state_root) = &self.state_root {
+            bytes.extend(state_root.as_bytes()); // 73 + 32 = 105
+        }
 
-        if bytes.len() != HEADER_WORK_SIZE {
-            panic!("Error, invalid header work size, got {} but expected {}", bytes.len(), HEADER_WORK_SIZE)
+        if bytes.len() != size {
+            panic!("Error, invalid header work size, got {} but expected {}", bytes.len(), size)
         }
         bytes
     }
@@ -178,10 +199,14 @@ impl BlockHeader {
         bytes
     }
 
-    // compute the block POW hash
-    pub fn get_pow_hash(&self) -> Hash {
-        // TODO replace with the real POW algorithm
-        hash(&self.get_serialized_header())
+    // retrieve the PoW algorithm selected by the block version
+    pub fn get_pow_algorithm(&self) -> Option<&'static dyn PowAlgorithm> {
+        get_pow_algorithm(self.version)
+    }
+
+    // compute the block POW hash using the requested algorithm
+    pub fn get_pow_hash(&self, algorithm: &dyn PowAlgorithm) -> Hash {
+        algorithm.hash(&self.get_serialized_header())
     }
 
     pub fn get_transactions(&self) -> &Vec<Hash> {
@@ -235,6 +260,9 @@ impl Serializer for BlockHeader {
             writer.write_hash(tx); // 32
         }
         self.miner.write(writer); // 68 + 32 = 100
+        if self.version >= STATE_ROOT_BLOCK_VERSION {
+            writer.write_hash(self.state_root.as_ref().unwrap_or(&Hash::zero())); // 100 + 32 = 132
+        }
     }
 
     fn read(reader: &mut Reader) -> Result<BlockHeader, ReaderError> {
@@ -257,6 +285,12 @@ impl Serializer for BlockHeader {
         }
 
         let miner = PublicKey::read(reader)?;
+        let state_root = if version >= STATE_ROOT_BLOCK_VERSION {
+            Some(reader.read_hash()?)
+        } else {
+            None
+        };
+
         Ok(
             BlockHeader {
                 version,
@@ -266,7 +300,8 @@ impl Serializer for BlockHeader {
                 tips,
                 miner,
                 nonce,
-                txs_hashes
+                txs_hashes,
+                state_root
             }
         )
     }
@@ -0,0 +1,34 @@
+use crate::crypto::hash::{Hash, hash};
+
+// Proof-of-Work algorithm used to compute the POW hash of a block
+// Each block version is bound to one algorithm, so switching to a new
+// algorithm is done through a hard fork by increasing the block version
+pub trait PowAlgorithm: Send + Sync {
+    // name of the algorithm, used for display purpose
+    fn name(&self) -> &'static str;
+
+    // compute the POW hash of the block work (BLOCK_WORK_SIZE bytes)
+    fn hash(&self, input: &[u8]) -> Hash;
+}
+
+// Keccak256, used since genesis (block version 0)
+pub struct KeccakPow;
+
+impl PowAlgorithm for KeccakPow {
+    fn name(&self) -> &'static str {
+        "keccak256"
+    }
+
+    fn hash(&self, input: &[u8]) -> Hash {
+        hash(input)
+    }
+}
+
+// PoW algorithms indexed by block version
+// version 1 only adds the state root in the header
+static POW_ALGORITHMS: [&dyn PowAlgorithm; 2] = [&KeccakPow, &KeccakPow];
+
+// retrieve the PoW algorithm to use for the requested block version
+pub fn get_pow_algorithm(version: u8) -> Option<&'static dyn PowAlgorithm> {
+    POW_ALGORITHMS.get(version as usize).copied()
+}
@@ -8,6 +8,13 @@ pub const FEE_PER_KB: u64 = 1000;
 pub const COIN_DECIMALS: u8 = 5;
 // 100 000 to represent 1 XEL
 pub const COIN_VALUE: u64 = 10u64.pow(COIN_DECIMALS as u32);
+// 1 XEL burned to register a new asset, prevent spamming the assets list
+pub const ASSET_REGISTRATION_FEE: u64 = COIN_VALUE;
+// a TX replacing a pending one with the same nonce must pay at least 10% more fees
+pub const DEFAULT_RBF_MIN_FEE_BUMP_PERCENT: u64 = 10;
+// Minimum difficulty of mainnet and testnet blocks (each difficulty point is in H/s)
+// Current: 15s block time in millis * 1000 = 1 MH/s minimum
+pub const MINIMUM_DIFFICULTY: u64 = 15 * 1000 * 1000;
 
 // Addresses format
 // mainnet prefix address
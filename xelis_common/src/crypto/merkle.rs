@@ -0,0 +1,182 @@
+use serde::{Deserialize, Serialize};
+use super::{
+    hash::{hash, Hash, HASH_SIZE},
+    key::{PublicKey, KEY_LENGTH}
+};
+
+// Commitment over all balances and nonces of the chain
+// It is a compact sparse Merkle tree indexed by the bits of the leaf key:
+// a subtree containing only one leaf is replaced by this leaf, and an empty subtree has a zero hash
+pub const STATE_TREE_DEPTH: usize = HASH_SIZE * 8;
+
+// Prefixes used to never have a leaf hash equal to a node hash
+const LEAF_PREFIX: u8 = 0;
+const NODE_PREFIX: u8 = 1;
+
+// Prefixes used to derive the leaf keys
+const BALANCE_LEAF: u8 = 0;
+const NONCE_LEAF: u8 = 1;
+
+// Root of a tree without any leaf
+pub const EMPTY_STATE_ROOT: Hash = Hash::zero();
+
+// Position of the balance of an account for an asset in the tree
+pub fn balance_leaf_key(key: &PublicKey, asset: &Hash) -> Hash {
+    let mut bytes = [0u8; 1 + KEY_LENGTH + HASH_SIZE];
+    bytes[0] = BALANCE_LEAF;
+    bytes[1..1 + KEY_LENGTH].copy_from_slice(key.as_bytes());
+    bytes[1 + KEY_LENGTH..].copy_from_slice(asset.as_bytes());
+    hash(&bytes)
+}
+
+// Position of the nonce of an account in the tree
+pub fn nonce_leaf_key(key: &PublicKey) -> Hash {
+    let mut bytes = [0u8; 1 + KEY_LENGTH];
+    bytes[0] = NONCE_LEAF;
+    bytes[1..].copy_from_slice(key.as_bytes());
+    hash(&bytes)
+}
+
+// Only the value is committed and not the previous topoheight of the version
+// so a fast synced node, which has no previous versions, computes the same root
+pub fn hash_leaf(leaf_key: &Hash, value: u64) -> Hash {
+    let mut bytes = [0u8; 1 + HASH_SIZE + 8];
+    bytes[0] = LEAF_PREFIX;
+    bytes[1..1 + HASH_SIZE].copy_from_slice(leaf_key.as_bytes());
+    bytes[1 + HASH_SIZE..].copy_from_slice(&value.to_be_bytes());
+    hash(&bytes)
+}
+
+pub fn hash_node(left: &Hash, right: &Hash) -> Hash {
+    let mut bytes = [0u8; 1 + HASH_SIZE * 2];
+    bytes[0] = NODE_PREFIX;
+    bytes[1..1 + HASH_SIZE].copy_from_slice(left.as_bytes());
+    bytes[1 + HASH_SIZE..].copy_from_slice(right.as_bytes());
+    hash(&bytes)
+}
+
+// Returns true if the leaf is in the right subtree of the node at this depth
+pub fn is_right_at_depth(leaf_key: &Hash, depth: usize) -> bool {
+    leaf_key.as_bytes()[depth / 8] & (0x80 >> (depth % 8)) != 0
+}
+
+// Compute the root of the tree containing all these leaves without storing its nodes
+// Leaves must be sorted by key, and each key must be unique
+pub fn compute_state_root(leaves: &[(Hash, u64)]) -> Hash {
+    compute_subtree_root(leaves, 0)
+}
+
+fn compute_subtree_root(leaves: &[(Hash, u64)], depth: usize) -> Hash {
+    match leaves {
+        [] => EMPTY_STATE_ROOT,
+        [(leaf_key, value)] => hash_leaf(leaf_key, *value),
+        _ => {
+            let split = leaves.partition_point(|(leaf_key, _)| !is_right_at_depth(leaf_key, depth));
+            hash_node(&compute_subtree_root(&leaves[..split], depth + 1), &compute_subtree_root(&leaves[split..], depth + 1))
+        }
+    }
+}
+
+// Inclusion proof of a leaf in the state tree
+// Siblings are ordered from the root to the leaf, the leaf is at depth siblings.len()
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StateProof {
+    siblings: Vec<Hash>
+}
+
+impl StateProof {
+    pub fn new(siblings: Vec<Hash>) -> Self {
+        Self {
+            siblings
+        }
+    }
+
+    pub fn get_siblings(&self) -> &Vec<Hash> {
+        &self.siblings
+    }
+
+    // Compute the root of the tree containing this leaf
+    // Returns None if the proof is deeper than the tree
+    pub fn compute_root(&self, leaf_key: &Hash, value: u64) -> Option<Hash> {
+        if self.siblings.len() > STATE_TREE_DEPTH {
+            return None
+        }
+
+        let mut hash = hash_leaf(leaf_key, value);
+        for (depth, sibling) in self.siblings.iter().enumerate().rev() {
+            hash = if is_right_at_depth(leaf_key, depth) {
+                hash_node(sibling, &hash)
+            } else {
+                hash_node(&hash, sibling)
+            };
+        }
+        Some(hash)
+    }
+
+    pub fn verify(&self, root: &Hash, leaf_key: &Hash, value: u64) -> bool {
+        match self.compute_root(leaf_key, value) {
+            Some(computed) => computed == *root,
+            None => false
+        }
+    }
+}
+
+// Verify that the account has this balance for the asset in the state committed by root
+pub fn verify_balance_proof(root: &Hash, key: &PublicKey, asset: &Hash, balance: u64, proof: &StateProof) -> bool {
+    proof.verify(root, &balance_leaf_key(key, asset), balance)
+}
+
+// Verify that the account has this nonce in the state committed by root
+pub fn verify_nonce_proof(root: &Hash, key: &PublicKey, nonce: u64, proof: &StateProof) -> bool {
+    proof.verify(root, &nonce_leaf_key(key), nonce)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_right_at_depth() {
+        let mut bytes = [0u8; HASH_SIZE];
+        bytes[0] = 0b1010_0000;
+        bytes[HASH_SIZE - 1] = 1;
+        let key = Hash::new(bytes);
+        assert!(is_right_at_depth(&key, 0));
+        assert!(!is_right_at_depth(&key, 1));
+        assert!(is_right_at_depth(&key, 2));
+        assert!(!is_right_at_depth(&key, 3));
+        assert!(is_right_at_depth(&key, STATE_TREE_DEPTH - 1));
+    }
+
+    #[test]
+    fn test_leaf_and_node_hashes_are_distinct() {
+        let key = Hash::zero();
+        assert_ne!(hash_leaf(&key, 0), hash_node(&key, &Hash::new([0u8; HASH_SIZE])));
+        assert_ne!(hash_leaf(&key, 1), hash_leaf(&key, 2));
+    }
+
+    #[test]
+    fn test_state_proof() {
+        let left = Hash::new([0u8; HASH_SIZE]);
+        let mut bytes = [0u8; HASH_SIZE];
+        bytes[0] = 0b0100_0000;
+        let right = Hash::new(bytes);
+
+        // both leaves are split at depth 1, depth 0 has an empty sibling
+        let left_hash = hash_leaf(&left, 10);
+        let right_hash = hash_leaf(&right, 20);
+        let root = hash_node(&hash_node(&left_hash, &right_hash), &EMPTY_STATE_ROOT);
+
+        let proof = StateProof::new(vec![EMPTY_STATE_ROOT, right_hash]);
+        assert!(proof.verify(&root, &left, 10));
+        assert!(!proof.verify(&root, &left, 11));
+        assert!(!proof.verify(&root, &right, 10));
+
+        // a single leaf is the root
+        assert!(StateProof::new(Vec::new()).verify(&left_hash, &left, 10));
+        assert!(!StateProof::new(vec![EMPTY_STATE_ROOT; STATE_TREE_DEPTH + 1]).verify(&root, &left, 10));
+
+        assert_eq!(compute_state_root(&[(left, 10), (right, 20)]), root);
+        assert_eq!(compute_state_root(&[]), EMPTY_STATE_ROOT);
+    }
+}
@@ -2,4 +2,5 @@ pub mod hash;
 pub mod key;
 pub mod bech32;
 pub mod address;
-pub mod elgamal;
\ No newline at end of file
+pub mod elgamal;
+pub mod merkle;
\ No newline at end of file
@@ -1,16 +1,82 @@
-use crate::crypto::key::{PublicKey, Signature, SIGNATURE_LENGTH};
+use crate::crypto::key::{PublicKey, Signature, KeyPair, SIGNATURE_LENGTH};
 use crate::crypto::hash::{Hashable, hash, Hash};
 use crate::serializer::{Serializer, Writer, Reader, ReaderError};
-use std::collections::HashMap;
+use std::{collections::{HashMap, HashSet}, fmt::{self, Display, Formatter}};
 
 pub const EXTRA_DATA_LIMIT_SIZE: usize = 1024;
+// limits of a custom asset registered with CreateAsset
+pub const ASSET_NAME_MAX_SIZE: usize = 32;
+pub const ASSET_TICKER_MAX_SIZE: usize = 8;
+pub const ASSET_MAX_DECIMALS: u8 = 18;
+// participants of a multisig account are identified by a u8 index
+pub const MAX_MULTISIG_PARTICIPANTS: usize = u8::MAX as usize;
+// first transaction version able to carry the signatures of multisig participants
+pub const MULTISIG_TX_VERSION: u8 = 1;
+// first transaction version able to expire at a topoheight
+pub const VALID_UNTIL_TX_VERSION: u8 = 2;
+
+// condition to reach before the receiver of a transfer can spend it
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum UnlockCondition {
+    // funds are spendable once the chain reached this topoheight
+    #[serde(rename = "topoheight")]
+    Topoheight(u64),
+    // funds are spendable once a block reached this timestamp in milliseconds
+    #[serde(rename = "timestamp")]
+    Timestamp(u128)
+}
+
+impl UnlockCondition {
+    // is the condition reached at topoheight, timestamp is the one of the block at this topoheight
+    pub fn is_unlocked(&self, topoheight: u64, timestamp: u128) -> bool {
+        match self {
+            Self::Topoheight(unlock) => topoheight >= *unlock,
+            Self::Timestamp(unlock) => timestamp >= *unlock
+        }
+    }
+}
+
+impl Display for UnlockCondition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Topoheight(topoheight) => write!(f, "topoheight {}", topoheight),
+            Self::Timestamp(timestamp) => write!(f, "timestamp {}", timestamp)
+        }
+    }
+}
+
+impl Serializer for UnlockCondition {
+    fn write(&self, writer: &mut Writer) {
+        match self {
+            Self::Topoheight(topoheight) => {
+                writer.write_u8(0);
+                writer.write_u64(topoheight);
+            },
+            Self::Timestamp(timestamp) => {
+                writer.write_u8(1);
+                writer.write_u128(timestamp);
+            }
+        }
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        Ok(match reader.read_u8()? {
+            0 => Self::Topoheight(reader.read_u64()?),
+            1 => Self::Timestamp(reader.read_u128()?),
+            _ => return Err(ReaderError::InvalidValue)
+        })
+    }
+}
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 pub struct Transfer {
     pub amount: u64,
     pub asset: Hash,
     pub to: PublicKey,
-    pub extra_data: Option<Vec<u8>> // we can put whatever we want up to EXTRA_DATA_LIMIT_SIZE bytes
+    pub extra_data: Option<Vec<u8>>, // we can put whatever we want up to EXTRA_DATA_LIMIT_SIZE bytes
+    // funds are credited to the receiver as locked balance until this condition is reached
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unlock: Option<UnlockCondition>
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
@@ -20,9 +86,81 @@ pub struct SmartContractCall {
     pub params: HashMap<String, String> // TODO
 }
 
+// participants and number of their signatures required to send a transaction from an account
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MultiSigSetup {
+    pub participants: Vec<PublicKey>,
+    pub threshold: u8
+}
+
+// signature of a participant, identified by its index in the multisig setup
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct SignatureId {
+    pub id: u8,
+    pub signature: Signature
+}
+
+// signatures of the participants of a multisig account
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct MultiSig {
+    signatures: Vec<SignatureId>
+}
+
+impl MultiSig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // add the signature of a participant, returns false if it has already signed
+    pub fn add_signature(&mut self, signature: SignatureId) -> bool {
+        if self.signatures.iter().any(|s| s.id == signature.id) {
+            return false
+        }
+
+        self.signatures.push(signature);
+        true
+    }
+
+    pub fn get_signatures(&self) -> &Vec<SignatureId> {
+        &self.signatures
+    }
+
+    pub fn len(&self) -> usize {
+        self.signatures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.signatures.is_empty()
+    }
+
+    // verify that enough distinct participants of the setup signed the hash
+    pub fn verify(&self, setup: &MultiSigSetup, hash: &Hash) -> bool {
+        if self.signatures.len() < setup.threshold as usize {
+            return false
+        }
+
+        let mut signers = HashSet::with_capacity(self.signatures.len());
+        for signature in &self.signatures {
+            let Some(participant) = setup.participants.get(signature.id as usize) else {
+                return false
+            };
+
+            if !signers.insert(signature.id) || !participant.verify_signature(hash, &signature.signature) {
+                return false
+            }
+        }
+
+        true
+    }
+}
+
 // this enum represent all types of transaction available on XELIS Network
 // you're able to send multi assets in one TX to different addresses
+// each transfer can be locked for its receiver until a topoheight or a timestamp
 // you can burn one asset at a time (so the TX Hash can be used as unique proof)
+// you can register a new asset, identified by the TX Hash
+// the mint authority of an asset can mint more supply up to its maximum supply or give its authority to another key
+// an account can require the signatures of a set of participants for all its next transactions
 // Smart Contract system is not yet available but types are already there
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 pub enum TransactionType {
@@ -34,6 +172,16 @@ pub enum TransactionType {
     CallContract(SmartContractCall),
     #[serde(rename = "deploy_contract")]
     DeployContract(String), // represent the code to deploy
+    #[serde(rename = "create_asset")]
+    CreateAsset { name: String, ticker: String, decimals: u8, max_supply: u64, mint_authority: Option<PublicKey> },
+    #[serde(rename = "mint_asset")]
+    MintAsset { asset: Hash, amount: u64 },
+    // no authority means it is renounced and no more supply can be minted
+    #[serde(rename = "set_asset_authority")]
+    SetAssetAuthority { asset: Hash, authority: Option<PublicKey> },
+    // no participants and a threshold of 0 removes the multisig from the account
+    #[serde(rename = "set_multisig")]
+    SetMultisig(MultiSigSetup),
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
@@ -43,6 +191,10 @@ pub struct Transaction {
     data: TransactionType,
     fee: u64, // fees in XELIS for this tx
     nonce: u64, // nonce must be equal to the one on account
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    valid_until_topoheight: Option<u64>, // can't be included anymore above this topoheight, only available from VALID_UNTIL_TX_VERSION
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    multisig: Option<MultiSig>, // signatures of the participants, only available from MULTISIG_TX_VERSION
     signature: Signature // signature of this Transaction by the owner
 }
 
@@ -55,7 +207,9 @@ impl Serializer for TransactionType {
                 writer.write_u64(amount);
             }
             TransactionType::Transfer(txs) => {
-                writer.write_u8(1);
+                // transfers with unlock conditions have their own id so other transfers keep the same format
+                let locked = txs.iter().any(|tx| tx.unlock.is_some());
+                writer.write_u8(if locked { 8 } else { 1 });
                 let len: u8 = txs.len() as u8; // max 255 txs
                 writer.write_u8(len);
                 for tx in txs {
@@ -68,6 +222,10 @@ impl Serializer for TransactionType {
                         writer.write_u16(extra_data.len() as u16);
                         writer.write_bytes(extra_data);
                     }
+
+                    if locked {
+                        tx.unlock.write(writer);
+                    }
                 }
             }
             TransactionType::CallContract(tx) => {
@@ -89,6 +247,34 @@ impl Serializer for TransactionType {
                 writer.write_u8(3);
                 writer.write_string(code);
             }
+            TransactionType::CreateAsset { name, ticker, decimals, max_supply, mint_authority } => {
+                writer.write_u8(4);
+                writer.write_string(name);
+                writer.write_string(ticker);
+                writer.write_u8(*decimals);
+                writer.write_u64(max_supply);
+                writer.write_bool(mint_authority.is_some());
+                if let Some(key) = mint_authority {
+                    key.write(writer);
+                }
+            }
+            TransactionType::MintAsset { asset, amount } => {
+                writer.write_u8(5);
+                writer.write_hash(asset);
+                writer.write_u64(amount);
+            }
+            TransactionType::SetAssetAuthority { asset, authority } => {
+                writer.write_u8(6);
+                writer.write_hash(asset);
+                writer.write_bool(authority.is_some());
+                if let Some(key) = authority {
+                    key.write(writer);
+                }
+            }
+            TransactionType::SetMultisig(setup) => {
+                writer.write_u8(7);
+                setup.write(writer);
+            }
         };
     }
 
@@ -99,7 +285,8 @@ impl Serializer for TransactionType {
                 let amount = reader.read_u64()?;
                 TransactionType::Burn { asset, amount }
             },
-            1 => { // Normal
+            id @ (1 | 8) => { // Normal or with unlock conditions
+                let locked = id == 8;
                 let txs_count = reader.read_u8()?;
                 let mut txs = Vec::with_capacity(txs_count as usize);
                 for _ in 0..txs_count {
@@ -120,13 +307,25 @@ impl Serializer for TransactionType {
                         None
                     };
 
+                    let unlock = if locked {
+                        Option::read(reader)?
+                    } else {
+                        None
+                    };
+
                     txs.push(Transfer {
                         asset,
                         amount,
                         to,
-                        extra_data
+                        extra_data,
+                        unlock
                     });
                 }
+
+                // keep only one valid format for the same transfers
+                if locked && txs.iter().all(|tx| tx.unlock.is_none()) {
+                    return Err(ReaderError::InvalidValue)
+                }
                 TransactionType::Transfer(txs)
             },
             2 => {
@@ -151,6 +350,44 @@ impl Serializer for TransactionType {
             },
             3 => {
                 TransactionType::DeployContract(reader.read_string()?)
+            },
+            4 => {
+                let name = reader.read_string()?;
+                if name.len() > ASSET_NAME_MAX_SIZE {
+                    return Err(ReaderError::InvalidSize)
+                }
+
+                let ticker = reader.read_string()?;
+                if ticker.len() > ASSET_TICKER_MAX_SIZE {
+                    return Err(ReaderError::InvalidSize)
+                }
+
+                let decimals = reader.read_u8()?;
+                let max_supply = reader.read_u64()?;
+                let mint_authority = if reader.read_bool()? {
+                    Some(PublicKey::read(reader)?)
+                } else {
+                    None
+                };
+
+                TransactionType::CreateAsset { name, ticker, decimals, max_supply, mint_authority }
+            },
+            5 => {
+                let asset = reader.read_hash()?;
+                let amount = reader.read_u64()?;
+                TransactionType::MintAsset { asset, amount }
+            },
+            6 => {
+                let asset = reader.read_hash()?;
+                let authority = if reader.read_bool()? {
+                    Some(PublicKey::read(reader)?)
+                } else {
+                    None
+                };
+                TransactionType::SetAssetAuthority { asset, authority }
+            },
+            7 => {
+                TransactionType::SetMultisig(MultiSigSetup::read(reader)?)
             }
             _ => {
                 return Err(ReaderError::InvalidValue)
@@ -167,6 +404,22 @@ impl Transaction {
             data,
             fee,
             nonce,
+            valid_until_topoheight: None,
+            multisig: None,
+            signature
+        }
+    }
+
+    // transaction which expires once the chain is above valid_until_topoheight
+    pub fn with_valid_until_topoheight(owner: PublicKey, data: TransactionType, fee: u64, nonce: u64, valid_until_topoheight: u64, signature: Signature) -> Self {
+        Transaction {
+            version: VALID_UNTIL_TX_VERSION,
+            owner,
+            data,
+            fee,
+            nonce,
+            valid_until_topoheight: Some(valid_until_topoheight),
+            multisig: None,
             signature
         }
     }
@@ -191,6 +444,27 @@ impl Transaction {
         self.nonce
     }
 
+    pub fn get_valid_until_topoheight(&self) -> Option<u64> {
+        self.valid_until_topoheight
+    }
+
+    // can the transaction still be included while the chain is at topoheight
+    pub fn is_valid_at_topoheight(&self, topoheight: u64) -> bool {
+        self.valid_until_topoheight.is_none_or(|valid_until| topoheight <= valid_until)
+    }
+
+    pub fn get_multisig(&self) -> Option<&MultiSig> {
+        self.multisig.as_ref()
+    }
+
+    // hash signed by the participants of a multisig account
+    // it doesn't include any signature so all participants can sign it independently
+    pub fn get_multisig_hash(&self) -> Hash {
+        let mut writer = Writer::new();
+        write_unsigned_fields(&mut writer, self.version, &self.owner, &self.data, self.fee, self.nonce, self.valid_until_topoheight);
+        hash(writer.as_bytes())
+    }
+
     // verify the validity of the signature
     pub fn verify_signature(&self) -> bool {
         let bytes = self.to_bytes();
@@ -203,13 +477,24 @@ impl Transaction {
     }
 }
 
+// fields of a transaction covered by the signatures of multisig participants
+fn write_unsigned_fields(writer: &mut Writer, version: u8, owner: &PublicKey, data: &TransactionType, fee: u64, nonce: u64, valid_until_topoheight: Option<u64>) {
+    writer.write_u8(version);
+    owner.write(writer);
+    data.write(writer);
+    writer.write_u64(&fee);
+    writer.write_u64(&nonce);
+    if version >= VALID_UNTIL_TX_VERSION {
+        valid_until_topoheight.write(writer);
+    }
+}
+
 impl Serializer for Transaction {
     fn write(&self, writer: &mut Writer) {
-        writer.write_u8(self.version);
-        self.owner.write(writer);
-        self.data.write(writer);
-        writer.write_u64(&self.fee);
-        writer.write_u64(&self.nonce);
+        write_unsigned_fields(writer, self.version, &self.owner, &self.data, self.fee, self.nonce, self.valid_until_topoheight);
+        if self.version >= MULTISIG_TX_VERSION {
+            self.multisig.write(writer);
+        }
         self.signature.write(writer);
     }
 
@@ -219,6 +504,16 @@ impl Serializer for Transaction {
         let data = TransactionType::read(reader)?;
         let fee = reader.read_u64()?;
         let nonce = reader.read_u64()?;
+        let valid_until_topoheight = if version >= VALID_UNTIL_TX_VERSION {
+            Option::read(reader)?
+        } else {
+            None
+        };
+        let multisig = if version >= MULTISIG_TX_VERSION {
+            Option::read(reader)?
+        } else {
+            None
+        };
         let signature = Signature::read(reader)?;
 
         Ok(Transaction {
@@ -227,7 +522,192 @@ impl Serializer for Transaction {
             data,
             fee,
             nonce,
+            valid_until_topoheight,
+            multisig,
+            signature
+        })
+    }
+}
+
+// transaction of a multisig account waiting for the signatures of its participants
+// it can be exchanged between participants, each one adding its own signature,
+// then finalized by the owner once enough signatures are collected
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct UnsignedTransaction {
+    owner: PublicKey,
+    data: TransactionType,
+    fee: u64,
+    nonce: u64,
+    valid_until_topoheight: Option<u64>,
+    multisig: MultiSig
+}
+
+impl UnsignedTransaction {
+    pub fn new(owner: PublicKey, data: TransactionType, fee: u64, nonce: u64, valid_until_topoheight: Option<u64>) -> Self {
+        Self {
+            owner,
+            data,
+            fee,
+            nonce,
+            valid_until_topoheight,
+            multisig: MultiSig::new()
+        }
+    }
+
+    // lowest version able to carry all the fields
+    fn get_version(&self) -> u8 {
+        if self.valid_until_topoheight.is_some() {
+            VALID_UNTIL_TX_VERSION
+        } else {
+            MULTISIG_TX_VERSION
+        }
+    }
+
+    pub fn get_owner(&self) -> &PublicKey {
+        &self.owner
+    }
+
+    pub fn get_data(&self) -> &TransactionType {
+        &self.data
+    }
+
+    pub fn get_fee(&self) -> u64 {
+        self.fee
+    }
+
+    pub fn get_nonce(&self) -> u64 {
+        self.nonce
+    }
+
+    pub fn get_valid_until_topoheight(&self) -> Option<u64> {
+        self.valid_until_topoheight
+    }
+
+    pub fn get_multisig(&self) -> &MultiSig {
+        &self.multisig
+    }
+
+    // same hash as Transaction::get_multisig_hash once finalized
+    pub fn get_multisig_hash(&self) -> Hash {
+        let mut writer = Writer::new();
+        write_unsigned_fields(&mut writer, self.get_version(), &self.owner, &self.data, self.fee, self.nonce, self.valid_until_topoheight);
+        hash(writer.as_bytes())
+    }
+
+    // sign as the participant at index id, returns false if it already signed
+    pub fn sign_multisig(&mut self, keypair: &KeyPair, id: u8) -> bool {
+        let signature = keypair.sign(self.get_multisig_hash().as_bytes());
+        self.multisig.add_signature(SignatureId { id, signature })
+    }
+
+    // add the signatures collected by another participant on the same transaction
+    // returns false if the other one is for a different transaction
+    pub fn combine(&mut self, other: UnsignedTransaction) -> bool {
+        if self.get_multisig_hash() != other.get_multisig_hash() {
+            return false
+        }
+
+        for signature in other.multisig.signatures {
+            self.multisig.add_signature(signature);
+        }
+        true
+    }
+
+    // sign the transaction with all collected signatures as its owner
+    pub fn finalize(self, keypair: &KeyPair) -> Transaction {
+        let version = self.get_version();
+        let mut writer = Writer::new();
+        write_unsigned_fields(&mut writer, version, &self.owner, &self.data, self.fee, self.nonce, self.valid_until_topoheight);
+        let multisig = Some(self.multisig);
+        multisig.write(&mut writer);
+        let signature = keypair.sign(hash(writer.as_bytes()).as_bytes());
+
+        Transaction {
+            version,
+            owner: self.owner,
+            data: self.data,
+            fee: self.fee,
+            nonce: self.nonce,
+            valid_until_topoheight: self.valid_until_topoheight,
+            multisig,
             signature
+        }
+    }
+}
+
+impl Serializer for UnsignedTransaction {
+    fn write(&self, writer: &mut Writer) {
+        self.owner.write(writer);
+        self.data.write(writer);
+        writer.write_u64(&self.fee);
+        writer.write_u64(&self.nonce);
+        self.valid_until_topoheight.write(writer);
+        self.multisig.write(writer);
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let owner = PublicKey::read(reader)?;
+        let data = TransactionType::read(reader)?;
+        let fee = reader.read_u64()?;
+        let nonce = reader.read_u64()?;
+        let valid_until_topoheight = Option::read(reader)?;
+        let multisig = MultiSig::read(reader)?;
+
+        Ok(Self {
+            owner,
+            data,
+            fee,
+            nonce,
+            valid_until_topoheight,
+            multisig
+        })
+    }
+}
+
+impl Serializer for MultiSigSetup {
+    fn write(&self, writer: &mut Writer) {
+        writer.write_u8(self.participants.len() as u8); // max 255 participants
+        for participant in &self.participants {
+            participant.write(writer);
+        }
+        writer.write_u8(self.threshold);
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let count = reader.read_u8()?;
+        let mut participants = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            participants.push(PublicKey::read(reader)?);
+        }
+        let threshold = reader.read_u8()?;
+
+        Ok(Self {
+            participants,
+            threshold
+        })
+    }
+}
+
+impl Serializer for MultiSig {
+    fn write(&self, writer: &mut Writer) {
+        writer.write_u8(self.signatures.len() as u8); // one signature per participant
+        for signature in &self.signatures {
+            writer.write_u8(signature.id);
+            signature.signature.write(writer);
+        }
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let count = reader.read_u8()?;
+        let mut signatures = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let id = reader.read_u8()?;
+            let signature = Signature::read(reader)?;
+            signatures.push(SignatureId { id, signature });
+        }
+
+        Ok(Self {
+            signatures
         })
     }
 }
@@ -91,6 +91,13 @@ pub fn calculate_tx_fee(tx_size: usize) -> u64 {
     size_in_kb * FEE_PER_KB
 }
 
+// return the minimum fee required to replace a pending transaction paying this fee
+// it is always strictly higher than the replaced fee
+pub fn calculate_replacement_fee(fee: u64, bump_percent: u64) -> u64 {
+    let bump = (fee as u128 * bump_percent as u128 / 100) as u64;
+    fee.saturating_add(bump.max(1))
+}
+
 const HASHRATE_FORMATS: [&str; 5] = ["H/s", "KH/s", "MH/s", "GH/s", "TH/s"];
 
 // Format a hashrate in human-readable format
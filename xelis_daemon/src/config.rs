@@ -12,6 +12,9 @@ use xelis_common::{
 // In case of potential forks, have a unique network id to not connect to others compatible chains
 pub const NETWORK_ID_SIZE: usize = 16;
 pub const NETWORK_ID: [u8; NETWORK_ID_SIZE] = [0x73, 0x6c, 0x69, 0x78, 0x65, 0x5f, 0x78, 0x65, 0x6c, 0x69, 0x73, 0x5f, 0x62, 0x6c, 0x6f, 0x63];
+// P2P protocol version, increased at each incompatible change of the packets format
+// Version 1: handshake advertises the hash of the hard forks schedule
+pub const P2P_PROTOCOL_VERSION: u8 = 1;
 pub const SEED_NODES: [&str; 2] = ["74.208.251.149:2125", "162.19.249.100:2125"];
 
 // bind addresses
@@ -21,14 +24,25 @@ pub const DEFAULT_RPC_BIND_ADDRESS: &str = "0.0.0.0:8080";
 // Default cache size for storage DB
 pub const DEFAULT_CACHE_SIZE: usize = 1024;
 
+// Mempool limits
+pub const DEFAULT_MEMPOOL_MAX_TXS: usize = 10_000; // maximum count of txs
+pub const DEFAULT_MEMPOOL_MAX_BYTES: usize = 100 * 1024 * 1024; // maximum 100 MB of txs
+pub const DEFAULT_MEMPOOL_TX_EXPIRY: u64 = 60 * 60 * 24 * 3; // txs are deleted after 3 days
+pub const MEMPOOL_SAVE_INTERVAL: u64 = 60; // save the mempool on disk every 60 seconds
+
+// Fee estimation
+pub const FEE_ESTIMATION_BLOCKS: u64 = 20; // last blocks used to estimate the fee rates
+pub const FEE_ESTIMATION_TARGETS: [u64; 3] = [1, 3, 10]; // estimated inclusion targets in blocks
+pub const FEE_ESTIMATION_FULL_BLOCK_PERCENT: usize = 90; // a block is considered full above this percent of MAX_BLOCK_SIZE
+
 // Block rules
 // Millis per second, it is used to prevent having random 1000 values anywhere
 pub const MILLIS_PER_SECOND: u64 = 1000;
 // Block Time in milliseconds
 pub const BLOCK_TIME_MILLIS: u64 = 15 * MILLIS_PER_SECOND; // 15s block time
 // Minimum difficulty (each difficulty point is in H/s)
-// Current: BLOCK TIME in millis * 1000 = 1 MH/s minimum
-pub const MINIMUM_DIFFICULTY: Difficulty = BLOCK_TIME_MILLIS as Difficulty * 1000;
+// Shared with wallets to verify block headers
+pub use xelis_common::config::MINIMUM_DIFFICULTY;
 pub const GENESIS_BLOCK_DIFFICULTY: Difficulty = 1;
 // 1024 * 1024 + (256 * 1024) bytes = 1.25 MB maximum size per block with txs
 pub const MAX_BLOCK_SIZE: usize = (1024 * 1024) + (256 * 1024);
@@ -63,6 +77,47 @@ pub const MAXIMUM_SUPPLY: u64 = 18_400_000 * COIN_VALUE; // 18.4M full coin
 
 // Genesis block to have the same starting point for every nodes
 pub const GENESIS_BLOCK: &str = "0000000000000000000000000000000000000001872f3e0c02000000000000000000000000000000000000000000000000000000000000000000000000000000000000006c24cdc1c8ee8f028b8cafe7b79a66a0902f26d89dd54eeff80abcf251a9a3bd"; // Genesis block in hexadecimal format
+// Checkpoints (topoheight, block hash) of each network
+// A node never reorganizes its chain below a checkpoint it has reached
+// Sorted by topoheight, only the genesis block is pinned for now
+// Operators can add trusted checkpoints using --checkpoint
+pub const MAINNET_CHECKPOINTS: &[(u64, &str)] = &[
+    (0, "57c4c418b84db5db139dd5d95c42e24a49101434d6fe36f06efeb5cf9c8af9ee")
+];
+pub const TESTNET_CHECKPOINTS: &[(u64, &str)] = &[
+    (0, "57c4c418b84db5db139dd5d95c42e24a49101434d6fe36f06efeb5cf9c8af9ee")
+];
+// Network upgrade activated at a specific height
+pub struct HardFork {
+    // height from which the upgrade is active
+    pub height: u64,
+    // block version required from this height, it also selects the PoW algorithm
+    pub version: u8,
+    // highest transaction version accepted from this height
+    pub tx_version: u8,
+    // consensus rules enabled by this upgrade, they stay enabled in next ones
+    pub features: &'static [&'static str],
+    // short description for node operators
+    pub changelog: &'static str
+}
+// Tx version 1 allows multisig signatures, version 2 allows an expiry topoheight
+// Known features: "multisig" allows SetMultisig transactions
+// "locked_transfers" allows transfers with an unlock condition
+// "custom_assets" allows CreateAsset, MintAsset and SetAssetAuthority transactions
+// Block version 1 commits the state root in the header, fast sync verifies the downloaded state against it
+// It is not scheduled yet on mainnet and testnet, fast sync there still requires a trusted peer
+// Hard forks of each network, sorted by activation height
+// First one must be at height 0
+pub const MAINNET_HARD_FORKS: &[HardFork] = &[
+    HardFork { height: 0, version: 0, tx_version: 0, features: &[], changelog: "Initial version" }
+];
+pub const TESTNET_HARD_FORKS: &[HardFork] = &[
+    HardFork { height: 0, version: 0, tx_version: 0, features: &[], changelog: "Initial version" }
+];
+pub const DEV_HARD_FORKS: &[HardFork] = &[
+    HardFork { height: 0, version: 0, tx_version: 2, features: &["multisig", "locked_transfers", "custom_assets"], changelog: "Initial version with multisig accounts, locked transfers, expiring transactions and custom assets" },
+    HardFork { height: 10, version: 1, tx_version: 2, features: &[], changelog: "Block headers commit the state root of balances and nonces" }
+];
 // Developer address for paying dev fees until Smart Contracts integration
 // (testnet/mainnet format is converted lazily later)
 pub const DEV_ADDRESS: &str = "xel1qyqxcfxdc8ywarcz3wx2leahnfn2pyp0ymvfm42waluq408j2x5680g05xfx5";
@@ -2,15 +2,15 @@ use anyhow::Error;
 use lru::LruCache;
 use serde_json::{Value, json};
 use xelis_common::{
-    config::{XELIS_ASSET, COIN_DECIMALS},
-    crypto::{key::PublicKey, hash::{Hashable, Hash, HASH_SIZE}},
+    config::{XELIS_ASSET, COIN_DECIMALS, ASSET_REGISTRATION_FEE, DEFAULT_RBF_MIN_FEE_BUMP_PERCENT},
+    crypto::{key::PublicKey, hash::{Hashable, Hash, HASH_SIZE, hash}},
     difficulty::check_difficulty,
-    transaction::{Transaction, TransactionType, EXTRA_DATA_LIMIT_SIZE},
-    utils::{get_current_timestamp, format_xelis, get_current_time},
-    block::{Block, BlockHeader, EXTRA_NONCE_SIZE, Difficulty},
+    transaction::{Transaction, TransactionType, EXTRA_DATA_LIMIT_SIZE, ASSET_NAME_MAX_SIZE, ASSET_TICKER_MAX_SIZE, ASSET_MAX_DECIMALS, MAX_MULTISIG_PARTICIPANTS},
+    utils::{get_current_timestamp, format_xelis, get_current_time, calculate_replacement_fee},
+    block::{Block, BlockHeader, EXTRA_NONCE_SIZE, STATE_ROOT_BLOCK_VERSION, Difficulty, PowAlgorithm, get_pow_algorithm},
     immutable::Immutable,
-    serializer::Serializer,
-    account::VersionedBalance,
+    serializer::{Serializer, Writer},
+    account::{VersionedBalance, LockedBalance},
     api::{
         daemon::{
             NotifyEvent,
@@ -18,22 +18,29 @@ use xelis_common::{
             TransactionExecutedEvent,
             BlockType,
             StableHeightChangedEvent,
-            TransactionResponse
+            TransactionReplacedEvent,
+            FeeRateEstimate,
+            TransactionResponse,
+            AccountHistoryDirection,
+            DevFeeThreshold
         },
         DataHash
     },
     network::Network,
-    asset::AssetData
+    asset::{AssetData, AssetMetadata, AssetWithData}
 };
 use crate::{
     config::{
         DEFAULT_P2P_BIND_ADDRESS, P2P_DEFAULT_MAX_PEERS, DEFAULT_RPC_BIND_ADDRESS, DEFAULT_CACHE_SIZE, MAX_BLOCK_SIZE,
-        EMISSION_SPEED_FACTOR, MAXIMUM_SUPPLY, DEV_FEES, GENESIS_BLOCK, TIPS_LIMIT, TIMESTAMP_IN_FUTURE_LIMIT,
-        STABLE_LIMIT, GENESIS_BLOCK_HASH, MINIMUM_DIFFICULTY, GENESIS_BLOCK_DIFFICULTY, SIDE_BLOCK_REWARD_PERCENT,
-        DEV_PUBLIC_KEY, PRUNE_SAFETY_LIMIT, BLOCK_TIME_MILLIS, MILLIS_PER_SECOND,
+        DEFAULT_MEMPOOL_MAX_TXS, DEFAULT_MEMPOOL_MAX_BYTES, DEFAULT_MEMPOOL_TX_EXPIRY, MEMPOOL_SAVE_INTERVAL,
+        FEE_ESTIMATION_BLOCKS, FEE_ESTIMATION_TARGETS,
+        EMISSION_SPEED_FACTOR, MAXIMUM_SUPPLY, GENESIS_BLOCK, TIPS_LIMIT, TIMESTAMP_IN_FUTURE_LIMIT,
+        STABLE_LIMIT, GENESIS_BLOCK_HASH, GENESIS_BLOCK_DIFFICULTY, SIDE_BLOCK_REWARD_PERCENT,
+        PRUNE_SAFETY_LIMIT, MILLIS_PER_SECOND, MAINNET_CHECKPOINTS, TESTNET_CHECKPOINTS,
+        MAINNET_HARD_FORKS, TESTNET_HARD_FORKS, DEV_HARD_FORKS, HardFork
     },
-    core::difficulty::calculate_difficulty,
-    p2p::P2pServer,
+    core::{difficulty::calculate_difficulty, chain_spec::ChainSpec, fee_estimator, tx_selector::{self, TxCandidate}},
+    p2p::{P2pServer, packet::bootstrap_chain::{BlockMetadata, AssetState, MAX_ITEMS_PER_PAGE}},
     rpc::{
         rpc::{
             get_block_response_for_hash, get_block_type_for_block
@@ -41,9 +48,13 @@ use crate::{
         DaemonRpcServer, SharedDaemonRpcServer
     }
 };
-use super::storage::{Storage, DifficultyProvider};
+use super::{
+    storage::{Storage, DifficultyProvider, StorageBackend, IntegrityReport},
+    chain_file::{ChainFileReader, ChainFileWriter},
+    snapshot_file::{SnapshotFileHeader, SnapshotFileReader, SnapshotFileWriter, SnapshotEntry, SnapshotAccount}
+};
 use std::{sync::atomic::{Ordering, AtomicU64}, collections::hash_map::Entry, time::{Duration, Instant}, borrow::Cow};
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, BTreeMap};
 use async_recursion::async_recursion;
 use tokio::{time::interval, sync::{Mutex, RwLock}};
 use log::{info, error, debug, warn, trace};
@@ -80,6 +91,10 @@ pub struct Config {
     /// Set dir path for blockchain storage
     #[clap(short = 's', long)]
     pub dir_path: Option<String>,
+    /// Database engine used to store the chain
+    /// memory backend keeps everything in RAM and is lost at exit
+    #[clap(long, arg_enum, default_value = "sled")]
+    pub storage_backend: StorageBackend,
     /// Set LRUCache size (0 = disabled)
     #[clap(short, long, default_value_t = DEFAULT_CACHE_SIZE)]
     pub cache_size: usize,
@@ -101,7 +116,64 @@ pub struct Config {
     /// It will not store any blocks / TXs and will not verify the history locally.
     /// Use it with extreme cautions and trusted nodes to have a valid bootstrapped chain
     #[clap(long)]
-    pub allow_fast_sync: bool
+    pub allow_fast_sync: bool,
+    /// Import all blocks from a chain file at startup
+    #[clap(long)]
+    pub import_chain: Option<String>,
+    /// Don't verify PoW and transactions of imported blocks
+    /// Use it only with chain files from a trusted source
+    #[clap(long)]
+    pub trusted_import: bool,
+    /// Export the chain to a file at startup
+    #[clap(long)]
+    pub export_chain: Option<String>,
+    /// Boot a new chain from a snapshot file at startup
+    #[clap(long)]
+    pub load_snapshot: Option<String>,
+    /// Write a snapshot of the chain state at the stable topoheight to a file at startup
+    #[clap(long)]
+    pub create_snapshot: Option<String>,
+    /// Index the transactions of each account for the get_account_transactions RPC method
+    /// The index is built at startup from the available blocks and deleted once disabled
+    #[clap(long)]
+    pub enable_account_history_index: bool,
+    /// Add a trusted checkpoint in the format topoheight:hash
+    /// It replaces the hard-coded checkpoint at the same topoheight
+    #[clap(long)]
+    pub checkpoint: Vec<String>,
+    /// Load the chain parameters (genesis, block time, minimum difficulty, dev fees)
+    /// from a JSON file, only available in dev network
+    #[clap(long)]
+    pub chain_spec: Option<String>,
+    /// Minimum fee increase in percent required to replace a pending TX with the same nonce
+    #[clap(long, default_value_t = DEFAULT_RBF_MIN_FEE_BUMP_PERCENT)]
+    pub rbf_min_fee_bump: u64,
+    /// Maximum count of TXs in mempool
+    /// Once full, the TXs paying the lowest fee per byte are deleted first
+    #[clap(long, default_value_t = DEFAULT_MEMPOOL_MAX_TXS)]
+    pub mempool_max_txs: usize,
+    /// Maximum total size in bytes of the TXs in mempool
+    #[clap(long, default_value_t = DEFAULT_MEMPOOL_MAX_BYTES)]
+    pub mempool_max_bytes: usize,
+    /// Time in seconds after which a TX not included in a block is deleted from mempool
+    #[clap(long, default_value_t = DEFAULT_MEMPOOL_TX_EXPIRY)]
+    pub mempool_tx_expiry: u64
+}
+
+// Side effects of a block added to the chain
+// they are applied only once its storage batch is committed
+struct BlockAddedEffects {
+    block: Arc<BlockHeader>,
+    block_hash: Hash,
+    cumulative_difficulty: Difficulty,
+    topoheight: u64,
+    height: u64,
+    // last nonce of each account having txs executed, to clean the mempool
+    nonces: HashMap<PublicKey, u64>,
+    should_track_events: HashSet<NotifyEvent>,
+    events: HashMap<NotifyEvent, Vec<Value>>,
+    broadcast: bool,
+    mining: bool
 }
 
 pub struct Blockchain<S: Storage> {
@@ -109,6 +181,8 @@ pub struct Blockchain<S: Storage> {
     topoheight: AtomicU64, // current topo height
     stable_height: AtomicU64, // current stable height
     mempool: RwLock<Mempool>, // mempool to retrieve/add all txs
+    // txs of the mempool saved on disk with their owner and nonce, used to only write the changes
+    saved_mempool: Mutex<HashMap<Arc<Hash>, (PublicKey, u64)>>,
     storage: RwLock<S>, // storage to retrieve/add blocks
     p2p: RwLock<Option<Arc<P2pServer<S>>>>, // P2p module
     rpc: RwLock<Option<SharedDaemonRpcServer<S>>>, // Rpc module
@@ -129,11 +203,25 @@ pub struct Blockchain<S: Storage> {
     auto_prune_keep_n_blocks: Option<u64>,
     // allow fast syncing (only balances / assets / Smart Contracts changes)
     // without syncing the history
-    allow_fast_sync_mode: bool
+    allow_fast_sync_mode: bool,
+    // maintain the index of transactions for each account
+    account_history_index: bool,
+    // trusted block hash at each checkpoint topoheight
+    checkpoints: BTreeMap<u64, Hash>,
+    // network upgrades schedule, sorted by activation height
+    hard_forks: &'static [HardFork],
+    // hash of the schedule, peers must have the same one
+    hard_forks_hash: Hash,
+    // chain parameters used by the consensus
+    chain_spec: ChainSpec,
+    // hash of the genesis block expected by the chain spec
+    genesis_hash: Hash,
+    // minimum fee increase in percent to replace a pending TX
+    rbf_min_fee_bump: u64
 }
 
 impl<S: Storage> Blockchain<S> {
-    pub async fn new(config: Config, network: Network, storage: S) -> Result<Arc<Self>, Error> {
+    pub async fn new(config: Config, network: Network, mut storage: S) -> Result<Arc<Self>, Error> {
         if config.simulator && network != Network::Dev {
             error!("Impossible to enable simulator mode except in dev network!");
             return Err(BlockchainError::InvalidNetwork.into())
@@ -146,9 +234,66 @@ impl<S: Storage> Blockchain<S> {
             }
         }
 
+        let mut checkpoints = BTreeMap::new();
+        let hard_coded = match network {
+            Network::Mainnet => MAINNET_CHECKPOINTS,
+            Network::Testnet => TESTNET_CHECKPOINTS,
+            Network::Dev => &[]
+        };
+        for (topoheight, hash) in hard_coded {
+            checkpoints.insert(*topoheight, Hash::from_hex(hash.to_string())?);
+        }
+
+        for value in config.checkpoint {
+            let (topoheight, hash) = parse_checkpoint(&value)?;
+            info!("Using checkpoint {} at topoheight {}", hash, topoheight);
+            checkpoints.insert(topoheight, hash);
+        }
+
+        // hard-coded checkpoints only pin the genesis block
+        if network != Network::Dev && checkpoints.range(1..).next().is_none() {
+            info!("No checkpoint above the genesis block, trusted ones can be added using --checkpoint");
+        }
+
+        let hard_forks = match network {
+            Network::Mainnet => MAINNET_HARD_FORKS,
+            Network::Testnet => TESTNET_HARD_FORKS,
+            Network::Dev => DEV_HARD_FORKS
+        };
+
+        let chain_spec = if let Some(path) = &config.chain_spec {
+            if network != Network::Dev {
+                error!("Chain spec can only be used in dev network!");
+                return Err(BlockchainError::InvalidNetwork.into())
+            }
+
+            info!("Loading chain spec from {}", path);
+            ChainSpec::from_file(path)?
+        } else {
+            ChainSpec::default()
+        };
+        let genesis_hash = match chain_spec.build_genesis_block() {
+            Some(block) => block.hash(),
+            None => GENESIS_BLOCK_HASH.clone()
+        };
+
+        // node may have crashed while applying a block, revert all its changes
+        if storage.has_pending_batch()? {
+            warn!("Incomplete batch found in storage, rolling it back...");
+            storage.rollback_batch().await?;
+            info!("Storage has been restored to its last consistent state");
+        }
+
         let on_disk = storage.has_blocks();
         let (height, topoheight) = if on_disk {
             info!("Reading last metadata available...");
+            // chain may have been created with another chain spec
+            let stored_genesis_hash = storage.get_hash_at_topo_height(0).await?;
+            if stored_genesis_hash != genesis_hash {
+                error!("Genesis block hash is invalid! Expected: {}, got: {}", genesis_hash, stored_genesis_hash);
+                return Err(BlockchainError::InvalidGenesisHash.into())
+            }
+
             let height = storage.get_top_height()?;
             let topoheight = storage.get_top_topoheight()?;
 
@@ -160,7 +305,8 @@ impl<S: Storage> Blockchain<S> {
             height: AtomicU64::new(height),
             topoheight: AtomicU64::new(topoheight),
             stable_height: AtomicU64::new(0),
-            mempool: RwLock::new(Mempool::new()),
+            mempool: RwLock::new(Mempool::new(config.mempool_max_txs, config.mempool_max_bytes, config.mempool_tx_expiry)),
+            saved_mempool: Mutex::new(HashMap::new()),
             storage: RwLock::new(storage),
             p2p: RwLock::new(None),
             rpc: RwLock::new(None),
@@ -171,7 +317,14 @@ impl<S: Storage> Blockchain<S> {
             tip_work_score_cache: Mutex::new(LruCache::new(1024)),
             full_order_cache: Mutex::new(LruCache::new(1024)),
             auto_prune_keep_n_blocks: config.auto_prune_keep_n_blocks,
-            allow_fast_sync_mode: config.allow_fast_sync
+            allow_fast_sync_mode: config.allow_fast_sync,
+            account_history_index: config.enable_account_history_index,
+            checkpoints,
+            hard_forks,
+            hard_forks_hash: hash_hard_forks(hard_forks),
+            chain_spec,
+            genesis_hash,
+            rbf_min_fee_bump: config.rbf_min_fee_bump
         };
 
         // include genesis block
@@ -199,6 +352,20 @@ impl<S: Storage> Blockchain<S> {
             blockchain.stable_height.store(stable_height, Ordering::SeqCst);
         }
 
+        {
+            let mut storage = blockchain.get_storage().write().await;
+            if blockchain.account_history_index {
+                if !storage.is_account_history_indexed()? {
+                    blockchain.build_account_history_index(&mut storage).await?;
+                }
+            } else if storage.is_account_history_indexed()? {
+                info!("Account history index is disabled, deleting it...");
+                storage.set_account_history_indexed(false)?;
+            }
+        }
+
+        blockchain.load_mempool().await?;
+
         let arc = Arc::new(blockchain);
         // create P2P Server
         if !config.disable_p2p_server && arc.network != Network::Dev  {
@@ -244,15 +411,35 @@ impl<S: Storage> Blockchain<S> {
             };
         }
 
+        // save the mempool periodically to not lose it on a crash
+        {
+            let zelf = Arc::downgrade(&arc);
+            tokio::spawn(async move {
+                let mut interval = interval(Duration::from_secs(MEMPOOL_SAVE_INTERVAL));
+                // first tick is instant
+                interval.tick().await;
+                loop {
+                    interval.tick().await;
+                    let Some(zelf) = zelf.upgrade() else {
+                        break;
+                    };
+
+                    if let Err(e) = zelf.save_mempool().await {
+                        error!("Error while saving mempool: {}", e);
+                    }
+                }
+            });
+        }
+
         if arc.simulator {
             warn!("Simulator mode enabled!");
             let zelf = Arc::clone(&arc);
             tokio::spawn(async move {
-                let mut interval = interval(Duration::from_millis(BLOCK_TIME_MILLIS));
+                let mut interval = interval(Duration::from_millis(zelf.get_block_time_target()));
                 loop {
                     interval.tick().await;
                     info!("Adding new simulated block...");
-                    if let Err(e) = zelf.mine_block(&DEV_PUBLIC_KEY).await {
+                    if let Err(e) = zelf.mine_block(zelf.get_dev_public_key()).await {
                         error!("Simulator error: {}", e);
                     }
                 }
@@ -281,6 +468,10 @@ impl<S: Storage> Blockchain<S> {
             }
         }
 
+        if let Err(e) = self.save_mempool().await {
+            error!("Error while saving mempool: {}", e);
+        }
+
         {
             let mut storage = self.storage.write().await;
             if let Err(e) = storage.stop().await {
@@ -291,6 +482,94 @@ impl<S: Storage> Blockchain<S> {
         info!("All modules are now stopped!");
     }
 
+    // write the changes of the mempool since the last save on disk, they are loaded again at next start
+    // nothing is written if the mempool didn't change
+    pub async fn save_mempool(&self) -> Result<(), BlockchainError> {
+        let mut saved = self.saved_mempool.lock().await;
+        // don't keep the mempool locked while writing on disk
+        let (added, deleted) = {
+            let mempool = self.mempool.read().await;
+            let txs = mempool.get_txs();
+            let added: Vec<(Arc<Hash>, Arc<Transaction>, u64)> = txs.iter()
+                .filter(|(hash, _)| !saved.contains_key(*hash))
+                .map(|(hash, sorted_tx)| (Arc::clone(hash), Arc::clone(sorted_tx.get_tx()), sorted_tx.get_first_seen()))
+                .collect();
+            let deleted: Vec<Arc<Hash>> = saved.keys()
+                .filter(|hash| !txs.contains_key(*hash))
+                .cloned()
+                .collect();
+            (added, deleted)
+        };
+
+        if added.is_empty() && deleted.is_empty() {
+            trace!("Mempool didn't change since last save");
+            return Ok(())
+        }
+
+        debug!("Saving mempool: {} txs added, {} txs deleted", added.len(), deleted.len());
+        let mut storage = self.storage.write().await;
+        // written in a batch to never load a partially saved mempool
+        storage.start_batch()?;
+        if let Err(e) = Self::write_mempool_changes(&mut *storage, &saved, &added, &deleted).await {
+            storage.rollback_batch().await?;
+            return Err(e)
+        }
+        storage.commit_batch()?;
+
+        for hash in deleted {
+            saved.remove(&hash);
+        }
+        for (hash, tx, _) in added {
+            saved.insert(hash, (tx.get_owner().clone(), tx.get_nonce()));
+        }
+
+        Ok(())
+    }
+
+    // deleted txs are written first as an added tx may replace one with the same owner and nonce
+    async fn write_mempool_changes(storage: &mut S, saved: &HashMap<Arc<Hash>, (PublicKey, u64)>, added: &[(Arc<Hash>, Arc<Transaction>, u64)], deleted: &[Arc<Hash>]) -> Result<(), BlockchainError> {
+        for hash in deleted {
+            if let Some((owner, nonce)) = saved.get(hash) {
+                storage.delete_mempool_tx(owner, *nonce).await?;
+            }
+        }
+
+        for (_, tx, first_seen) in added {
+            storage.add_mempool_tx(tx, *first_seen).await?;
+        }
+
+        Ok(())
+    }
+
+    // add back the txs saved on disk to the mempool
+    // they are verified again against the current chain state and invalid ones are discarded
+    async fn load_mempool(&self) -> Result<(), BlockchainError> {
+        let storage = self.storage.read().await;
+        let txs = storage.get_mempool_txs().await?;
+        if txs.is_empty() {
+            return Ok(())
+        }
+
+        info!("Loading {} txs from saved mempool...", txs.len());
+        let mut saved = self.saved_mempool.lock().await;
+        let mut discarded = 0;
+        for (tx, first_seen) in txs {
+            let hash = Arc::new(tx.hash());
+            // discarded txs are deleted from disk at the next save
+            saved.insert(Arc::clone(&hash), (tx.get_owner().clone(), tx.get_nonce()));
+            if let Err(e) = self.add_tx_to_mempool_with_first_seen(&*storage, Arc::new(tx), hash.as_ref().clone(), first_seen, false).await {
+                debug!("Discarding saved TX {}: {}", hash, e);
+                discarded += 1;
+            }
+        }
+
+        if discarded > 0 {
+            info!("{} saved txs were not valid anymore", discarded);
+        }
+
+        Ok(())
+    }
+
     pub async fn reload_from_disk(&self) -> Result<(), BlockchainError> {
         trace!("Reloading chain from disk");
         let storage = self.storage.read().await;
@@ -319,14 +598,38 @@ impl<S: Storage> Blockchain<S> {
     async fn create_genesis_block(&self) -> Result<(), BlockchainError> {
         let mut storage = self.storage.write().await;
 
+        storage.start_batch()?;
+        if let Err(e) = self.create_genesis_block_for_storage(&mut storage).await {
+            error!("Error while creating genesis block, rolling back changes: {}", e);
+            storage.rollback_batch().await?;
+            return Err(e)
+        }
+        storage.commit_batch()?;
+
+        Ok(())
+    }
+
+    async fn create_genesis_block_for_storage(&self, storage: &mut S) -> Result<(), BlockchainError> {
         // register XELIS asset
         debug!("Registering XELIS asset: {} at topoheight 0", XELIS_ASSET);
         storage.add_asset(&XELIS_ASSET, AssetData::new(0, COIN_DECIMALS)).await?;
 
-        let genesis_block = if GENESIS_BLOCK.len() != 0 {
+        // register the other assets allocated in genesis
+        // their supply is set at topoheight 0 when the allocations are credited
+        for allocation in &self.chain_spec.allocations {
+            if !storage.asset_exist(&allocation.asset).await? {
+                debug!("Registering allocated asset: {} at topoheight 0", allocation.asset);
+                storage.add_asset(&allocation.asset, AssetData::new(0, COIN_DECIMALS)).await?;
+            }
+        }
+
+        let genesis_block = if let Some(genesis) = self.chain_spec.build_genesis_block() {
+            info!("Using genesis block '{}' from chain spec", self.genesis_hash);
+            genesis
+        } else if GENESIS_BLOCK.len() != 0 {
             info!("De-serializing genesis block...");
             let genesis = Block::from_hex(GENESIS_BLOCK.to_owned())?;
-            if *genesis.get_miner() != *DEV_PUBLIC_KEY {
+            if *genesis.get_miner() != *self.get_dev_public_key() {
                 return Err(BlockchainError::GenesisBlockMiner)
             }
 
@@ -341,7 +644,7 @@ impl<S: Storage> Blockchain<S> {
         } else {
             error!("No genesis block found!");
             info!("Generating a new genesis block...");
-            let header = BlockHeader::new(0, 0, get_current_timestamp(), Vec::new(), [0u8; EXTRA_NONCE_SIZE], DEV_PUBLIC_KEY.clone(), Vec::new());
+            let header = BlockHeader::new(0, 0, get_current_timestamp(), Vec::new(), [0u8; EXTRA_NONCE_SIZE], self.get_dev_public_key().clone(), Vec::new());
             let block = Block::new(Immutable::Owned(header), Vec::new());
             info!("Genesis generated: {}", block.to_hex());
             block
@@ -351,7 +654,7 @@ impl<S: Storage> Blockchain<S> {
         storage.set_topo_height_for_block(&genesis_block.hash(), 0).await?;
         storage.set_top_height(0)?;
 
-        self.add_new_block_for_storage(&mut storage, genesis_block, false, false).await?;
+        self.add_new_block_in_batch(storage, genesis_block, false, false, false).await?;
 
         Ok(())
     }
@@ -389,6 +692,384 @@ impl<S: Storage> Blockchain<S> {
         self.allow_fast_sync_mode
     }
 
+    pub fn is_account_history_indexed(&self) -> bool {
+        self.account_history_index
+    }
+
+    // Index all transactions executed in the blocks available on disk
+    async fn build_account_history_index(&self, storage: &mut S) -> Result<(), BlockchainError> {
+        // delete entries of a previous incomplete build
+        storage.set_account_history_indexed(false)?;
+        let start = storage.get_pruned_topoheight()?.unwrap_or(0);
+        let topoheight = self.get_topo_height();
+        info!("Building account history index from topoheight {} to {}", start, topoheight);
+        for topo in start..=topoheight {
+            let hash = storage.get_hash_at_topo_height(topo).await?;
+            let block = storage.get_block(&hash).await?;
+            for (tx, tx_hash) in block.get_transactions().iter().zip(block.get_txs_hashes()) {
+                if storage.is_tx_executed_in_block(tx_hash, &hash)? {
+                    self.add_transaction_to_account_history(storage, tx, tx_hash, topo).await?;
+                }
+            }
+        }
+        storage.set_account_history_indexed(true)?;
+        info!("Account history index built");
+        Ok(())
+    }
+
+    // index the transaction for its sender and all its receivers
+    async fn add_transaction_to_account_history(&self, storage: &mut S, tx: &Transaction, tx_hash: &Hash, topoheight: u64) -> Result<(), BlockchainError> {
+        storage.add_account_history_entry(tx.get_owner(), topoheight, tx_hash, AccountHistoryDirection::Outgoing).await?;
+        if let TransactionType::Transfer(transfers) = tx.get_data() {
+            for transfer in transfers {
+                storage.add_account_history_entry(&transfer.to, topoheight, tx_hash, AccountHistoryDirection::Incoming).await?;
+            }
+        }
+        Ok(())
+    }
+
+    // Export all blocks with their transactions in topological order to a chain file
+    // Returns the number of blocks exported
+    pub async fn export_chain(&self, path: &str) -> Result<u64, BlockchainError> {
+        let storage = self.storage.read().await;
+        if let Some(pruned_topoheight) = storage.get_pruned_topoheight()? {
+            return Err(BlockchainError::ChainIsPruned(pruned_topoheight))
+        }
+
+        let topoheight = self.get_topo_height();
+        let count = topoheight + 1;
+        info!("Exporting {} blocks to {}", count, path);
+        let mut writer = ChainFileWriter::create(path, self.network, count)?;
+        for topo in 0..=topoheight {
+            let (_, header) = storage.get_block_header_at_topoheight(topo).await?;
+            let mut transactions = Vec::with_capacity(header.get_txs_count());
+            for hash in header.get_txs_hashes() {
+                transactions.push(Immutable::Arc(storage.get_transaction(hash).await?));
+            }
+
+            writer.write_block(&Block::new(Immutable::Arc(header), transactions))?;
+            if (topo + 1) % 1000 == 0 {
+                info!("{}/{} blocks exported", topo + 1, count);
+            }
+        }
+
+        let checksum = writer.finish()?;
+        info!("Chain exported to {} with checksum {}", path, checksum);
+        Ok(count)
+    }
+
+    // Import all blocks from a chain file, blocks already in chain are skipped
+    // In trusted mode, PoW and transactions are not verified
+    // Returns the number of blocks imported and skipped
+    pub async fn import_chain(&self, path: &str, trusted: bool) -> Result<(u64, u64), BlockchainError> {
+        info!("Verifying chain file {}", path);
+        let header = ChainFileReader::verify(path)?;
+        if *header.get_network() != self.network {
+            return Err(BlockchainError::InvalidNetwork)
+        }
+
+        if trusted {
+            warn!("Importing chain in trusted mode, blocks PoW and transactions will not be verified");
+        }
+
+        let total = header.get_blocks_count();
+        let mut reader = ChainFileReader::open(path)?;
+        let mut storage = self.storage.write().await;
+        let (mut imported, mut skipped) = (0, 0);
+        while let Some(block) = reader.next_block()? {
+            if storage.has_block(&block.hash()).await? {
+                skipped += 1;
+                continue;
+            }
+
+            // a genesis block can't be added again
+            if block.get_tips().is_empty() {
+                error!("Genesis block from chain file doesn't match our genesis block");
+                return Err(BlockchainError::InvalidGenesisBlock)
+            }
+
+            self.add_new_block_for_storage(&mut storage, block, false, false, trusted).await?;
+            imported += 1;
+            if (imported + skipped) % 1000 == 0 {
+                info!("{}/{} blocks imported", imported + skipped, total);
+            }
+        }
+
+        info!("Chain imported: {} blocks added, {} already in chain", imported, skipped);
+        Ok((imported, skipped))
+    }
+
+    // Write the state (assets, accounts) at the stable topoheight and the last blocks metadata to a snapshot file
+    // This is the same data as the one sent to a peer during a fast sync
+    // Returns the snapshot header and its state hash
+    pub async fn create_snapshot(&self, path: &str) -> Result<(SnapshotFileHeader, Hash), BlockchainError> {
+        let storage = self.storage.read().await;
+        let tips = storage.get_tips().await?;
+        let (top_hash, height) = self.find_common_base(&storage, &tips).await?;
+        let topoheight = storage.get_topo_height_for_hash(&top_hash).await?;
+        let genesis_hash = storage.get_hash_at_topo_height(0).await?;
+
+        info!("Creating snapshot at stable topoheight {} to {}", topoheight, path);
+        let header = SnapshotFileHeader::new(self.network, topoheight, height, top_hash, genesis_hash);
+        let mut writer = SnapshotFileWriter::create(path, &header)?;
+
+        let mut assets = Vec::new();
+        loop {
+            let page = storage.get_partial_assets(MAX_ITEMS_PER_PAGE, assets.len(), 0, topoheight).await?;
+            let count = page.len();
+            for asset in page {
+                assets.push(asset.get_asset().clone());
+                let state = self.get_asset_state(&storage, asset.get_asset(), topoheight).await?;
+                writer.write_entry(&SnapshotEntry::Asset(asset, state))?;
+            }
+
+            if count < MAX_ITEMS_PER_PAGE {
+                break;
+            }
+        }
+        debug!("{} assets written to snapshot", assets.len());
+
+        let mut accounts = 0;
+        loop {
+            let keys = storage.get_partial_keys(MAX_ITEMS_PER_PAGE, accounts, 0, topoheight).await?;
+            let mut balances: Vec<Vec<(Hash, u64)>> = vec![Vec::new(); keys.len()];
+            let mut locked: Vec<Vec<(Hash, LockedBalance)>> = vec![Vec::new(); keys.len()];
+            for asset in &assets {
+                for (i, balance) in storage.get_balances(asset, keys.iter(), topoheight).await?.into_iter().enumerate() {
+                    if let Some(balance) = balance {
+                        balances[i].push((asset.clone(), balance));
+                    }
+                }
+
+                for (i, key) in keys.iter().enumerate() {
+                    for pending in self.get_pending_locked_balances(&storage, key, asset, topoheight).await? {
+                        locked[i].push((asset.clone(), pending));
+                    }
+                }
+            }
+
+            let count = keys.len();
+            for ((key, balances), locked) in keys.into_iter().zip(balances).zip(locked) {
+                let nonce = storage.get_nonce_at_maximum_topoheight(&key, topoheight).await?.map(|(_, v)| v.get_nonce()).unwrap_or(0);
+                let multisig = storage.get_multisig_at_maximum_topoheight(&key, topoheight).await?;
+                writer.write_entry(&SnapshotEntry::Account(SnapshotAccount { key, nonce, balances, locked, multisig }))?;
+            }
+            accounts += count;
+
+            if count < MAX_ITEMS_PER_PAGE {
+                break;
+            }
+        }
+        debug!("{} accounts written to snapshot", accounts);
+
+        // same range as the blocks metadata sent during a fast sync
+        let pruned_topoheight = storage.get_pruned_topoheight()?.unwrap_or(0);
+        let lower = if topoheight.saturating_sub(PRUNE_SAFETY_LIMIT) <= pruned_topoheight {
+            pruned_topoheight + 1
+        } else {
+            topoheight - PRUNE_SAFETY_LIMIT
+        };
+
+        for topo in (lower..=topoheight).rev() {
+            let (hash, block_header) = storage.get_block_header_at_topoheight(topo).await?;
+            let mut transactions = Vec::with_capacity(block_header.get_txs_count());
+            for tx_hash in block_header.get_txs_hashes() {
+                transactions.push(Immutable::Arc(storage.get_transaction(tx_hash).await?));
+            }
+
+            let metadata = BlockMetadata {
+                supply: storage.get_supply_at_topo_height(topo).await?,
+                reward: storage.get_block_reward_at_topo_height(topo)?,
+                difficulty: storage.get_difficulty_for_block_hash(&hash).await?,
+                cumulative_difficulty: storage.get_cumulative_difficulty_for_block_hash(&hash).await?,
+                hash
+            };
+            writer.write_entry(&SnapshotEntry::Block(metadata, Block::new(Immutable::Arc(block_header), transactions)))?;
+        }
+
+        let state_hash = writer.finish()?;
+        let state_root = storage.get_state_root_at_topoheight(topoheight).await?;
+        info!("Snapshot created at topoheight {} with top hash {}, state hash {} and state root {}", topoheight, header.get_top_hash(), state_hash, state_root);
+        Ok((header, state_hash))
+    }
+
+    // Boot a new chain from a snapshot file, like a fast sync would do with a peer
+    // Returns the snapshot header and its state hash
+    pub async fn load_snapshot(&self, path: &str) -> Result<(SnapshotFileHeader, Hash), BlockchainError> {
+        info!("Verifying snapshot file {}", path);
+        let (header, state_hash) = SnapshotFileReader::verify(path)?;
+        if *header.get_network() != self.network {
+            return Err(BlockchainError::InvalidNetwork)
+        }
+
+        let topoheight = self.get_topo_height();
+        if topoheight != 0 {
+            return Err(BlockchainError::ChainNotEmpty(topoheight))
+        }
+
+        let mut storage = self.storage.write().await;
+        if storage.get_hash_at_topo_height(0).await? != *header.get_genesis_hash() {
+            error!("Genesis block from snapshot file doesn't match our genesis block");
+            return Err(BlockchainError::InvalidGenesisBlock)
+        }
+
+        storage.start_batch()?;
+        if let Err(e) = self.load_snapshot_in_batch(&mut storage, path).await {
+            debug!("Rolling back changes from snapshot: {}", e);
+            storage.rollback_batch().await?;
+            return Err(e)
+        }
+        storage.commit_batch()?;
+        let state_root = storage.get_state_root_at_topoheight(header.get_topoheight()).await?;
+        drop(storage);
+
+        self.reload_from_disk().await?;
+        info!("Snapshot loaded at topoheight {} with top hash {}, state hash {} and state root {}", header.get_topoheight(), header.get_top_hash(), state_hash, state_root);
+        Ok((header, state_hash))
+    }
+
+    async fn load_snapshot_in_batch(&self, storage: &mut S, path: &str) -> Result<(), BlockchainError> {
+        let mut reader = SnapshotFileReader::open(path)?;
+        let stable_topoheight = reader.get_header().get_topoheight();
+        let mut lowest_topoheight = stable_topoheight;
+        let mut blocks = 0;
+        while let Some(entry) = reader.next_entry()? {
+            match entry {
+                SnapshotEntry::Asset(asset, state) => {
+                    let (asset, data) = asset.consume();
+                    debug!("Saving asset {} at topoheight {}", asset, stable_topoheight);
+                    storage.add_asset(&asset, data).await?;
+                    Self::set_asset_state(storage, &asset, stable_topoheight, &state)?;
+                },
+                SnapshotEntry::Account(account) => {
+                    debug!("Saving nonce {} for {}", account.nonce, account.key);
+                    storage.set_nonce_at_topoheight(&account.key, account.nonce, stable_topoheight).await?;
+                    if let Some(setup) = &account.multisig {
+                        storage.set_multisig_at_topoheight(&account.key, stable_topoheight, Some(setup))?;
+                    }
+                    for (asset, balance) in account.balances {
+                        let mut versioned_balance = storage.get_new_versioned_balance(&account.key, &asset, stable_topoheight).await?;
+                        versioned_balance.set_balance(balance);
+                        storage.set_balance_to(&account.key, &asset, stable_topoheight, &versioned_balance).await?;
+                        storage.set_last_topoheight_for_balance(&account.key, &asset, stable_topoheight)?;
+                    }
+
+                    for (asset, locked) in account.locked {
+                        storage.add_locked_balance(&account.key, &asset, stable_topoheight, locked.get_unlock(), locked.get_amount()).await?;
+                    }
+                },
+                SnapshotEntry::Block(metadata, block) => {
+                    let hash = block.hash();
+                    if hash != metadata.hash {
+                        return Err(BlockchainError::InvalidSnapshotFile)
+                    }
+
+                    blocks += 1;
+                    if storage.has_block(&hash).await? {
+                        continue;
+                    }
+
+                    lowest_topoheight = stable_topoheight - (blocks - 1);
+                    debug!("Saving block metadata {} at topoheight {}", hash, lowest_topoheight);
+                    let (block_header, txs) = block.split();
+                    for tx_hash in block_header.get_txs_hashes() {
+                        storage.add_block_for_tx(tx_hash, &hash)?;
+                    }
+
+                    storage.set_supply_at_topo_height(lowest_topoheight, metadata.supply)?;
+                    storage.set_block_reward_at_topo_height(lowest_topoheight, metadata.reward)?;
+                    storage.set_topo_height_for_block(&hash, lowest_topoheight).await?;
+                    storage.set_cumulative_difficulty_for_block_hash(&hash, metadata.cumulative_difficulty).await?;
+                    storage.save_block(block_header.to_arc(), &txs, metadata.difficulty, hash).await?;
+                }
+            }
+        }
+
+        let header = reader.get_header();
+        storage.set_pruned_topoheight(lowest_topoheight)?;
+        storage.set_top_topoheight(stable_topoheight)?;
+        storage.set_top_height(header.get_height())?;
+        storage.store_tips(&HashSet::from([header.get_top_hash().clone()]))?;
+        Ok(())
+    }
+
+    // Verify the consistency of the storage and recompute the cumulative difficulty of all ordered blocks
+    // In repair mode, derived data and wrong cumulative difficulties are rewritten
+    pub async fn check_db(&self, repair: bool) -> Result<Vec<IntegrityReport>, BlockchainError> {
+        let mut storage = self.storage.write().await;
+        let mut reports = storage.check_db(repair).await?;
+
+        let name = "Cumulative difficulties are recomputed correctly";
+        info!("Integrity check {}/{}: {}", reports.len() + 1, reports.len() + 1, name);
+        let mut report = IntegrityReport::new(name);
+        if repair {
+            storage.start_batch()?;
+        }
+
+        match self.check_cumulative_difficulties(&mut storage, repair, &mut report).await {
+            Ok(()) => if repair {
+                storage.commit_batch()?;
+            },
+            Err(e) => {
+                if repair {
+                    storage.rollback_batch().await?;
+                }
+                return Err(e)
+            }
+        };
+
+        info!("{} entries checked, {} errors found, {} repaired", report.checked, report.errors.len(), report.repaired);
+        reports.push(report);
+        Ok(reports)
+    }
+
+    // blocks are checked in topological order so the cumulative difficulty of their base is already verified
+    async fn check_cumulative_difficulties(&self, storage: &mut S, repair: bool, report: &mut IntegrityReport) -> Result<(), BlockchainError> {
+        let pruned_topoheight = storage.get_pruned_topoheight()?;
+        for topoheight in pruned_topoheight.unwrap_or(0)..=self.get_topo_height() {
+            // missing blocks are already reported by the storage checks
+            let hash = match storage.get_hash_at_topo_height(topoheight).await {
+                Ok(hash) => hash,
+                Err(_) => continue
+            };
+            let header = match storage.get_block_header_by_hash(&hash).await {
+                Ok(header) => header,
+                Err(_) => continue
+            };
+
+            let expected = if header.get_tips().is_empty() {
+                GENESIS_BLOCK_DIFFICULTY
+            } else {
+                let res = match self.find_common_base(storage, header.get_tips()).await {
+                    Ok((base, base_height)) => self.find_tip_work_score(storage, &hash, &base, base_height).await,
+                    Err(e) => Err(e)
+                };
+
+                match res {
+                    Ok((_, cumulative_difficulty)) => cumulative_difficulty,
+                    // past blocks of the first blocks above the pruned topoheight may be deleted
+                    Err(e) if pruned_topoheight.is_some() => {
+                        debug!("Cumulative difficulty of block {} can't be recomputed: {}", hash, e);
+                        continue;
+                    },
+                    Err(e) => return Err(e)
+                }
+            };
+
+            report.checked += 1;
+            let cumulative_difficulty = storage.get_cumulative_difficulty_for_block_hash(&hash).await.ok();
+            if cumulative_difficulty != Some(expected) {
+                report.errors.push(format!("Cumulative difficulty of block {} at topoheight {} is {:?} but should be {}", hash, topoheight, cumulative_difficulty, expected));
+                if repair {
+                    storage.set_cumulative_difficulty_for_block_hash(&hash, expected).await?;
+                    report.repaired += 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     pub async fn prune_until_topoheight(&self, topoheight: u64) -> Result<u64, BlockchainError> {
         let mut storage = self.storage.write().await;
         self.prune_until_topoheight_for_storage(topoheight, &mut storage).await
@@ -434,6 +1115,10 @@ impl<S: Storage> Blockchain<S> {
             storage.delete_versioned_balances_below_topoheight(located_sync_topoheight).await?;
             // delete nonces versions
             storage.delete_versioned_nonces_below_topoheight(located_sync_topoheight).await?;
+            // delete state tree nodes versions
+            storage.delete_state_tree_below_topoheight(located_sync_topoheight).await?;
+            // delete history of pruned transactions
+            storage.delete_account_history_below_topoheight(located_sync_topoheight).await?;
 
             storage.set_pruned_topoheight(located_sync_topoheight)?;
             Ok(located_sync_topoheight)
@@ -459,6 +1144,31 @@ impl<S: Storage> Blockchain<S> {
         Ok(0)
     }
 
+    // Verify that the block ordered at this topoheight is the one expected by the checkpoint, if any
+    pub fn verify_checkpoint(&self, topoheight: u64, hash: &Hash) -> Result<(), BlockchainError> {
+        match self.checkpoints.get(&topoheight) {
+            Some(expected) if expected != hash => {
+                error!("Block {} at topoheight {} doesn't match checkpoint {}", hash, topoheight, expected);
+                Err(BlockchainError::CheckpointMismatch(topoheight, hash.clone(), expected.clone()))
+            },
+            _ => Ok(())
+        }
+    }
+
+    // Verify that reordering all blocks from this topoheight doesn't change a checkpoint already reached
+    pub fn verify_reorg_above_checkpoints(&self, topoheight: u64) -> Result<(), BlockchainError> {
+        let current_topoheight = self.get_topo_height();
+        if topoheight > current_topoheight {
+            return Ok(())
+        }
+
+        if let Some((checkpoint, _)) = self.checkpoints.range(topoheight..=current_topoheight).next_back() {
+            error!("Reorganization from topoheight {} would change the checkpoint at topoheight {}", topoheight, checkpoint);
+            return Err(BlockchainError::ReorgBelowCheckpoint(*checkpoint))
+        }
+        Ok(())
+    }
+
     // returns the highest (unstable) height on the chain
     pub fn get_height(&self) -> u64 {
         self.height.load(Ordering::Acquire)
@@ -840,7 +1550,7 @@ impl<S: Storage> Blockchain<S> {
             let block_tips = storage.get_past_blocks_for_block_hash(hash).await?;
             // only the genesis block can have 0 tips, returns its hash
             if block_tips.len() == 0 {
-                let result = vec![GENESIS_BLOCK_HASH.clone()];
+                let result = vec![self.genesis_hash.clone()];
                 cache.put((hash.clone(), base.clone(), base_height), result.clone());
                 return Ok(result)
             }
@@ -904,7 +1614,7 @@ impl<S: Storage> Blockchain<S> {
 
         let height = blockdag::calculate_height_at_tips(provider, tips).await?;
         if height < 3 {
-            return Ok(MINIMUM_DIFFICULTY)
+            return Ok(self.get_minimum_difficulty())
         }
 
         let best_tip = blockdag::find_best_tip_by_cumulative_difficulty(provider, tips).await?;
@@ -915,7 +1625,7 @@ impl<S: Storage> Blockchain<S> {
         let parent_best_tip = blockdag::find_best_tip_by_cumulative_difficulty(provider, &parent_tips).await?;
         let parent_best_tip_timestamp = provider.get_timestamp_for_block_hash(parent_best_tip).await?;
  
-        let difficulty = calculate_difficulty(parent_best_tip_timestamp, best_tip_timestamp, biggest_difficulty);
+        let difficulty = calculate_difficulty(parent_best_tip_timestamp, best_tip_timestamp, biggest_difficulty, self.get_block_time_target(), self.get_minimum_difficulty());
         Ok(difficulty)
     }
 
@@ -924,37 +1634,107 @@ impl<S: Storage> Blockchain<S> {
         self.difficulty.load(Ordering::SeqCst)
     }
 
-    // pass in params the already computed block hash and its tips
-    // check the difficulty calculated at tips
+    // retrieve the PoW algorithm to use for a block header
+    // its version must match the one expected at its height
+    pub fn get_pow_algorithm_for_block(&self, header: &BlockHeader) -> Result<&'static dyn PowAlgorithm, BlockchainError> {
+        let expected = self.get_version_at_height(header.get_height());
+        if header.get_version() != expected {
+            return Err(BlockchainError::InvalidBlockVersion(expected, header.get_version()))
+        }
+
+        get_pow_algorithm(expected).ok_or(BlockchainError::NoPowAlgorithm(expected))
+    }
+
+    // compute the POW hash of the block header using the algorithm selected by its version
+    // check the difficulty calculated at its tips
     // if the difficulty is valid, returns it (prevent to re-compute it)
-    pub async fn verify_proof_of_work<D: DifficultyProvider>(&self, provider: &D, hash: &Hash, tips: &Vec<Hash>) -> Result<Difficulty, BlockchainError> {
-        let difficulty = self.get_difficulty_at_tips(provider, tips).await?;
-        if self.simulator || check_difficulty(hash, difficulty)? {
+    pub async fn verify_proof_of_work<D: DifficultyProvider>(&self, provider: &D, header: &BlockHeader) -> Result<Difficulty, BlockchainError> {
+        let algorithm = self.get_pow_algorithm_for_block(header)?;
+        let pow_hash = header.get_pow_hash(algorithm);
+        trace!("POW hash ({}): {}", algorithm.name(), pow_hash);
+
+        let difficulty = self.get_difficulty_at_tips(provider, header.get_tips()).await?;
+        if self.simulator || check_difficulty(&pow_hash, difficulty)? {
             Ok(difficulty)
         } else {
             Err(BlockchainError::InvalidDifficulty)
         }
     }
 
-    pub fn get_p2p(&self) -> &RwLock<Option<Arc<P2pServer<S>>>> {
-        &self.p2p
+    // topoheight of the common base of the tips, a block built on them commits the state root at it
+    pub async fn get_tips_base_topoheight<'a, I: IntoIterator<Item = &'a Hash> + Copy>(&self, storage: &S, tips: I) -> Result<u64, BlockchainError> {
+        let (base_hash, _) = self.find_common_base(storage, tips).await?;
+        storage.get_topo_height_for_hash(&base_hash).await
     }
 
-    pub fn get_rpc(&self) -> &RwLock<Option<SharedDaemonRpcServer<S>>> {
-        &self.rpc
-    }
+    // state root to commit in a block built on these tips, None if this node doesn't have the state at their base
+    // it is the state root at the common base of the tips, which is stable and executed the same way by all nodes
+    pub async fn get_state_root_at_tips<'a, I: IntoIterator<Item = &'a Hash> + Copy>(&self, storage: &S, tips: I) -> Result<Option<Hash>, BlockchainError> {
+        let topoheight = self.get_tips_base_topoheight(storage, tips).await?;
+        if !storage.has_state_root_at_topoheight(topoheight).await? {
+            return Ok(None)
+        }
 
-    pub fn get_storage(&self) -> &RwLock<S> {
-        &self.storage
+        Ok(Some(storage.get_state_root_at_topoheight(topoheight).await?))
     }
 
-    pub fn get_mempool(&self) -> &RwLock<Mempool> {
-        &self.mempool
-    }
+    // verify the state root committed by the block header from STATE_ROOT_BLOCK_VERSION
+    // it's skipped when the state at its tips base was not built by this node (fast sync or snapshot)
+    async fn verify_state_root(&self, storage: &S, header: &BlockHeader) -> Result<(), BlockchainError> {
+        let state_root = match header.get_state_root() {
+            Some(state_root) if header.get_version() >= STATE_ROOT_BLOCK_VERSION => state_root,
+            None if header.get_version() < STATE_ROOT_BLOCK_VERSION => return Ok(()),
+            _ => return Err(BlockchainError::InvalidBlockStateRoot)
+        };
 
-    pub async fn add_tx_to_mempool(&self, tx: Transaction, broadcast: bool) -> Result<(), BlockchainError> {
-        let hash = tx.hash();
-        self.add_tx_to_mempool_with_hash(tx, hash, broadcast).await
+        match self.get_state_root_at_tips(storage, header.get_tips()).await? {
+            Some(expected) if expected != *state_root => {
+                error!("Block at height {} commits the state root {} while {} is expected", header.get_height(), state_root, expected);
+                Err(BlockchainError::StateRootMismatch(expected, state_root.clone()))
+            },
+            Some(_) => Ok(()),
+            None => {
+                debug!("State at the tips base of block at height {} is not available, state root {} can't be verified", header.get_height(), state_root);
+                Ok(())
+            }
+        }
+    }
+
+    // verify the PoW of a header received without all its past blocks against a known difficulty
+    // the difficulty can't be lower than the minimum one
+    pub fn verify_proof_of_work_at_difficulty(&self, header: &BlockHeader, difficulty: Difficulty) -> Result<(), BlockchainError> {
+        if difficulty < self.get_minimum_difficulty() {
+            return Err(BlockchainError::InvalidDifficulty)
+        }
+
+        let algorithm = self.get_pow_algorithm_for_block(header)?;
+        let pow_hash = header.get_pow_hash(algorithm);
+        if self.simulator || check_difficulty(&pow_hash, difficulty)? {
+            Ok(())
+        } else {
+            Err(BlockchainError::InvalidDifficulty)
+        }
+    }
+
+    pub fn get_p2p(&self) -> &RwLock<Option<Arc<P2pServer<S>>>> {
+        &self.p2p
+    }
+
+    pub fn get_rpc(&self) -> &RwLock<Option<SharedDaemonRpcServer<S>>> {
+        &self.rpc
+    }
+
+    pub fn get_storage(&self) -> &RwLock<S> {
+        &self.storage
+    }
+
+    pub fn get_mempool(&self) -> &RwLock<Mempool> {
+        &self.mempool
+    }
+
+    pub async fn add_tx_to_mempool(&self, tx: Transaction, broadcast: bool) -> Result<(), BlockchainError> {
+        let hash = tx.hash();
+        self.add_tx_to_mempool_with_hash(tx, hash, broadcast).await
     }
 
     pub async fn add_tx_to_mempool_with_hash<'a>(&'a self, tx: Transaction, hash: Hash, broadcast: bool) -> Result<(), BlockchainError> {
@@ -963,6 +1743,13 @@ impl<S: Storage> Blockchain<S> {
     }
 
     pub async fn add_tx_to_mempool_with_storage_and_hash<'a>(&'a self, storage: &S, tx: Arc<Transaction>, hash: Hash, broadcast: bool) -> Result<(), BlockchainError> {
+        self.add_tx_to_mempool_with_first_seen(storage, tx, hash, get_current_time(), broadcast).await
+    }
+
+    // first_seen is the time the TX was received, saved TXs keep their original one
+    async fn add_tx_to_mempool_with_first_seen(&self, storage: &S, tx: Arc<Transaction>, hash: Hash, first_seen: u64, broadcast: bool) -> Result<(), BlockchainError> {
+        // hash and fee of the TX replaced by this one
+        let mut replaced = None;
         {
             let mut mempool = self.mempool.write().await;
     
@@ -974,20 +1761,23 @@ impl<S: Storage> Blockchain<S> {
             if storage.is_tx_executed_in_a_block(&hash)? {
                 return Err(BlockchainError::TxAlreadyInBlockchain(hash))
             }
+
+            self.verify_transaction_not_expired(&tx, &hash)?;
     
             // get the highest nonce for this owner
             let owner = tx.get_owner();
             // get the highest nonce available
             // if presents, it means we have at least one tx from this owner in mempool
             if let Some(cache) = mempool.get_cached_nonce(owner) {
-                // we accept to delete a tx from mempool if the new one has a higher fee
+                // replace-by-fee: we accept to delete a tx from mempool
+                // if the new one pays at least rbf_min_fee_bump percent more fees
                 if let Some(hash) = cache.has_tx_with_same_nonce(tx.get_nonce()) {
-                    // TX is in range, we have to delete an existing TX
-                    // check that fees are higher than the future deleted one
                     let other_tx = mempool.view_tx(hash)?;
-                    if other_tx.get_fee() >= tx.get_fee() {
-                        return Err(BlockchainError::InvalidTxFee(other_tx.get_fee() + 1, tx.get_fee()));
+                    let required_fee = calculate_replacement_fee(other_tx.get_fee(), self.rbf_min_fee_bump);
+                    if tx.get_fee() < required_fee {
+                        return Err(BlockchainError::InvalidTxFee(required_fee, tx.get_fee()));
                     }
+                    replaced = Some((hash.clone(), other_tx.get_fee()));
                 }
 
                 // check that the nonce is in the range
@@ -997,19 +1787,23 @@ impl<S: Storage> Blockchain<S> {
                 }
                 // we need to do it in two times because of the constraint of lifetime on &tx
                 let mut balances = HashMap::new();
+                let mut mints = Self::get_pending_mints(&mempool, &tx, replaced.as_ref().map(|(hash, _)| hash.as_ref()));
                 let mut nonces = HashMap::new();
                 // because we already verified the range of nonce
                 nonces.insert(tx.get_owner(), tx.get_nonce());
 
                 // Verify original TX
                 // We may have double spending in balances, but it is ok because miner check that all txs included are valid
-                self.verify_transaction_with_hash(&storage, &tx, &hash, &mut balances, Some(&mut nonces), false).await?;
+                self.verify_transaction_with_hash(&storage, &tx, &hash, self.get_height() + 1, self.get_topo_height(), &mut balances, &mut mints, Some(&mut nonces), false).await?;
             } else {
                 let mut balances = HashMap::new();
-                self.verify_transaction_with_hash(&storage, &tx, &hash, &mut balances, None, false).await?;
+                let mut mints = Self::get_pending_mints(&mempool, &tx, None);
+                self.verify_transaction_with_hash(&storage, &tx, &hash, self.get_height() + 1, self.get_topo_height(), &mut balances, &mut mints, None, false).await?;
             }
 
-            mempool.add_tx(hash.clone(), tx.clone())?;
+            if let Some(replaced_hash) = mempool.add_tx(hash.clone(), tx.clone(), first_seen)? {
+                debug!("TX {} replaced TX {} in mempool", hash, replaced_hash);
+            }
         }
 
         if broadcast {
@@ -1027,6 +1821,23 @@ impl<S: Storage> Blockchain<S> {
                     }
                 }
 
+                if let Some((replaced_hash, replaced_fee)) = replaced {
+                    if rpc.is_event_tracked(&NotifyEvent::TransactionReplaced).await {
+                        let data = TransactionReplacedEvent {
+                            replaced_tx_hash: Cow::Borrowed(&replaced_hash),
+                            tx_hash: Cow::Borrowed(&hash),
+                            owner: Cow::Owned(tx.get_owner().clone().to_address(self.network.is_mainnet())),
+                            nonce: tx.get_nonce(),
+                            replaced_fee,
+                            fee: tx.get_fee()
+                        };
+
+                        if let Err(e) = rpc.notify_clients(&NotifyEvent::TransactionReplaced, json!(data)).await {
+                            debug!("Error while broadcasting event TransactionReplaced to websocket: {}", e);
+                        }
+                    }
+                }
+
                 if rpc.is_event_tracked(&NotifyEvent::TransactionAddedInMempool).await {
                     let data: TransactionResponse<'_, Arc<Transaction>> = TransactionResponse {
                         blocks: None,
@@ -1046,9 +1857,88 @@ impl<S: Storage> Blockchain<S> {
         Ok(())
     }
 
-    // this will be used in future for hard fork versions
-    pub fn get_version_at_height(&self, _height: u64) -> u8 {
-        0
+    // estimate the fee rates required to be included within each target of blocks
+    // based on the fee rates of the last blocks and the current mempool backlog
+    pub async fn estimate_fee_rates(&self, storage: &S) -> Result<Vec<FeeRateEstimate>, BlockchainError> {
+        let topoheight = self.get_topo_height();
+        let mut recent_rates = Vec::with_capacity(FEE_ESTIMATION_BLOCKS as usize);
+        for topoheight in (topoheight.saturating_sub(FEE_ESTIMATION_BLOCKS - 1)..=topoheight).rev() {
+            let hash = storage.get_hash_at_topo_height(topoheight).await?;
+            let block = storage.get_block(&hash).await?;
+            let txs: Vec<(u64, usize)> = block.get_transactions().iter().map(|tx| (tx.get_fee(), tx.size())).collect();
+            recent_rates.push(fee_estimator::get_required_fee_rate(block.size(), &txs, MAX_BLOCK_SIZE));
+        }
+
+        let pending: Vec<(u64, usize)> = {
+            let mempool = self.mempool.read().await;
+            mempool.get_txs().values().map(|sorted_tx| (sorted_tx.get_fee(), sorted_tx.get_size())).collect()
+        };
+
+        Ok(FEE_ESTIMATION_TARGETS.iter().map(|blocks| FeeRateEstimate {
+            blocks: *blocks,
+            fee_rate: fee_estimator::estimate_fee_rate(*blocks, &recent_rates, &pending, MAX_BLOCK_SIZE)
+        }).collect())
+    }
+
+    pub fn get_chain_spec(&self) -> &ChainSpec {
+        &self.chain_spec
+    }
+
+    pub fn get_genesis_hash(&self) -> &Hash {
+        &self.genesis_hash
+    }
+
+    // expected time between two blocks in milliseconds
+    pub fn get_block_time_target(&self) -> u64 {
+        self.chain_spec.block_time_millis
+    }
+
+    pub fn get_minimum_difficulty(&self) -> Difficulty {
+        self.chain_spec.minimum_difficulty
+    }
+
+    pub fn get_dev_fees(&self) -> &[DevFeeThreshold] {
+        &self.chain_spec.dev_fees
+    }
+
+    // key receiving the dev fees
+    pub fn get_dev_public_key(&self) -> &PublicKey {
+        self.chain_spec.get_dev_public_key()
+    }
+
+    // get the whole network upgrades schedule
+    pub fn get_hard_forks(&self) -> &'static [HardFork] {
+        self.hard_forks
+    }
+
+    // hash of the whole network upgrades schedule
+    pub fn get_hard_forks_hash(&self) -> &Hash {
+        &self.hard_forks_hash
+    }
+
+    // minimum fee increase in percent to replace a pending TX
+    pub fn get_rbf_min_fee_bump(&self) -> u64 {
+        self.rbf_min_fee_bump
+    }
+
+    // retrieve the last hard fork activated at this height
+    pub fn get_hard_fork_at_height(&self, height: u64) -> &'static HardFork {
+        self.hard_forks.iter()
+            .rev()
+            .find(|hard_fork| hard_fork.height <= height)
+            .unwrap_or(&self.hard_forks[0])
+    }
+
+    // retrieve the block version to use at this height based on the hard forks
+    pub fn get_version_at_height(&self, height: u64) -> u8 {
+        self.get_hard_fork_at_height(height).version
+    }
+
+    // check if a consensus rule has been enabled by a hard fork activated at this height
+    pub fn is_feature_active_at_height(&self, height: u64, feature: &str) -> bool {
+        self.hard_forks.iter()
+            .take_while(|hard_fork| hard_fork.height <= height)
+            .any(|hard_fork| hard_fork.features.contains(&feature))
     }
 
     pub async fn get_block_template(&self, address: PublicKey) -> Result<BlockHeader, BlockchainError> {
@@ -1125,53 +2015,47 @@ impl<S: Storage> Blockchain<S> {
 
         let height = blockdag::calculate_height_at_tips(storage, &sorted_tips).await?;
         let mut block = BlockHeader::new(self.get_version_at_height(height), height, get_current_timestamp(), sorted_tips, extra_nonce, address, Vec::new());
+        if block.get_version() >= STATE_ROOT_BLOCK_VERSION {
+            let state_root = self.get_state_root_at_tips(storage, block.get_tips()).await?
+                .ok_or(BlockchainError::StateRootNotAvailable)?;
+            block.set_state_root(Some(state_root));
+        }
 
         trace!("Locking mempool for building block template");
         let mempool = self.mempool.read().await;
         trace!("Mempool locked for building block template");
 
-        // get all availables txs and sort them by fee per size
-        let mut txs = mempool.get_txs()
-            .iter()
-            .map(|(hash, tx)| (tx.get_fee(), tx.get_size(), hash, tx.get_tx()))
-            .collect::<Vec<_>>();
-        txs.sort_by(|(a_fee, a_size, _, a_tx), (b_fee, b_size, _, b_tx)| {
-            // If its the same sender, check the nonce
-            if a_tx.get_owner() == b_tx.get_owner() {
-                // Increasing nonces (lower first)
-                let cmp = a_tx.get_nonce().cmp(&b_tx.get_nonce());
-                // If its not equal nonce, returns it
-                if cmp != std::cmp::Ordering::Equal {
-                    return cmp
-                }
-            }
-
-            let a = a_fee * *a_size as u64;
-            let b = b_fee * *b_size as u64;
-            // Decreasing fees (higher first)
-            b.cmp(&a)
-        });
-
-        let mut total_txs_size = 0;
+        // group all availables txs per owner ordered by nonce, each one also takes the space of its hash in the block
+        let mut packages = Vec::with_capacity(mempool.get_nonces_cache().len());
+        for cache in mempool.get_nonces_cache().values() {
+            let mut package = Vec::with_capacity(cache.get_txs().len());
+            for hash in cache.get_txs() {
+                let sorted_tx = mempool.get_sorted_tx(hash)?;
+                package.push(TxCandidate::new((hash, sorted_tx), sorted_tx.get_fee(), sorted_tx.get_size() + HASH_SIZE));
+            }
+            packages.push(package);
+        }
+
+        // the block must stay strictly below the maximum size
+        let max_txs_size = MAX_BLOCK_SIZE.saturating_sub(block.size() + 1);
+        let txs = tx_selector::select_txs(packages, max_txs_size);
+
         let mut nonces: HashMap<&PublicKey, u64> = HashMap::new();
-        let mut block_size = block.size();
         {
             let mut balances = HashMap::new();
-            'main: for (fee, size, hash, tx) in txs {
-                if block_size + total_txs_size + size >= MAX_BLOCK_SIZE {
-                    break 'main;
-                }
-
+            let mut mints = HashMap::new();
+            for (hash, sorted_tx) in txs {
+                let tx = sorted_tx.get_tx();
                 // Check if the TX is valid for this potential block
                 trace!("Checking TX {} with nonce {}", hash, tx.get_nonce());
-                if let Err(e) = self.verify_transaction_with_hash(&storage, tx, hash, &mut balances, Some(&mut nonces), false).await {
+                if let Err(e) = self.verify_transaction_not_expired(tx, hash) {
+                    warn!("TX {} is not valid for mining: {}", hash, e);
+                } else if let Err(e) = self.verify_transaction_with_hash(&storage, tx, hash, block.height, self.get_topo_height(), &mut balances, &mut mints, Some(&mut nonces), false).await {
                     warn!("TX {} is not valid for mining: {}", hash, e);
                 } else {
-                    trace!("Selected {} (nonce: {}, fees: {}) for mining", hash, tx.get_nonce(), format_xelis(fee));
+                    trace!("Selected {} (nonce: {}, fees: {}) for mining", hash, tx.get_nonce(), format_xelis(sorted_tx.get_fee()));
                     // TODO no clone
                     block.txs_hashes.push(hash.as_ref().clone());
-                    block_size += HASH_SIZE; // add the hash size
-                    total_txs_size += size;
                 }
             }
         }
@@ -1202,10 +2086,61 @@ impl<S: Storage> Blockchain<S> {
 
     pub async fn add_new_block(&self, block: Block, broadcast: bool, mining: bool) -> Result<(), BlockchainError> {
         let mut storage = self.storage.write().await;
-        self.add_new_block_for_storage(&mut storage, block, broadcast, mining).await
+        self.add_new_block_for_storage(&mut storage, block, broadcast, mining, false).await
+    }
+
+    // Add a new block to the chain, all changes are applied atomically in storage
+    // A trusted block skips the PoW and transactions verification
+    pub async fn add_new_block_for_storage(&self, storage: &mut S, block: Block, broadcast: bool, mining: bool, trusted: bool) -> Result<(), BlockchainError> {
+        // in-memory chain state, restored if the block can't be applied
+        let height = self.get_height();
+        let topoheight = self.get_topo_height();
+        let stable_height = self.get_stable_height();
+        let difficulty = self.get_difficulty();
+
+        storage.start_batch()?;
+        let effects = match self.add_new_block_in_batch(storage, block, broadcast, mining, trusted).await {
+            Ok(effects) => effects,
+            Err(e) => {
+                debug!("Rolling back changes from block: {}", e);
+                storage.rollback_batch().await?;
+
+                self.height.store(height, Ordering::SeqCst);
+                self.topoheight.store(topoheight, Ordering::SeqCst);
+                self.stable_height.store(stable_height, Ordering::SeqCst);
+                self.difficulty.store(difficulty, Ordering::SeqCst);
+                return Err(e)
+            }
+        };
+        storage.commit_batch()?;
+
+        // block is now saved, we can clean the mempool and announce it
+        self.apply_block_added_effects(storage, effects).await;
+
+        // auto prune mode, in its own batch so a failure doesn't leave the chain partially pruned
+        if let Some(keep_only) = self.auto_prune_keep_n_blocks {
+            // check that the topoheight is greater than the safety limit
+            // and that we can prune the chain using the config while respecting the safety limit
+            let current_topoheight = self.get_topo_height();
+            if current_topoheight > topoheight && current_topoheight % keep_only == 0 && current_topoheight - keep_only > 0 {
+                info!("Auto pruning chain until topoheight {} (keep only {} blocks)", current_topoheight - keep_only, keep_only);
+                storage.start_batch()?;
+                match self.prune_until_topoheight_for_storage(current_topoheight - keep_only, storage).await {
+                    Ok(_) => storage.commit_batch()?,
+                    Err(e) => {
+                        warn!("Error while trying to auto prune chain: {}", e);
+                        storage.rollback_batch().await?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
     }
 
-    pub async fn add_new_block_for_storage(&self, storage: &mut S, block: Block, broadcast: bool, mining: bool) -> Result<(), BlockchainError> {
+    // Verify and apply the block, storage batch must be started before calling it
+    // returns the side effects to apply once the batch is committed
+    async fn add_new_block_in_batch(&self, storage: &mut S, block: Block, broadcast: bool, mining: bool, trusted: bool) -> Result<BlockAddedEffects, BlockchainError> {
         let start = Instant::now();
         let block_hash = block.hash();
         debug!("Add new block {}", block_hash);
@@ -1304,11 +2239,17 @@ impl<S: Storage> Blockchain<S> {
         }
 
         // verify PoW and get difficulty for this block based on tips
-        let pow_hash = block.get_pow_hash();
-        debug!("POW hash: {}", pow_hash);
-        let difficulty = self.verify_proof_of_work(storage, &pow_hash, block.get_tips()).await?;
+        let difficulty = if trusted {
+            // PoW is trusted, only check the block version
+            self.get_pow_algorithm_for_block(&block)?;
+            self.get_difficulty_at_tips(storage, block.get_tips()).await?
+        } else {
+            self.verify_proof_of_work(storage, &block).await?
+        };
         debug!("PoW is valid for difficulty {}", difficulty);
 
+        self.verify_state_root(storage, &block).await?;
+
         { // Transaction verification
             let hashes_len = block.get_txs_hashes().len();
             let txs_len = block.get_transactions().len();
@@ -1317,9 +2258,25 @@ impl<S: Storage> Blockchain<S> {
                 return Err(BlockchainError::InvalidBlockTxs(hashes_len, txs_len));
             }
 
+            // txs are verified against the state of the block past, at the highest topoheight of its ordered tips
+            // or at the base of its tips if none of them is ordered yet
+            let mut tips_topoheight = None;
+            for tip in block.get_tips() {
+                if storage.is_block_topological_ordered(tip).await {
+                    let topoheight = storage.get_topo_height_for_hash(tip).await?;
+                    tips_topoheight = Some(tips_topoheight.map_or(topoheight, |current: u64| current.max(topoheight)));
+                }
+            }
+            let tips_topoheight = match tips_topoheight {
+                Some(topoheight) => topoheight,
+                None if block.get_tips().is_empty() => 0,
+                None => self.get_tips_base_topoheight(storage, block.get_tips()).await?
+            };
+
             let mut cache_account: HashMap<&PublicKey, u64> = HashMap::new();
             let mut cache_tx: HashMap<Hash, bool> = HashMap::new(); // avoid using a TX multiple times
             let mut balances = HashMap::new();
+            let mut mints = HashMap::new();
             let mut all_parents_txs: Option<HashSet<Hash>> = None;
             for (tx, hash) in block.get_transactions().iter().zip(block.get_txs_hashes()) {
                 // verification that the real TX Hash is the same as in block header (and also check the correct order)
@@ -1381,7 +2338,9 @@ impl<S: Storage> Blockchain<S> {
                     }
                 }
 
-                self.verify_transaction_with_hash(storage, tx, &tx_hash, &mut balances, Some(&mut cache_account), false).await?;
+                if !trusted {
+                    self.verify_transaction_with_hash(storage, tx, &tx_hash, block.get_height(), tips_topoheight, &mut balances, &mut mints, Some(&mut cache_account), false).await?;
+                }
 
                 // add tx hash in cache
                 cache_tx.insert(tx_hash, true);
@@ -1414,10 +2373,6 @@ impl<S: Storage> Blockchain<S> {
             cumulative_difficulty
         };
 
-        debug!("Locking mempool write mode");
-        let mut mempool = self.mempool.write().await;
-        debug!("mempool write mode ok");
-
         let mut tips = storage.get_tips().await?;
         tips.insert(block_hash.clone());
         for hash in block.get_tips() {
@@ -1434,9 +2389,7 @@ impl<S: Storage> Blockchain<S> {
         let mut full_order = self.generate_full_order(storage, &best_tip, &base_hash, base_height, base_topo_height).await?;
         debug!("Generated full order size: {}, with base ({}) topo height: {}", full_order.len(), base_hash, base_topo_height);
 
-        // rpc server lock
-        let rpc_server = self.rpc.read().await;
-        let should_track_events = if let Some(rpc) = rpc_server.as_ref() {
+        let should_track_events = if let Some(rpc) = self.rpc.read().await.as_ref() {
             rpc.get_tracked_events().await
         } else {
             HashSet::new()
@@ -1494,6 +2447,7 @@ impl<S: Storage> Blockchain<S> {
 
             // time to order the DAG that is moving
             debug!("Ordering blocks based on generated DAG order ({} blocks)", full_order.len());
+            let mut reordered_data_cleaned = false;
             for (i, hash) in full_order.into_iter().enumerate() {
                 highest_topo = base_topo_height + skipped + i as u64;
 
@@ -1505,11 +2459,36 @@ impl<S: Storage> Blockchain<S> {
                 }
                 is_written = true;
 
+                // state tree nodes and account history written with the previous order must not be reused
+                if !reordered_data_cleaned {
+                    if highest_topo > 0 && highest_topo <= current_topoheight {
+                        storage.delete_state_tree_above_topoheight(highest_topo - 1).await?;
+                        storage.delete_account_history_above_topoheight(highest_topo - 1).await?;
+                    }
+
+                    if highest_topo <= current_topoheight {
+                        // genesis has no transactions, so its own changes are kept when it is ordered again
+                        let topoheight = highest_topo.saturating_sub(1);
+                        // assets are registered again if their tx is still executed
+                        storage.delete_assets_above_topoheight(topoheight).await?;
+                        storage.delete_multisig_above_topoheight(topoheight).await?;
+                        storage.delete_locked_balances_above_topoheight(topoheight).await?;
+                    }
+                    reordered_data_cleaned = true;
+                }
+
+                // blocks may be ordered again at the same topoheight, only a different order is a reorganization
+                if tips_count != 0 && highest_topo <= current_topoheight && storage.get_hash_at_topo_height(highest_topo).await? != hash {
+                    self.verify_reorg_above_checkpoints(highest_topo)?;
+                }
+                self.verify_checkpoint(highest_topo, &hash)?;
+
                 trace!("Ordering block {} at topoheight {}", hash, highest_topo);
 
                 storage.set_topo_height_for_block(&hash, highest_topo).await?;
+                // genesis allocations are included in the initial supply
                 let past_supply = if highest_topo == 0 {
-                    0
+                    self.chain_spec.get_premine_supply()
                 } else {
                     storage.get_supply_at_topo_height(highest_topo - 1).await?
                 };
@@ -1551,11 +2530,39 @@ impl<S: Storage> Blockchain<S> {
                                 continue;
                             }
                         }
+
+                        // the block may be ordered above the expiry topoheight of the tx
+                        if !tx.is_valid_at_topoheight(highest_topo) {
+                            warn!("Tx {} is expired at topoheight {}, skipping...", tx_hash, highest_topo);
+                            // TX will be orphaned
+                            continue;
+                        }
+
+                        // multisig setup of the owner may have changed since the verification if another branch was ordered first
+                        match self.verify_multisig(storage, tx, tx_hash, highest_topo.saturating_sub(1)).await {
+                            Err(BlockchainError::InvalidMultiSig(_) | BlockchainError::MissingMultiSig(_)) => {
+                                warn!("Tx {} doesn't match the multisig setup of its owner at topoheight {}, skipping...", tx_hash, highest_topo);
+                                // TX will be orphaned
+                                continue;
+                            },
+                            res => res?
+                        };
                         // mark tx as executed
                         trace!("Executing tx {} in block {}", tx_hash, hash);
                         storage.set_tx_executed_in_block(tx_hash, &hash)?;
 
-                        self.execute_transaction(storage, &tx, &mut local_nonces, &mut balances, highest_topo).await?;    
+                        self.execute_transaction(storage, &tx, tx_hash, &mut local_nonces, &mut balances, highest_topo).await?;    
+                        if self.account_history_index {
+                            self.add_transaction_to_account_history(storage, tx, tx_hash, highest_topo).await?;
+                        }
+
+                        if let TransactionType::CreateAsset { .. } = tx.get_data() {
+                            if should_track_events.contains(&NotifyEvent::NewAsset) {
+                                let data = storage.get_asset_data(tx_hash)?;
+                                let value = json!(AssetWithData::new(tx_hash.clone(), data));
+                                events.entry(NotifyEvent::NewAsset).or_insert_with(Vec::new).push(value);
+                            }
+                        }
                         // if the rpc_server is enable, track events
                         if should_track_events.contains(&NotifyEvent::TransactionExecuted) {
                             let value = json!(TransactionExecutedEvent {
@@ -1572,6 +2579,18 @@ impl<S: Storage> Blockchain<S> {
                 // reward the miner
                 self.reward_miner(storage, &block, block_reward, total_fees, &mut balances, highest_topo).await?;
 
+                if highest_topo == 0 {
+                    for allocation in &self.chain_spec.allocations {
+                        trace!("Crediting genesis allocation of {} {} to {}", allocation.amount, allocation.asset, allocation.address);
+                        self.add_balance(storage, &mut balances, allocation.address.get_public_key(), &allocation.asset, allocation.amount, highest_topo).await?;
+                        // XELIS allocations are already included in the supply
+                        if allocation.asset != XELIS_ASSET {
+                            let supply = storage.get_asset_supply_at_maximum_topoheight(&allocation.asset, highest_topo).await?;
+                            storage.set_asset_supply_at_topoheight(&allocation.asset, highest_topo, supply + allocation.amount)?;
+                        }
+                    }
+                }
+
                 // save balances for each topoheight
                 for (key, assets) in balances {
                     for (asset, balance) in assets {
@@ -1648,20 +2667,6 @@ impl<S: Storage> Blockchain<S> {
             current_topoheight = highest_topo;
         }
 
-        // auto prune mode
-        if extended {
-            if let Some(keep_only) = self.auto_prune_keep_n_blocks {
-                // check that the topoheight is greater than the safety limit
-                // and that we can prune the chain using the config while respecting the safety limit
-                if current_topoheight % keep_only == 0 && current_topoheight - keep_only > 0 {
-                    info!("Auto pruning chain until topoheight {} (keep only {} blocks)", current_topoheight - keep_only, keep_only);
-                    if let Err(e) = self.prune_until_topoheight_for_storage(current_topoheight - keep_only, storage).await {
-                        warn!("Error while trying to auto prune chain: {}", e);
-                    }
-                }
-            }
-        }
-
         storage.store_tips(&tips)?;
 
         let mut current_height = current_height;
@@ -1703,26 +2708,55 @@ impl<S: Storage> Blockchain<S> {
             self.difficulty.store(difficulty, Ordering::SeqCst);
         }
 
-        // Clean all old txs
-        mempool.clean_up(nonces).await;
-
         info!("Processed block {} at height {} in {} ms with {} txs", block_hash, block.get_height(), start.elapsed().as_millis(), block.get_txs_count());
 
+        Ok(BlockAddedEffects {
+            block,
+            block_hash,
+            cumulative_difficulty,
+            topoheight: current_topoheight,
+            height: current_height,
+            nonces,
+            should_track_events,
+            events,
+            broadcast,
+            mining
+        })
+    }
+
+    // Clean the mempool, broadcast the block and notify the RPC clients
+    // only called once the block is committed in storage, so nothing here can fail
+    async fn apply_block_added_effects(&self, storage: &S, effects: BlockAddedEffects) {
+        let BlockAddedEffects { block, block_hash, cumulative_difficulty, topoheight, height, nonces, should_track_events, mut events, broadcast, mining } = effects;
+        {
+            let mut mempool = self.mempool.write().await;
+            // Clean all old txs
+            mempool.clean_up(nonces).await;
+            // and the ones which can't be included in the next block anymore
+            mempool.clean_up_expired(self.get_topo_height() + 1);
+            // and the ones waiting for too long
+            mempool.clean_up_old(get_current_time());
+        }
+
         if broadcast {
             trace!("Broadcasting block");
             if let Some(p2p) = self.p2p.read().await.as_ref() {
                 trace!("P2p locked, broadcasting in new task");
-                let p2p = p2p.clone();
-                let pruned_topoheight = storage.get_pruned_topoheight()?;
-                let block_hash = block_hash.clone();
-                tokio::spawn(async move {
-                    p2p.broadcast_block(&block, cumulative_difficulty, current_topoheight, current_height, pruned_topoheight, &block_hash, mining).await;
-                });
+                match storage.get_pruned_topoheight() {
+                    Ok(pruned_topoheight) => {
+                        let p2p = p2p.clone();
+                        let block_hash = block_hash.clone();
+                        tokio::spawn(async move {
+                            p2p.broadcast_block(&block, cumulative_difficulty, topoheight, height, pruned_topoheight, &block_hash, mining).await;
+                        });
+                    },
+                    Err(e) => error!("Error while retrieving pruned topoheight to broadcast block {}: {}", block_hash, e)
+                }
             }
         }
 
         // broadcast to websocket new block
-        if let Some(rpc) = rpc_server.as_ref() {
+        if let Some(rpc) = self.rpc.read().await.as_ref() {
             // if we have a getwork server, notify miners
             if let Some(getwork) = rpc.getwork_server() {
                 let getwork = getwork.clone();
@@ -1758,8 +2792,6 @@ impl<S: Storage> Blockchain<S> {
                 }
             });
         }
-
-        Ok(())
     }
 
     // Get block reward based on the type of the block
@@ -1767,10 +2799,10 @@ impl<S: Storage> Blockchain<S> {
     pub async fn get_block_reward(&self, storage: &S, hash: &Hash, past_supply: u64) -> Result<u64, BlockchainError> {
         let block_reward = if self.is_side_block(storage, &hash).await? {
             trace!("Block {} is a side block", hash);
-            let reward = get_block_reward(past_supply);
+            let reward = get_block_reward(past_supply, self.get_block_time_target());
             reward * SIDE_BLOCK_REWARD_PERCENT / 100
         } else {
-            get_block_reward(past_supply)
+            get_block_reward(past_supply, self.get_block_time_target())
         };
         Ok(block_reward)
     }
@@ -1868,7 +2900,17 @@ impl<S: Storage> Blockchain<S> {
         let current_height = self.get_height();
         let current_topoheight = self.get_topo_height();
         warn!("Rewind chain with count = {}, height = {}, topoheight = {}", count, current_height, current_topoheight);
-        let (new_height, new_topoheight, txs) = storage.pop_blocks(current_height, current_topoheight, count).await?;
+        // all blocks are popped atomically, so a crash can't leave the chain partially rewinded
+        storage.start_batch()?;
+        let (new_height, new_topoheight, txs) = match storage.pop_blocks(current_height, current_topoheight, count).await {
+            Ok(res) => res,
+            Err(e) => {
+                debug!("Rolling back changes from rewind: {}", e);
+                storage.rollback_batch().await?;
+                return Err(e)
+            }
+        };
+        storage.commit_batch()?;
         debug!("New topoheight: {} (diff: {})", new_topoheight, current_topoheight - new_topoheight);
 
         {
@@ -1915,19 +2957,42 @@ impl<S: Storage> Blockchain<S> {
     // verify the transaction and returns fees available
     // nonces allow us to support multiples tx from same owner in the same block
     // txs must be sorted in ascending order based on account nonce
-    async fn verify_transaction_with_hash<'a>(&self, storage: &S, tx: &'a Transaction, hash: &Hash, balances: &mut HashMap<&'a PublicKey, HashMap<&'a Hash, u64>>, nonces: Option<&mut HashMap<&'a PublicKey, u64>>, skip_nonces: bool) -> Result<(), BlockchainError> {
+    // height is the one of the block in which the tx is included
+    // the tx can't be included above its expiry topoheight
+    // it depends on our current topoheight, so it's only used for mempool and block template
+    // txs of a block are checked at the topoheight where they are executed
+    fn verify_transaction_not_expired(&self, tx: &Transaction, hash: &Hash) -> Result<(), BlockchainError> {
+        if let Some(valid_until) = tx.get_valid_until_topoheight() {
+            let next_topoheight = self.get_topo_height() + 1;
+            if !tx.is_valid_at_topoheight(next_topoheight) {
+                return Err(BlockchainError::TransactionExpired(hash.clone(), valid_until, next_topoheight))
+            }
+        }
+        Ok(())
+    }
+
+    // topoheight is the one of the state the tx is verified against
+    // mints are the amounts already minted per asset by the txs verified before it in the same block or mempool
+    async fn verify_transaction_with_hash<'a>(&self, storage: &S, tx: &'a Transaction, hash: &Hash, height: u64, topoheight: u64, balances: &mut HashMap<&'a PublicKey, HashMap<&'a Hash, u64>>, mints: &mut HashMap<&'a Hash, u64>, nonces: Option<&mut HashMap<&'a PublicKey, u64>>, skip_nonces: bool) -> Result<(), BlockchainError> {
         trace!("Verify transaction with hash {}", hash);
 
+        let max_version = self.get_hard_fork_at_height(height).tx_version;
+        if tx.get_version() > max_version {
+            return Err(BlockchainError::InvalidTxVersion(max_version, tx.get_version()))
+        }
+
         if !tx.verify_signature() {
             return Err(BlockchainError::InvalidTransactionSignature)
         }
 
+        self.verify_multisig(storage, tx, hash, topoheight).await?;
+
         let owner_balances: &mut HashMap<&'a Hash, u64> = balances.entry(tx.get_owner()).or_insert_with(HashMap::new);
         {
             let balance = match owner_balances.entry(&XELIS_ASSET) {
                 Entry::Vacant(entry) => {
-                    let (_, balance) = storage.get_last_balance(tx.get_owner(), &XELIS_ASSET).await?;
-                    entry.insert(balance.get_balance())
+                    let balance = self.get_spendable_balance(storage, tx.get_owner(), &XELIS_ASSET).await?;
+                    entry.insert(balance)
                 },
                 Entry::Occupied(entry) => entry.into_mut(),
             };
@@ -1951,6 +3016,11 @@ impl<S: Storage> Blockchain<S> {
                     return Err(BlockchainError::TooManyOutputInTx(hash.clone()))
                 }
 
+                // funds locked for the receiver require the feature to be active
+                if txs.iter().any(|output| output.unlock.is_some()) && !self.is_feature_active_at_height(height, "locked_transfers") {
+                    return Err(BlockchainError::FeatureNotActive("locked_transfers", height))
+                }
+
                 let mut extra_data_size = 0; 
                 for output in txs {
                     if output.to == *tx.get_owner() { // we can't transfer coins to ourself, why would you do that ?
@@ -1963,8 +3033,8 @@ impl<S: Storage> Blockchain<S> {
 
                     let balance = match owner_balances.entry(&output.asset) {
                         Entry::Vacant(entry) => {
-                            let (_, balance) = storage.get_last_balance(tx.get_owner(), &XELIS_ASSET).await?;
-                            entry.insert(balance.get_balance())
+                            let balance = self.get_spendable_balance(storage, tx.get_owner(), &output.asset).await?;
+                            entry.insert(balance)
                         },
                         Entry::Occupied(entry) => entry.into_mut(),
                     };
@@ -1988,8 +3058,8 @@ impl<S: Storage> Blockchain<S> {
 
                 let balance = match owner_balances.entry(asset) {
                     Entry::Vacant(entry) => {
-                        let (_, balance) = storage.get_last_balance(tx.get_owner(), asset).await?;
-                        entry.insert(balance.get_balance())
+                        let balance = self.get_spendable_balance(storage, tx.get_owner(), asset).await?;
+                        entry.insert(balance)
                     },
                     Entry::Occupied(entry) => entry.into_mut(),
                 };
@@ -2000,6 +3070,80 @@ impl<S: Storage> Blockchain<S> {
                     return Err(BlockchainError::Overflow)
                 }
             },
+            TransactionType::CreateAsset { name, ticker, decimals, max_supply, .. } => {
+                if !self.is_feature_active_at_height(height, "custom_assets") {
+                    return Err(BlockchainError::FeatureNotActive("custom_assets", height))
+                }
+
+                // ticker is displayed next to amounts, keep it short and readable
+                if name.is_empty() || name.len() > ASSET_NAME_MAX_SIZE || ticker.is_empty() || ticker.len() > ASSET_TICKER_MAX_SIZE || !ticker.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit()) {
+                    return Err(BlockchainError::InvalidAssetName(hash.clone()))
+                }
+
+                if *decimals > ASSET_MAX_DECIMALS {
+                    return Err(BlockchainError::InvalidAssetDecimals(ASSET_MAX_DECIMALS, *decimals))
+                }
+
+                if *max_supply == 0 {
+                    return Err(BlockchainError::NoMaxSupplyForAsset(hash.clone()))
+                }
+
+                // asset is identified by the hash of the tx registering it
+                if storage.asset_exist(hash).await? {
+                    return Err(BlockchainError::AssetAlreadyRegistered(hash.clone()))
+                }
+
+                // registration fee is burned in addition to the tx fee
+                let balance = owner_balances.get_mut(&XELIS_ASSET).ok_or(BlockchainError::NoBalance(tx.get_owner().clone()))?;
+                if let Some(value) = balance.checked_sub(ASSET_REGISTRATION_FEE) {
+                    *balance = value;
+                } else {
+                    warn!("Overflow detected with registration fee of asset in transaction {}", hash);
+                    return Err(BlockchainError::Overflow)
+                }
+            },
+            TransactionType::MintAsset { asset, amount } => {
+                if !self.is_feature_active_at_height(height, "custom_assets") {
+                    return Err(BlockchainError::FeatureNotActive("custom_assets", height))
+                }
+
+                if *amount == 0 {
+                    error!("Mint Tx {} has no value to mint", hash);
+                    return Err(BlockchainError::NoValueForMint)
+                }
+
+                self.verify_asset_authority(storage, tx, hash, asset, topoheight).await?;
+                let max_supply = storage.get_asset_data(asset)?.get_metadata().map_or(0, |metadata| metadata.get_max_supply());
+                let supply = storage.get_asset_supply_at_maximum_topoheight(asset, topoheight).await?;
+                let minted = mints.entry(asset).or_insert(0);
+                match supply.checked_add(*minted).and_then(|supply| supply.checked_add(*amount)) {
+                    Some(supply) if supply <= max_supply => *minted += amount,
+                    _ => return Err(BlockchainError::AssetMaxSupplyExceeded(asset.clone()))
+                };
+            },
+            TransactionType::SetAssetAuthority { asset, .. } => {
+                if !self.is_feature_active_at_height(height, "custom_assets") {
+                    return Err(BlockchainError::FeatureNotActive("custom_assets", height))
+                }
+
+                self.verify_asset_authority(storage, tx, hash, asset, topoheight).await?;
+            },
+            TransactionType::SetMultisig(setup) => {
+                if !self.is_feature_active_at_height(height, "multisig") {
+                    return Err(BlockchainError::FeatureNotActive("multisig", height))
+                }
+
+                // no participants and a threshold of 0 removes the multisig
+                let participants = &setup.participants;
+                let mut unique = HashSet::with_capacity(participants.len());
+                if participants.len() > MAX_MULTISIG_PARTICIPANTS
+                    || participants.is_empty() != (setup.threshold == 0)
+                    || setup.threshold as usize > participants.len()
+                    || participants.contains(tx.get_owner())
+                    || !participants.iter().all(|key| unique.insert(key)) {
+                    return Err(BlockchainError::InvalidMultiSigSetup(hash.clone()))
+                }
+            },
             _ => {
                 // TODO implement SC
                 return Err(BlockchainError::SmartContractTodo)
@@ -2048,6 +3192,101 @@ impl<S: Storage> Blockchain<S> {
         Ok(())
     }
 
+    // supply and mint authority of an asset at topoheight, sent during a fast sync and in snapshots
+    pub async fn get_asset_state(&self, storage: &S, asset: &Hash, topoheight: u64) -> Result<AssetState, BlockchainError> {
+        Ok(AssetState {
+            supply: storage.get_asset_supply_at_maximum_topoheight(asset, topoheight).await?,
+            authority: storage.get_asset_authority_at_maximum_topoheight(asset, topoheight).await?
+        })
+    }
+
+    // save the state of an asset received from a peer or loaded from a snapshot
+    pub fn set_asset_state(storage: &mut S, asset: &Hash, topoheight: u64, state: &AssetState) -> Result<(), BlockchainError> {
+        if state.supply > 0 {
+            storage.set_asset_supply_at_topoheight(asset, topoheight, state.supply)?;
+        }
+
+        if let Some(authority) = &state.authority {
+            storage.set_asset_authority_at_topoheight(asset, topoheight, Some(authority))?;
+        }
+
+        Ok(())
+    }
+
+    // amounts received by an account that can't be spent yet at topoheight
+    pub async fn get_pending_locked_balances(&self, storage: &S, key: &PublicKey, asset: &Hash, topoheight: u64) -> Result<Vec<LockedBalance>, BlockchainError> {
+        let mut locked = storage.get_locked_balances(key, asset, topoheight).await?;
+        if locked.is_empty() {
+            return Ok(locked)
+        }
+
+        // timestamp conditions are checked against the block at topoheight
+        let hash = storage.get_hash_at_topo_height(topoheight).await?;
+        let timestamp = storage.get_timestamp_for_block_hash(&hash).await?;
+        locked.retain(|locked| !locked.get_unlock().is_unlocked(topoheight, timestamp));
+        Ok(locked)
+    }
+
+    // last balance of an account without the amounts still locked at current topoheight
+    async fn get_spendable_balance(&self, storage: &S, key: &PublicKey, asset: &Hash) -> Result<u64, BlockchainError> {
+        let (_, balance) = storage.get_last_balance(key, asset).await?;
+        let locked: u64 = self.get_pending_locked_balances(storage, key, asset, self.get_topo_height()).await?
+            .iter()
+            .map(|locked| locked.get_amount())
+            .sum();
+
+        Ok(balance.get_balance().saturating_sub(locked))
+    }
+
+    // amount already minted by the pending txs in mempool for the asset minted by this tx
+    // the tx replaced by it is not counted
+    fn get_pending_mints<'a>(mempool: &Mempool, tx: &'a Transaction, replaced: Option<&Hash>) -> HashMap<&'a Hash, u64> {
+        let mut mints = HashMap::new();
+        if let TransactionType::MintAsset { asset, .. } = tx.get_data() {
+            let mut minted: u64 = 0;
+            for (hash, sorted_tx) in mempool.get_txs() {
+                if let TransactionType::MintAsset { asset: pending, amount } = sorted_tx.get_tx().get_data() {
+                    if pending == asset && Some(hash.as_ref()) != replaced {
+                        minted = minted.saturating_add(*amount);
+                    }
+                }
+            }
+            mints.insert(asset, minted);
+        }
+        mints
+    }
+
+    // an account with a multisig setup requires the signatures of enough participants
+    // setup is the one at topoheight, a change of setup in a block only applies to txs of next blocks
+    async fn verify_multisig(&self, storage: &S, tx: &Transaction, hash: &Hash, topoheight: u64) -> Result<(), BlockchainError> {
+        let multisig = storage.get_multisig_at_maximum_topoheight(tx.get_owner(), topoheight).await?;
+        match (multisig, tx.get_multisig()) {
+            (Some(setup), Some(multisig)) => {
+                if !multisig.verify(&setup, &tx.get_multisig_hash()) {
+                    return Err(BlockchainError::InvalidMultiSig(hash.clone()))
+                }
+            },
+            (Some(_), None) => return Err(BlockchainError::MissingMultiSig(hash.clone())),
+            (None, Some(_)) => return Err(BlockchainError::InvalidMultiSig(hash.clone())),
+            (None, None) => {}
+        };
+        Ok(())
+    }
+
+    // only the mint authority of an asset at topoheight can mint it or change its authority
+    async fn verify_asset_authority(&self, storage: &S, tx: &Transaction, hash: &Hash, asset: &Hash, topoheight: u64) -> Result<(), BlockchainError> {
+        if !storage.asset_exist(asset).await? {
+            return Err(BlockchainError::AssetNotFound(asset.clone()))
+        }
+
+        let authority = storage.get_asset_authority_at_maximum_topoheight(asset, topoheight).await?;
+        if authority.as_ref() != Some(tx.get_owner()) {
+            return Err(BlockchainError::InvalidAssetAuthority(hash.clone(), asset.clone()))
+        }
+
+        Ok(())
+    }
+
     // retrieve the already added balance with changes OR generate a new versioned balance
     async fn retrieve_balance<'a, 'b>(&self, storage: &S, balances: &'b mut HashMap<&'a PublicKey, HashMap<&'a Hash, VersionedBalance>>, key: &'a PublicKey, asset: &'a Hash, topoheight: u64) -> Result<&'b mut VersionedBalance, BlockchainError> {
         trace!("retrieve balance {} for {} at topoheight {}", asset, key, topoheight);
@@ -2080,22 +3319,22 @@ impl<S: Storage> Blockchain<S> {
     }
 
     // reward block miner and dev fees if any.
-    async fn reward_miner<'a>(&self, storage: &S, block: &'a BlockHeader, mut block_reward: u64, total_fees: u64, balances: &mut HashMap<&'a PublicKey, HashMap<&'a Hash, VersionedBalance>>, topoheight: u64) -> Result<(), BlockchainError> {
+    async fn reward_miner<'a>(&'a self, storage: &S, block: &'a BlockHeader, mut block_reward: u64, total_fees: u64, balances: &mut HashMap<&'a PublicKey, HashMap<&'a Hash, VersionedBalance>>, topoheight: u64) -> Result<(), BlockchainError> {
         debug!("reward miner {} at topoheight {} with block reward = {}, total fees = {}", block.get_miner(), topoheight, block_reward, total_fees);
-        let dev_fee_percentage = get_block_dev_fee(block.get_height());
+        let dev_fee_percentage = get_block_dev_fee(self.get_dev_fees(), block.get_height());
         // if dev fee are enabled, give % from block reward only
         if dev_fee_percentage != 0 {
             let dev_fee = block_reward * dev_fee_percentage / 100;
             debug!("adding {}% to dev address for dev fees", dev_fee_percentage);
             block_reward -= dev_fee;
-            self.add_balance(storage, balances, &DEV_PUBLIC_KEY, &XELIS_ASSET, dev_fee, topoheight).await?;
+            self.add_balance(storage, balances, self.get_dev_public_key(), &XELIS_ASSET, dev_fee, topoheight).await?;
         }
 
         // now we reward the miner with block reward and total fees
         self.add_balance(storage, balances, block.get_miner(), &XELIS_ASSET, block_reward + total_fees, topoheight).await
     }
 
-    async fn execute_transaction<'a>(&self, storage: &mut S, transaction: &'a Transaction, nonces: &mut HashMap<PublicKey, u64>, balances: &mut HashMap<&'a PublicKey, HashMap<&'a Hash, VersionedBalance>>, topoheight: u64) -> Result<(), BlockchainError> {
+    async fn execute_transaction<'a>(&self, storage: &mut S, transaction: &'a Transaction, hash: &'a Hash, nonces: &mut HashMap<PublicKey, u64>, balances: &mut HashMap<&'a PublicKey, HashMap<&'a Hash, VersionedBalance>>, topoheight: u64) -> Result<(), BlockchainError> {
         let mut total_deducted: HashMap<&'a Hash, u64> = HashMap::new();
         total_deducted.insert(&XELIS_ASSET, transaction.get_fee());
 
@@ -2103,10 +3342,62 @@ impl<S: Storage> Blockchain<S> {
             TransactionType::Burn { asset, amount } => {
                 *total_deducted.entry(asset).or_insert(0) += amount;
             }
+            TransactionType::CreateAsset { name, ticker, decimals, max_supply, mint_authority } => {
+                *total_deducted.entry(&XELIS_ASSET).or_insert(0) += ASSET_REGISTRATION_FEE;
+
+                debug!("Registering asset {} ({}) at topoheight {}", hash, ticker, topoheight);
+                let metadata = AssetMetadata::new(name.clone(), ticker.clone(), *max_supply, mint_authority.clone());
+                storage.add_asset(hash, AssetData::with_metadata(topoheight, *decimals, metadata)).await?;
+
+                // without mint authority, the whole supply is created for the owner
+                if let Some(authority) = mint_authority {
+                    storage.set_asset_authority_at_topoheight(hash, topoheight, Some(authority))?;
+                } else {
+                    storage.set_asset_supply_at_topoheight(hash, topoheight, *max_supply)?;
+                    self.add_balance(storage, balances, transaction.get_owner(), hash, *max_supply, topoheight).await?;
+                }
+            }
+            // authority and supply may have changed since the verification if another branch was ordered first
+            TransactionType::MintAsset { asset, amount } => {
+                let authority = storage.get_asset_authority_at_maximum_topoheight(asset, topoheight).await?;
+                if authority.as_ref() == Some(transaction.get_owner()) {
+                    let max_supply = storage.get_asset_data(asset)?.get_metadata().map_or(0, |metadata| metadata.get_max_supply());
+                    let supply = storage.get_asset_supply_at_maximum_topoheight(asset, topoheight).await?;
+                    match supply.checked_add(*amount) {
+                        Some(supply) if supply <= max_supply => {
+                            storage.set_asset_supply_at_topoheight(asset, topoheight, supply)?;
+                            self.add_balance(storage, balances, transaction.get_owner(), asset, *amount, topoheight).await?;
+                        },
+                        _ => warn!("Mint of {} {} in tx {} exceeds its maximum supply, nothing is minted", amount, asset, hash)
+                    };
+                } else {
+                    warn!("Owner of tx {} is not anymore the authority of asset {}, nothing is minted", hash, asset);
+                }
+            }
+            TransactionType::SetAssetAuthority { asset, authority } => {
+                let current = storage.get_asset_authority_at_maximum_topoheight(asset, topoheight).await?;
+                if current.as_ref() == Some(transaction.get_owner()) {
+                    storage.set_asset_authority_at_topoheight(asset, topoheight, authority.as_ref())?;
+                } else {
+                    warn!("Owner of tx {} is not anymore the authority of asset {}, authority is unchanged", hash, asset);
+                }
+            }
+            TransactionType::SetMultisig(setup) => {
+                let setup = if setup.participants.is_empty() {
+                    None
+                } else {
+                    Some(setup)
+                };
+                storage.set_multisig_at_topoheight(transaction.get_owner(), topoheight, setup)?;
+            }
             TransactionType::Transfer(txs) => {
                 for output in txs {
                     // update receiver's account
                     self.add_balance(storage, balances, &output.to, &output.asset, output.amount, topoheight).await?;
+                    // amount is part of the balance but can't be spent until the condition is reached
+                    if let Some(unlock) = &output.unlock {
+                        storage.add_locked_balance(&output.to, &output.asset, topoheight, unlock, output.amount).await?;
+                    }
                     *total_deducted.entry(&output.asset).or_insert(0) += output.amount;
                 }
             }
@@ -2145,7 +3436,7 @@ impl<S: Storage> Blockchain<S> {
         let mut count = if topoheight > 50 {
             50
         } else if topoheight == 0 {
-            return Ok(BLOCK_TIME_MILLIS);
+            return Ok(self.get_block_time_target());
         } else {
             topoheight
         };
@@ -2168,7 +3459,31 @@ impl<S: Storage> Blockchain<S> {
     }
 }
 
-pub fn get_block_reward(supply: u64) -> u64 {
+// Parse a checkpoint in the format topoheight:hash
+fn parse_checkpoint(value: &str) -> Result<(u64, Hash), BlockchainError> {
+    let (topoheight, hash) = value.split_once(':').ok_or_else(|| BlockchainError::InvalidCheckpoint(value.to_owned()))?;
+    let topoheight = topoheight.parse().map_err(|_| BlockchainError::InvalidCheckpoint(value.to_owned()))?;
+    let hash = Hash::from_hex(hash.to_owned()).map_err(|_| BlockchainError::InvalidCheckpoint(value.to_owned()))?;
+    Ok((topoheight, hash))
+}
+
+// Hash the activation height, versions and features of each hard fork
+// changelog is only informative and not included
+fn hash_hard_forks(hard_forks: &[HardFork]) -> Hash {
+    let mut writer = Writer::new();
+    for hard_fork in hard_forks {
+        writer.write_u64(&hard_fork.height);
+        writer.write_u8(hard_fork.version);
+        writer.write_u8(hard_fork.tx_version);
+        writer.write_u8(hard_fork.features.len() as u8);
+        for feature in hard_fork.features {
+            writer.write_string(&feature.to_string());
+        }
+    }
+    hash(writer.as_bytes())
+}
+
+pub fn get_block_reward(supply: u64, block_time_target: u64) -> u64 {
     // Prevent any overflow
     if supply >= MAXIMUM_SUPPLY {
         // Max supply reached, do we want to generate small fixed amount of coins? 
@@ -2176,15 +3491,848 @@ pub fn get_block_reward(supply: u64) -> u64 {
     }
 
     let base_reward = (MAXIMUM_SUPPLY - supply) >> EMISSION_SPEED_FACTOR;
-    base_reward * BLOCK_TIME_MILLIS / MILLIS_PER_SECOND / 180
+    base_reward * block_time_target / MILLIS_PER_SECOND / 180
 }
 
-pub fn get_block_dev_fee(height: u64) -> u64 {
-    for threshold in DEV_FEES.iter() {
+pub fn get_block_dev_fee(dev_fees: &[DevFeeThreshold], height: u64) -> u64 {
+    for threshold in dev_fees {
         if height <= threshold.height {
             return threshold.fee_percentage
         }
     }
 
     0
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use xelis_common::{config::{COIN_VALUE, FEE_PER_KB}, crypto::{key::KeyPair, hash::hash}, serializer::Writer, transaction::{Transfer, MultiSigSetup, UnsignedTransaction, UnlockCondition, VALID_UNTIL_TX_VERSION}};
+    use crate::{config::{DEV_PUBLIC_KEY, DEV_ADDRESS, BLOCK_TIME_MILLIS, FEE_ESTIMATION_FULL_BLOCK_PERCENT}, core::storage::{MemoryStorage, Storage}};
+    use super::*;
+
+    async fn create_blockchain(args: &[&str]) -> Arc<Blockchain<MemoryStorage>> {
+        let config = Config::parse_from(["xelis_daemon", "--disable-p2p-server", "--rpc-bind-address", "127.0.0.1:0"].iter().chain(args));
+        let storage = MemoryStorage::new(None, Network::Dev).unwrap();
+        Blockchain::new(config, Network::Dev, storage).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_genesis_block_in_memory() {
+        let blockchain = create_blockchain(&[]).await;
+        assert_eq!(blockchain.get_topo_height(), 0);
+        assert!(blockchain.get_storage().read().await.has_blocks());
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_mine_blocks_in_memory() {
+        // simulator skips the PoW, its own miner may also add blocks meanwhile
+        let blockchain = create_blockchain(&["--simulator"]).await;
+        let topoheight = blockchain.get_topo_height();
+        for _ in 0..3 {
+            blockchain.mine_block(&DEV_PUBLIC_KEY).await.unwrap();
+        }
+        assert!(blockchain.get_topo_height() >= topoheight + 3);
+
+        let storage = blockchain.get_storage().read().await;
+        assert_eq!(storage.count_blocks().unwrap(), blockchain.get_topo_height() + 1);
+        assert!(storage.get_supply_at_topo_height(blockchain.get_topo_height()).await.unwrap() > 0);
+        drop(storage);
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_auto_prune() {
+        // blocks are mined faster than the block time, keep the difficulty from growing
+        let path = temp_file_path("auto-prune");
+        std::fs::write(&path, r#"{"block_time_millis": 1}"#).unwrap();
+        let keep_only = PRUNE_SAFETY_LIMIT.to_string();
+        let blockchain = create_blockchain(&["--simulator", "--chain-spec", &path, "--auto-prune-keep-n-blocks", &keep_only]).await;
+        let _ = std::fs::remove_file(&path);
+        while blockchain.get_topo_height() < 2 * PRUNE_SAFETY_LIMIT {
+            blockchain.mine_block(&DEV_PUBLIC_KEY).await.unwrap();
+        }
+
+        // pruning is applied after the block batch, in its own one
+        let storage = blockchain.get_storage().read().await;
+        assert!(!storage.has_pending_batch().unwrap());
+        assert!(storage.get_pruned_topoheight().unwrap().is_some_and(|topoheight| topoheight <= PRUNE_SAFETY_LIMIT));
+        drop(storage);
+
+        // counters and indexes are kept in sync by the pruning
+        assert!(blockchain.check_db(false).await.unwrap().iter().all(|report| report.is_valid()));
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_rewind_chain() {
+        let blockchain = create_blockchain(&["--simulator"]).await;
+        for _ in 0..5 {
+            blockchain.mine_block(&DEV_PUBLIC_KEY).await.unwrap();
+        }
+
+        let topoheight = blockchain.get_topo_height();
+        let new_topoheight = blockchain.rewind_chain(2).await.unwrap();
+        assert!(new_topoheight < topoheight);
+
+        // blocks are popped in a committed batch
+        let storage = blockchain.get_storage().read().await;
+        assert!(!storage.has_pending_batch().unwrap());
+        assert_eq!(storage.get_top_topoheight().unwrap(), new_topoheight);
+        drop(storage);
+
+        assert!(blockchain.check_db(false).await.unwrap().iter().all(|report| report.is_valid()));
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_account_history_index() {
+        let blockchain = create_blockchain(&["--enable-account-history-index"]).await;
+        assert!(blockchain.is_account_history_indexed());
+        assert!(blockchain.get_storage().read().await.is_account_history_indexed().unwrap());
+        blockchain.stop().await;
+
+        let blockchain = create_blockchain(&[]).await;
+        assert!(!blockchain.get_storage().read().await.is_account_history_indexed().unwrap());
+        blockchain.stop().await;
+    }
+
+    fn temp_file_path(name: &str) -> String {
+        let mut path = std::env::temp_dir();
+        path.push(format!("xelis-{}-{}.chain", name, std::process::id()));
+        path.to_string_lossy().into_owned()
+    }
+
+    // create a blockchain from a chain spec crediting XELIS to each key at genesis
+    // the chain spec file is only needed while creating it
+    async fn create_funded_blockchain_with(name: &str, allocations: &[(&KeyPair, u64)], args: &[&str], storage: MemoryStorage) -> Arc<Blockchain<MemoryStorage>> {
+        let path = temp_file_path(name);
+        let allocations: Vec<String> = allocations.iter()
+            .map(|(keypair, amount)| format!(r#"{{"address": "{}", "amount": {}}}"#, keypair.get_public_key().to_address(false), amount))
+            .collect();
+        std::fs::write(&path, format!(r#"{{"genesis_timestamp": 1000, "allocations": [{}]}}"#, allocations.join(", "))).unwrap();
+
+        let config = Config::parse_from(["xelis_daemon", "--disable-p2p-server", "--rpc-bind-address", "127.0.0.1:0", "--chain-spec", &path].iter().chain(args));
+        let blockchain = Blockchain::new(config, Network::Dev, storage).await;
+        let _ = std::fs::remove_file(&path);
+        blockchain.unwrap()
+    }
+
+    async fn create_funded_blockchain(name: &str, allocations: &[(&KeyPair, u64)]) -> Arc<Blockchain<MemoryStorage>> {
+        create_funded_blockchain_with(name, allocations, &["--simulator"], MemoryStorage::new(None, Network::Dev).unwrap()).await
+    }
+
+    #[tokio::test]
+    async fn test_export_import_chain() {
+        let path = temp_file_path("export");
+        let source = create_blockchain(&["--simulator"]).await;
+        for _ in 0..3 {
+            source.mine_block(&DEV_PUBLIC_KEY).await.unwrap();
+        }
+        let count = source.export_chain(&path).await.unwrap();
+        let top_hash = source.get_storage().read().await.get_hash_at_topo_height(count - 1).await.unwrap();
+        source.stop().await;
+
+        let blockchain = create_blockchain(&[]).await;
+        // simulated blocks have no valid PoW
+        assert!(matches!(blockchain.import_chain(&path, false).await, Err(BlockchainError::InvalidDifficulty)));
+        assert_eq!(blockchain.get_topo_height(), 0);
+
+        assert_eq!(blockchain.import_chain(&path, true).await.unwrap(), (count - 1, 1));
+        assert_eq!(blockchain.get_topo_height(), count - 1);
+        assert_eq!(blockchain.get_storage().read().await.get_hash_at_topo_height(count - 1).await.unwrap(), top_hash);
+
+        // everything is already in chain
+        assert_eq!(blockchain.import_chain(&path, true).await.unwrap(), (0, count));
+        blockchain.stop().await;
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_create_load_snapshot() {
+        let path = temp_file_path("snapshot");
+        let source = create_blockchain(&["--simulator"]).await;
+        for _ in 0..20 {
+            source.mine_block(&DEV_PUBLIC_KEY).await.unwrap();
+        }
+        let (header, state_hash) = source.create_snapshot(&path).await.unwrap();
+        let topoheight = header.get_topoheight();
+        assert!(topoheight > 0);
+        let balance = source.get_storage().read().await.get_balance_at_maximum_topoheight(&DEV_PUBLIC_KEY, &XELIS_ASSET, topoheight).await.unwrap().unwrap().1.get_balance();
+        let state_root = source.get_storage().read().await.get_state_root_at_topoheight(topoheight).await.unwrap();
+        source.stop().await;
+
+        let blockchain = create_blockchain(&["--simulator"]).await;
+        let (loaded, loaded_state_hash) = blockchain.load_snapshot(&path).await.unwrap();
+        assert_eq!(loaded_state_hash, state_hash);
+        assert_eq!(blockchain.get_topo_height(), topoheight);
+        assert_eq!(blockchain.get_top_block_hash().await.unwrap(), *loaded.get_top_hash());
+        {
+            let storage = blockchain.get_storage().read().await;
+            assert_eq!(storage.get_last_balance(&DEV_PUBLIC_KEY, &XELIS_ASSET).await.unwrap().1.get_balance(), balance);
+            // same state as the source chain
+            assert_eq!(storage.get_state_root_at_topoheight(topoheight).await.unwrap(), state_root);
+        }
+
+        // chain can continue from the snapshot
+        blockchain.mine_block(&DEV_PUBLIC_KEY).await.unwrap();
+        assert!(blockchain.get_topo_height() > topoheight);
+        assert!(matches!(blockchain.load_snapshot(&path).await, Err(BlockchainError::ChainNotEmpty(_))));
+        blockchain.stop().await;
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_checkpoints() {
+        let path = temp_file_path("checkpoints");
+        let source = create_blockchain(&["--simulator"]).await;
+        for _ in 0..3 {
+            source.mine_block(&DEV_PUBLIC_KEY).await.unwrap();
+        }
+        source.export_chain(&path).await.unwrap();
+        let checkpoint_hash = source.get_storage().read().await.get_hash_at_topo_height(2).await.unwrap();
+        source.stop().await;
+
+        assert!(matches!(parse_checkpoint("2"), Err(BlockchainError::InvalidCheckpoint(_))));
+        assert!(matches!(parse_checkpoint("two:00"), Err(BlockchainError::InvalidCheckpoint(_))));
+
+        // chain file doesn't contain the block expected at the checkpoint
+        let checkpoint = format!("2:{}", Hash::zero());
+        let blockchain = create_blockchain(&["--checkpoint", &checkpoint]).await;
+        assert!(matches!(blockchain.import_chain(&path, true).await, Err(BlockchainError::CheckpointMismatch(2, _, _))));
+        assert_eq!(blockchain.get_topo_height(), 1);
+        blockchain.stop().await;
+
+        let checkpoint = format!("2:{}", checkpoint_hash);
+        let blockchain = create_blockchain(&["--checkpoint", &checkpoint]).await;
+        blockchain.import_chain(&path, true).await.unwrap();
+        assert!(blockchain.get_topo_height() >= 3);
+        assert!(matches!(blockchain.verify_reorg_above_checkpoints(2), Err(BlockchainError::ReorgBelowCheckpoint(2))));
+        assert!(blockchain.verify_reorg_above_checkpoints(3).is_ok());
+        blockchain.stop().await;
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_hard_coded_checkpoints() {
+        for checkpoints in [MAINNET_CHECKPOINTS, TESTNET_CHECKPOINTS] {
+            assert_eq!(checkpoints[0].0, 0);
+            assert_eq!(Hash::from_hex(checkpoints[0].1.to_owned()).unwrap(), *GENESIS_BLOCK_HASH);
+            assert!(checkpoints.windows(2).all(|pair| pair[0].0 < pair[1].0));
+            assert!(checkpoints.iter().all(|(_, hash)| Hash::from_hex(hash.to_string()).is_ok()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_block_version() {
+        let blockchain = create_blockchain(&["--simulator"]).await;
+        assert_eq!(blockchain.get_version_at_height(0), 0);
+
+        let mut header = blockchain.get_block_template(DEV_PUBLIC_KEY.clone()).await.unwrap();
+        let algorithm = blockchain.get_pow_algorithm_for_block(&header).unwrap();
+        // Keccak PoW hash is the same as the block hash
+        assert_eq!(header.get_pow_hash(algorithm), header.hash());
+
+        header.version = 1;
+        assert!(matches!(blockchain.get_pow_algorithm_for_block(&header), Err(BlockchainError::InvalidBlockVersion(0, 1))));
+        let block = blockchain.build_block_from_header(Immutable::Owned(header)).await.unwrap();
+        assert!(matches!(blockchain.add_new_block(block, false, false).await, Err(BlockchainError::InvalidBlockVersion(0, 1))));
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_state_root_in_header() {
+        let blockchain = create_blockchain(&["--simulator"]).await;
+        let fork_height = DEV_HARD_FORKS[1].height;
+        while blockchain.get_height() < fork_height {
+            blockchain.mine_block(&DEV_PUBLIC_KEY).await.unwrap();
+        }
+
+        let header = blockchain.get_block_template(DEV_PUBLIC_KEY.clone()).await.unwrap();
+        assert_eq!(header.get_version(), STATE_ROOT_BLOCK_VERSION);
+        let storage = blockchain.get_storage().read().await;
+        let state_root = blockchain.get_state_root_at_tips(&*storage, header.get_tips()).await.unwrap();
+        assert!(state_root.is_some());
+        assert_eq!(header.get_state_root(), state_root.as_ref());
+        drop(storage);
+
+        // the state root is serialized and committed in the block work
+        let decoded = BlockHeader::from_bytes(&header.to_bytes()).unwrap();
+        assert_eq!(decoded.get_state_root(), header.get_state_root());
+        let mut tampered = header.clone();
+        tampered.set_state_root(Some(Hash::zero()));
+        assert_ne!(tampered.get_work_hash(), header.get_work_hash());
+
+        let block = blockchain.build_block_from_header(Immutable::Owned(tampered)).await.unwrap();
+        assert!(matches!(blockchain.add_new_block(block, false, false).await, Err(BlockchainError::StateRootMismatch(_, _))));
+
+        let mut missing = header.clone();
+        missing.set_state_root(None);
+        let block = blockchain.build_block_from_header(Immutable::Owned(missing)).await.unwrap();
+        assert!(matches!(blockchain.add_new_block(block, false, false).await, Err(BlockchainError::InvalidBlockStateRoot)));
+
+        let block = blockchain.build_block_from_header(Immutable::Owned(header)).await.unwrap();
+        blockchain.add_new_block(block, false, false).await.unwrap();
+        blockchain.stop().await;
+    }
+
+    #[test]
+    fn test_hard_forks_schedule() {
+        for hard_forks in [MAINNET_HARD_FORKS, TESTNET_HARD_FORKS, DEV_HARD_FORKS] {
+            assert_eq!(hard_forks[0].height, 0);
+            for hard_fork in hard_forks {
+                assert!(get_pow_algorithm(hard_fork.version).is_some());
+            }
+
+            for window in hard_forks.windows(2) {
+                assert!(window[0].height < window[1].height);
+                assert!(window[0].version <= window[1].version);
+                assert!(window[0].tx_version <= window[1].tx_version);
+            }
+        }
+    }
+
+    #[test]
+    fn test_hard_forks_hash() {
+        assert_eq!(hash_hard_forks(MAINNET_HARD_FORKS), hash_hard_forks(MAINNET_HARD_FORKS));
+        assert_ne!(hash_hard_forks(MAINNET_HARD_FORKS), hash_hard_forks(DEV_HARD_FORKS));
+
+        // a different activation height is a different schedule
+        let moved = [HardFork { ..DEV_HARD_FORKS[0] }, HardFork { height: DEV_HARD_FORKS[1].height + 1, ..DEV_HARD_FORKS[1] }];
+        assert_ne!(hash_hard_forks(&moved), hash_hard_forks(DEV_HARD_FORKS));
+    }
+
+    #[tokio::test]
+    async fn test_hard_fork_at_height() {
+        let blockchain = create_blockchain(&[]).await;
+        let last = blockchain.get_hard_forks().last().unwrap();
+        assert_eq!(blockchain.get_hard_fork_at_height(u64::MAX).version, last.version);
+        assert_eq!(blockchain.get_version_at_height(last.height), last.version);
+        assert!(!blockchain.is_feature_active_at_height(u64::MAX, "unknown"));
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_chain_spec() {
+        let path = temp_file_path("spec");
+        std::fs::write(&path, format!(r#"{{"genesis_miner": "{}", "genesis_timestamp": 1000, "block_time_millis": 1000, "minimum_difficulty": 5, "dev_fees": [{{"height": 10, "fee_percentage": 50}}]}}"#, DEV_ADDRESS)).unwrap();
+        let blockchain = create_blockchain(&["--simulator", "--chain-spec", &path]).await;
+        assert_eq!(blockchain.get_block_time_target(), 1000);
+        assert_eq!(blockchain.get_minimum_difficulty(), 5);
+        assert_eq!(get_block_dev_fee(blockchain.get_dev_fees(), 1), 50);
+        assert_ne!(*blockchain.get_genesis_hash(), *GENESIS_BLOCK_HASH);
+
+        let (hash, genesis) = blockchain.get_storage().read().await.get_block_header_at_topoheight(0).await.unwrap();
+        assert_eq!(genesis.get_timestamp(), 1000);
+        assert_eq!(hash, *blockchain.get_genesis_hash());
+        blockchain.mine_block(&DEV_PUBLIC_KEY).await.unwrap();
+        blockchain.stop().await;
+
+        // fields not set use the hard-coded parameters
+        std::fs::write(&path, r#"{"block_time_millis": 0}"#).unwrap();
+        assert!(matches!(ChainSpec::from_file(&path), Err(BlockchainError::InvalidChainSpec(_))));
+        std::fs::write(&path, r#"{"minimum_difficulty": 5}"#).unwrap();
+        let spec = ChainSpec::from_file(&path).unwrap();
+        assert_eq!(spec.block_time_millis, BLOCK_TIME_MILLIS);
+        assert!(spec.build_genesis_block().is_none());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_genesis_allocations() {
+        let path = temp_file_path("allocations");
+        let asset = Hash::new([1u8; HASH_SIZE]);
+        let keypair = xelis_common::crypto::key::KeyPair::new();
+        let spec = format!(r#"{{"genesis_timestamp": 1000, "allocations": [{{"address": "{}", "amount": 500}}, {{"address": "{}", "asset": "{}", "amount": 10}}, {{"address": "{}", "asset": "{}", "amount": 5}}]}}"#, DEV_ADDRESS, keypair.get_public_key().to_address(false), asset, DEV_ADDRESS, asset);
+        std::fs::write(&path, &spec).unwrap();
+        let blockchain = create_blockchain(&["--chain-spec", &path]).await;
+        let spec = blockchain.get_chain_spec();
+        assert_eq!(spec.get_premine_supply(), 500);
+
+        {
+            let storage = blockchain.get_storage().read().await;
+            let (_, genesis) = storage.get_block_header_at_topoheight(0).await.unwrap();
+            assert_eq!(genesis.extra_nonce, spec.get_allocations_hash().to_bytes());
+
+            let reward = storage.get_block_reward_at_topo_height(0).unwrap();
+            assert_eq!(storage.get_supply_at_topo_height(0).await.unwrap(), 500 + reward);
+            assert!(storage.asset_exist(&asset).await.unwrap());
+            assert_eq!(storage.get_balance_at_exact_topoheight(keypair.get_public_key(), &asset, 0).await.unwrap().get_balance(), 10);
+            assert_eq!(storage.get_balance_at_exact_topoheight(&DEV_PUBLIC_KEY, &asset, 0).await.unwrap().get_balance(), 5);
+            // allocated assets supply is registered at genesis
+            assert_eq!(storage.get_asset_supply_at_maximum_topoheight(&asset, 0).await.unwrap(), 15);
+            assert_eq!(storage.get_asset_supply_at_maximum_topoheight(&asset, blockchain.get_topo_height()).await.unwrap(), 15);
+            assert_eq!(storage.get_balance_at_exact_topoheight(&DEV_PUBLIC_KEY, &XELIS_ASSET, 0).await.unwrap().get_balance(), 500 + reward);
+        }
+        let genesis_hash = blockchain.get_genesis_hash().clone();
+        blockchain.stop().await;
+
+        // a different allocation leads to a different genesis
+        std::fs::write(&path, format!(r#"{{"genesis_timestamp": 1000, "allocations": [{{"address": "{}", "amount": 501}}]}}"#, DEV_ADDRESS)).unwrap();
+        assert_ne!(ChainSpec::from_file(&path).unwrap().build_genesis_block().unwrap().hash(), genesis_hash);
+
+        std::fs::write(&path, format!(r#"{{"allocations": [{{"address": "{}", "amount": 1}}]}}"#, DEV_ADDRESS)).unwrap();
+        assert!(matches!(ChainSpec::from_file(&path), Err(BlockchainError::InvalidChainSpec(_))));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    // sign a transaction the same way the wallet does
+    fn create_transaction(keypair: &KeyPair, data: TransactionType, fee: u64, nonce: u64) -> Transaction {
+        let mut writer = Writer::new();
+        writer.write_u8(0);
+        keypair.get_public_key().write(&mut writer);
+        data.write(&mut writer);
+        writer.write_u64(&fee);
+        writer.write_u64(&nonce);
+        let signature = keypair.sign(hash(writer.as_bytes()).as_bytes());
+        Transaction::new(keypair.get_public_key().clone(), data, fee, nonce, signature)
+    }
+
+    fn create_expiring_transaction(keypair: &KeyPair, data: TransactionType, fee: u64, nonce: u64, valid_until_topoheight: u64) -> Transaction {
+        let mut writer = Writer::new();
+        writer.write_u8(VALID_UNTIL_TX_VERSION);
+        keypair.get_public_key().write(&mut writer);
+        data.write(&mut writer);
+        writer.write_u64(&fee);
+        writer.write_u64(&nonce);
+        Some(valid_until_topoheight).write(&mut writer);
+        writer.write_bool(false);
+        let signature = keypair.sign(hash(writer.as_bytes()).as_bytes());
+        Transaction::with_valid_until_topoheight(keypair.get_public_key().clone(), data, fee, nonce, valid_until_topoheight, signature)
+    }
+
+    #[tokio::test]
+    async fn test_create_asset() {
+        let keypair = KeyPair::new();
+        let blockchain = create_funded_blockchain("create-asset", &[(&keypair, 10 * COIN_VALUE)]).await;
+
+        let data = TransactionType::CreateAsset { name: "Test".to_owned(), ticker: "tst".to_owned(), decimals: 8, max_supply: 1000, mint_authority: None };
+        let tx = create_transaction(&keypair, data, 1000, 0);
+        assert!(matches!(blockchain.add_tx_to_mempool(tx, false).await, Err(BlockchainError::InvalidAssetName(_))));
+
+        let data = TransactionType::CreateAsset { name: "Test".to_owned(), ticker: "TST".to_owned(), decimals: ASSET_MAX_DECIMALS + 1, max_supply: 1000, mint_authority: None };
+        let tx = create_transaction(&keypair, data, 1000, 0);
+        assert!(matches!(blockchain.add_tx_to_mempool(tx, false).await, Err(BlockchainError::InvalidAssetDecimals(_, _))));
+
+        let data = TransactionType::CreateAsset { name: "Test".to_owned(), ticker: "TST".to_owned(), decimals: 8, max_supply: 1000, mint_authority: None };
+        let tx = create_transaction(&keypair, data, 1000, 0);
+        let asset = tx.hash();
+        blockchain.add_tx_to_mempool(tx, false).await.unwrap();
+        blockchain.mine_block(&DEV_PUBLIC_KEY).await.unwrap();
+
+        {
+            let storage = blockchain.get_storage().read().await;
+            let data = storage.get_asset_data(&asset).unwrap();
+            assert_eq!(data.get_decimals(), 8);
+            assert_eq!(data.get_metadata().unwrap().get_ticker(), "TST");
+            // whole supply is created for the owner as there is no mint authority
+            assert_eq!(storage.get_last_balance(keypair.get_public_key(), &asset).await.unwrap().1.get_balance(), 1000);
+            assert_eq!(storage.get_last_balance(keypair.get_public_key(), &XELIS_ASSET).await.unwrap().1.get_balance(), 10 * COIN_VALUE - 1000 - ASSET_REGISTRATION_FEE);
+        }
+
+        // the new asset can be transferred like the native one
+        let transfer = Transfer { amount: 400, asset: asset.clone(), to: DEV_PUBLIC_KEY.clone(), extra_data: None, unlock: None };
+        let tx = create_transaction(&keypair, TransactionType::Transfer(vec![transfer]), 1000, 1);
+        blockchain.add_tx_to_mempool(tx, false).await.unwrap();
+        blockchain.mine_block(&DEV_PUBLIC_KEY).await.unwrap();
+        {
+            let storage = blockchain.get_storage().read().await;
+            assert_eq!(storage.get_last_balance(keypair.get_public_key(), &asset).await.unwrap().1.get_balance(), 600);
+            assert_eq!(storage.get_last_balance(&DEV_PUBLIC_KEY, &asset).await.unwrap().1.get_balance(), 400);
+        }
+
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_mint_asset() {
+        let keypair = KeyPair::new();
+        let other = KeyPair::new();
+        let blockchain = create_funded_blockchain("mint-asset", &[(&keypair, 10 * COIN_VALUE), (&other, COIN_VALUE)]).await;
+
+        let data = TransactionType::CreateAsset { name: "Test".to_owned(), ticker: "TST".to_owned(), decimals: 8, max_supply: 1000, mint_authority: Some(keypair.get_public_key().clone()) };
+        let tx = create_transaction(&keypair, data, 1000, 0);
+        let asset = tx.hash();
+        blockchain.add_tx_to_mempool(tx, false).await.unwrap();
+        blockchain.mine_block(&DEV_PUBLIC_KEY).await.unwrap();
+        {
+            let storage = blockchain.get_storage().read().await;
+            // nothing is created until the authority mints it
+            assert_eq!(storage.get_asset_supply_at_maximum_topoheight(&asset, blockchain.get_topo_height()).await.unwrap(), 0);
+            assert!(!storage.has_balance_for(keypair.get_public_key(), &asset).await.unwrap());
+        }
+
+        let tx = create_transaction(&keypair, TransactionType::MintAsset { asset: asset.clone(), amount: 1001 }, 1000, 1);
+        assert!(matches!(blockchain.add_tx_to_mempool(tx, false).await, Err(BlockchainError::AssetMaxSupplyExceeded(_))));
+
+        let tx = create_transaction(&other, TransactionType::MintAsset { asset: asset.clone(), amount: 10 }, 1000, 0);
+        assert!(matches!(blockchain.add_tx_to_mempool(tx, false).await, Err(BlockchainError::InvalidAssetAuthority(_, _))));
+
+        let tx = create_transaction(&keypair, TransactionType::MintAsset { asset: asset.clone(), amount: 300 }, 1000, 1);
+        blockchain.add_tx_to_mempool(tx, false).await.unwrap();
+
+        // pending mints are counted in the supply
+        let tx = create_transaction(&keypair, TransactionType::MintAsset { asset: asset.clone(), amount: 701 }, 1000, 2);
+        assert!(matches!(blockchain.add_tx_to_mempool(tx, false).await, Err(BlockchainError::AssetMaxSupplyExceeded(_))));
+
+        let tx = create_transaction(&keypair, TransactionType::MintAsset { asset: asset.clone(), amount: 700 }, 1000, 2);
+        blockchain.add_tx_to_mempool(tx, false).await.unwrap();
+        blockchain.mine_block(&DEV_PUBLIC_KEY).await.unwrap();
+        {
+            let storage = blockchain.get_storage().read().await;
+            assert_eq!(storage.get_asset_supply_at_maximum_topoheight(&asset, blockchain.get_topo_height()).await.unwrap(), 1000);
+            assert_eq!(storage.get_last_balance(keypair.get_public_key(), &asset).await.unwrap().1.get_balance(), 1000);
+        }
+
+        // renounce the authority, no more supply can be minted
+        let tx = create_transaction(&keypair, TransactionType::SetAssetAuthority { asset: asset.clone(), authority: None }, 1000, 3);
+        blockchain.add_tx_to_mempool(tx, false).await.unwrap();
+        blockchain.mine_block(&DEV_PUBLIC_KEY).await.unwrap();
+        {
+            let storage = blockchain.get_storage().read().await;
+            assert!(storage.get_asset_authority_at_maximum_topoheight(&asset, blockchain.get_topo_height()).await.unwrap().is_none());
+        }
+
+        let tx = create_transaction(&keypair, TransactionType::MintAsset { asset: asset.clone(), amount: 10 }, 1000, 4);
+        assert!(matches!(blockchain.add_tx_to_mempool(tx, false).await, Err(BlockchainError::InvalidAssetAuthority(_, _))));
+
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_multisig() {
+        let keypair = KeyPair::new();
+        let participants = [KeyPair::new(), KeyPair::new(), KeyPair::new()];
+        let blockchain = create_funded_blockchain("multisig", &[(&keypair, 10 * COIN_VALUE)]).await;
+
+        let keys: Vec<PublicKey> = participants.iter().map(|p| p.get_public_key().clone()).collect();
+        let tx = create_transaction(&keypair, TransactionType::SetMultisig(MultiSigSetup { participants: keys.clone(), threshold: 4 }), 1000, 0);
+        assert!(matches!(blockchain.add_tx_to_mempool(tx, false).await, Err(BlockchainError::InvalidMultiSigSetup(_))));
+
+        let tx = create_transaction(&keypair, TransactionType::SetMultisig(MultiSigSetup { participants: keys.clone(), threshold: 2 }), 1000, 0);
+        blockchain.add_tx_to_mempool(tx, false).await.unwrap();
+        blockchain.mine_block(&DEV_PUBLIC_KEY).await.unwrap();
+
+        // the owner signature alone is not enough anymore
+        let data = TransactionType::Burn { asset: XELIS_ASSET, amount: 100 };
+        let tx = create_transaction(&keypair, data.clone(), 1000, 1);
+        assert!(matches!(blockchain.add_tx_to_mempool(tx, false).await, Err(BlockchainError::MissingMultiSig(_))));
+
+        // each participant signs its own copy, then they are exchanged and combined
+        let mut first = UnsignedTransaction::new(keypair.get_public_key().clone(), data.clone(), 1000, 1, None);
+        assert!(first.sign_multisig(&participants[0], 0));
+        let mut second = UnsignedTransaction::from_bytes(&first.to_bytes()).unwrap();
+        assert!(second.sign_multisig(&participants[2], 2));
+
+        let tx = first.clone().finalize(&keypair);
+        assert!(matches!(blockchain.add_tx_to_mempool(tx, false).await, Err(BlockchainError::InvalidMultiSig(_))));
+
+        // signature of a participant with the index of another one
+        let mut wrong = first.clone();
+        wrong.sign_multisig(&participants[1], 2);
+        assert!(matches!(blockchain.add_tx_to_mempool(wrong.finalize(&keypair), false).await, Err(BlockchainError::InvalidMultiSig(_))));
+
+        assert!(first.combine(second));
+        assert_eq!(first.get_multisig().len(), 2);
+        let tx = first.finalize(&keypair);
+        blockchain.add_tx_to_mempool(tx, false).await.unwrap();
+        blockchain.mine_block(&DEV_PUBLIC_KEY).await.unwrap();
+        {
+            let storage = blockchain.get_storage().read().await;
+            assert_eq!(storage.get_last_balance(keypair.get_public_key(), &XELIS_ASSET).await.unwrap().1.get_balance(), 10 * COIN_VALUE - 2000 - 100);
+        }
+
+        // remove the multisig with the approval of the participants
+        let mut unsigned = UnsignedTransaction::new(keypair.get_public_key().clone(), TransactionType::SetMultisig(MultiSigSetup { participants: Vec::new(), threshold: 0 }), 1000, 2, None);
+        unsigned.sign_multisig(&participants[1], 1);
+        unsigned.sign_multisig(&participants[2], 2);
+        blockchain.add_tx_to_mempool(unsigned.finalize(&keypair), false).await.unwrap();
+        blockchain.mine_block(&DEV_PUBLIC_KEY).await.unwrap();
+
+        let tx = create_transaction(&keypair, data, 1000, 3);
+        blockchain.add_tx_to_mempool(tx, false).await.unwrap();
+
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_locked_transfer() {
+        let keypair = KeyPair::new();
+        let receiver = KeyPair::new();
+        let blockchain = create_funded_blockchain("locked-transfer", &[(&keypair, 10 * COIN_VALUE), (&receiver, COIN_VALUE)]).await;
+
+        let transfers = vec![
+            Transfer { amount: 5 * COIN_VALUE, asset: XELIS_ASSET, to: receiver.get_public_key().clone(), extra_data: None, unlock: Some(UnlockCondition::Topoheight(3)) },
+            Transfer { amount: COIN_VALUE, asset: XELIS_ASSET, to: receiver.get_public_key().clone(), extra_data: None, unlock: Some(UnlockCondition::Timestamp(u128::MAX)) }
+        ];
+        let tx = create_transaction(&keypair, TransactionType::Transfer(transfers), 1000, 0);
+        blockchain.add_tx_to_mempool(tx, false).await.unwrap();
+        blockchain.mine_block(&DEV_PUBLIC_KEY).await.unwrap();
+        {
+            let storage = blockchain.get_storage().read().await;
+            // locked funds are already part of the balance
+            assert_eq!(storage.get_last_balance(receiver.get_public_key(), &XELIS_ASSET).await.unwrap().1.get_balance(), 7 * COIN_VALUE);
+            let locked = blockchain.get_pending_locked_balances(&storage, receiver.get_public_key(), &XELIS_ASSET, blockchain.get_topo_height()).await.unwrap();
+            assert_eq!(locked.iter().map(|locked| locked.get_amount()).sum::<u64>(), 6 * COIN_VALUE);
+        }
+
+        let burn = TransactionType::Burn { asset: XELIS_ASSET, amount: 2 * COIN_VALUE };
+        let tx = create_transaction(&receiver, burn.clone(), 1000, 0);
+        assert!(matches!(blockchain.add_tx_to_mempool(tx, false).await, Err(BlockchainError::Overflow)));
+
+        blockchain.mine_block(&DEV_PUBLIC_KEY).await.unwrap();
+        blockchain.mine_block(&DEV_PUBLIC_KEY).await.unwrap();
+        {
+            let storage = blockchain.get_storage().read().await;
+            let locked = blockchain.get_pending_locked_balances(&storage, receiver.get_public_key(), &XELIS_ASSET, blockchain.get_topo_height()).await.unwrap();
+            assert_eq!(locked, vec![LockedBalance::new(UnlockCondition::Timestamp(u128::MAX), COIN_VALUE)]);
+        }
+
+        // only the funds locked by timestamp are still locked
+        let tx = create_transaction(&receiver, TransactionType::Burn { asset: XELIS_ASSET, amount: 6 * COIN_VALUE }, 1000, 0);
+        assert!(matches!(blockchain.add_tx_to_mempool(tx, false).await, Err(BlockchainError::Overflow)));
+        let tx = create_transaction(&receiver, burn, 1000, 0);
+        blockchain.add_tx_to_mempool(tx, false).await.unwrap();
+
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_transaction_expiry() {
+        let keypair = KeyPair::new();
+        let blockchain = create_funded_blockchain("tx-expiry", &[(&keypair, 10 * COIN_VALUE)]).await;
+        let burn = TransactionType::Burn { asset: XELIS_ASSET, amount: COIN_VALUE };
+
+        // next block is at topoheight 1
+        let tx = create_expiring_transaction(&keypair, burn.clone(), 1000, 0, 0);
+        assert!(matches!(blockchain.add_tx_to_mempool(tx, false).await, Err(BlockchainError::TransactionExpired(_, 0, 1))));
+
+        // an expired tx also evicts the next nonces of its owner
+        let tx = create_expiring_transaction(&keypair, burn.clone(), 1000, 0, 1);
+        blockchain.add_tx_to_mempool(tx, false).await.unwrap();
+        let tx = create_transaction(&keypair, burn.clone(), 1000, 1);
+        blockchain.add_tx_to_mempool(tx, false).await.unwrap();
+        {
+            let mut mempool = blockchain.get_mempool().write().await;
+            mempool.clean_up_expired(1);
+            assert_eq!(mempool.size(), 2);
+            mempool.clean_up_expired(2);
+            assert_eq!(mempool.size(), 0);
+            assert!(mempool.get_cached_nonce(keypair.get_public_key()).is_none());
+        }
+
+        // still valid in the next block
+        let tx = create_expiring_transaction(&keypair, burn, 1000, 0, 1);
+        blockchain.add_tx_to_mempool(tx, false).await.unwrap();
+        blockchain.mine_block(&DEV_PUBLIC_KEY).await.unwrap();
+        {
+            let storage = blockchain.get_storage().read().await;
+            assert_eq!(storage.get_last_nonce(keypair.get_public_key()).await.unwrap().1.get_nonce(), 1);
+        }
+
+        // a block including an expired tx is valid, but the tx is skipped when the block is ordered
+        let tx = create_expiring_transaction(&keypair, TransactionType::Burn { asset: XELIS_ASSET, amount: COIN_VALUE }, 1000, 1, 1);
+        let hash = tx.hash();
+        let mut header = blockchain.get_block_template(DEV_PUBLIC_KEY.clone()).await.unwrap();
+        header.txs_hashes.push(hash.clone());
+        let block = Block::new(Immutable::Owned(header), vec![Immutable::Owned(tx)]);
+        blockchain.add_new_block(block, false, false).await.unwrap();
+        {
+            let storage = blockchain.get_storage().read().await;
+            assert!(!storage.is_tx_executed_in_a_block(&hash).unwrap());
+            assert_eq!(storage.get_last_nonce(keypair.get_public_key()).await.unwrap().1.get_nonce(), 1);
+        }
+
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_replace_by_fee() {
+        let keypair = KeyPair::new();
+        let blockchain = create_funded_blockchain("replace-by-fee", &[(&keypair, 10 * COIN_VALUE)]).await;
+        let burn = TransactionType::Burn { asset: XELIS_ASSET, amount: COIN_VALUE };
+
+        let mut hashes = Vec::new();
+        for nonce in 0..3 {
+            let tx = create_transaction(&keypair, burn.clone(), 1000, nonce);
+            hashes.push(tx.hash());
+            blockchain.add_tx_to_mempool(tx, false).await.unwrap();
+        }
+
+        // a replacement must pay the minimum fee bump
+        let tx = create_transaction(&keypair, burn.clone(), 1050, 1);
+        assert!(matches!(blockchain.add_tx_to_mempool(tx, false).await, Err(BlockchainError::InvalidTxFee(1100, 1050))));
+
+        let tx = create_transaction(&keypair, burn, 1100, 1);
+        let replacement = tx.hash();
+        blockchain.add_tx_to_mempool(tx, false).await.unwrap();
+        {
+            let mempool = blockchain.get_mempool().read().await;
+            assert_eq!(mempool.size(), 3);
+            assert!(!mempool.contains_tx(&hashes[1]));
+
+            // txs of the owner are still ordered by nonce
+            let cache = mempool.get_cached_nonce(keypair.get_public_key()).unwrap();
+            assert_eq!(cache.has_tx_with_same_nonce(0).map(|hash| hash.as_ref()), Some(&hashes[0]));
+            assert_eq!(cache.has_tx_with_same_nonce(1).map(|hash| hash.as_ref()), Some(&replacement));
+            assert_eq!(cache.has_tx_with_same_nonce(2).map(|hash| hash.as_ref()), Some(&hashes[2]));
+        }
+
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_mempool_limits() {
+        let first = KeyPair::new();
+        let second = KeyPair::new();
+        let blockchain = create_funded_blockchain_with("mempool-limits", &[(&first, 10 * COIN_VALUE), (&second, 10 * COIN_VALUE)], &["--simulator", "--mempool-max-txs", "2"], MemoryStorage::new(None, Network::Dev).unwrap()).await;
+        let burn = TransactionType::Burn { asset: XELIS_ASSET, amount: COIN_VALUE };
+
+        let parent = create_transaction(&first, burn.clone(), 1000, 0);
+        let parent_hash = parent.hash();
+        blockchain.add_tx_to_mempool(parent, false).await.unwrap();
+        blockchain.add_tx_to_mempool(create_transaction(&first, burn.clone(), 5000, 1), false).await.unwrap();
+
+        // only the last tx of an owner can be evicted, even if its parent pays less
+        let tx = create_transaction(&second, burn.clone(), 2000, 0);
+        assert!(matches!(blockchain.add_tx_to_mempool(tx, false).await, Err(BlockchainError::MempoolFull(_))));
+
+        let tx = create_transaction(&second, burn, 6000, 0);
+        let hash = tx.hash();
+        blockchain.add_tx_to_mempool(tx, false).await.unwrap();
+        {
+            let mut mempool = blockchain.get_mempool().write().await;
+            assert_eq!(mempool.size(), 2);
+            assert!(mempool.contains_tx(&parent_hash) && mempool.contains_tx(&hash));
+            assert_eq!(mempool.get_cached_nonce(first.get_public_key()).unwrap().get_max(), 0);
+            let bytes: usize = mempool.get_txs().values().map(|sorted_tx| sorted_tx.get_size()).sum();
+            assert_eq!(mempool.get_bytes(), bytes);
+
+            mempool.clean_up_old(get_current_time() + DEFAULT_MEMPOOL_TX_EXPIRY + 1);
+            assert_eq!(mempool.size(), 0);
+            assert_eq!(mempool.get_bytes(), 0);
+        }
+
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_fee_estimation() {
+        let keypair = KeyPair::new();
+        let blockchain = create_funded_blockchain("fee-estimation", &[(&keypair, 10 * COIN_VALUE)]).await;
+        let base_rate = fee_estimator::get_base_fee_rate();
+
+        // blocks are empty and the mempool fits in the next block
+        let tx = create_transaction(&keypair, TransactionType::Burn { asset: XELIS_ASSET, amount: COIN_VALUE }, 100 * FEE_PER_KB, 0);
+        blockchain.add_tx_to_mempool(tx, false).await.unwrap();
+        {
+            let storage = blockchain.get_storage().read().await;
+            let estimates = blockchain.estimate_fee_rates(&storage).await.unwrap();
+            assert_eq!(estimates.iter().map(|estimate| estimate.blocks).collect::<Vec<_>>(), FEE_ESTIMATION_TARGETS);
+            assert!(estimates.iter().all(|estimate| estimate.fee_rate == base_rate));
+        }
+        blockchain.stop().await;
+
+        // a full block only accepted TXs paying at least its lowest fee rate
+        let full_block = 1000 * FEE_ESTIMATION_FULL_BLOCK_PERCENT;
+        assert_eq!(fee_estimator::get_required_fee_rate(full_block - 1, &[(1, 1)], 100_000), base_rate);
+        assert_eq!(fee_estimator::get_required_fee_rate(full_block, &[(4000, 100), (5000, 100)], 100_000), 40f64);
+
+        // the most demanding recent blocks only affect the fastest targets
+        let recent_rates = [50f64, 20f64, base_rate, base_rate];
+        assert_eq!(fee_estimator::estimate_fee_rate(1, &recent_rates, &[], 1000), 50f64);
+        assert_eq!(fee_estimator::estimate_fee_rate(2, &recent_rates, &[], 1000), 20f64);
+        assert_eq!(fee_estimator::estimate_fee_rate(10, &recent_rates, &[], 1000), base_rate);
+
+        // a backlog in the mempool must be outbid to be included in time
+        let pending = [(3000, 500), (2000, 500), (1000, 500), (500, 500)];
+        assert_eq!(fee_estimator::estimate_fee_rate(1, &[], &pending, 1000), 1001f64 / 500f64);
+        assert!(fee_estimator::estimate_fee_rate(1, &[], &pending, 1000) > 2f64);
+        assert_eq!(fee_estimator::estimate_fee_rate(3, &[], &pending, 1000), base_rate);
+    }
+
+    #[tokio::test]
+    async fn test_block_template_packages() {
+        let first = KeyPair::new();
+        let second = KeyPair::new();
+        let blockchain = create_funded_blockchain("template-packages", &[(&first, 10 * COIN_VALUE), (&second, 10 * COIN_VALUE)]).await;
+        let burn = TransactionType::Burn { asset: XELIS_ASSET, amount: COIN_VALUE };
+
+        // the child pays for its parent, which pays less than the TX of the second owner
+        let parent = create_transaction(&first, burn.clone(), 1000, 0);
+        let child = create_transaction(&first, burn.clone(), 50000, 1);
+        let other = create_transaction(&second, burn, 5000, 0);
+        let hashes = vec![parent.hash(), child.hash(), other.hash()];
+        for tx in [parent, child, other] {
+            blockchain.add_tx_to_mempool(tx, false).await.unwrap();
+        }
+
+        let header = blockchain.get_block_template(DEV_PUBLIC_KEY.clone()).await.unwrap();
+        assert_eq!(header.get_txs_hashes(), &hashes);
+
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_mempool_saved_on_stop() {
+        let keypair = KeyPair::new();
+        let burn = TransactionType::Burn { asset: XELIS_ASSET, amount: COIN_VALUE };
+        let txs = vec![create_transaction(&keypair, burn.clone(), 1000, 0), create_transaction(&keypair, burn, 1000, 1)];
+
+        let blockchain = create_funded_blockchain_with("mempool-saved", &[(&keypair, 10 * COIN_VALUE)], &[], MemoryStorage::new(None, Network::Dev).unwrap()).await;
+        for tx in &txs {
+            blockchain.add_tx_to_mempool(tx.clone(), false).await.unwrap();
+        }
+        blockchain.stop().await;
+        let saved = blockchain.get_storage().read().await.get_mempool_txs().await.unwrap();
+        assert_eq!(saved.len(), 2);
+
+        // the time they were first seen is kept, so the age expiry doesn't restart
+        let first_seen = get_current_time() - 100;
+        let mut storage = MemoryStorage::new(None, Network::Dev).unwrap();
+        for (tx, _) in &saved {
+            storage.add_mempool_tx(tx, first_seen).await.unwrap();
+        }
+        // not enough funds anymore, it must be discarded when loaded
+        storage.add_mempool_tx(&create_transaction(&keypair, TransactionType::Burn { asset: XELIS_ASSET, amount: 100 * COIN_VALUE }, 1000, 2), first_seen).await.unwrap();
+        let blockchain = create_funded_blockchain_with("mempool-saved", &[(&keypair, 10 * COIN_VALUE)], &[], storage).await;
+        {
+            let mempool = blockchain.get_mempool().read().await;
+            assert_eq!(mempool.size(), 2);
+            for tx in &txs {
+                assert_eq!(mempool.get_sorted_tx(&tx.hash()).unwrap().get_first_seen(), first_seen);
+            }
+        }
+
+        // the discarded TX is deleted on save
+        blockchain.save_mempool().await.unwrap();
+        assert_eq!(blockchain.get_storage().read().await.get_mempool_txs().await.unwrap().len(), 2);
+
+        // nothing is written while the mempool doesn't change
+        blockchain.get_storage().write().await.delete_mempool_tx(keypair.get_public_key(), 0).await.unwrap();
+        blockchain.save_mempool().await.unwrap();
+        assert_eq!(blockchain.get_storage().read().await.get_mempool_txs().await.unwrap().len(), 1);
+
+        blockchain.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_import_corrupted_chain() {
+        let path = temp_file_path("corrupted");
+        let source = create_blockchain(&["--simulator"]).await;
+        source.mine_block(&DEV_PUBLIC_KEY).await.unwrap();
+        source.export_chain(&path).await.unwrap();
+        source.stop().await;
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        std::fs::write(&path, bytes).unwrap();
+
+        let blockchain = create_blockchain(&[]).await;
+        assert!(matches!(blockchain.import_chain(&path, true).await, Err(BlockchainError::ChainFileChecksumMismatch)));
+        assert_eq!(blockchain.get_topo_height(), 0);
+        blockchain.stop().await;
+        let _ = std::fs::remove_file(&path);
+    }
+}
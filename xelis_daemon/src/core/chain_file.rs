@@ -0,0 +1,189 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write}
+};
+use xelis_common::{
+    serializer::{Serializer, Reader, ReaderError, Writer},
+    crypto::hash::{hash, Hash, HASH_SIZE},
+    block::Block,
+    network::Network
+};
+use crate::config::MAX_BLOCK_SIZE;
+use super::error::BlockchainError;
+
+// Portable file containing all blocks of a chain in topological order
+// Format: [magic][header][(block size, block)...][checksum]
+// The checksum is chained: each block is hashed with the previous checksum, starting from the header
+const CHAIN_FILE_MAGIC: &[u8; 8] = b"XELCHAIN";
+// Increase it each time the format is changed
+pub const CHAIN_FILE_VERSION: u8 = 1;
+// magic + version + network + blocks count
+const CHAIN_FILE_HEADER_SIZE: usize = 8 + 1 + 1 + 8;
+
+pub struct ChainFileHeader {
+    version: u8,
+    network: Network,
+    // number of blocks stored in the file
+    blocks: u64
+}
+
+impl ChainFileHeader {
+    pub fn get_network(&self) -> &Network {
+        &self.network
+    }
+
+    pub fn get_blocks_count(&self) -> u64 {
+        self.blocks
+    }
+}
+
+impl Serializer for ChainFileHeader {
+    fn write(&self, writer: &mut Writer) {
+        writer.write_bytes(CHAIN_FILE_MAGIC);
+        writer.write_u8(self.version);
+        self.network.write(writer);
+        writer.write_u64(&self.blocks);
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        if reader.read_bytes_ref(CHAIN_FILE_MAGIC.len())? != CHAIN_FILE_MAGIC {
+            return Err(ReaderError::InvalidValue)
+        }
+
+        let version = reader.read_u8()?;
+        let network = Network::read(reader)?;
+        let blocks = reader.read_u64()?;
+        Ok(Self {
+            version,
+            network,
+            blocks
+        })
+    }
+}
+
+fn chain_checksum(previous: &Hash, bytes: &[u8]) -> Hash {
+    let mut buffer = Vec::with_capacity(HASH_SIZE + bytes.len());
+    buffer.extend_from_slice(previous.as_bytes());
+    buffer.extend_from_slice(bytes);
+    hash(&buffer)
+}
+
+pub struct ChainFileWriter {
+    writer: BufWriter<File>,
+    checksum: Hash,
+    expected: u64,
+    written: u64
+}
+
+impl ChainFileWriter {
+    pub fn create(path: &str, network: Network, blocks: u64) -> Result<Self, BlockchainError> {
+        let header = ChainFileHeader {
+            version: CHAIN_FILE_VERSION,
+            network,
+            blocks
+        };
+        let bytes = header.to_bytes();
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(&bytes)?;
+
+        Ok(Self {
+            writer,
+            checksum: hash(&bytes),
+            expected: blocks,
+            written: 0
+        })
+    }
+
+    pub fn write_block(&mut self, block: &Block) -> Result<(), BlockchainError> {
+        if self.written >= self.expected {
+            return Err(BlockchainError::InvalidChainFile)
+        }
+
+        let bytes = block.to_bytes();
+        self.writer.write_all(&(bytes.len() as u32).to_be_bytes())?;
+        self.writer.write_all(&bytes)?;
+        self.checksum = chain_checksum(&self.checksum, &bytes);
+        self.written += 1;
+        Ok(())
+    }
+
+    // write the checksum at the end of the file and returns it
+    pub fn finish(mut self) -> Result<Hash, BlockchainError> {
+        if self.written != self.expected {
+            return Err(BlockchainError::InvalidChainFile)
+        }
+
+        self.writer.write_all(self.checksum.as_bytes())?;
+        self.writer.flush()?;
+        Ok(self.checksum)
+    }
+}
+
+pub struct ChainFileReader {
+    reader: BufReader<File>,
+    header: ChainFileHeader,
+    checksum: Hash,
+    read: u64
+}
+
+impl ChainFileReader {
+    pub fn open(path: &str) -> Result<Self, BlockchainError> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut bytes = [0u8; CHAIN_FILE_HEADER_SIZE];
+        reader.read_exact(&mut bytes)?;
+        let header = ChainFileHeader::from_bytes(&bytes).map_err(|_| BlockchainError::InvalidChainFile)?;
+        if header.version != CHAIN_FILE_VERSION {
+            return Err(BlockchainError::UnsupportedChainFileVersion(header.version))
+        }
+
+        Ok(Self {
+            reader,
+            header,
+            checksum: hash(&bytes),
+            read: 0
+        })
+    }
+
+    // Read the whole file to verify its checksum before using it
+    pub fn verify(path: &str) -> Result<ChainFileHeader, BlockchainError> {
+        let mut reader = Self::open(path)?;
+        while reader.next_block_bytes()?.is_some() {}
+        Ok(reader.header)
+    }
+
+    fn next_block_bytes(&mut self) -> Result<Option<Vec<u8>>, BlockchainError> {
+        if self.read == self.header.blocks {
+            // all blocks were read, verify the checksum
+            let mut checksum = [0u8; HASH_SIZE];
+            self.reader.read_exact(&mut checksum)?;
+            if Hash::new(checksum) != self.checksum {
+                return Err(BlockchainError::ChainFileChecksumMismatch)
+            }
+            self.read += 1;
+            return Ok(None)
+        } else if self.read > self.header.blocks {
+            return Ok(None)
+        }
+
+        let mut size = [0u8; 4];
+        self.reader.read_exact(&mut size)?;
+        let size = u32::from_be_bytes(size) as usize;
+        if size > MAX_BLOCK_SIZE {
+            return Err(BlockchainError::InvalidChainFile)
+        }
+
+        let mut bytes = vec![0u8; size];
+        self.reader.read_exact(&mut bytes)?;
+        self.checksum = chain_checksum(&self.checksum, &bytes);
+        self.read += 1;
+        Ok(Some(bytes))
+    }
+
+    // Returns the next block, or None when all blocks were read and the checksum is valid
+    pub fn next_block(&mut self) -> Result<Option<Block>, BlockchainError> {
+        match self.next_block_bytes()? {
+            Some(bytes) => Ok(Some(Block::from_bytes(&bytes).map_err(|_| BlockchainError::InvalidChainFile)?)),
+            None => Ok(None)
+        }
+    }
+}
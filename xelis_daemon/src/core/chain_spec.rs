@@ -0,0 +1,151 @@
+use std::fs;
+use serde::{Serialize, Deserialize};
+use xelis_common::{
+    api::daemon::DevFeeThreshold,
+    block::{Block, BlockHeader, Difficulty, EXTRA_NONCE_SIZE},
+    crypto::{address::Address, key::PublicKey, hash::{Hash, hash}},
+    immutable::Immutable,
+    config::XELIS_ASSET,
+    serializer::{Serializer, Writer}
+};
+use crate::config::{DEV_ADDRESS, BLOCK_TIME_MILLIS, MINIMUM_DIFFICULTY, DEV_FEES, MAXIMUM_SUPPLY};
+use super::error::BlockchainError;
+
+// Chain parameters used by the consensus rules
+// Mainnet and testnet always use the hard-coded ones from the config
+// while a devnet can load its own from a JSON file
+// Missing fields in the file are set to their hard-coded value
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChainSpec {
+    // miner of the genesis block, it also receives the dev fees
+    pub genesis_miner: Address,
+    // timestamp in milliseconds of the genesis block
+    // if not set, the hard-coded genesis block is used
+    pub genesis_timestamp: Option<u64>,
+    // expected time between two blocks in milliseconds
+    pub block_time_millis: u64,
+    // difficulty can't go below it
+    pub minimum_difficulty: Difficulty,
+    // dev fee percentage applied until each height
+    pub dev_fees: Vec<DevFeeThreshold>,
+    // balances credited at topoheight 0, requires a genesis timestamp
+    pub allocations: Vec<GenesisAllocation>
+}
+
+// Amount of an asset credited to an account in the genesis block
+#[derive(Serialize, Deserialize)]
+pub struct GenesisAllocation {
+    pub address: Address,
+    #[serde(default = "default_allocation_asset")]
+    pub asset: Hash,
+    pub amount: u64
+}
+
+fn default_allocation_asset() -> Hash {
+    XELIS_ASSET
+}
+
+impl Default for ChainSpec {
+    fn default() -> Self {
+        Self {
+            genesis_miner: Address::from_string(&DEV_ADDRESS.to_owned()).unwrap(),
+            genesis_timestamp: None,
+            block_time_millis: BLOCK_TIME_MILLIS,
+            minimum_difficulty: MINIMUM_DIFFICULTY,
+            dev_fees: DEV_FEES.to_vec(),
+            allocations: Vec::new()
+        }
+    }
+}
+
+impl ChainSpec {
+    // Load a chain spec from a JSON file and verify its parameters
+    pub fn from_file(path: &str) -> Result<Self, BlockchainError> {
+        let content = fs::read_to_string(path)?;
+        let spec: Self = serde_json::from_str(&content).map_err(|e| BlockchainError::InvalidChainSpec(e.to_string()))?;
+        spec.verify()?;
+        Ok(spec)
+    }
+
+    fn verify(&self) -> Result<(), BlockchainError> {
+        if !self.genesis_miner.is_normal() {
+            return Err(BlockchainError::InvalidChainSpec("genesis miner must be a normal address".to_owned()))
+        }
+
+        if self.block_time_millis == 0 {
+            return Err(BlockchainError::InvalidChainSpec("block time can't be zero".to_owned()))
+        }
+
+        if self.minimum_difficulty == 0 {
+            return Err(BlockchainError::InvalidChainSpec("minimum difficulty can't be zero".to_owned()))
+        }
+
+        if self.dev_fees.iter().any(|threshold| threshold.fee_percentage > 100) {
+            return Err(BlockchainError::InvalidChainSpec("dev fee percentage can't be above 100".to_owned()))
+        }
+
+        if self.dev_fees.windows(2).any(|window| window[0].height >= window[1].height) {
+            return Err(BlockchainError::InvalidChainSpec("dev fees must be sorted by height".to_owned()))
+        }
+
+        if !self.allocations.is_empty() && self.genesis_timestamp.is_none() {
+            return Err(BlockchainError::InvalidChainSpec("allocations require a genesis timestamp".to_owned()))
+        }
+
+        let mut premine: u64 = 0;
+        for allocation in &self.allocations {
+            if !allocation.address.is_normal() {
+                return Err(BlockchainError::InvalidChainSpec("allocation address must be a normal address".to_owned()))
+            }
+
+            if allocation.amount == 0 {
+                return Err(BlockchainError::InvalidChainSpec("allocation amount can't be zero".to_owned()))
+            }
+
+            if allocation.asset == XELIS_ASSET {
+                premine = premine.checked_add(allocation.amount).filter(|premine| *premine <= MAXIMUM_SUPPLY)
+                    .ok_or_else(|| BlockchainError::InvalidChainSpec("allocations are above the maximum supply".to_owned()))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn get_dev_public_key(&self) -> &PublicKey {
+        self.genesis_miner.get_public_key()
+    }
+
+    // XELIS amount allocated in the genesis block, it is included in the initial supply
+    pub fn get_premine_supply(&self) -> u64 {
+        self.allocations.iter()
+            .filter(|allocation| allocation.asset == XELIS_ASSET)
+            .map(|allocation| allocation.amount)
+            .sum()
+    }
+
+    // Hash committing to all allocations in their order
+    pub fn get_allocations_hash(&self) -> Hash {
+        let mut writer = Writer::new();
+        for allocation in &self.allocations {
+            allocation.address.get_public_key().write(&mut writer);
+            writer.write_hash(&allocation.asset);
+            writer.write_u64(&allocation.amount);
+        }
+        hash(writer.as_bytes())
+    }
+
+    // Build the genesis block from its timestamp and miner
+    // allocations hash is set as extra nonce so peers with different allocations have a different genesis
+    // Returns None if the hard-coded genesis block must be used
+    pub fn build_genesis_block(&self) -> Option<Block> {
+        let timestamp = self.genesis_timestamp?;
+        let extra_nonce = if self.allocations.is_empty() {
+            [0u8; EXTRA_NONCE_SIZE]
+        } else {
+            self.get_allocations_hash().to_bytes()
+        };
+        let header = BlockHeader::new(0, 0, timestamp as u128, Vec::new(), extra_nonce, self.get_dev_public_key().clone(), Vec::new());
+        Some(Block::new(Immutable::Owned(header), Vec::new()))
+    }
+}
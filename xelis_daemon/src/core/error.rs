@@ -1,4 +1,4 @@
-use crate::p2p::error::P2pError;
+use crate::{p2p::error::P2pError, core::storage::StorageBackend};
 use std::sync::PoisonError;
 use thiserror::Error;
 use xelis_common::{crypto::{hash::Hash, key::PublicKey, bech32::Bech32Error}, serializer::ReaderError, prompt::PromptError, difficulty::DifficultyError};
@@ -127,6 +127,46 @@ pub enum BlockchainError {
     UnexpectedTransactionVariant,
     #[error("Unexpected error on database: {}", _0)]
     DatabaseError(#[from] sled::Error),
+    #[cfg(feature = "rocksdb")]
+    #[error("Unexpected error on RocksDB database: {}", _0)]
+    RocksDBError(#[from] rocksdb::Error),
+    #[cfg(feature = "rocksdb")]
+    #[error("Column family {} not found in database", _0)]
+    ColumnFamilyNotFound(String),
+    #[error("Database schema version {} is not supported, maximum supported is {}", _0, _1)]
+    UnsupportedDatabaseVersion(u64, u64),
+    #[error("Cannot migrate from version {}, database is at version {}", _0, _1)]
+    InvalidMigrationVersion(u64, u64),
+    #[error("Invalid chain file")]
+    InvalidChainFile,
+    #[error("Chain file version {} is not supported", _0)]
+    UnsupportedChainFileVersion(u8),
+    #[error("Chain file checksum mismatch")]
+    ChainFileChecksumMismatch,
+    #[error("Chain is pruned until topoheight {}, it can't be exported", _0)]
+    ChainIsPruned(u64),
+    #[error("State root at topoheight {} is not available, chain is pruned until topoheight {}", _0, _1)]
+    StateRootPruned(u64, u64),
+    #[error("State root mismatch: expected {}, got {}", _0, _1)]
+    StateRootMismatch(Hash, Hash),
+    #[error("State root at the common base of the tips is not available")]
+    StateRootNotAvailable,
+    #[error("Invalid state root in block header for its version")]
+    InvalidBlockStateRoot,
+    #[error("Block {} committing the state root doesn't descend from the stable block {}", _0, _1)]
+    StateRootBlockNotDescendant(Hash, Hash),
+    #[error("Invalid cumulative difficulty for block {}", _0)]
+    InvalidCumulativeDifficulty(Hash),
+    #[error("Invalid snapshot file")]
+    InvalidSnapshotFile,
+    #[error("Snapshot file version {} is not supported", _0)]
+    UnsupportedSnapshotFileVersion(u8),
+    #[error("Snapshot file checksum mismatch")]
+    SnapshotChecksumMismatch,
+    #[error("Chain is not empty (topoheight {}), snapshot can only be loaded on a new chain", _0)]
+    ChainNotEmpty(u64),
+    #[error("Storage backend {:?} is not available in this build", _0)]
+    StorageBackendNotAvailable(StorageBackend),
     #[error("Data not found on disk: {}", _0)]
     NotFoundOnDisk(DiskContext),
     #[error("Expected at least one tips")]
@@ -172,7 +212,49 @@ pub enum BlockchainError {
     #[error("Prune topoheight is lower or equal than previous pruned topoheight")]
     PruneLowerThanLastPruned,
     #[error("Auto prune mode is misconfigured")]
-    AutoPruneMode
+    AutoPruneMode,
+    #[error("A storage batch is already in progress")]
+    BatchAlreadyInProgress,
+    #[error("Invalid checkpoint '{}', expected format is topoheight:hash", _0)]
+    InvalidCheckpoint(String),
+    #[error("Block {} at topoheight {} doesn't match checkpoint {}", _1, _0, _2)]
+    CheckpointMismatch(u64, Hash, Hash),
+    #[error("Chain can't be reorganized below the checkpoint at topoheight {}", _0)]
+    ReorgBelowCheckpoint(u64),
+    #[error("Invalid block version, expected {}, got {}", _0, _1)]
+    InvalidBlockVersion(u8, u8),
+    #[error("No PoW algorithm found for block version {}", _0)]
+    NoPowAlgorithm(u8),
+    #[error("Invalid transaction version, maximum allowed is {}, got {}", _0, _1)]
+    InvalidTxVersion(u8, u8),
+    #[error("Invalid chain spec: {}", _0)]
+    InvalidChainSpec(String),
+    #[error("Invalid asset name or ticker in tx {}", _0)]
+    InvalidAssetName(Hash),
+    #[error("Invalid asset decimals, maximum allowed is {}, got {}", _0, _1)]
+    InvalidAssetDecimals(u8, u8),
+    #[error("A non-zero maximum supply is required for asset in tx {}", _0)]
+    NoMaxSupplyForAsset(Hash),
+    #[error("Asset {} is already registered", _0)]
+    AssetAlreadyRegistered(Hash),
+    #[error("A non-zero value is required for mint")]
+    NoValueForMint,
+    #[error("Tx {} is not sent by the mint authority of asset {}", _0, _1)]
+    InvalidAssetAuthority(Hash, Hash),
+    #[error("Maximum supply of asset {} would be exceeded", _0)]
+    AssetMaxSupplyExceeded(Hash),
+    #[error("Feature {} is not active at height {}", _0, _1)]
+    FeatureNotActive(&'static str, u64),
+    #[error("Invalid multisig participants or threshold in tx {}", _0)]
+    InvalidMultiSigSetup(Hash),
+    #[error("Tx {} is missing the signatures of the multisig participants", _0)]
+    MissingMultiSig(Hash),
+    #[error("Invalid multisig signatures in tx {}", _0)]
+    InvalidMultiSig(Hash),
+    #[error("Tx {} is only valid until topoheight {}, next topoheight is {}", _0, _1, _2)]
+    TransactionExpired(Hash, u64, u64),
+    #[error("Mempool is full and tx {} doesn't pay enough fees per byte", _0)]
+    MempoolFull(Hash)
 }
 
 impl<T> From<PoisonError<T>> for BlockchainError {
@@ -0,0 +1,57 @@
+use xelis_common::config::FEE_PER_KB;
+use crate::config::FEE_ESTIMATION_FULL_BLOCK_PERCENT;
+
+// lowest fee rate accepted, a TX pays at least FEE_PER_KB per KB
+pub fn get_base_fee_rate() -> f64 {
+    FEE_PER_KB as f64 / 1024f64
+}
+
+// fee in atomic units paid per byte
+pub fn get_fee_rate(fee: u64, size: usize) -> f64 {
+    fee as f64 / size as f64
+}
+
+// lowest fee rate that was required to be included in a block
+// if the block was not full, any TX paying the minimum fee could be included
+pub fn get_required_fee_rate(block_size: usize, txs: &[(u64, usize)], max_block_size: usize) -> f64 {
+    if block_size * 100 < max_block_size * FEE_ESTIMATION_FULL_BLOCK_PERCENT {
+        return get_base_fee_rate()
+    }
+
+    txs.iter()
+        .map(|(fee, size)| get_fee_rate(*fee, *size))
+        .min_by(|a, b| a.total_cmp(b))
+        .unwrap_or_else(get_base_fee_rate)
+}
+
+// estimate the fee rate required to be included within this count of blocks
+// recent_rates are the rates required by the last blocks, see get_required_fee_rate
+// mempool contains the fee and size of each pending TX
+pub fn estimate_fee_rate(blocks: u64, recent_rates: &[f64], mempool: &[(u64, usize)], max_block_size: usize) -> f64 {
+    let blocks = blocks.max(1);
+
+    // the pending TXs paying the most are included first in the next blocks
+    // a new TX must outbid the first one which doesn't fit in them
+    // paying the same rate is not enough as the order between equal rates is not guaranteed
+    let mut pending: Vec<(f64, u64, usize)> = mempool.iter().map(|(fee, size)| (get_fee_rate(*fee, *size), *fee, *size)).collect();
+    pending.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let capacity = max_block_size.saturating_mul(blocks as usize);
+    let mut total_size = 0;
+    let mut mempool_rate = get_base_fee_rate();
+    for (_, fee, size) in pending {
+        total_size += size;
+        if total_size > capacity {
+            // one more atomic unit than the TX to outbid
+            mempool_rate = get_fee_rate(fee.saturating_add(1), size);
+            break;
+        }
+    }
+
+    // to be included within N blocks, the N-th most demanding recent block must have accepted it
+    let mut recent: Vec<f64> = recent_rates.to_vec();
+    recent.sort_by(|a, b| b.total_cmp(a));
+    let index = (blocks as usize - 1).min(recent.len().saturating_sub(1));
+    let recent_rate = recent.get(index).copied().unwrap_or_else(get_base_fee_rate);
+
+    mempool_rate.max(recent_rate).max(get_base_fee_rate())
+}
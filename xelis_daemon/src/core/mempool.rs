@@ -3,7 +3,6 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use indexmap::IndexSet;
 use log::{trace, debug, warn};
-use xelis_common::utils::get_current_time;
 use xelis_common::{
     crypto::{
         hash::Hash,
@@ -33,21 +32,126 @@ pub struct Mempool {
     // store all txs waiting to be included in a block
     txs: HashMap<Arc<Hash>, SortedTx>,
     // store all sender's nonce for faster finding
-    nonces_cache: HashMap<PublicKey, NonceCache>
+    nonces_cache: HashMap<PublicKey, NonceCache>,
+    // total size in bytes of all txs
+    bytes: usize,
+    // maximum count of txs
+    #[serde(skip)]
+    max_txs: usize,
+    // maximum total size in bytes of all txs
+    #[serde(skip)]
+    max_bytes: usize,
+    // time in seconds after which a tx is deleted
+    #[serde(skip)]
+    tx_expiry: u64
 }
 
 impl Mempool {
-    pub fn new() -> Self {
+    pub fn new(max_txs: usize, max_bytes: usize, tx_expiry: u64) -> Self {
         Mempool {
             txs: HashMap::new(),
-            nonces_cache: HashMap::new()
+            nonces_cache: HashMap::new(),
+            bytes: 0,
+            max_txs,
+            max_bytes,
+            tx_expiry
+        }
+    }
+
+    // delete a tx from the map and update the total size
+    fn remove_sorted_tx(txs: &mut HashMap<Arc<Hash>, SortedTx>, bytes: &mut usize, hash: &Hash) -> Option<SortedTx> {
+        let sorted_tx = txs.remove(hash)?;
+        *bytes -= sorted_tx.get_size();
+        Some(sorted_tx)
+    }
+
+    // select the txs to delete to make room for a tx of this size, lowest fee per byte first
+    // only the last tx of each owner can be selected, so a tx is never kept without its parent
+    // the owner of the new tx is excluded as its txs are the parents of the new one
+    // fails if the new tx doesn't pay a higher fee per byte than a tx to delete
+    fn select_txs_to_evict(&self, hash: &Hash, tx: &Transaction, size: usize, replaced_size: Option<usize>) -> Result<Vec<(PublicKey, Arc<Hash>)>, BlockchainError> {
+        let mut count = self.txs.len();
+        let mut bytes = self.bytes + size;
+        if let Some(replaced_size) = replaced_size {
+            bytes -= replaced_size;
+        } else {
+            count += 1;
+        }
+
+        let mut evicted = Vec::new();
+        // count of txs still available for each owner
+        let mut remaining: HashMap<&PublicKey, usize> = HashMap::new();
+        while count > self.max_txs || bytes > self.max_bytes {
+            let mut lowest: Option<(&PublicKey, &Arc<Hash>, &SortedTx)> = None;
+            for (key, cache) in self.nonces_cache.iter().filter(|(key, _)| *key != tx.get_owner()) {
+                let end = *remaining.get(key).unwrap_or(&cache.txs.len());
+                let Some(last) = end.checked_sub(1).and_then(|index| cache.txs.get_index(index)) else {
+                    continue;
+                };
+
+                let Some(sorted_tx) = self.txs.get(last) else {
+                    continue;
+                };
+
+                if lowest.is_none_or(|(_, _, lowest)| has_higher_fee_rate(lowest.get_fee(), lowest.get_size(), sorted_tx.get_fee(), sorted_tx.get_size())) {
+                    lowest = Some((key, last, sorted_tx));
+                }
+            }
+
+            let Some((key, last, sorted_tx)) = lowest else {
+                return Err(BlockchainError::MempoolFull(hash.clone()))
+            };
+
+            if !has_higher_fee_rate(tx.get_fee(), size, sorted_tx.get_fee(), sorted_tx.get_size()) {
+                return Err(BlockchainError::MempoolFull(hash.clone()))
+            }
+
+            let end = remaining.entry(key).or_insert(self.nonces_cache[key].txs.len());
+            *end -= 1;
+            count -= 1;
+            bytes -= sorted_tx.get_size();
+            evicted.push((key.clone(), Arc::clone(last)));
+        }
+
+        Ok(evicted)
+    }
+
+    // delete the last tx of each owner selected for eviction
+    fn evict_txs(&mut self, evicted: Vec<(PublicKey, Arc<Hash>)>) {
+        for (key, hash) in evicted {
+            debug!("Evicting TX {} from mempool to make room", hash);
+            Self::remove_sorted_tx(&mut self.txs, &mut self.bytes, &hash);
+
+            let mut delete_cache = false;
+            if let Some(cache) = self.nonces_cache.get_mut(&key) {
+                cache.txs.pop();
+                delete_cache = cache.txs.is_empty();
+                cache.max = cache.max.saturating_sub(1);
+            }
+
+            if delete_cache {
+                self.nonces_cache.remove(&key);
+            }
         }
     }
 
     // All checks are made in Blockchain before calling this function
-    pub fn add_tx(&mut self, hash: Hash, tx: Arc<Transaction>) -> Result<(), BlockchainError> {
+    // if the mempool is full, txs with a lower fee per byte are deleted to make room
+    // returns the hash of the TX replaced by this one if it has the same nonce
+    // first_seen is the timestamp when the tx was received, kept across restarts for the age expiry
+    pub fn add_tx(&mut self, hash: Hash, tx: Arc<Transaction>, first_seen: u64) -> Result<Option<Arc<Hash>>, BlockchainError> {
+        let size = tx.size();
+        let replaced_size = self.nonces_cache.get(tx.get_owner())
+            .and_then(|cache| cache.has_tx_with_same_nonce(tx.get_nonce()))
+            .and_then(|hash| self.txs.get(hash))
+            .map(|sorted_tx| sorted_tx.get_size());
+
+        let evicted = self.select_txs_to_evict(&hash, &tx, size, replaced_size)?;
+        self.evict_txs(evicted);
+
         let hash = Arc::new(hash);
         let nonce = tx.get_nonce();
+        let mut replaced = None;
         // update the cache for this owner
         let mut must_update = true;
         if let Some(cache) = self.nonces_cache.get_mut(tx.get_owner()) {
@@ -55,20 +159,24 @@ impl Mempool {
             trace!("Cache found for owner {} with nonce range {}-{}, nonce = {}", tx.get_owner(), cache.get_min(), cache.get_max(), nonce);
             if nonce >= cache.get_min() && nonce <= cache.get_max() {
                 trace!("nonce {} is in range {}-{}", nonce, cache.get_min(), cache.get_max());
-                // because it's based on order and we may have the same order
-                let index = ((nonce - cache.get_min()) % (cache.get_max() - cache.get_min())) as usize;
-                cache.txs.insert(hash.clone());
+                // txs are ordered by nonce
+                let index = (nonce - cache.get_min()) as usize;
                 must_update = false;
 
                 if let Some(tx_hash) = cache.txs.swap_remove_index(index) {
                     trace!("TX {} with same nonce found in cache, removing it from sorted txs", tx_hash);
                     // remove the tx hash from sorted txs
-                    if self.txs.remove(&tx_hash).is_none() {
+                    if Self::remove_sorted_tx(&mut self.txs, &mut self.bytes, &tx_hash).is_none() {
                         warn!("TX {} not found in mempool while deleting collision with {}", tx_hash, hash);
                     }
+                    replaced = Some(tx_hash);
                 } else {
                     warn!("No TX found in cache for nonce {} while adding {}", nonce, hash);
                 }
+
+                // put the new TX at the same place to keep the order
+                let (last, _) = cache.txs.insert_full(hash.clone());
+                cache.txs.swap_indices(index.min(last), last);
             }
 
             if must_update {
@@ -88,15 +196,16 @@ impl Mempool {
         }
 
         let sorted_tx = SortedTx {
-            size: tx.size(),
-            first_seen: get_current_time(),
+            size,
+            first_seen,
             tx
         };
 
         // insert in map
+        self.bytes += size;
         self.txs.insert(hash, sorted_tx);
 
-        Ok(())
+        Ok(replaced)
     }
 
     pub fn get_nonces_cache(&self) -> &HashMap<PublicKey, NonceCache> {
@@ -137,9 +246,22 @@ impl Mempool {
         self.txs.len()
     }
 
+    // total size in bytes of all txs
+    pub fn get_bytes(&self) -> usize {
+        self.bytes
+    }
+
+    // lowest fee per byte paid by a tx
+    pub fn get_min_fee_rate(&self) -> Option<f64> {
+        self.txs.values()
+            .map(|sorted_tx| sorted_tx.get_fee() as f64 / sorted_tx.get_size() as f64)
+            .min_by(|a, b| a.total_cmp(b))
+    }
+
     pub fn clear(&mut self) {
         self.txs.clear();
         self.nonces_cache.clear();
+        self.bytes = 0;
     }
 
     // delete all old txs not compatible anymore with current state of account
@@ -177,10 +299,11 @@ impl Mempool {
 
                     // delete the nonce cache if no txs are left
                     delete_cache = cache.txs.is_empty();
+                    cache.min = nonce;
 
                     // now delete all necessary txs
                     for hash in hashes {
-                        if self.txs.remove(&hash).is_none() {
+                        if Self::remove_sorted_tx(&mut self.txs, &mut self.bytes, &hash).is_none() {
                             warn!("TX {} not found in mempool while deleting", hash);
                         }
                     }
@@ -193,6 +316,66 @@ impl Mempool {
             }
         }
     }
+
+    // delete all txs which can't be included anymore at this topoheight
+    pub fn clean_up_expired(&mut self, topoheight: u64) {
+        self.delete_from_first_match(|sorted_tx| !sorted_tx.get_tx().is_valid_at_topoheight(topoheight));
+    }
+
+    // delete all txs waiting for more than tx_expiry seconds
+    pub fn clean_up_old(&mut self, now: u64) {
+        let tx_expiry = self.tx_expiry;
+        self.delete_from_first_match(|sorted_tx| sorted_tx.get_first_seen().saturating_add(tx_expiry) < now);
+    }
+
+    // delete for each owner all txs starting at the lowest nonce matching the predicate
+    // all txs of the same owner with a higher nonce are deleted too as they would be stuck
+    fn delete_from_first_match<F: Fn(&SortedTx) -> bool>(&mut self, predicate: F) {
+        let Self { txs, nonces_cache, bytes, .. } = self;
+        if txs.is_empty() {
+            return;
+        }
+
+        nonces_cache.retain(|key, cache| {
+            // lowest nonce of a matching tx for this owner
+            let first_nonce = cache.txs.iter()
+                .filter_map(|hash| txs.get(hash))
+                .filter(|sorted_tx| predicate(sorted_tx))
+                .map(|sorted_tx| sorted_tx.get_tx().get_nonce())
+                .min();
+
+            let Some(first_nonce) = first_nonce else {
+                return true;
+            };
+
+            debug!("Deleting txs of owner {} starting at nonce {}", key, first_nonce);
+            cache.txs.retain(|hash| {
+                let delete = match txs.get(hash) {
+                    Some(sorted_tx) => sorted_tx.get_tx().get_nonce() >= first_nonce,
+                    None => true
+                };
+
+                if delete {
+                    trace!("Deleting TX {} from mempool", hash);
+                    Self::remove_sorted_tx(txs, bytes, hash);
+                }
+                !delete
+            });
+
+            if cache.txs.is_empty() {
+                trace!("Removing empty nonce cache for owner {}", key);
+                return false;
+            }
+
+            cache.max = first_nonce - 1;
+            true
+        });
+    }
+}
+
+// check if fee_a / size_a is strictly higher than fee_b / size_b
+pub fn has_higher_fee_rate(fee_a: u64, size_a: usize, fee_b: u64, size_b: usize) -> bool {
+    fee_a as u128 * size_b as u128 > fee_b as u128 * size_a as u128
 }
 
 impl SortedTx {
@@ -253,12 +436,8 @@ impl NonceCache {
         }
 
         trace!("has tx with same nonce: {}, max: {}, min: {}, size: {}", nonce, self.max, self.min, self.txs.len());
-        let mut r = self.max - self.min;
-        if r == 0 {
-            r = self.txs.len() as u64;
-        }
-
-        let index = ((nonce - self.min) % r) as usize;
+        // txs are ordered by nonce starting at min
+        let index = (nonce - self.min) as usize;
         self.txs.get_index(index)
     }
 }
\ No newline at end of file
@@ -3,4 +3,9 @@ pub mod mempool;
 pub mod error;
 pub mod blockdag;
 pub mod storage;
-pub mod difficulty;
\ No newline at end of file
+pub mod difficulty;
+pub mod fee_estimator;
+pub mod tx_selector;
+pub mod chain_file;
+pub mod snapshot_file;
+pub mod chain_spec;
@@ -0,0 +1,324 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write}
+};
+use xelis_common::{
+    serializer::{Serializer, Reader, ReaderError, Writer},
+    crypto::{hash::{hash, Hash, HASH_SIZE}, key::PublicKey},
+    asset::AssetWithData,
+    account::LockedBalance,
+    block::Block,
+    network::Network,
+    transaction::MultiSigSetup
+};
+use crate::{
+    config::MAX_BLOCK_SIZE,
+    p2p::packet::bootstrap_chain::{BlockMetadata, AssetState}
+};
+use super::error::BlockchainError;
+
+// State of the chain at a stable topoheight, same data as the one sent during a fast sync
+// Format: [magic][header][(entry size, entry)...][0u32][state hash][checksum]
+// The state hash is chained over the header, assets and accounts entries only
+// The checksum is chained over the header and all the entries
+const SNAPSHOT_FILE_MAGIC: &[u8; 8] = b"XELSNAPS";
+// Increase it each time the format is changed
+pub const SNAPSHOT_FILE_VERSION: u8 = 4;
+// magic + version + network + topoheight + height + top hash + genesis hash
+const SNAPSHOT_FILE_HEADER_SIZE: usize = 8 + 1 + 1 + 8 + 8 + HASH_SIZE + HASH_SIZE;
+// a block entry contains the block and its metadata
+const MAX_SNAPSHOT_ENTRY_SIZE: usize = MAX_BLOCK_SIZE + 1024;
+
+pub struct SnapshotFileHeader {
+    version: u8,
+    network: Network,
+    // stable topoheight at which the snapshot was created
+    topoheight: u64,
+    height: u64,
+    top_hash: Hash,
+    genesis_hash: Hash
+}
+
+impl SnapshotFileHeader {
+    pub fn new(network: Network, topoheight: u64, height: u64, top_hash: Hash, genesis_hash: Hash) -> Self {
+        Self {
+            version: SNAPSHOT_FILE_VERSION,
+            network,
+            topoheight,
+            height,
+            top_hash,
+            genesis_hash
+        }
+    }
+
+    pub fn get_network(&self) -> &Network {
+        &self.network
+    }
+
+    pub fn get_topoheight(&self) -> u64 {
+        self.topoheight
+    }
+
+    pub fn get_height(&self) -> u64 {
+        self.height
+    }
+
+    pub fn get_top_hash(&self) -> &Hash {
+        &self.top_hash
+    }
+
+    pub fn get_genesis_hash(&self) -> &Hash {
+        &self.genesis_hash
+    }
+}
+
+impl Serializer for SnapshotFileHeader {
+    fn write(&self, writer: &mut Writer) {
+        writer.write_bytes(SNAPSHOT_FILE_MAGIC);
+        writer.write_u8(self.version);
+        self.network.write(writer);
+        writer.write_u64(&self.topoheight);
+        writer.write_u64(&self.height);
+        writer.write_hash(&self.top_hash);
+        writer.write_hash(&self.genesis_hash);
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        if reader.read_bytes_ref(SNAPSHOT_FILE_MAGIC.len())? != SNAPSHOT_FILE_MAGIC {
+            return Err(ReaderError::InvalidValue)
+        }
+
+        let version = reader.read_u8()?;
+        let network = Network::read(reader)?;
+        let topoheight = reader.read_u64()?;
+        let height = reader.read_u64()?;
+        let top_hash = reader.read_hash()?;
+        let genesis_hash = reader.read_hash()?;
+        Ok(Self {
+            version,
+            network,
+            topoheight,
+            height,
+            top_hash,
+            genesis_hash
+        })
+    }
+}
+
+// Account state at the snapshot topoheight
+pub struct SnapshotAccount {
+    pub key: PublicKey,
+    pub nonce: u64,
+    // only assets for which the account has a balance
+    pub balances: Vec<(Hash, u64)>,
+    // amounts still locked for each asset
+    pub locked: Vec<(Hash, LockedBalance)>,
+    pub multisig: Option<MultiSigSetup>
+}
+
+pub enum SnapshotEntry {
+    Asset(AssetWithData, AssetState),
+    Account(SnapshotAccount),
+    // blocks are ordered from the snapshot topoheight to the lowest one
+    Block(BlockMetadata, Block)
+}
+
+impl SnapshotEntry {
+    // blocks are not part of the state
+    fn is_state(&self) -> bool {
+        !matches!(self, Self::Block(_, _))
+    }
+}
+
+impl Serializer for SnapshotEntry {
+    fn write(&self, writer: &mut Writer) {
+        match self {
+            Self::Asset(asset, state) => {
+                writer.write_u8(0);
+                asset.write(writer);
+                state.write(writer);
+            },
+            Self::Account(account) => {
+                writer.write_u8(1);
+                account.key.write(writer);
+                writer.write_u64(&account.nonce);
+                writer.write_u32(&(account.balances.len() as u32));
+                for (asset, balance) in &account.balances {
+                    writer.write_hash(asset);
+                    writer.write_u64(balance);
+                }
+                writer.write_u32(&(account.locked.len() as u32));
+                for (asset, locked) in &account.locked {
+                    writer.write_hash(asset);
+                    locked.write(writer);
+                }
+                account.multisig.write(writer);
+            },
+            Self::Block(metadata, block) => {
+                writer.write_u8(2);
+                metadata.write(writer);
+                block.write(writer);
+            }
+        }
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        Ok(match reader.read_u8()? {
+            0 => Self::Asset(AssetWithData::read(reader)?, AssetState::read(reader)?),
+            1 => {
+                let key = PublicKey::read(reader)?;
+                let nonce = reader.read_u64()?;
+                let count = reader.read_u32()? as usize;
+                // each balance is an asset hash and an amount
+                if count > MAX_SNAPSHOT_ENTRY_SIZE / (HASH_SIZE + 8) {
+                    return Err(ReaderError::InvalidSize)
+                }
+
+                let mut balances = Vec::with_capacity(count);
+                for _ in 0..count {
+                    balances.push((reader.read_hash()?, reader.read_u64()?));
+                }
+                let count = reader.read_u32()? as usize;
+                if count > MAX_SNAPSHOT_ENTRY_SIZE / (HASH_SIZE + 8) {
+                    return Err(ReaderError::InvalidSize)
+                }
+
+                let mut locked = Vec::with_capacity(count);
+                for _ in 0..count {
+                    locked.push((reader.read_hash()?, LockedBalance::read(reader)?));
+                }
+                let multisig = Option::read(reader)?;
+                Self::Account(SnapshotAccount { key, nonce, balances, locked, multisig })
+            },
+            2 => Self::Block(BlockMetadata::read(reader)?, Block::read(reader)?),
+            _ => return Err(ReaderError::InvalidValue)
+        })
+    }
+}
+
+fn chain_checksum(previous: &Hash, bytes: &[u8]) -> Hash {
+    let mut buffer = Vec::with_capacity(HASH_SIZE + bytes.len());
+    buffer.extend_from_slice(previous.as_bytes());
+    buffer.extend_from_slice(bytes);
+    hash(&buffer)
+}
+
+pub struct SnapshotFileWriter {
+    writer: BufWriter<File>,
+    state_hash: Hash,
+    checksum: Hash
+}
+
+impl SnapshotFileWriter {
+    pub fn create(path: &str, header: &SnapshotFileHeader) -> Result<Self, BlockchainError> {
+        let bytes = header.to_bytes();
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(&bytes)?;
+
+        let checksum = hash(&bytes);
+        Ok(Self {
+            writer,
+            state_hash: checksum.clone(),
+            checksum
+        })
+    }
+
+    pub fn write_entry(&mut self, entry: &SnapshotEntry) -> Result<(), BlockchainError> {
+        let bytes = entry.to_bytes();
+        if bytes.len() > MAX_SNAPSHOT_ENTRY_SIZE {
+            return Err(BlockchainError::InvalidSnapshotFile)
+        }
+
+        self.writer.write_all(&(bytes.len() as u32).to_be_bytes())?;
+        self.writer.write_all(&bytes)?;
+        if entry.is_state() {
+            self.state_hash = chain_checksum(&self.state_hash, &bytes);
+        }
+        self.checksum = chain_checksum(&self.checksum, &bytes);
+        Ok(())
+    }
+
+    // write the end marker, the state hash and the checksum, returns the state hash
+    pub fn finish(mut self) -> Result<Hash, BlockchainError> {
+        self.writer.write_all(&0u32.to_be_bytes())?;
+        self.writer.write_all(self.state_hash.as_bytes())?;
+        self.writer.write_all(self.checksum.as_bytes())?;
+        self.writer.flush()?;
+        Ok(self.state_hash)
+    }
+}
+
+pub struct SnapshotFileReader {
+    reader: BufReader<File>,
+    header: SnapshotFileHeader,
+    state_hash: Hash,
+    checksum: Hash,
+    done: bool
+}
+
+impl SnapshotFileReader {
+    pub fn open(path: &str) -> Result<Self, BlockchainError> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut bytes = [0u8; SNAPSHOT_FILE_HEADER_SIZE];
+        reader.read_exact(&mut bytes)?;
+        let header = SnapshotFileHeader::from_bytes(&bytes).map_err(|_| BlockchainError::InvalidSnapshotFile)?;
+        if header.version != SNAPSHOT_FILE_VERSION {
+            return Err(BlockchainError::UnsupportedSnapshotFileVersion(header.version))
+        }
+
+        let checksum = hash(&bytes);
+        Ok(Self {
+            reader,
+            header,
+            state_hash: checksum.clone(),
+            checksum,
+            done: false
+        })
+    }
+
+    // Read the whole file to verify its checksum before using it
+    // Returns the header and the state hash
+    pub fn verify(path: &str) -> Result<(SnapshotFileHeader, Hash), BlockchainError> {
+        let mut reader = Self::open(path)?;
+        while reader.next_entry()?.is_some() {}
+        Ok((reader.header, reader.state_hash))
+    }
+
+    pub fn get_header(&self) -> &SnapshotFileHeader {
+        &self.header
+    }
+
+    // Returns the next entry, or None when all entries were read and the checksums are valid
+    pub fn next_entry(&mut self) -> Result<Option<SnapshotEntry>, BlockchainError> {
+        if self.done {
+            return Ok(None)
+        }
+
+        let mut size = [0u8; 4];
+        self.reader.read_exact(&mut size)?;
+        let size = u32::from_be_bytes(size) as usize;
+        if size == 0 {
+            // end marker, verify the state hash and the checksum
+            let mut state_hash = [0u8; HASH_SIZE];
+            self.reader.read_exact(&mut state_hash)?;
+            let mut checksum = [0u8; HASH_SIZE];
+            self.reader.read_exact(&mut checksum)?;
+            if Hash::new(state_hash) != self.state_hash || Hash::new(checksum) != self.checksum {
+                return Err(BlockchainError::SnapshotChecksumMismatch)
+            }
+            self.done = true;
+            return Ok(None)
+        } else if size > MAX_SNAPSHOT_ENTRY_SIZE {
+            return Err(BlockchainError::InvalidSnapshotFile)
+        }
+
+        let mut bytes = vec![0u8; size];
+        self.reader.read_exact(&mut bytes)?;
+        let entry = SnapshotEntry::from_bytes(&bytes).map_err(|_| BlockchainError::InvalidSnapshotFile)?;
+        if entry.is_state() {
+            self.state_hash = chain_checksum(&self.state_hash, &bytes);
+        }
+        self.checksum = chain_checksum(&self.checksum, &bytes);
+        Ok(Some(entry))
+    }
+}
@@ -0,0 +1,103 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex, RwLock}
+};
+use async_trait::async_trait;
+use crate::core::error::BlockchainError;
+use super::{Backend, Tree, TreeIterator};
+
+type Entries = BTreeMap<Vec<u8>, Vec<u8>>;
+
+// Backend keeping all trees in RAM, nothing is persisted
+#[derive(Default)]
+pub struct MemoryBackend {
+    trees: Mutex<HashMap<String, Arc<RwLock<Entries>>>>
+}
+
+pub struct MemoryTree {
+    name: String,
+    entries: Arc<RwLock<Entries>>
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl Backend for MemoryBackend {
+    type Tree = MemoryTree;
+
+    fn open_tree(&self, name: &str) -> Result<MemoryTree, BlockchainError> {
+        let mut trees = self.trees.lock()?;
+        let entries = trees.entry(name.to_owned()).or_default();
+        Ok(MemoryTree {
+            name: name.to_owned(),
+            entries: Arc::clone(entries)
+        })
+    }
+
+    fn size_on_disk(&self) -> Result<u64, BlockchainError> {
+        Ok(0)
+    }
+
+    async fn flush(&self) -> Result<(), BlockchainError> {
+        Ok(())
+    }
+}
+
+impl MemoryTree {
+    // entries are copied so no lock is held while iterating
+    fn collect<'a, I: Iterator<Item = (&'a Vec<u8>, &'a Vec<u8>)>>(iter: I) -> TreeIterator<'static> {
+        let entries: Vec<_> = iter.map(|(key, value)| Ok((key.clone(), value.clone()))).collect();
+        Box::new(entries.into_iter())
+    }
+}
+
+impl Tree for MemoryTree {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, BlockchainError> {
+        Ok(self.entries.read()?.get(key).cloned())
+    }
+
+    fn contains_key(&self, key: &[u8]) -> Result<bool, BlockchainError> {
+        Ok(self.entries.read()?.contains_key(key))
+    }
+
+    fn insert(&self, key: &[u8], value: &[u8]) -> Result<(), BlockchainError> {
+        self.entries.write()?.insert(key.to_vec(), value.to_vec());
+        Ok(())
+    }
+
+    fn remove(&self, key: &[u8]) -> Result<(), BlockchainError> {
+        self.entries.write()?.remove(key);
+        Ok(())
+    }
+
+    fn is_empty(&self) -> Result<bool, BlockchainError> {
+        Ok(self.entries.read()?.is_empty())
+    }
+
+    fn clear(&self) -> Result<(), BlockchainError> {
+        self.entries.write()?.clear();
+        Ok(())
+    }
+
+    fn iter(&self) -> TreeIterator<'_> {
+        match self.entries.read() {
+            Ok(entries) => Self::collect(entries.iter()),
+            Err(e) => Box::new(std::iter::once(Err(e.into())))
+        }
+    }
+
+    fn scan_prefix(&self, prefix: &[u8]) -> TreeIterator<'_> {
+        match self.entries.read() {
+            Ok(entries) => Self::collect(entries.range(prefix.to_vec()..).take_while(|(key, _)| key.starts_with(prefix))),
+            Err(e) => Box::new(std::iter::once(Err(e.into())))
+        }
+    }
+}
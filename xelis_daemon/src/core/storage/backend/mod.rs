@@ -0,0 +1,43 @@
+mod sled;
+mod memory;
+#[cfg(feature = "rocksdb")]
+mod rocksdb;
+
+pub use self::sled::SledBackend;
+pub use self::memory::MemoryBackend;
+#[cfg(feature = "rocksdb")]
+pub use self::rocksdb::RocksBackend;
+
+use async_trait::async_trait;
+use crate::core::error::BlockchainError;
+
+// Iterator over all entries (key, value) of a tree, ordered by key
+pub type TreeIterator<'a> = Box<dyn Iterator<Item = Result<(Vec<u8>, Vec<u8>), BlockchainError>> + Send + 'a>;
+
+// A tree is a named key-value map stored by the backend
+pub trait Tree: Send + Sync + 'static {
+    fn name(&self) -> &str;
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, BlockchainError>;
+    fn contains_key(&self, key: &[u8]) -> Result<bool, BlockchainError>;
+    fn insert(&self, key: &[u8], value: &[u8]) -> Result<(), BlockchainError>;
+    fn remove(&self, key: &[u8]) -> Result<(), BlockchainError>;
+    fn is_empty(&self) -> Result<bool, BlockchainError>;
+    // delete all entries of the tree
+    fn clear(&self) -> Result<(), BlockchainError>;
+    fn iter(&self) -> TreeIterator<'_>;
+    // iterate over all entries having a key starting with prefix
+    fn scan_prefix(&self, prefix: &[u8]) -> TreeIterator<'_>;
+}
+
+// Database engine on which the chain storage is built
+#[async_trait]
+pub trait Backend: Send + Sync + 'static {
+    type Tree: Tree;
+
+    // open the tree, it is created if it doesn't exist yet
+    fn open_tree(&self, name: &str) -> Result<Self::Tree, BlockchainError>;
+    // Returns the current size on disk in bytes
+    fn size_on_disk(&self) -> Result<u64, BlockchainError>;
+    // make sure that all changes are persisted on disk
+    async fn flush(&self) -> Result<(), BlockchainError>;
+}
@@ -0,0 +1,143 @@
+use std::sync::Arc;
+use async_trait::async_trait;
+use rocksdb::{DBWithThreadMode, MultiThreaded, Options, IteratorMode, Direction, BoundColumnFamily};
+use log::debug;
+use crate::core::error::BlockchainError;
+use super::{Backend, Tree, TreeIterator};
+
+type InnerDB = DBWithThreadMode<MultiThreaded>;
+
+pub struct RocksBackend {
+    db: Arc<InnerDB>
+}
+
+// A tree is a column family in RocksDB
+pub struct RocksTree {
+    name: String,
+    db: Arc<InnerDB>
+}
+
+impl RocksBackend {
+    pub fn open(dir_path: String) -> Result<Self, BlockchainError> {
+        let mut options = Options::default();
+        options.create_if_missing(true);
+        options.create_missing_column_families(true);
+
+        // all column families must be opened at the same time
+        let column_families = match InnerDB::list_cf(&options, &dir_path) {
+            Ok(column_families) => column_families,
+            Err(e) => {
+                debug!("No column families found in {}: {}", dir_path, e);
+                Vec::new()
+            }
+        };
+
+        Ok(Self {
+            db: Arc::new(InnerDB::open_cf(&options, &dir_path, column_families)?)
+        })
+    }
+}
+
+#[async_trait]
+impl Backend for RocksBackend {
+    type Tree = RocksTree;
+
+    fn open_tree(&self, name: &str) -> Result<RocksTree, BlockchainError> {
+        if self.db.cf_handle(name).is_none() {
+            debug!("Creating column family {}", name);
+            self.db.create_cf(name, &Options::default())?;
+        }
+
+        Ok(RocksTree {
+            name: name.to_owned(),
+            db: Arc::clone(&self.db)
+        })
+    }
+
+    fn size_on_disk(&self) -> Result<u64, BlockchainError> {
+        let size = self.db.live_files()?.iter().map(|file| file.size as u64).sum();
+        Ok(size)
+    }
+
+    async fn flush(&self) -> Result<(), BlockchainError> {
+        self.db.flush_wal(true)?;
+        Ok(())
+    }
+}
+
+impl RocksTree {
+    fn cf(&self) -> Result<Arc<BoundColumnFamily<'_>>, BlockchainError> {
+        self.db.cf_handle(&self.name).ok_or_else(|| BlockchainError::ColumnFamilyNotFound(self.name.clone()))
+    }
+
+    fn iter_from<'a>(&'a self, prefix: &[u8]) -> TreeIterator<'a> {
+        let cf = match self.cf() {
+            Ok(cf) => cf,
+            Err(e) => return Box::new(std::iter::once(Err(e)))
+        };
+
+        let mode = if prefix.is_empty() {
+            IteratorMode::Start
+        } else {
+            IteratorMode::From(prefix, Direction::Forward)
+        };
+
+        let prefix = prefix.to_vec();
+        let iter = self.db.iterator_cf(&cf, mode)
+            .map(|res| {
+                let (key, value) = res?;
+                Ok((key.into_vec(), value.into_vec()))
+            })
+            .take_while(move |res: &Result<(Vec<u8>, Vec<u8>), BlockchainError>| match res {
+                Ok((key, _)) => key.starts_with(&prefix),
+                Err(_) => true
+            });
+
+        Box::new(iter)
+    }
+}
+
+impl Tree for RocksTree {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, BlockchainError> {
+        Ok(self.db.get_cf(&self.cf()?, key)?)
+    }
+
+    fn contains_key(&self, key: &[u8]) -> Result<bool, BlockchainError> {
+        Ok(self.db.get_pinned_cf(&self.cf()?, key)?.is_some())
+    }
+
+    fn insert(&self, key: &[u8], value: &[u8]) -> Result<(), BlockchainError> {
+        self.db.put_cf(&self.cf()?, key, value)?;
+        Ok(())
+    }
+
+    fn remove(&self, key: &[u8]) -> Result<(), BlockchainError> {
+        self.db.delete_cf(&self.cf()?, key)?;
+        Ok(())
+    }
+
+    fn is_empty(&self) -> Result<bool, BlockchainError> {
+        Ok(self.db.iterator_cf(&self.cf()?, IteratorMode::Start).next().is_none())
+    }
+
+    fn clear(&self) -> Result<(), BlockchainError> {
+        let cf = self.cf()?;
+        for res in self.db.iterator_cf(&cf, IteratorMode::Start) {
+            let (key, _) = res?;
+            self.db.delete_cf(&cf, key)?;
+        }
+        Ok(())
+    }
+
+    fn iter(&self) -> TreeIterator<'_> {
+        self.iter_from(&[])
+    }
+
+    fn scan_prefix(&self, prefix: &[u8]) -> TreeIterator<'_> {
+        self.iter_from(prefix)
+    }
+}
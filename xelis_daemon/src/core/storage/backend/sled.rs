@@ -0,0 +1,88 @@
+use async_trait::async_trait;
+use crate::core::error::BlockchainError;
+use super::{Backend, Tree, TreeIterator};
+
+pub struct SledBackend {
+    db: sled::Db
+}
+
+pub struct SledTree {
+    name: String,
+    tree: sled::Tree
+}
+
+impl SledBackend {
+    pub fn open(dir_path: String) -> Result<Self, BlockchainError> {
+        Ok(Self {
+            db: sled::open(dir_path)?
+        })
+    }
+}
+
+#[async_trait]
+impl Backend for SledBackend {
+    type Tree = SledTree;
+
+    fn open_tree(&self, name: &str) -> Result<SledTree, BlockchainError> {
+        Ok(SledTree {
+            name: name.to_owned(),
+            tree: self.db.open_tree(name)?
+        })
+    }
+
+    fn size_on_disk(&self) -> Result<u64, BlockchainError> {
+        Ok(self.db.size_on_disk()?)
+    }
+
+    async fn flush(&self) -> Result<(), BlockchainError> {
+        self.db.flush_async().await?;
+        Ok(())
+    }
+}
+
+impl Tree for SledTree {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, BlockchainError> {
+        Ok(self.tree.get(key)?.map(|value| value.to_vec()))
+    }
+
+    fn contains_key(&self, key: &[u8]) -> Result<bool, BlockchainError> {
+        Ok(self.tree.contains_key(key)?)
+    }
+
+    fn insert(&self, key: &[u8], value: &[u8]) -> Result<(), BlockchainError> {
+        self.tree.insert(key, value)?;
+        Ok(())
+    }
+
+    fn remove(&self, key: &[u8]) -> Result<(), BlockchainError> {
+        self.tree.remove(key)?;
+        Ok(())
+    }
+
+    fn is_empty(&self) -> Result<bool, BlockchainError> {
+        Ok(self.tree.is_empty())
+    }
+
+    fn clear(&self) -> Result<(), BlockchainError> {
+        self.tree.clear()?;
+        Ok(())
+    }
+
+    fn iter(&self) -> TreeIterator<'_> {
+        Box::new(self.tree.iter().map(|res| {
+            let (key, value) = res?;
+            Ok((key.to_vec(), value.to_vec()))
+        }))
+    }
+
+    fn scan_prefix(&self, prefix: &[u8]) -> TreeIterator<'_> {
+        Box::new(self.tree.scan_prefix(prefix).map(|res| {
+            let (key, value) = res?;
+            Ok((key.to_vec(), value.to_vec()))
+        }))
+    }
+}
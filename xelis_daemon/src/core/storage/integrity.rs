@@ -0,0 +1,155 @@
+use crate::core::error::BlockchainError;
+use super::{backend::Backend, KeyValueStorage};
+
+// Result of an integrity check over the storage
+pub struct IntegrityReport {
+    pub name: &'static str,
+    // number of entries verified
+    pub checked: u64,
+    // description of each inconsistency found
+    pub errors: Vec<String>,
+    // number of entries rewritten in repair mode
+    pub repaired: u64
+}
+
+impl IntegrityReport {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            checked: 0,
+            errors: Vec::new(),
+            repaired: 0
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+// An invariant verified on all entries of the storage
+// Only derived data can be repaired, other errors are only reported
+pub(super) struct IntegrityCheck<B: Backend> {
+    pub name: &'static str,
+    // second parameter is the repair mode
+    pub run: fn(&KeyValueStorage<B>, bool, &mut IntegrityReport) -> Result<(), BlockchainError>
+}
+
+// All checks, in the order they are run
+pub(super) fn get_integrity_checks<B: Backend>() -> Vec<IntegrityCheck<B>> {
+    vec![
+        IntegrityCheck {
+            name: "Topoheights and block hashes are bijective",
+            run: check_topoheights
+        },
+        IntegrityCheck {
+            name: "Transactions of all blocks are stored",
+            run: check_block_transactions
+        },
+        IntegrityCheck {
+            name: "Blocks linked to transactions match blocks contents",
+            run: check_tx_blocks
+        },
+        IntegrityCheck {
+            name: "Versioned balances are chained",
+            run: check_versioned_balances
+        },
+        IntegrityCheck {
+            name: "Versioned nonces are chained",
+            run: check_versioned_nonces
+        },
+        IntegrityCheck {
+            name: "State tree commits the latest balances and nonces",
+            run: check_state_tree
+        },
+        IntegrityCheck {
+            name: "Account history entries are indexed and their transactions stored",
+            run: check_account_history
+        },
+        IntegrityCheck {
+            name: "Assets supply and authority versions belong to registered assets",
+            run: check_assets_versions
+        },
+        IntegrityCheck {
+            name: "Multisig setups are indexed and valid",
+            run: check_multisig
+        },
+        IntegrityCheck {
+            name: "Locked balances are indexed and valid",
+            run: check_locked_balances
+        },
+        IntegrityCheck {
+            name: "Blocks at each height match stored blocks",
+            run: check_blocks_at_height
+        },
+        IntegrityCheck {
+            name: "Executed transactions are included in their ordered block",
+            run: check_txs_executed
+        },
+        IntegrityCheck {
+            name: "Counters match the number of stored entries",
+            run: check_counters
+        }
+    ]
+}
+
+// Topoheight by hash is rebuilt from the hash at each topoheight
+fn check_topoheights<B: Backend>(storage: &KeyValueStorage<B>, repair: bool, report: &mut IntegrityReport) -> Result<(), BlockchainError> {
+    storage.check_topoheights(repair, report)
+}
+
+fn check_block_transactions<B: Backend>(storage: &KeyValueStorage<B>, _: bool, report: &mut IntegrityReport) -> Result<(), BlockchainError> {
+    storage.check_block_transactions(report)
+}
+
+// Blocks of each transaction are rebuilt from the stored blocks
+fn check_tx_blocks<B: Backend>(storage: &KeyValueStorage<B>, repair: bool, report: &mut IntegrityReport) -> Result<(), BlockchainError> {
+    storage.check_tx_blocks(repair, report)
+}
+
+fn check_versioned_balances<B: Backend>(storage: &KeyValueStorage<B>, _: bool, report: &mut IntegrityReport) -> Result<(), BlockchainError> {
+    storage.check_versioned_balances(report)
+}
+
+fn check_versioned_nonces<B: Backend>(storage: &KeyValueStorage<B>, _: bool, report: &mut IntegrityReport) -> Result<(), BlockchainError> {
+    storage.check_versioned_nonces(report)
+}
+
+// State tree is rebuilt from the versions of balances and nonces
+fn check_state_tree<B: Backend>(storage: &KeyValueStorage<B>, repair: bool, report: &mut IntegrityReport) -> Result<(), BlockchainError> {
+    storage.check_state_tree(repair, report)
+}
+
+// Only the topoheight index of the entries is rebuilt
+fn check_account_history<B: Backend>(storage: &KeyValueStorage<B>, repair: bool, report: &mut IntegrityReport) -> Result<(), BlockchainError> {
+    storage.check_account_history(repair, report)
+}
+
+// Only the topoheight index of the versions is rebuilt
+fn check_assets_versions<B: Backend>(storage: &KeyValueStorage<B>, repair: bool, report: &mut IntegrityReport) -> Result<(), BlockchainError> {
+    storage.check_assets_versions(repair, report)
+}
+
+// Only the topoheight index of the versions is rebuilt
+fn check_multisig<B: Backend>(storage: &KeyValueStorage<B>, repair: bool, report: &mut IntegrityReport) -> Result<(), BlockchainError> {
+    storage.check_multisig(repair, report)
+}
+
+// Only the topoheight index of the amounts is rebuilt
+fn check_locked_balances<B: Backend>(storage: &KeyValueStorage<B>, repair: bool, report: &mut IntegrityReport) -> Result<(), BlockchainError> {
+    storage.check_locked_balances(repair, report)
+}
+
+// Blocks at each height are rebuilt from the stored blocks
+fn check_blocks_at_height<B: Backend>(storage: &KeyValueStorage<B>, repair: bool, report: &mut IntegrityReport) -> Result<(), BlockchainError> {
+    storage.check_blocks_at_height(repair, report)
+}
+
+fn check_txs_executed<B: Backend>(storage: &KeyValueStorage<B>, _: bool, report: &mut IntegrityReport) -> Result<(), BlockchainError> {
+    storage.check_txs_executed(report)
+}
+
+// Counters are rebuilt from the number of entries of their tree
+fn check_counters<B: Backend>(storage: &KeyValueStorage<B>, repair: bool, report: &mut IntegrityReport) -> Result<(), BlockchainError> {
+    storage.check_counters(repair, report)
+}
@@ -0,0 +1,3160 @@
+use async_trait::async_trait;
+use indexmap::IndexSet;
+use crate::{
+    core::error::{BlockchainError, DiskContext},
+    config::PRUNE_SAFETY_LIMIT
+};
+use xelis_common::{
+    serializer::{Reader, ReaderError, Serializer, Writer},
+    crypto::{
+        key::PublicKey,
+        hash::{Hash, HASH_SIZE},
+        merkle::{balance_leaf_key, nonce_leaf_key, hash_leaf, hash_node, is_right_at_depth, compute_state_root, StateProof, EMPTY_STATE_ROOT}
+    },
+    immutable::Immutable,
+    transaction::{Transaction, MultiSigSetup, UnlockCondition},
+    block::{BlockHeader, Block, Difficulty},
+    account::{VersionedBalance, VersionedNonce, LockedBalance},
+    network::Network, asset::{AssetData, AssetWithData},
+    config::XELIS_ASSET,
+    api::daemon::AccountHistoryDirection
+};
+use std::{
+    collections::{BTreeMap, HashSet},
+    hash::Hash as StdHash,
+    sync::Arc
+};
+use tokio::sync::Mutex;
+use lru::LruCache;
+use log::{debug, trace, error, warn, info};
+
+use super::{
+    Tips, Storage, DifficultyProvider,
+    backend::{Backend, Tree},
+    migrations::{get_migrations, MigrationReport, DB_VERSION},
+    integrity::{get_integrity_checks, IntegrityReport}
+};
+
+// Constant keys used for extra Tree
+const TIPS: &[u8; 4] = b"TIPS";
+const TOP_TOPO_HEIGHT: &[u8; 4] = b"TOPO";
+const TOP_HEIGHT: &[u8; 4] = b"TOPH";
+const NETWORK: &[u8] = b"NET";
+const PRUNED_TOPOHEIGHT: &[u8; 4] = b"PRUN";
+const DB_VERSION_KEY: &[u8; 4] = b"VERS";
+const ACCOUNT_HISTORY_INDEXED: &[u8; 4] = b"AHIX";
+// Counters (prevent to perform a O(n))
+const ACCOUNTS_COUNT: &[u8; 4] = b"CACC";
+const TXS_COUNT: &[u8; 4] = b"CTXS";
+const ASSETS_COUNT: &[u8; 4] = b"CAST";
+const BLOCKS_COUNT: &[u8; 4] = b"CBLK";
+
+// Journal values are prefixed to know if the key was present before the batch
+const JOURNAL_ABSENT: u8 = 0;
+const JOURNAL_PRESENT: u8 = 1;
+
+// State tree node id is the depth (u16) followed by the path to the node
+const STATE_TREE_NODE_ID_SIZE: usize = 2 + HASH_SIZE;
+
+// Account history key is the public key, the inverted topoheight, the tx hash and the direction
+const ACCOUNT_HISTORY_KEY_SIZE: usize = 32 + 8 + HASH_SIZE + 1;
+
+// Node of the state tree, a subtree with only one leaf is stored as this leaf
+enum StateTreeNode {
+    // leaf key and its value
+    Leaf(Hash, u64),
+    // hash of both children
+    Node(Hash)
+}
+
+impl StateTreeNode {
+    fn hash(&self) -> Hash {
+        match self {
+            Self::Leaf(key, value) => hash_leaf(key, *value),
+            Self::Node(hash) => hash.clone()
+        }
+    }
+}
+
+impl Serializer for StateTreeNode {
+    fn write(&self, writer: &mut Writer) {
+        match self {
+            Self::Leaf(key, value) => {
+                writer.write_u8(0);
+                writer.write_hash(key);
+                writer.write_u64(value);
+            },
+            Self::Node(hash) => {
+                writer.write_u8(1);
+                writer.write_hash(hash);
+            }
+        }
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        Ok(match reader.read_u8()? {
+            0 => Self::Leaf(reader.read_hash()?, reader.read_u64()?),
+            1 => Self::Node(reader.read_hash()?),
+            _ => return Err(ReaderError::InvalidValue)
+        })
+    }
+}
+
+// Chain storage built on top of any key-value backend
+pub struct KeyValueStorage<B: Backend> {
+    transactions: B::Tree, // all txs stored on disk
+    txs_executed: B::Tree, // all txs executed in block
+    blocks: B::Tree, // all blocks on disk
+    blocks_at_height: B::Tree, // all blocks height at specific height
+    extra: B::Tree, // all extra data saved on disk
+    topo_by_hash: B::Tree, // topo at hash on disk
+    hash_at_topo: B::Tree, // hash at topo height on disk
+    cumulative_difficulty: B::Tree, // cumulative difficulty for each block hash on disk
+    assets: B::Tree, // keep tracks of all available assets on network
+    assets_supply: B::Tree, // supply of each custom asset at each topoheight it changed
+    assets_supply_changes: B::Tree, // assets supply changed at each topoheight
+    assets_authority: B::Tree, // mint authority of each custom asset at each topoheight it changed
+    assets_authority_changes: B::Tree, // assets authority changed at each topoheight
+    multisig: B::Tree, // multisig setup of each account at each topoheight it changed
+    multisig_changes: B::Tree, // multisig setups changed at each topoheight
+    locked_balances: B::Tree, // amounts received by each account and asset that are locked until their unlock condition
+    locked_balances_changes: B::Tree, // locked balances received at each topoheight
+    mempool: B::Tree, // pending txs saved periodically and on stop with the time they were first seen, sorted by owner and nonce
+    nonces: B::Tree, // account nonces to prevent TX replay attack
+    rewards: B::Tree, // block reward for each block topoheight
+    supply: B::Tree, // supply for each block topoheight
+    difficulty: B::Tree, // difficulty for each block hash
+    tx_blocks: B::Tree, // tree to store all blocks hashes where a tx was included in 
+    versioned_nonces: B::Tree, // Tree that store all versioned nonces using hashed keys
+    balances: B::Tree, // Tree that store all balances with prefixed keys
+    versioned_balances: B::Tree, // Tree that store all versioned balances using hashed keys
+    state_tree: B::Tree, // all versions of the state tree nodes, from the newest to the oldest
+    state_tree_changes: B::Tree, // state tree nodes written at each topoheight
+    account_history: B::Tree, // transactions of each account from the newest to the oldest, only if indexed
+    account_history_changes: B::Tree, // account history entries written at each topoheight
+    journal: B::Tree, // previous values of all keys modified by the current batch, used to rollback it
+    backend: B, // opened database on which all trees are stored
+    // cached in memory
+    transactions_cache: Option<Mutex<LruCache<Hash, Arc<Transaction>>>>,
+    blocks_cache: Option<Mutex<LruCache<Hash, Arc<BlockHeader>>>>,
+    past_blocks_cache: Option<Mutex<LruCache<Hash, Arc<Vec<Hash>>>>>, // previous blocks saved at each new block
+    topo_by_hash_cache: Option<Mutex<LruCache<Hash, u64>>>,
+    hash_at_topo_cache: Option<Mutex<LruCache<u64, Hash>>>,
+    cumulative_difficulty_cache: Option<Mutex<LruCache<Hash, Difficulty>>>,
+    assets_cache: Option<Mutex<LruCache<Hash, ()>>>,
+    tips_cache: Tips,
+    pruned_topoheight: Option<u64>,
+    // is a batch in progress, all writes are journaled
+    batch: bool
+}
+
+macro_rules! init_cache {
+    ($cache_size: expr) => {{
+        if let Some(size) = &$cache_size {
+            Some(Mutex::new(LruCache::new(*size)))
+        } else {
+            None
+        }
+    }};
+}
+
+impl<B: Backend> KeyValueStorage<B> {
+    pub fn with_backend(backend: B, cache_size: Option<usize>, network: Network) -> Result<Self, BlockchainError> {
+        let mut storage = Self {
+            transactions: backend.open_tree("transactions")?,
+            txs_executed: backend.open_tree("txs_executed")?,
+            blocks: backend.open_tree("blocks")?,
+            blocks_at_height: backend.open_tree("blocks_at_height")?,
+            extra: backend.open_tree("extra")?,
+            topo_by_hash: backend.open_tree("topo_at_hash")?,
+            hash_at_topo: backend.open_tree("hash_at_topo")?,
+            cumulative_difficulty: backend.open_tree("cumulative_difficulty")?,
+            assets: backend.open_tree("assets")?,
+            assets_supply: backend.open_tree("assets_supply")?,
+            assets_supply_changes: backend.open_tree("assets_supply_changes")?,
+            assets_authority: backend.open_tree("assets_authority")?,
+            assets_authority_changes: backend.open_tree("assets_authority_changes")?,
+            multisig: backend.open_tree("multisig")?,
+            multisig_changes: backend.open_tree("multisig_changes")?,
+            locked_balances: backend.open_tree("locked_balances")?,
+            locked_balances_changes: backend.open_tree("locked_balances_changes")?,
+            mempool: backend.open_tree("mempool")?,
+            nonces: backend.open_tree("nonces")?,
+            rewards: backend.open_tree("rewards")?,
+            supply: backend.open_tree("supply")?,
+            difficulty: backend.open_tree("difficulty")?,
+            tx_blocks: backend.open_tree("tx_blocks")?,
+            versioned_nonces: backend.open_tree("versioned_nonces")?,
+            balances: backend.open_tree("balances")?,
+            versioned_balances: backend.open_tree("versioned_balances")?,
+            state_tree: backend.open_tree("state_tree")?,
+            state_tree_changes: backend.open_tree("state_tree_changes")?,
+            account_history: backend.open_tree("account_history")?,
+            account_history_changes: backend.open_tree("account_history_changes")?,
+            journal: backend.open_tree("journal")?,
+            backend,
+            transactions_cache: init_cache!(cache_size),
+            blocks_cache: init_cache!(cache_size),
+            past_blocks_cache: init_cache!(cache_size),
+            topo_by_hash_cache: init_cache!(cache_size),
+            hash_at_topo_cache: init_cache!(cache_size),
+            cumulative_difficulty_cache: init_cache!(cache_size),
+            assets_cache: init_cache!(cache_size),
+            tips_cache: HashSet::new(),
+            pruned_topoheight: None,
+            batch: false
+        };
+
+        if storage.has_network()? {
+            let storage_network = storage.get_network()?;
+            if storage_network != network {
+                return Err(BlockchainError::InvalidNetwork);
+            }
+
+            let version = storage.get_db_version()?;
+            if version < DB_VERSION {
+                // changes from an incomplete batch must be reverted before migrating
+                if storage.has_pending_batch()? {
+                    warn!("Incomplete batch found in storage, rolling it back before migrating...");
+                    storage.restore_journal()?;
+                }
+
+                info!("Database schema version {} is outdated, migrating to version {}", version, DB_VERSION);
+                storage.apply_migrations(None, false)?;
+            } else if version > DB_VERSION {
+                return Err(BlockchainError::UnsupportedDatabaseVersion(version, DB_VERSION));
+            }
+        } else {
+            storage.set_network(&network)?;
+            storage.insert_into_tree(&storage.extra, DB_VERSION_KEY, DB_VERSION.to_be_bytes())?;
+        }
+
+        storage.load_cached_metadata();
+
+        Ok(storage)
+    }
+
+    // load tips and pruned topoheight from disk
+    fn load_cached_metadata(&mut self) {
+        self.tips_cache = match self.load_from_disk::<Tips>(&self.extra, TIPS) {
+            Ok(tips) => {
+                debug!("Found tips: {}", tips.len());
+                tips
+            },
+            Err(_) => HashSet::new()
+        };
+
+        self.pruned_topoheight = match self.load_from_disk::<u64>(&self.extra, PRUNED_TOPOHEIGHT) {
+            Ok(pruned_topoheight) => {
+                debug!("Found pruned topoheight: {}", pruned_topoheight);
+                Some(pruned_topoheight)
+            },
+            Err(_) => None
+        };
+    }
+
+    // Journal key is the tree name length, the tree name and then the key
+    fn get_journal_key(&self, tree: &B::Tree, key: &[u8]) -> Vec<u8> {
+        let name = tree.name();
+        let mut bytes = Vec::with_capacity(1 + name.len() + key.len());
+        bytes.push(name.len() as u8);
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.extend_from_slice(key);
+        bytes
+    }
+
+    // Save the current value of the key before its first modification in the batch
+    // Writes are applied in order by the backend, so the journal entry is always persisted before the change itself
+    fn journal_key(&self, tree: &B::Tree, key: &[u8]) -> Result<(), BlockchainError> {
+        let journal_key = self.get_journal_key(tree, key);
+        if self.journal.contains_key(&journal_key)? {
+            return Ok(())
+        }
+
+        let value = match tree.get(key)? {
+            Some(value) => {
+                let mut bytes = Vec::with_capacity(1 + value.len());
+                bytes.push(JOURNAL_PRESENT);
+                bytes.extend_from_slice(&value);
+                bytes
+            },
+            None => vec![JOURNAL_ABSENT]
+        };
+        self.journal.insert(&journal_key, &value)?;
+        Ok(())
+    }
+
+    fn insert_into_tree<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, tree: &B::Tree, key: K, value: V) -> Result<(), BlockchainError> {
+        if self.batch {
+            self.journal_key(tree, key.as_ref())?;
+        }
+        tree.insert(key.as_ref(), value.as_ref())
+    }
+
+    fn remove_from_tree<K: AsRef<[u8]>>(&self, tree: &B::Tree, key: K) -> Result<(), BlockchainError> {
+        if self.batch {
+            self.journal_key(tree, key.as_ref())?;
+        }
+        tree.remove(key.as_ref())
+    }
+
+    // find the tree using its name
+    fn get_tree_by_name(&self, name: &str) -> Option<&B::Tree> {
+        [
+            &self.transactions,
+            &self.txs_executed,
+            &self.blocks,
+            &self.blocks_at_height,
+            &self.extra,
+            &self.topo_by_hash,
+            &self.hash_at_topo,
+            &self.cumulative_difficulty,
+            &self.assets,
+            &self.assets_supply,
+            &self.assets_supply_changes,
+            &self.assets_authority,
+            &self.assets_authority_changes,
+            &self.multisig,
+            &self.multisig_changes,
+            &self.locked_balances,
+            &self.locked_balances_changes,
+            &self.nonces,
+            &self.rewards,
+            &self.supply,
+            &self.difficulty,
+            &self.tx_blocks,
+            &self.versioned_nonces,
+            &self.balances,
+            &self.versioned_balances,
+            &self.state_tree,
+            &self.state_tree_changes,
+            &self.account_history,
+            &self.account_history_changes,
+            &self.mempool
+        ].into_iter().find(|tree| tree.name() == name)
+    }
+
+    async fn clear_caches(&self) {
+        if let Some(cache) = self.transactions_cache.as_ref() {
+            let mut cache = cache.lock().await;
+            cache.clear();
+        }
+
+        if let Some(cache) = self.blocks_cache.as_ref() {
+            let mut cache = cache.lock().await;
+            cache.clear();
+        }
+
+        if let Some(cache) = self.past_blocks_cache.as_ref() {
+            let mut cache = cache.lock().await;
+            cache.clear();
+        }
+
+        if let Some(cache) = self.topo_by_hash_cache.as_ref() {
+            let mut cache = cache.lock().await;
+            cache.clear();
+        }
+
+        if let Some(cache) = self.hash_at_topo_cache.as_ref() {
+            let mut cache = cache.lock().await;
+            cache.clear();
+        }
+
+        if let Some(cache) = self.cumulative_difficulty_cache.as_ref() {
+            let mut cache = cache.lock().await;
+            cache.clear();
+        }
+
+        if let Some(cache) = self.assets_cache.as_ref() {
+            let mut cache = cache.lock().await;
+            cache.clear();
+        }
+    }
+
+    // Revert all changes saved in the journal, returns the number of keys restored
+    fn restore_journal(&mut self) -> Result<usize, BlockchainError> {
+        // don't journal the restored values
+        self.batch = false;
+
+        let mut count = 0;
+        for el in self.journal.iter() {
+            let (journal_key, value) = el?;
+            let name_len = journal_key[0] as usize;
+            let name = String::from_utf8_lossy(&journal_key[1..1 + name_len]);
+            let tree = match self.get_tree_by_name(&name) {
+                Some(tree) => tree,
+                None => {
+                    error!("Unknown tree {} found in journal", name);
+                    return Err(BlockchainError::Unknown)
+                }
+            };
+
+            let key = &journal_key[1 + name_len..];
+            match value.split_first() {
+                Some((&JOURNAL_PRESENT, previous)) => {
+                    tree.insert(key, previous)?;
+                },
+                Some((&JOURNAL_ABSENT, _)) => {
+                    tree.remove(key)?;
+                },
+                _ => {
+                    error!("Invalid journal entry found for tree {}", name);
+                    return Err(BlockchainError::Unknown)
+                }
+            };
+            count += 1;
+        }
+        debug!("{} keys restored from journal", count);
+
+        if count > 0 {
+            self.journal.clear()?;
+        }
+
+        Ok(count)
+    }
+
+    // Apply all migrations above from_version (current schema version by default)
+    // Each migration is applied in its own batch
+    fn apply_migrations(&mut self, from_version: Option<u64>, dry_run: bool) -> Result<Vec<MigrationReport>, BlockchainError> {
+        let version = self.get_db_version()?;
+        if version > DB_VERSION {
+            return Err(BlockchainError::UnsupportedDatabaseVersion(version, DB_VERSION))
+        }
+
+        // a migration can't be applied if the previous ones were not
+        let from_version = from_version.unwrap_or(version);
+        if from_version > version {
+            return Err(BlockchainError::InvalidMigrationVersion(from_version, version))
+        }
+
+        let migrations: Vec<_> = get_migrations::<B>().into_iter().filter(|migration| migration.version > from_version).collect();
+        let total = migrations.len();
+        let mut reports = Vec::with_capacity(total);
+        for (i, migration) in migrations.into_iter().enumerate() {
+            info!("{} migration {}/{} to version {}: {}", if dry_run { "Checking" } else { "Applying" }, i + 1, total, migration.version, migration.description);
+            let changes = if dry_run {
+                (migration.apply)(self, true)?
+            } else {
+                self.start_batch()?;
+                let res = (migration.apply)(self, false).and_then(|changes| {
+                    // don't downgrade the version when a migration is applied again
+                    if migration.version > version {
+                        self.insert_into_tree(&self.extra, DB_VERSION_KEY, migration.version.to_be_bytes())?;
+                    }
+                    Ok(changes)
+                });
+
+                match res {
+                    Ok(changes) => {
+                        self.commit_batch()?;
+                        changes
+                    },
+                    Err(e) => {
+                        error!("Error while applying migration to version {}: {}", migration.version, e);
+                        self.restore_journal()?;
+                        return Err(e)
+                    }
+                }
+            };
+
+            info!("Migration to version {} done with {} changes", migration.version, changes);
+            reports.push(MigrationReport {
+                version: migration.version,
+                description: migration.description,
+                changes
+            });
+        }
+
+        Ok(reports)
+    }
+
+    // Decrease a counter when the entries it tracks are deleted
+    fn decrease_counter(&self, key: &[u8; 4], deleted: u64) -> Result<(), BlockchainError> {
+        if deleted > 0 {
+            let count = self.load_from_disk::<u64>(&self.extra, key).unwrap_or(0).saturating_sub(deleted);
+            self.insert_into_tree(&self.extra, key, count.to_be_bytes())?;
+        }
+        Ok(())
+    }
+
+    // Count again all entries tracked by a counter
+    // Returns the tree, the counter key, the stored and the real count of each counter
+    fn count_entries(&self) -> Result<Vec<(&B::Tree, &'static [u8; 4], u64, u64)>, BlockchainError> {
+        let mut counters = Vec::new();
+        for (tree, key) in [(&self.nonces, ACCOUNTS_COUNT), (&self.transactions, TXS_COUNT), (&self.assets, ASSETS_COUNT), (&self.blocks, BLOCKS_COUNT)] {
+            let mut count: u64 = 0;
+            for el in tree.iter() {
+                el?;
+                count += 1;
+                if count % 100_000 == 0 {
+                    info!("{} entries counted in {}", count, tree.name());
+                }
+            }
+
+            let current: u64 = self.load_from_disk(&self.extra, key).unwrap_or(0);
+            counters.push((tree, key, current, count));
+        }
+
+        Ok(counters)
+    }
+
+    // Count again all entries tracked by a counter and fix the wrong ones
+    // Returns the number of counters that were incorrect
+    pub(super) fn rebuild_counters(&self, dry_run: bool) -> Result<u64, BlockchainError> {
+        let mut changes = 0;
+        for (tree, key, current, count) in self.count_entries()? {
+            if current != count {
+                info!("Counter for {} is {} but {} entries were found", tree.name(), current, count);
+                if !dry_run {
+                    self.insert_into_tree(&self.extra, key, count.to_be_bytes())?;
+                }
+                changes += 1;
+            }
+        }
+
+        Ok(changes)
+    }
+
+    // Replay all versions of balances and nonces in topoheight order to build the state tree again
+    // Returns the number of leaves versions applied
+    pub(super) fn rebuild_state_tree(&self, dry_run: bool) -> Result<u64, BlockchainError> {
+        let mut updates: BTreeMap<u64, Vec<(Hash, u64)>> = BTreeMap::new();
+        for el in self.balances.iter() {
+            let (key_bytes, value) = el?;
+            let key = PublicKey::from_bytes(&key_bytes[0..32])?;
+            let asset = Hash::from_bytes(&key_bytes[32..64])?;
+            let leaf_key = balance_leaf_key(&key, &asset);
+            let mut topoheight = Some(u64::from_bytes(&value)?);
+            while let Some(topo) = topoheight {
+                // versions may still use the legacy key format if the keys migration was not applied yet
+                let version: VersionedBalance = match self.versioned_balances.get(&self.get_versioned_balance_key(&key, &asset, topo))? {
+                    Some(bytes) => VersionedBalance::from_bytes(&bytes)?,
+                    None => self.load_from_disk(&self.versioned_balances, &self.get_versioned_key(&key, topo))?
+                };
+                updates.entry(topo).or_default().push((leaf_key.clone(), version.get_balance()));
+                topoheight = version.get_previous_topoheight();
+            }
+        }
+
+        for el in self.nonces.iter() {
+            let (key_bytes, value) = el?;
+            let key = PublicKey::from_bytes(&key_bytes)?;
+            let leaf_key = nonce_leaf_key(&key);
+            let mut topoheight = Some(u64::from_bytes(&value)?);
+            while let Some(topo) = topoheight {
+                let version: VersionedNonce = self.load_from_disk(&self.versioned_nonces, &self.get_versioned_key(&key, topo))?;
+                updates.entry(topo).or_default().push((leaf_key.clone(), version.get_nonce()));
+                topoheight = version.get_previous_topoheight();
+            }
+        }
+
+        let changes = updates.values().map(|leaves| leaves.len() as u64).sum();
+        if dry_run {
+            return Ok(changes)
+        }
+
+        // start from an empty tree so it can be applied again
+        for tree in [&self.state_tree, &self.state_tree_changes] {
+            for el in tree.iter() {
+                let (key, _) = el?;
+                self.remove_from_tree(tree, &key)?;
+            }
+        }
+
+        for (topoheight, leaves) in updates {
+            for (leaf_key, value) in leaves {
+                self.update_state_tree(&leaf_key, value, topoheight)?;
+            }
+
+            if topoheight % 1000 == 0 {
+                info!("State tree built until topoheight {}", topoheight);
+            }
+        }
+
+        Ok(changes)
+    }
+
+    // Versioned balances were stored without their asset in the key, which was only valid for the native asset
+    // Rewrite all legacy 40 bytes keys to the 72 bytes format using XELIS asset
+    // Returns the number of keys rewritten
+    pub(super) fn migrate_versioned_balance_keys(&self, dry_run: bool) -> Result<u64, BlockchainError> {
+        let mut legacy = Vec::new();
+        for el in self.versioned_balances.iter() {
+            let (key, value) = el?;
+            if key.len() == 40 {
+                legacy.push((key, value));
+            }
+        }
+
+        let changes = legacy.len() as u64;
+        if dry_run {
+            return Ok(changes)
+        }
+
+        for (key, value) in legacy {
+            let mut new_key = Vec::with_capacity(72);
+            new_key.extend_from_slice(&key);
+            new_key.extend_from_slice(XELIS_ASSET.as_bytes());
+            self.insert_into_tree(&self.versioned_balances, new_key, value)?;
+            self.remove_from_tree(&self.versioned_balances, &key)?;
+        }
+
+        Ok(changes)
+    }
+
+    // Assets data were stored without the metadata flag added with the CreateAsset transaction
+    // Returns the number of assets rewritten
+    pub(super) fn migrate_assets_metadata(&self, dry_run: bool) -> Result<u64, BlockchainError> {
+        let mut legacy = Vec::new();
+        for el in self.assets.iter() {
+            let (key, value) = el?;
+            // topoheight and decimals only
+            if value.len() == 9 {
+                legacy.push((key, value));
+            }
+        }
+
+        let changes = legacy.len() as u64;
+        if dry_run {
+            return Ok(changes)
+        }
+
+        for (key, value) in legacy {
+            let mut new_value = Vec::with_capacity(10);
+            new_value.extend_from_slice(&value);
+            // no metadata
+            new_value.push(0);
+            self.insert_into_tree(&self.assets, key, new_value)?;
+        }
+
+        Ok(changes)
+    }
+
+    // hash_at_topo is the reference, topo_by_hash is rebuilt from it in repair mode
+    pub(super) fn check_topoheights(&self, repair: bool, report: &mut IntegrityReport) -> Result<(), BlockchainError> {
+        let mut ordered = HashSet::new();
+        let mut topoheights = HashSet::new();
+        for el in self.hash_at_topo.iter() {
+            let (key, value) = el?;
+            let topoheight = u64::from_bytes(&key)?;
+            let hash = Hash::from_bytes(&value)?;
+            report.checked += 1;
+
+            if !self.blocks.contains_key(hash.as_bytes())? {
+                report.errors.push(format!("Block {} at topoheight {} is not stored", hash, topoheight));
+            }
+
+            if !ordered.insert(hash.clone()) {
+                report.errors.push(format!("Block {} is at several topoheights", hash));
+                continue;
+            }
+            topoheights.insert(topoheight);
+
+            let error = match self.topo_by_hash.get(hash.as_bytes())? {
+                Some(bytes) if u64::from_bytes(&bytes)? == topoheight => continue,
+                Some(bytes) => format!("Block {} at topoheight {} is ordered at topoheight {}", hash, topoheight, u64::from_bytes(&bytes)?),
+                None => format!("Block {} at topoheight {} is not ordered", hash, topoheight)
+            };
+            report.errors.push(error);
+            if repair {
+                self.insert_into_tree(&self.topo_by_hash, hash.as_bytes(), topoheight.to_be_bytes())?;
+                report.repaired += 1;
+            }
+        }
+
+        if self.has_blocks() {
+            // genesis block is never pruned
+            let pruned_topoheight = self.pruned_topoheight.unwrap_or(0).max(1);
+            let top_topoheight = self.get_top_topoheight()?;
+            for topoheight in std::iter::once(0).chain(pruned_topoheight..=top_topoheight) {
+                if !topoheights.remove(&topoheight) {
+                    report.errors.push(format!("No block at topoheight {}", topoheight));
+                }
+            }
+
+            for topoheight in topoheights {
+                report.errors.push(format!("Block at topoheight {} is outside of the chain ({}-{})", topoheight, pruned_topoheight, top_topoheight));
+            }
+        }
+
+        for el in self.topo_by_hash.iter() {
+            let (key, value) = el?;
+            let hash = Hash::from_bytes(&key)?;
+            report.checked += 1;
+            // inconsistencies of ordered blocks are already reported
+            if ordered.contains(&hash) {
+                continue;
+            }
+
+            report.errors.push(format!("Block {} is ordered at topoheight {} but another block is there", hash, u64::from_bytes(&value)?));
+            if repair {
+                self.remove_from_tree(&self.topo_by_hash, &key)?;
+                report.repaired += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn check_block_transactions(&self, report: &mut IntegrityReport) -> Result<(), BlockchainError> {
+        for el in self.blocks.iter() {
+            let (key, value) = el?;
+            let hash = Hash::from_bytes(&key)?;
+            let block = BlockHeader::from_bytes(&value)?;
+            report.checked += 1;
+            for tx in block.get_txs_hashes() {
+                if !self.transactions.contains_key(tx.as_bytes())? {
+                    report.errors.push(format!("Transaction {} of block {} is not stored", tx, hash));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // tx_blocks must contain, for each transaction, exactly all the stored blocks including it
+    pub(super) fn check_tx_blocks(&self, repair: bool, report: &mut IntegrityReport) -> Result<(), BlockchainError> {
+        let mut expected: BTreeMap<Hash, Tips> = BTreeMap::new();
+        for el in self.blocks.iter() {
+            let (key, value) = el?;
+            let hash = Hash::from_bytes(&key)?;
+            let block = BlockHeader::from_bytes(&value)?;
+            for tx in block.get_txs_hashes() {
+                expected.entry(tx.clone()).or_default().insert(hash.clone());
+            }
+        }
+
+        for el in self.tx_blocks.iter() {
+            let (key, value) = el?;
+            let tx = Hash::from_bytes(&key)?;
+            let blocks = Tips::from_bytes(&value)?;
+            report.checked += 1;
+            match expected.remove(&tx) {
+                Some(expected_blocks) if expected_blocks == blocks => {},
+                Some(expected_blocks) => {
+                    report.errors.push(format!("Transaction {} is linked to {} blocks but is included in {} blocks", tx, blocks.len(), expected_blocks.len()));
+                    if repair {
+                        self.insert_into_tree(&self.tx_blocks, &key, expected_blocks.to_bytes())?;
+                        report.repaired += 1;
+                    }
+                },
+                None => {
+                    report.errors.push(format!("Transaction {} is linked to {} blocks but is not included in any block", tx, blocks.len()));
+                    if repair {
+                        self.remove_from_tree(&self.tx_blocks, &key)?;
+                        report.repaired += 1;
+                    }
+                }
+            };
+        }
+
+        // transactions without any linked block
+        for (tx, blocks) in expected {
+            report.errors.push(format!("Transaction {} is included in {} blocks but is not linked to them", tx, blocks.len()));
+            if repair {
+                self.insert_into_tree(&self.tx_blocks, tx.as_bytes(), blocks.to_bytes())?;
+                report.repaired += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Each version must exist and point to an older version, until the first one
+    // Asset is only set for balances, as nonces are not versioned per asset
+    fn check_versions_chain<F: Fn(&[u8]) -> Result<Option<u64>, BlockchainError>>(&self, tree: &B::Tree, key: &PublicKey, asset: Option<&Hash>, name: &str, topoheight: u64, top_topoheight: u64, read_previous: F, report: &mut IntegrityReport) -> Result<(), BlockchainError> {
+        if topoheight > top_topoheight {
+            report.errors.push(format!("Last {} of {} is at topoheight {} above the top topoheight {}", name, key, topoheight, top_topoheight));
+        }
+
+        let mut current = Some(topoheight);
+        while let Some(topo) = current {
+            report.checked += 1;
+            let disk_key = match asset {
+                Some(asset) => self.get_versioned_balance_key(key, asset, topo).to_vec(),
+                None => self.get_versioned_key(key, topo).to_vec()
+            };
+
+            let bytes = match tree.get(&disk_key)? {
+                Some(bytes) => bytes,
+                None => {
+                    report.errors.push(format!("Version of {} for {} at topoheight {} is not stored", name, key, topo));
+                    break;
+                }
+            };
+
+            current = read_previous(&bytes)?;
+            if let Some(previous) = current {
+                if previous >= topo {
+                    report.errors.push(format!("Version of {} for {} at topoheight {} has a previous topoheight {} not below it", name, key, topo, previous));
+                    break;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn check_versioned_balances(&self, report: &mut IntegrityReport) -> Result<(), BlockchainError> {
+        let top_topoheight = if self.has_blocks() { self.get_top_topoheight()? } else { 0 };
+        for el in self.balances.iter() {
+            let (key_bytes, value) = el?;
+            let key = PublicKey::from_bytes(&key_bytes[0..32])?;
+            let asset = Hash::from_bytes(&key_bytes[32..64])?;
+            if !self.assets.contains_key(asset.as_bytes())? {
+                report.errors.push(format!("Balance of {} is for unknown asset {}", key, asset));
+            }
+
+            let topoheight = u64::from_bytes(&value)?;
+            let read_previous = |bytes: &[u8]| Ok(VersionedBalance::from_bytes(bytes)?.get_previous_topoheight());
+            self.check_versions_chain(&self.versioned_balances, &key, Some(&asset), "balance", topoheight, top_topoheight, read_previous, report)?;
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn check_versioned_nonces(&self, report: &mut IntegrityReport) -> Result<(), BlockchainError> {
+        let top_topoheight = if self.has_blocks() { self.get_top_topoheight()? } else { 0 };
+        for el in self.nonces.iter() {
+            let (key_bytes, value) = el?;
+            let key = PublicKey::from_bytes(&key_bytes)?;
+            let topoheight = u64::from_bytes(&value)?;
+            let read_previous = |bytes: &[u8]| Ok(VersionedNonce::from_bytes(bytes)?.get_previous_topoheight());
+            self.check_versions_chain(&self.versioned_nonces, &key, None, "nonce", topoheight, top_topoheight, read_previous, report)?;
+        }
+
+        Ok(())
+    }
+
+    // Each entry of a tree versioned by topoheight must be indexed at its topoheight in the changes tree
+    // and each change must point to a stored entry
+    // get_change_key returns the topoheight and the change key of an entry, get_key returns the entry key of a change
+    // Changes are derived from the entries, so they are rewritten in repair mode
+    fn check_changes_index<F, G>(&self, tree: &B::Tree, changes: &B::Tree, get_change_key: F, get_key: G, repair: bool, report: &mut IntegrityReport) -> Result<(), BlockchainError>
+    where
+        F: Fn(&[u8]) -> Result<(u64, Vec<u8>), BlockchainError>,
+        G: Fn(&[u8]) -> Result<Vec<u8>, BlockchainError>
+    {
+        let top_topoheight = self.get_top_topoheight_or_genesis()?;
+        for el in tree.iter() {
+            let (key, _) = el?;
+            let (topoheight, change_key) = get_change_key(&key)?;
+            report.checked += 1;
+            if topoheight > top_topoheight {
+                report.errors.push(format!("Entry {} of {} is at topoheight {} above the top topoheight {}", hex::encode(&key), tree.name(), topoheight, top_topoheight));
+            }
+
+            if !changes.contains_key(&change_key)? {
+                report.errors.push(format!("Entry {} of {} is not indexed at topoheight {}", hex::encode(&key), tree.name(), topoheight));
+                if repair {
+                    self.insert_into_tree(changes, change_key, [])?;
+                    report.repaired += 1;
+                }
+            }
+        }
+
+        for el in changes.iter() {
+            let (change_key, _) = el?;
+            report.checked += 1;
+            if !tree.contains_key(&get_key(&change_key)?)? {
+                report.errors.push(format!("Change {} of {} points to an entry not stored", hex::encode(&change_key), changes.name()));
+                if repair {
+                    self.remove_from_tree(changes, &change_key)?;
+                    report.repaired += 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // The root at the top topoheight must commit the latest version of all balances and nonces
+    // The state tree is derived from them, so it is rebuilt in repair mode
+    pub(super) fn check_state_tree(&self, repair: bool, report: &mut IntegrityReport) -> Result<(), BlockchainError> {
+        let errors = report.errors.len();
+        let get_change_key = |key: &[u8]| {
+            let topoheight = u64::MAX - u64::from_bytes(&key[STATE_TREE_NODE_ID_SIZE..])?;
+            Ok((topoheight, self.get_state_tree_change_key(&key[..STATE_TREE_NODE_ID_SIZE], topoheight)))
+        };
+        let get_key = |change_key: &[u8]| Ok(self.get_state_tree_versioned_key(&change_key[8..], u64::from_bytes(&change_key[0..8])?));
+        self.check_changes_index(&self.state_tree, &self.state_tree_changes, get_change_key, get_key, false, report)?;
+
+        let mut leaves = Vec::new();
+        for el in self.balances.iter() {
+            let (key_bytes, value) = el?;
+            let key = PublicKey::from_bytes(&key_bytes[0..32])?;
+            let asset = Hash::from_bytes(&key_bytes[32..64])?;
+            let topoheight = u64::from_bytes(&value)?;
+            report.checked += 1;
+            match self.versioned_balances.get(&self.get_versioned_balance_key(&key, &asset, topoheight))? {
+                Some(bytes) => leaves.push((balance_leaf_key(&key, &asset), VersionedBalance::from_bytes(&bytes)?.get_balance())),
+                None => {
+                    report.errors.push(format!("State root can't be verified without the balance of {} for {} at topoheight {}", key, asset, topoheight));
+                    return Ok(())
+                }
+            };
+        }
+
+        for el in self.nonces.iter() {
+            let (key_bytes, value) = el?;
+            let key = PublicKey::from_bytes(&key_bytes)?;
+            let topoheight = u64::from_bytes(&value)?;
+            report.checked += 1;
+            match self.versioned_nonces.get(&self.get_versioned_key(&key, topoheight))? {
+                Some(bytes) => leaves.push((nonce_leaf_key(&key), VersionedNonce::from_bytes(&bytes)?.get_nonce())),
+                None => {
+                    report.errors.push(format!("State root can't be verified without the nonce of {} at topoheight {}", key, topoheight));
+                    return Ok(())
+                }
+            };
+        }
+
+        leaves.sort_by(|(a, _), (b, _)| a.as_bytes().cmp(b.as_bytes()));
+        let expected = compute_state_root(&leaves);
+        let top_topoheight = self.get_top_topoheight_or_genesis()?;
+        let root = self.get_state_tree_node_hash(&self.get_state_tree_node_id(0, &EMPTY_STATE_ROOT, false), top_topoheight)?;
+        if root != expected {
+            report.errors.push(format!("State root at topoheight {} is {} but balances and nonces commit to {}", top_topoheight, root, expected));
+        }
+
+        if repair && report.errors.len() > errors {
+            report.repaired += self.rebuild_state_tree(false)?;
+        }
+
+        Ok(())
+    }
+
+    // Entries must be indexed by topoheight and only exist while the index is enabled
+    pub(super) fn check_account_history(&self, repair: bool, report: &mut IntegrityReport) -> Result<(), BlockchainError> {
+        if !self.is_account_history_indexed()? {
+            for tree in [&self.account_history, &self.account_history_changes] {
+                if !tree.is_empty()? {
+                    report.errors.push(format!("Account history is not indexed but {} has entries", tree.name()));
+                }
+            }
+        }
+
+        let get_change_key = |key: &[u8]| {
+            let topoheight = u64::MAX - u64::from_bytes(&key[32..40])?;
+            Ok((topoheight, self.get_account_history_change_key(topoheight, key)))
+        };
+        let get_key = |change_key: &[u8]| Ok(change_key[8..].to_vec());
+        self.check_changes_index(&self.account_history, &self.account_history_changes, get_change_key, get_key, repair, report)?;
+
+        for el in self.account_history.iter() {
+            let (key, _) = el?;
+            let hash = Hash::from_bytes(&key[40..40 + HASH_SIZE])?;
+            if !self.transactions.contains_key(hash.as_bytes())? {
+                report.errors.push(format!("Transaction {} of the history of {} is not stored", hash, PublicKey::from_bytes(&key[0..32])?));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Versions of a tree using a prefixed versioned key, indexed by topoheight
+    fn check_prefixed_versions_index(&self, tree: &B::Tree, changes: &B::Tree, repair: bool, report: &mut IntegrityReport) -> Result<(), BlockchainError> {
+        let get_change_key = |key: &[u8]| {
+            let topoheight = u64::from_bytes(&key[32..40])?;
+            Ok((topoheight, self.get_prefixed_version_change_key(&key[0..32], topoheight).to_vec()))
+        };
+        let get_key = |change_key: &[u8]| {
+            let prefix: [u8; 32] = change_key[8..40].try_into().map_err(|_| ReaderError::InvalidSize)?;
+            Ok(self.get_prefixed_versioned_key(&prefix, u64::from_bytes(&change_key[0..8])?).to_vec())
+        };
+        self.check_changes_index(tree, changes, get_change_key, get_key, repair, report)
+    }
+
+    // Supply and authority can only change for a registered asset, after its registration
+    pub(super) fn check_assets_versions(&self, repair: bool, report: &mut IntegrityReport) -> Result<(), BlockchainError> {
+        self.check_prefixed_versions_index(&self.assets_supply, &self.assets_supply_changes, repair, report)?;
+        self.check_prefixed_versions_index(&self.assets_authority, &self.assets_authority_changes, repair, report)?;
+
+        for tree in [&self.assets_supply, &self.assets_authority] {
+            for el in tree.iter() {
+                let (key, value) = el?;
+                let asset = Hash::from_bytes(&key[0..32])?;
+                let topoheight = u64::from_bytes(&key[32..40])?;
+                match self.assets.get(asset.as_bytes())? {
+                    Some(bytes) => {
+                        let registration_topoheight = AssetData::from_bytes(&bytes)?.get_topoheight();
+                        if topoheight < registration_topoheight {
+                            report.errors.push(format!("Version of {} for asset {} at topoheight {} is below its registration at topoheight {}", tree.name(), asset, topoheight, registration_topoheight));
+                        }
+                    },
+                    None => report.errors.push(format!("Version of {} at topoheight {} is for unknown asset {}", tree.name(), topoheight, asset))
+                };
+
+                // an empty authority means it was renounced
+                let valid = if tree.name() == self.assets_supply.name() {
+                    u64::from_bytes(&value).is_ok()
+                } else {
+                    value.is_empty() || PublicKey::from_bytes(&value).is_ok()
+                };
+                if !valid {
+                    report.errors.push(format!("Version of {} for asset {} at topoheight {} can't be decoded", tree.name(), asset, topoheight));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn check_multisig(&self, repair: bool, report: &mut IntegrityReport) -> Result<(), BlockchainError> {
+        self.check_prefixed_versions_index(&self.multisig, &self.multisig_changes, repair, report)?;
+
+        for el in self.multisig.iter() {
+            let (key, value) = el?;
+            let topoheight = u64::from_bytes(&key[32..40])?;
+            // an empty setup means the multisig was removed
+            if PublicKey::from_bytes(&key[0..32]).is_err() || (!value.is_empty() && MultiSigSetup::from_bytes(&value).is_err()) {
+                report.errors.push(format!("Multisig setup {} at topoheight {} can't be decoded", hex::encode(&key[0..32]), topoheight));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn check_locked_balances(&self, repair: bool, report: &mut IntegrityReport) -> Result<(), BlockchainError> {
+        let get_change_key = |key: &[u8]| {
+            let topoheight = u64::from_bytes(&key[64..72])?;
+            Ok((topoheight, self.get_locked_balance_change_key(topoheight, key)))
+        };
+        let get_key = |change_key: &[u8]| Ok(change_key[8..].to_vec());
+        self.check_changes_index(&self.locked_balances, &self.locked_balances_changes, get_change_key, get_key, repair, report)?;
+
+        for el in self.locked_balances.iter() {
+            let (key, value) = el?;
+            let asset = Hash::from_bytes(&key[32..64])?;
+            let topoheight = u64::from_bytes(&key[64..72])?;
+            if !self.assets.contains_key(asset.as_bytes())? {
+                report.errors.push(format!("Amount locked at topoheight {} is for unknown asset {}", topoheight, asset));
+            }
+
+            if UnlockCondition::from_bytes(&key[72..]).is_err() {
+                report.errors.push(format!("Unlock condition of the amount locked at topoheight {} for asset {} can't be decoded", topoheight, asset));
+            }
+
+            if u64::from_bytes(&value)? == 0 {
+                report.errors.push(format!("No amount locked at topoheight {} for asset {}", topoheight, asset));
+            }
+        }
+
+        Ok(())
+    }
+
+    // blocks_at_height must contain, for each height, exactly all the stored blocks at this height
+    pub(super) fn check_blocks_at_height(&self, repair: bool, report: &mut IntegrityReport) -> Result<(), BlockchainError> {
+        let mut expected: BTreeMap<u64, Tips> = BTreeMap::new();
+        for el in self.blocks.iter() {
+            let (key, value) = el?;
+            let block = BlockHeader::from_bytes(&value)?;
+            expected.entry(block.get_height()).or_default().insert(Hash::from_bytes(&key)?);
+        }
+
+        for el in self.blocks_at_height.iter() {
+            let (key, value) = el?;
+            let height = u64::from_bytes(&key)?;
+            let blocks = Tips::from_bytes(&value)?;
+            report.checked += 1;
+            match expected.remove(&height) {
+                Some(expected_blocks) if expected_blocks == blocks => {},
+                Some(expected_blocks) => {
+                    report.errors.push(format!("{} blocks are linked to height {} but {} blocks are stored at this height", blocks.len(), height, expected_blocks.len()));
+                    if repair {
+                        self.insert_into_tree(&self.blocks_at_height, &key, expected_blocks.to_bytes())?;
+                        report.repaired += 1;
+                    }
+                },
+                None => {
+                    report.errors.push(format!("{} blocks are linked to height {} but no block is stored at this height", blocks.len(), height));
+                    if repair {
+                        self.remove_from_tree(&self.blocks_at_height, &key)?;
+                        report.repaired += 1;
+                    }
+                }
+            };
+        }
+
+        // heights without any linked block
+        for (height, blocks) in expected {
+            report.errors.push(format!("{} blocks are stored at height {} but are not linked to it", blocks.len(), height));
+            if repair {
+                self.insert_into_tree(&self.blocks_at_height, height.to_be_bytes(), blocks.to_bytes())?;
+                report.repaired += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    // An executed transaction must be stored and included in the ordered block executing it
+    pub(super) fn check_txs_executed(&self, report: &mut IntegrityReport) -> Result<(), BlockchainError> {
+        for el in self.txs_executed.iter() {
+            let (key, value) = el?;
+            let tx = Hash::from_bytes(&key)?;
+            let hash = Hash::from_bytes(&value)?;
+            report.checked += 1;
+            if !self.transactions.contains_key(&key)? {
+                report.errors.push(format!("Transaction {} executed in block {} is not stored", tx, hash));
+            }
+
+            match self.blocks.get(hash.as_bytes())? {
+                Some(bytes) if !BlockHeader::from_bytes(&bytes)?.get_txs_hashes().contains(&tx) => {
+                    report.errors.push(format!("Transaction {} is executed in block {} which doesn't include it", tx, hash));
+                },
+                Some(_) => {},
+                None => report.errors.push(format!("Transaction {} is executed in block {} which is not stored", tx, hash))
+            };
+
+            if !self.topo_by_hash.contains_key(hash.as_bytes())? {
+                report.errors.push(format!("Transaction {} is executed in block {} which is not ordered", tx, hash));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Counters are derived from the entries of their tree, so they are rebuilt in repair mode
+    pub(super) fn check_counters(&self, repair: bool, report: &mut IntegrityReport) -> Result<(), BlockchainError> {
+        for (tree, key, current, count) in self.count_entries()? {
+            report.checked += 1;
+            if current == count {
+                continue;
+            }
+
+            report.errors.push(format!("Counter of {} is {} but {} entries are stored", tree.name(), current, count));
+            if repair {
+                self.insert_into_tree(&self.extra, key, count.to_be_bytes())?;
+                report.repaired += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn load_from_disk<T: Serializer>(&self, tree: &B::Tree, key: &[u8]) -> Result<T, BlockchainError> {
+        match tree.get(key)? {
+            Some(bytes) => {
+                let mut reader = Reader::new(&bytes);
+                let value = T::read(&mut reader)?;
+                Ok(value)
+            },
+            None => Err(BlockchainError::NotFoundOnDisk(DiskContext::LoadData))
+        }
+    }
+
+    async fn get_cacheable_arc_data<K: Eq + StdHash + Serializer + Clone, V: Serializer>(&self, tree: &B::Tree, cache: &Option<Mutex<LruCache<K, Arc<V>>>>, key: &K) -> Result<Arc<V>, BlockchainError> {
+        let value = if let Some(cache) = cache {
+            let mut cache = cache.lock().await;
+            if let Some(value) = cache.get(key) {
+                return Ok(Arc::clone(&value));
+            }
+
+            let value = Arc::new(self.load_from_disk(tree, &key.to_bytes())?);
+            cache.put(key.clone(), Arc::clone(&value));
+            value
+        } else {
+            Arc::new(self.load_from_disk(tree, &key.to_bytes())?)
+        };
+
+        Ok(value)
+    }
+
+    async fn get_cacheable_data_copiable<K: Eq + StdHash + Serializer + Clone, V: Serializer + Copy>(&self, tree: &B::Tree, cache: &Option<Mutex<LruCache<K, V>>>, key: &K) -> Result<V, BlockchainError> {
+        let value = if let Some(cache) = cache {
+            let mut cache = cache.lock().await;
+            if let Some(value) = cache.get(key) {
+                return Ok(*value);
+            }
+
+            let value = self.load_from_disk(tree, &key.to_bytes())?;
+            cache.put(key.clone(), value);
+            value
+        } else {
+            self.load_from_disk(tree, &key.to_bytes())?
+        };
+
+        Ok(value)
+    }
+
+    async fn delete_cacheable_data<K: Eq + StdHash + Serializer + Clone, V: Serializer>(&self, tree: &B::Tree, cache: &Option<Mutex<LruCache<K, V>>>, key: &K) -> Result<V, BlockchainError> {
+        let key_bytes = key.to_bytes();
+        let bytes = match tree.get(&key_bytes)? {
+            Some(data) => data,
+            None => return Err(BlockchainError::NotFoundOnDisk(DiskContext::DeleteData))
+        };
+        self.remove_from_tree(tree, &key_bytes)?;
+
+        if let Some(cache) = cache {
+            let mut cache = cache.lock().await;
+            if let Some(value) = cache.pop(key) {
+                return Ok(value);
+            }
+        }
+
+        let mut reader = Reader::new(&bytes);
+        let value = V::read(&mut reader)?;
+        Ok(value)
+    }
+
+    async fn delete_data<K: Eq + StdHash + Serializer + Clone, V: Serializer>(&self, tree: &B::Tree, cache: &Option<Mutex<LruCache<K, Arc<V>>>>, key: &K) -> Result<Arc<V>, BlockchainError> {
+        let key_bytes = key.to_bytes();
+        let bytes = match tree.get(&key_bytes)? {
+            Some(data) => data,
+            None => return Err(BlockchainError::NotFoundOnDisk(DiskContext::DeleteData))
+        };
+        self.remove_from_tree(tree, &key_bytes)?;
+
+        if let Some(cache) = cache {
+            let mut cache = cache.lock().await;
+            if let Some(value) = cache.pop(key) {
+                return Ok(value);
+            }
+        }
+
+        let mut reader = Reader::new(&bytes);
+        let value = V::read(&mut reader)?;
+        Ok(Arc::new(value))
+    }
+
+    async fn contains_data<K: Eq + StdHash + Serializer + Clone, V>(&self, tree: &B::Tree, cache: &Option<Mutex<LruCache<K, V>>>, key: &K) -> Result<bool, BlockchainError> {
+        if let Some(cache) = cache {
+            let cache = cache.lock().await;
+            return Ok(cache.contains(key) || tree.contains_key(&key.to_bytes())?)
+        }
+
+        Ok(tree.contains_key(&key.to_bytes())?)
+    }
+
+    fn get_balance_key_for(&self, key: &PublicKey, asset: &Hash) -> [u8; 64] {
+        let mut bytes = [0; 64];
+        bytes[0..32].copy_from_slice(key.as_bytes());
+        bytes[32..64].copy_from_slice(asset.as_bytes());
+        bytes
+    }
+
+    // Versioned key is a 40 bytes key with topoheight as first bytes and the key as last bytes
+    fn get_versioned_key(&self, key: &PublicKey, topoheight: u64) -> [u8; 40] {
+        trace!("get versioned key at {} for {}", topoheight, key);
+        let mut bytes = [0; 40];
+        bytes[0..8].copy_from_slice(&topoheight.to_be_bytes());
+        bytes[8..40].copy_from_slice(key.as_bytes());
+
+        bytes
+    }
+
+    // Mempool tx key is a 40 bytes key with the owner as first bytes and the nonce as last bytes
+    fn get_mempool_tx_key(&self, owner: &PublicKey, nonce: u64) -> [u8; 40] {
+        let mut bytes = [0; 40];
+        bytes[0..32].copy_from_slice(owner.as_bytes());
+        bytes[32..40].copy_from_slice(&nonce.to_be_bytes());
+
+        bytes
+    }
+
+    // Versioned balance key is a 72 bytes key with topoheight as first bytes, then the key and the asset
+    // so balances of different assets changed at the same topoheight don't overwrite each other
+    fn get_versioned_balance_key(&self, key: &PublicKey, asset: &Hash, topoheight: u64) -> [u8; 72] {
+        trace!("get versioned balance key at {} for {} {}", topoheight, key, asset);
+        let mut bytes = [0; 72];
+        bytes[0..40].copy_from_slice(&self.get_versioned_key(key, topoheight));
+        bytes[40..72].copy_from_slice(asset.as_bytes());
+
+        bytes
+    }
+
+
+    // Prefixed versioned key is a 40 bytes key with the asset or account as first bytes so all its versions can be scanned
+    fn get_prefixed_versioned_key(&self, prefix: &[u8; 32], topoheight: u64) -> [u8; 40] {
+        let mut bytes = [0; 40];
+        bytes[0..32].copy_from_slice(prefix);
+        bytes[32..40].copy_from_slice(&topoheight.to_be_bytes());
+
+        bytes
+    }
+
+    // Change key is the topoheight followed by the asset or account
+    fn get_prefixed_version_change_key(&self, prefix: &[u8], topoheight: u64) -> [u8; 40] {
+        let mut bytes = [0; 40];
+        bytes[0..8].copy_from_slice(&topoheight.to_be_bytes());
+        bytes[8..40].copy_from_slice(prefix);
+
+        bytes
+    }
+
+    // write the version of an asset or account at topoheight and index it in the changes tree
+    fn set_prefixed_version(&self, tree: &B::Tree, changes: &B::Tree, prefix: &[u8; 32], topoheight: u64, value: &[u8]) -> Result<(), BlockchainError> {
+        self.insert_into_tree(changes, self.get_prefixed_version_change_key(prefix, topoheight), [])?;
+        self.insert_into_tree(tree, self.get_prefixed_versioned_key(prefix, topoheight), value)
+    }
+
+    // Find the highest version of an asset or account at or below topoheight
+    fn get_prefixed_version_at_maximum_topoheight(&self, tree: &B::Tree, prefix: &[u8; 32], topoheight: u64) -> Result<Option<(u64, Vec<u8>)>, BlockchainError> {
+        let mut highest: Option<(u64, Vec<u8>)> = None;
+        for el in tree.scan_prefix(prefix) {
+            let (key, value) = el?;
+            let version = u64::from_bytes(&key[32..40])?;
+            if version <= topoheight && highest.as_ref().map_or(true, |(topo, _)| version > *topo) {
+                highest = Some((version, value));
+            }
+        }
+
+        Ok(highest)
+    }
+
+    // Locked balance key is the account, the asset, the topoheight at which it was received then the unlock condition
+    fn get_locked_balance_key(&self, key: &PublicKey, asset: &Hash, topoheight: u64, condition: &UnlockCondition) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(72 + 17);
+        bytes.extend_from_slice(key.as_bytes());
+        bytes.extend_from_slice(asset.as_bytes());
+        bytes.extend_from_slice(&topoheight.to_be_bytes());
+        bytes.extend(condition.to_bytes());
+
+        bytes
+    }
+
+    // Change key is the topoheight followed by the locked balance key
+    fn get_locked_balance_change_key(&self, topoheight: u64, locked_key: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(8 + locked_key.len());
+        bytes.extend_from_slice(&topoheight.to_be_bytes());
+        bytes.extend_from_slice(locked_key);
+        bytes
+    }
+
+    // top topoheight is only set once the genesis block is ordered
+    fn get_top_topoheight_or_genesis(&self) -> Result<u64, BlockchainError> {
+        if self.extra.contains_key(TOP_TOPO_HEIGHT)? {
+            self.get_top_topoheight()
+        } else {
+            Ok(0)
+        }
+    }
+
+    // delete all versions stored with a prefixed versioned key above topoheight
+    // only the topoheights indexed in the changes tree are read
+    fn delete_prefixed_versions_above_topoheight(&self, tree: &B::Tree, changes: &B::Tree, topoheight: u64) -> Result<(), BlockchainError> {
+        let top_topoheight = self.get_top_topoheight_or_genesis()?;
+        for topo in topoheight + 1..=top_topoheight {
+            for el in changes.scan_prefix(&topo.to_be_bytes()) {
+                let (key, _) = el?;
+                let mut prefix = [0; 32];
+                prefix.copy_from_slice(&key[8..40]);
+                self.remove_from_tree(tree, self.get_prefixed_versioned_key(&prefix, topo))?;
+                self.remove_from_tree(changes, &key)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // delete all versions of supply and authority of assets above topoheight
+    fn delete_assets_versions_above_topoheight(&self, topoheight: u64) -> Result<(), BlockchainError> {
+        self.delete_prefixed_versions_above_topoheight(&self.assets_supply, &self.assets_supply_changes, topoheight)?;
+        self.delete_prefixed_versions_above_topoheight(&self.assets_authority, &self.assets_authority_changes, topoheight)
+    }
+
+    async fn has_balance_internal(&self, key: &[u8; 64]) -> Result<bool, BlockchainError> {
+        trace!("has balance internal");
+        Ok(self.balances.contains_key(key)?)
+    }
+
+    fn delete_versioned_tree_above_topoheight(&self, tree: &B::Tree, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("delete versioned nonces above or at topoheight {}", topoheight);
+        for el in tree.iter() {
+            let (key, _) = el?;
+            let topo = u64::from_bytes(&key[0..8])?;
+            if topo > topoheight {
+                self.remove_from_tree(tree, &key)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Node id is the depth and the first depth bits of the leaf key
+    // If sibling is set, the last bit of the path is flipped to get the other child of the parent
+    fn get_state_tree_node_id(&self, depth: usize, leaf_key: &Hash, sibling: bool) -> [u8; STATE_TREE_NODE_ID_SIZE] {
+        let mut id = [0; STATE_TREE_NODE_ID_SIZE];
+        id[0..2].copy_from_slice(&(depth as u16).to_be_bytes());
+        let path = &mut id[2..];
+        let bytes = leaf_key.as_bytes();
+        path[0..depth / 8].copy_from_slice(&bytes[0..depth / 8]);
+        if depth % 8 != 0 {
+            path[depth / 8] = bytes[depth / 8] & (0xFF << (8 - depth % 8));
+        }
+
+        if sibling {
+            let bit = depth - 1;
+            path[bit / 8] ^= 0x80 >> (bit % 8);
+        }
+        id
+    }
+
+    // Versions are sorted from the newest to the oldest as topoheight is inverted
+    fn get_state_tree_versioned_key(&self, id: &[u8], topoheight: u64) -> Vec<u8> {
+        let mut key = Vec::with_capacity(STATE_TREE_NODE_ID_SIZE + 8);
+        key.extend_from_slice(id);
+        key.extend_from_slice(&(u64::MAX - topoheight).to_be_bytes());
+        key
+    }
+
+    fn get_state_tree_change_key(&self, id: &[u8], topoheight: u64) -> Vec<u8> {
+        let mut key = Vec::with_capacity(8 + STATE_TREE_NODE_ID_SIZE);
+        key.extend_from_slice(&topoheight.to_be_bytes());
+        key.extend_from_slice(id);
+        key
+    }
+
+    // returns the latest version of the node at maximum topoheight with its topoheight
+    fn get_state_tree_node(&self, id: &[u8], topoheight: u64) -> Result<Option<(u64, StateTreeNode)>, BlockchainError> {
+        for el in self.state_tree.scan_prefix(id) {
+            let (key, value) = el?;
+            let topo = u64::MAX - u64::from_bytes(&key[STATE_TREE_NODE_ID_SIZE..])?;
+            if topo <= topoheight {
+                return Ok(Some((topo, StateTreeNode::from_bytes(&value)?)))
+            }
+        }
+        Ok(None)
+    }
+
+    fn get_state_tree_node_hash(&self, id: &[u8], topoheight: u64) -> Result<Hash, BlockchainError> {
+        Ok(self.get_state_tree_node(id, topoheight)?.map(|(_, node)| node.hash()).unwrap_or(EMPTY_STATE_ROOT))
+    }
+
+    fn set_state_tree_node(&self, id: &[u8], topoheight: u64, node: &StateTreeNode) -> Result<(), BlockchainError> {
+        self.insert_into_tree(&self.state_tree, self.get_state_tree_versioned_key(id, topoheight), node.to_bytes())?;
+        self.insert_into_tree(&self.state_tree_changes, self.get_state_tree_change_key(id, topoheight), [])
+    }
+
+    fn get_account_history_key(&self, key: &PublicKey, topoheight: u64, hash: &Hash, direction: AccountHistoryDirection) -> [u8; ACCOUNT_HISTORY_KEY_SIZE] {
+        let mut bytes = [0; ACCOUNT_HISTORY_KEY_SIZE];
+        bytes[0..32].copy_from_slice(key.as_bytes());
+        bytes[32..40].copy_from_slice(&(u64::MAX - topoheight).to_be_bytes());
+        bytes[40..40 + HASH_SIZE].copy_from_slice(hash.as_bytes());
+        bytes[40 + HASH_SIZE] = match direction {
+            AccountHistoryDirection::Outgoing => 0,
+            AccountHistoryDirection::Incoming => 1
+        };
+        bytes
+    }
+
+    // Change key is the topoheight followed by the account history key
+    fn get_account_history_change_key(&self, topoheight: u64, history_key: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(8 + ACCOUNT_HISTORY_KEY_SIZE);
+        bytes.extend_from_slice(&topoheight.to_be_bytes());
+        bytes.extend_from_slice(history_key);
+        bytes
+    }
+
+    // delete all account history entries written at these topoheights
+    fn delete_account_history_at_topoheights(&self, topoheights: impl Iterator<Item = u64>) -> Result<(), BlockchainError> {
+        for topo in topoheights {
+            for el in self.account_history_changes.scan_prefix(&topo.to_be_bytes()) {
+                let (key, _) = el?;
+                self.remove_from_tree(&self.account_history, &key[8..])?;
+                self.remove_from_tree(&self.account_history_changes, &key)?;
+            }
+        }
+        Ok(())
+    }
+
+    // old versions of the nodes are deleted during pruning
+    fn check_state_tree_not_pruned(&self, topoheight: u64) -> Result<(), BlockchainError> {
+        if let Some(pruned_topoheight) = self.pruned_topoheight {
+            if topoheight < pruned_topoheight {
+                return Err(BlockchainError::StateRootPruned(topoheight, pruned_topoheight))
+            }
+        }
+        Ok(())
+    }
+
+    // Set the value of a leaf and update all nodes up to the root at topoheight
+    fn update_state_tree(&self, leaf_key: &Hash, value: u64, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("update state tree leaf {} to {} at topoheight {}", leaf_key, value, topoheight);
+        // siblings[depth] is the hash of the other child of the node at depth
+        let mut siblings = Vec::new();
+        let mut depth = 0;
+        loop {
+            let id = self.get_state_tree_node_id(depth, leaf_key, false);
+            match self.get_state_tree_node(&id, topoheight)?.map(|(_, node)| node) {
+                None => break,
+                Some(StateTreeNode::Leaf(key, _)) if key == *leaf_key => break,
+                Some(StateTreeNode::Leaf(key, other_value)) => {
+                    // move the other leaf down until both leaves are in different subtrees
+                    while is_right_at_depth(&key, depth) == is_right_at_depth(leaf_key, depth) {
+                        siblings.push(EMPTY_STATE_ROOT);
+                        depth += 1;
+                    }
+                    depth += 1;
+                    let other = StateTreeNode::Leaf(key, other_value);
+                    siblings.push(other.hash());
+                    self.set_state_tree_node(&self.get_state_tree_node_id(depth, leaf_key, true), topoheight, &other)?;
+                    break;
+                },
+                Some(StateTreeNode::Node(_)) => {
+                    depth += 1;
+                    siblings.push(self.get_state_tree_node_hash(&self.get_state_tree_node_id(depth, leaf_key, true), topoheight)?);
+                }
+            }
+        }
+
+        let leaf = StateTreeNode::Leaf(leaf_key.clone(), value);
+        let mut hash = leaf.hash();
+        self.set_state_tree_node(&self.get_state_tree_node_id(depth, leaf_key, false), topoheight, &leaf)?;
+        while depth > 0 {
+            depth -= 1;
+            let sibling = &siblings[depth];
+            hash = if is_right_at_depth(leaf_key, depth) {
+                hash_node(sibling, &hash)
+            } else {
+                hash_node(&hash, sibling)
+            };
+            self.set_state_tree_node(&self.get_state_tree_node_id(depth, leaf_key, false), topoheight, &StateTreeNode::Node(hash.clone()))?;
+        }
+
+        Ok(())
+    }
+
+    fn delete_versioned_tree_below_topoheight(&self, tree: &B::Tree, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("delete versioned nonces above or at topoheight {}", topoheight);
+        for el in tree.iter() {
+            let (key, _) = el?;
+            let topo = u64::from_bytes(&key[0..8])?;
+            if topo < topoheight {
+                self.remove_from_tree(tree, &key)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+
+#[async_trait]
+impl<B: Backend> DifficultyProvider for KeyValueStorage<B> {
+    // TODO optimize all these functions to read only what is necessary
+    async fn get_height_for_block_hash(&self, hash: &Hash) -> Result<u64, BlockchainError> {
+        trace!("get height for block hash {}", hash);
+        let block = self.get_block_header_by_hash(hash).await?;
+        Ok(block.get_height())
+    }
+
+    async fn get_timestamp_for_block_hash(&self, hash: &Hash) -> Result<u128, BlockchainError> {
+        trace!("get timestamp for hash {}", hash);
+        let block = self.get_block_header_by_hash(hash).await?;
+        Ok(block.get_timestamp())
+    }
+
+    async fn get_difficulty_for_block_hash(&self, hash: &Hash) -> Result<Difficulty, BlockchainError> {
+        trace!("get difficulty for hash {}", hash);
+        self.load_from_disk(&self.difficulty, hash.as_bytes())
+    }
+
+    async fn get_cumulative_difficulty_for_block_hash(&self, hash: &Hash) -> Result<Difficulty, BlockchainError> {
+        trace!("get cumulative difficulty for hash {}", hash);
+        self.get_cacheable_data_copiable(&self.cumulative_difficulty, &self.cumulative_difficulty_cache, hash).await
+    }
+
+    async fn get_past_blocks_for_block_hash(&self, hash: &Hash) -> Result<Arc<Vec<Hash>>, BlockchainError> {
+        trace!("get past blocks of {}", hash);
+        let tips = if let Some(cache) = &self.past_blocks_cache {
+            let mut cache = cache.lock().await;
+            if let Some(tips) = cache.get(hash) {
+                return Ok(tips.clone())
+            }
+    
+            let block = self.get_block_header_by_hash(hash).await?;
+            let mut tips = Vec::with_capacity(block.get_tips().len());
+            for hash in block.get_tips() {
+                tips.push(hash.clone());
+            }
+    
+            let tips = Arc::new(tips);
+            cache.put(hash.clone(), tips.clone());
+            tips
+        } else {
+            let block = self.get_block_header_by_hash(hash).await?;
+            let mut tips = Vec::with_capacity(block.get_tips().len());
+            for hash in block.get_tips() {
+                tips.push(hash.clone());
+            }
+            Arc::new(tips)
+        };
+
+        Ok(tips)
+    }
+
+    async fn get_block_header_by_hash(&self, hash: &Hash) -> Result<Arc<BlockHeader>, BlockchainError> {
+        trace!("get block by hash: {}", hash);
+        self.get_cacheable_arc_data(&self.blocks, &self.blocks_cache, hash).await
+    }
+}
+
+#[async_trait]
+impl<B: Backend> Storage for KeyValueStorage<B> {
+    fn get_pruned_topoheight(&self) -> Result<Option<u64>, BlockchainError> {
+        Ok(self.pruned_topoheight)
+    }
+
+    fn set_pruned_topoheight(&mut self, pruned_topoheight: u64) -> Result<(), BlockchainError> {
+        self.pruned_topoheight = Some(pruned_topoheight);
+        self.insert_into_tree(&self.extra, PRUNED_TOPOHEIGHT, &pruned_topoheight.to_be_bytes())?;
+        Ok(())
+    }
+
+    // Delete the whole block using its topoheight
+    async fn delete_block_at_topoheight(&mut self, topoheight: u64) -> Result<(Hash, Arc<BlockHeader>, Vec<(Hash, Arc<Transaction>)>), BlockchainError> {
+        trace!("Delete block at topoheight {topoheight}");
+
+        // delete topoheight<->hash pointers
+        let hash = self.delete_cacheable_data(&self.hash_at_topo, &self.hash_at_topo_cache, &topoheight).await?;
+        trace!("Hash is {hash} at topo {topoheight}");
+
+        self.delete_cacheable_data::<Hash, u64>(&self.topo_by_hash, &self.topo_by_hash_cache, &hash).await?;
+
+        trace!("deleting block header {}", hash);
+        let block = self.delete_data(&self.blocks, &self.blocks_cache, &hash).await?;
+        trace!("block header deleted successfully");
+
+        trace!("Deleting supply and block reward");
+        let supply: u64 = self.delete_cacheable_data(&self.supply, &None, &topoheight).await?;
+        trace!("Supply was {}", supply);
+
+        let reward: u64 = self.delete_cacheable_data(&self.rewards, &None, &topoheight).await?;
+        trace!("Reward for block {} was: {}", hash, reward);
+
+        trace!("Deleting difficulty");
+        let _: Difficulty = self.delete_cacheable_data(&self.difficulty, &None, &hash).await?;
+
+        trace!("Deleting cumulative difficulty");
+        let cumulative_difficulty: Difficulty = self.delete_cacheable_data(&self.cumulative_difficulty, &self.cumulative_difficulty_cache, &hash).await?;
+        trace!("Cumulative difficulty deleted: {}", cumulative_difficulty);
+
+        let mut txs = Vec::new();
+        for tx_hash in block.get_transactions() {
+            if self.has_tx_blocks(tx_hash)? {
+                let mut blocks: Tips = self.delete_cacheable_data(&self.tx_blocks, &None, tx_hash).await?;
+                let blocks_len =  blocks.len();
+                blocks.remove(&hash);
+                self.set_blocks_for_tx(tx_hash, &blocks)?;
+                trace!("Tx was included in {}, blocks left: {}", blocks_len, blocks.into_iter().map(|b| b.to_string()).collect::<Vec<String>>().join(", "));
+            }
+
+            if self.is_tx_executed_in_a_block(tx_hash)? {
+                trace!("Tx {} was executed, deleting", tx_hash);
+                self.remove_tx_executed(&tx_hash)?;
+            }
+
+            // We have to check first as we may have already deleted it because of client protocol
+            // which allow multiple time the same txs in differents blocks
+            if self.contains_data(&self.transactions, &self.transactions_cache, tx_hash).await? {
+                trace!("Deleting TX {} in block {}", tx_hash, hash);
+                let tx: Arc<Transaction> = self.delete_data(&self.transactions, &self.transactions_cache, tx_hash).await?;
+                txs.push((tx_hash.clone(), tx));
+            }
+        }
+
+        self.decrease_counter(BLOCKS_COUNT, 1)?;
+        self.decrease_counter(TXS_COUNT, txs.len() as u64)?;
+
+        // remove the block hash from the set, and delete the set if empty
+        if self.has_blocks_at_height(block.get_height()).await? {
+            self.remove_block_hash_at_height(&hash, block.get_height()).await?;
+        }
+
+        // Delete cache of past blocks
+        if let Some(cache) = &self.past_blocks_cache {
+            let mut cache = cache.lock().await;
+            cache.pop(&hash);
+        }
+
+        Ok((hash, block, txs))
+    }
+
+    async fn delete_tx(&mut self, hash: &Hash) -> Result<Arc<Transaction>, BlockchainError> {
+        self.delete_cacheable_data::<Hash, HashSet<Hash>>(&self.tx_blocks, &None, hash).await?;
+        let tx = self.delete_data(&self.transactions, &self.transactions_cache, hash).await?;
+        self.decrease_counter(TXS_COUNT, 1)?;
+        Ok(tx)
+    }
+
+    async fn delete_versioned_balances_above_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("delete versioned balances above topoheight {}!", topoheight);
+        self.delete_versioned_tree_above_topoheight(&self.versioned_balances, topoheight)
+    }
+
+    async fn delete_versioned_nonces_above_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("delete versioned nonces above topoheight {}", topoheight);
+        self.delete_versioned_tree_above_topoheight(&self.versioned_nonces, topoheight)
+    }
+
+    async fn delete_versioned_balances_below_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("delete versioned balances below topoheight {}!", topoheight);
+        self.delete_versioned_tree_below_topoheight(&self.versioned_balances, topoheight)
+    }
+
+    async fn delete_versioned_nonces_below_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("delete versioned nonces below topoheight {}", topoheight);
+        self.delete_versioned_tree_below_topoheight(&self.versioned_nonces, topoheight)
+    }
+
+    async fn get_state_root_at_topoheight(&self, topoheight: u64) -> Result<Hash, BlockchainError> {
+        trace!("get state root at topoheight {}", topoheight);
+        self.check_state_tree_not_pruned(topoheight)?;
+        self.get_state_tree_node_hash(&self.get_state_tree_node_id(0, &EMPTY_STATE_ROOT, false), topoheight)
+    }
+
+    async fn has_state_root_at_topoheight(&self, topoheight: u64) -> Result<bool, BlockchainError> {
+        trace!("has state root at topoheight {}", topoheight);
+        if self.check_state_tree_not_pruned(topoheight).is_err() {
+            return Ok(false)
+        }
+
+        // the root is written with the first balance, so it's always present on a complete state
+        Ok(self.get_state_tree_node(&self.get_state_tree_node_id(0, &EMPTY_STATE_ROOT, false), topoheight)?.is_some())
+    }
+
+    async fn get_state_proof_at_topoheight(&self, leaf_key: &Hash, topoheight: u64) -> Result<Option<StateProof>, BlockchainError> {
+        trace!("get state proof of leaf {} at topoheight {}", leaf_key, topoheight);
+        self.check_state_tree_not_pruned(topoheight)?;
+        let mut siblings = Vec::new();
+        let mut depth = 0;
+        loop {
+            match self.get_state_tree_node(&self.get_state_tree_node_id(depth, leaf_key, false), topoheight)?.map(|(_, node)| node) {
+                Some(StateTreeNode::Leaf(key, _)) if key == *leaf_key => return Ok(Some(StateProof::new(siblings))),
+                Some(StateTreeNode::Node(_)) => {
+                    depth += 1;
+                    siblings.push(self.get_state_tree_node_hash(&self.get_state_tree_node_id(depth, leaf_key, true), topoheight)?);
+                },
+                // empty subtree or another leaf
+                _ => return Ok(None)
+            }
+        }
+    }
+
+    async fn delete_state_tree_above_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("delete state tree above topoheight {}", topoheight);
+        // nodes can be written until the top topoheight
+        let top_topoheight = self.get_top_topoheight()?;
+        for topo in topoheight + 1..=top_topoheight {
+            for el in self.state_tree_changes.scan_prefix(&topo.to_be_bytes()) {
+                let (key, _) = el?;
+                self.remove_from_tree(&self.state_tree, self.get_state_tree_versioned_key(&key[8..], topo))?;
+                self.remove_from_tree(&self.state_tree_changes, &key)?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn delete_state_tree_below_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("delete state tree below topoheight {}", topoheight);
+        // versions kept by a previous pruning are deleted once a node is updated again
+        let start = self.pruned_topoheight.unwrap_or(0);
+        for topo in start..topoheight {
+            for el in self.state_tree_changes.scan_prefix(&topo.to_be_bytes()) {
+                let (key, _) = el?;
+                let id = &key[8..];
+                // keep only the latest version at topoheight
+                let mut latest_found = false;
+                for el in self.state_tree.scan_prefix(id) {
+                    let (versioned_key, _) = el?;
+                    let version = u64::MAX - u64::from_bytes(&versioned_key[STATE_TREE_NODE_ID_SIZE..])?;
+                    if version > topoheight {
+                        continue;
+                    }
+
+                    if latest_found {
+                        self.remove_from_tree(&self.state_tree, &versioned_key)?;
+                        self.remove_from_tree(&self.state_tree_changes, self.get_state_tree_change_key(id, version))?;
+                    } else {
+                        latest_found = true;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn is_account_history_indexed(&self) -> Result<bool, BlockchainError> {
+        trace!("is account history indexed");
+        self.extra.contains_key(ACCOUNT_HISTORY_INDEXED)
+    }
+
+    fn set_account_history_indexed(&mut self, indexed: bool) -> Result<(), BlockchainError> {
+        trace!("set account history indexed {}", indexed);
+        if indexed {
+            return self.insert_into_tree(&self.extra, ACCOUNT_HISTORY_INDEXED, [])
+        }
+
+        self.remove_from_tree(&self.extra, ACCOUNT_HISTORY_INDEXED)?;
+        for tree in [&self.account_history, &self.account_history_changes] {
+            for el in tree.iter() {
+                let (key, _) = el?;
+                self.remove_from_tree(tree, &key)?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn add_account_history_entry(&mut self, key: &PublicKey, topoheight: u64, hash: &Hash, direction: AccountHistoryDirection) -> Result<(), BlockchainError> {
+        trace!("add account history entry {} for {} at topoheight {}", hash, key, topoheight);
+        let history_key = self.get_account_history_key(key, topoheight, hash, direction);
+        self.insert_into_tree(&self.account_history, history_key, [])?;
+        self.insert_into_tree(&self.account_history_changes, self.get_account_history_change_key(topoheight, &history_key), [])
+    }
+
+    async fn get_account_history_entries(&self, key: &PublicKey, minimum_topoheight: u64, maximum_topoheight: u64, skip: usize, maximum: usize) -> Result<Vec<(u64, Hash, AccountHistoryDirection)>, BlockchainError> {
+        trace!("get account history entries for {} from {} to {}", key, minimum_topoheight, maximum_topoheight);
+        let mut entries = Vec::new();
+        let mut skipped = 0;
+        for el in self.account_history.scan_prefix(key.as_bytes()) {
+            let (history_key, _) = el?;
+            let topoheight = u64::MAX - u64::from_bytes(&history_key[32..40])?;
+            if topoheight > maximum_topoheight {
+                continue;
+            }
+            // entries are sorted from the newest to the oldest
+            if topoheight < minimum_topoheight {
+                break;
+            }
+
+            if skipped < skip {
+                skipped += 1;
+                continue;
+            }
+
+            let hash = Hash::from_bytes(&history_key[40..40 + HASH_SIZE])?;
+            let direction = match history_key[40 + HASH_SIZE] {
+                0 => AccountHistoryDirection::Outgoing,
+                1 => AccountHistoryDirection::Incoming,
+                _ => return Err(ReaderError::InvalidValue.into())
+            };
+            entries.push((topoheight, hash, direction));
+            if entries.len() >= maximum {
+                break;
+            }
+        }
+        Ok(entries)
+    }
+
+    async fn delete_account_history_above_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("delete account history above topoheight {}", topoheight);
+        let top_topoheight = self.get_top_topoheight()?;
+        self.delete_account_history_at_topoheights(topoheight + 1..=top_topoheight)
+    }
+
+    async fn delete_account_history_below_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("delete account history below topoheight {}", topoheight);
+        self.delete_account_history_at_topoheights(self.pruned_topoheight.unwrap_or(0)..topoheight)
+    }
+
+    async fn create_snapshot_balances_at_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        // asset tree where PublicKey are stored with the highest balance topoheight in it
+        for el in self.balances.iter() {
+            let (key_bytes, value) = el?;
+            let key = PublicKey::from_bytes(&key_bytes[0..32])?;
+            let asset = Hash::from_bytes(&key_bytes[32..64])?;
+            let highest_balance_topoheight = u64::from_bytes(&value)?;
+
+            // retrieve the highest versioned balance
+            let mut versioned_balance = self.get_balance_at_exact_topoheight(&key, &asset, highest_balance_topoheight).await?;
+
+            // if the highest topoheight for this account is less than the snapshot topoheight
+            // update it to the topoheight
+            // otherwise, delete the previous topoheight in VersionedBalance which is under topoheight
+            if highest_balance_topoheight < topoheight {
+                // save the new highest topoheight
+                self.insert_into_tree(&self.balances, &key_bytes, &topoheight.to_be_bytes())?;
+                // remove the previous topoheight
+                versioned_balance.set_previous_topoheight(None);
+
+                // save it
+                let key = self.get_versioned_balance_key(&key, &asset, topoheight);
+                self.insert_into_tree(&self.versioned_balances, key, versioned_balance.to_bytes())?;
+            } else {
+                // find the first VersionedBalance which is under topoheight
+                while let Some(previous_topoheight) = versioned_balance.get_previous_topoheight() {
+                    if previous_topoheight < topoheight {
+                        versioned_balance.set_previous_topoheight(None);
+                        // save it
+                        let key = self.get_versioned_balance_key(&key, &asset, topoheight);
+                        self.insert_into_tree(&self.versioned_balances, key, versioned_balance.to_bytes())?;
+                        break;
+                    }
+
+                    // keep searching
+                    versioned_balance = self.get_balance_at_exact_topoheight(&key, &asset, previous_topoheight).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn create_snapshot_nonces_at_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        // tree where VersionedNonce are stored
+        // tree where PublicKey are stored with the highest noce topoheight in it
+        for el in self.nonces.iter() {
+            let (key_bytes, value) = el?;
+            let key = PublicKey::from_bytes(&key_bytes)?;
+            let highest_topoheight = u64::from_bytes(&value)?;
+
+            // retrieve the highest versioned nonce
+            let mut versioned_nonce = self.get_nonce_at_exact_topoheight(&key, highest_topoheight).await?;
+
+            // if the highest topoheight for this account is less than the snapshot topoheight
+            // update it to the topoheight
+            // otherwise, delete the previous topoheight in VersionedNonce which is under topoheight
+            if highest_topoheight < topoheight {
+                // save the new highest topoheight
+                self.insert_into_tree(&self.nonces, &key_bytes, &topoheight.to_be_bytes())?;
+                // remove the previous topoheight
+                versioned_nonce.set_previous_topoheight(None);
+
+                // save it
+                let key = self.get_versioned_key(&key, topoheight);
+                self.insert_into_tree(&self.versioned_nonces, key, versioned_nonce.to_bytes())?;
+            } else {
+                // find the first VersionedBalance which is under topoheight
+                while let Some(previous_topoheight) = versioned_nonce.get_previous_topoheight() {
+                    if previous_topoheight < topoheight {
+                        versioned_nonce.set_previous_topoheight(None);
+                        // save it
+                        let key = self.get_versioned_key(&key, topoheight);
+                        self.insert_into_tree(&self.versioned_nonces, key, versioned_nonce.to_bytes())?;
+                        break;
+                    }
+
+                    // keep searching
+                    versioned_nonce = self.get_nonce_at_exact_topoheight(&key, previous_topoheight).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn get_partial_assets(&self, maximum: usize, skip: usize, minimum_topoheight: u64, maximum_topoheight: u64) -> Result<IndexSet<AssetWithData>, BlockchainError> {
+        let mut assets = IndexSet::new();
+        let mut skip_count = 0;
+        for el in self.assets.iter() {
+            let (key, value) = el?;
+            let data = AssetData::from_bytes(&value)?;
+            // check that we have a registered asset before the maximum topoheight
+            if data.get_topoheight() >= minimum_topoheight && data.get_topoheight() <= maximum_topoheight {
+                if skip_count < skip {
+                    skip_count += 1;
+                } else {
+                    let asset = Hash::from_bytes(&key)?;
+                    assets.insert(AssetWithData::new(asset, data));
+
+                    if assets.len() == maximum {
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(assets)
+    }
+
+    // Get all keys that got a changes in their balances/nonces in the range given
+    async fn get_partial_keys(&self, maximum: usize, skip: usize, minimum_topoheight: u64, maximum_topoheight: u64) -> Result<IndexSet<PublicKey>, BlockchainError> {
+        trace!("get partial keys, maximum: {}, skip: {}, minimum_topoheight: {}, maximum_topoheight: {}", maximum, skip, minimum_topoheight, maximum_topoheight);
+
+        let mut keys: IndexSet<PublicKey> = IndexSet::new();
+        let mut skip_count = 0;
+        for el in self.nonces.iter() {
+            let (key, _) = el?;
+            let pkey = PublicKey::from_bytes(&key)?;
+
+            // check that we have a nonce before the maximum topoheight
+            if self.has_key_updated_in_range(&pkey, minimum_topoheight, maximum_topoheight).await? {
+                if skip_count < skip {
+                    skip_count += 1;
+                } else {
+                    keys.insert(pkey);
+
+                    if keys.len() == maximum {
+                        break;
+                    }
+                }
+            }
+        }
+
+        Ok(keys)
+    }
+
+    async fn has_key_updated_in_range(&self, key: &PublicKey, minimum_topoheight: u64, maximum_topoheight: u64) -> Result<bool, BlockchainError> {
+        trace!("has key {} updated in range min topoheight {} and max topoheight {}", key, minimum_topoheight, maximum_topoheight);
+        // check first that this address has nonce, if no returns None
+        if !self.has_nonce(key).await? {
+            return Ok(false)
+        }
+
+        // fast path check the latest nonce
+        let (topo, mut version) = self.get_last_nonce(key).await?;
+        trace!("Last version of nonce for {} is at topoheight {}", key, topo);
+
+        // if it's the latest and its under the maximum topoheight and above minimum topoheight
+        if topo >= minimum_topoheight && topo <= maximum_topoheight {
+            trace!("Last version nonce (valid) found at {} (maximum topoheight = {})", topo, maximum_topoheight);
+            return Ok(true)
+        }
+
+        // otherwise, we have to go through the whole chain
+        while let Some(previous) = version.get_previous_topoheight() {
+            // we are under the minimum topoheight, we can stop
+            if previous < minimum_topoheight {
+                break;
+            }
+
+            let previous_version = self.get_nonce_at_exact_topoheight(key, previous).await?;
+            trace!("previous nonce version is at {}", previous);
+            if previous <= maximum_topoheight {
+                trace!("Highest version nonce found at {} (maximum topoheight = {})", previous, maximum_topoheight);
+                return Ok(true)
+            }
+
+            // security in case of DB corruption
+            if let Some(value) = previous_version.get_previous_topoheight() {
+                if value > previous {
+                    error!("FATAL ERROR: Previous topoheight ({}) should not be higher than current version ({})!", value, previous);
+                    return Err(BlockchainError::Unknown)
+                }
+            }
+            version = previous_version;
+        }
+
+        // if we are here, we didn't find any nonce in the range
+        // it start to be more and more heavy...
+        // lets check on balances now...
+
+        // check that we have a VersionedBalance between range given
+        for asset in self.get_assets_for(key).await? {
+            let (topo, mut version) = self.get_last_balance(key, &asset).await?;
+            if topo >= minimum_topoheight && topo <= maximum_topoheight {
+                return Ok(true)
+            }
+
+            while let Some(previous) = version.get_previous_topoheight() {
+                // we are under the minimum topoheight, we can stop
+                if previous < minimum_topoheight {
+                    break;
+                }
+
+                let previous_version = self.get_balance_at_exact_topoheight(key, &asset, previous).await?;
+                if previous <= maximum_topoheight {
+                    return Ok(true)
+                }
+
+                // security in case of DB corruption
+                if let Some(value) = previous_version.get_previous_topoheight() {
+                    if value > previous {
+                        error!("FATAL ERROR: Previous topoheight for balance ({}) should not be higher than current version of balance ({})!", value, previous);
+                        return Err(BlockchainError::Unknown)
+                    }
+                }
+                version = previous_version;
+            }
+        }
+
+        Ok(false)
+    }
+
+    async fn get_balances<'a, I: Iterator<Item = &'a PublicKey> + Send>(&self, asset: &Hash, keys: I, maximum_topoheight: u64) -> Result<Vec<Option<u64>>, BlockchainError> {
+        let mut balances = Vec::new();
+        for key in keys {
+            if self.has_balance_for(key, asset).await? {
+                let res = match self.get_balance_at_maximum_topoheight(key, asset, maximum_topoheight).await? {
+                    Some((_, version)) => Some(version.get_balance()),
+                    None => None
+                };
+                balances.push(res);
+            } else {
+                balances.push(None);
+            }
+        }
+        Ok(balances)
+    }
+
+    fn count_accounts(&self) -> Result<u64, BlockchainError> {
+        trace!("count accounts");
+        Ok(self.load_from_disk(&self.extra, ACCOUNTS_COUNT).unwrap_or(0))
+    }
+
+    fn get_block_executer_for_tx(&self, tx: &Hash) -> Result<Hash, BlockchainError> {
+        self.load_from_disk(&self.txs_executed, tx.as_bytes())
+    }
+
+    fn set_tx_executed_in_block(&mut self, tx: &Hash, block: &Hash) -> Result<(), BlockchainError> {
+        self.insert_into_tree(&self.txs_executed, tx.as_bytes(), block.as_bytes())?;
+        Ok(())
+    }
+
+    fn remove_tx_executed(&mut self, tx: &Hash) -> Result<(), BlockchainError> {
+        self.remove_from_tree(&self.txs_executed, tx.as_bytes())?;
+        Ok(())
+    }
+
+    fn is_tx_executed_in_a_block(&self, tx: &Hash) -> Result<bool, BlockchainError> {
+        Ok(self.txs_executed.contains_key(tx.as_bytes())?)
+    }
+
+    fn is_tx_executed_in_block(&self, tx: &Hash, block: &Hash) -> Result<bool, BlockchainError> {
+        if let Ok(hash) = self.get_block_executer_for_tx(tx) {
+            if hash == *block {
+                return Ok(true)
+            }
+        }
+        Ok(false)
+    }
+
+    fn get_network(&self) -> Result<Network, BlockchainError> {
+        trace!("get network");
+        self.load_from_disk(&self.extra, NETWORK)
+    }
+
+    fn set_network(&mut self, network: &Network) -> Result<(), BlockchainError> {
+        trace!("set network to {}", network);
+        self.insert_into_tree(&self.extra, NETWORK, network.to_bytes())?;
+        Ok(())
+    }
+
+    fn has_network(&self) -> Result<bool, BlockchainError> {
+        trace!("has network");
+        Ok(self.extra.contains_key(NETWORK)?)
+    }
+
+    async fn asset_exist(&self, asset: &Hash) -> Result<bool, BlockchainError> {
+        trace!("asset exist {}", asset);
+        self.contains_data(&self.assets, &self.assets_cache, asset).await
+    }
+
+    async fn add_asset(&mut self, asset: &Hash, data: AssetData) -> Result<(), BlockchainError> {
+        trace!("add asset {} at topoheight {}", asset, data.get_topoheight());
+        // asset may be registered again at another topoheight after a reorganization
+        if !self.assets.contains_key(asset.as_bytes())? {
+            let assets_count = self.count_assets()? + 1;
+            self.insert_into_tree(&self.extra, ASSETS_COUNT, &assets_count.to_be_bytes())?;
+        }
+        self.insert_into_tree(&self.assets, asset.as_bytes(), data.to_bytes())?;
+
+        if let Some(cache) = &self.assets_cache {
+            let mut cache = cache.lock().await;
+            cache.put(asset.clone(), ());
+        }
+        Ok(())
+    }
+
+    async fn delete_assets_above_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("delete assets above topoheight {}", topoheight);
+        let mut deleted = 0;
+        for el in self.assets.iter() {
+            let (key, value) = el?;
+            let registration_topoheight = u64::from_bytes(&value)?;
+            if registration_topoheight > topoheight {
+                let asset = Hash::from_bytes(&key)?;
+                trace!("Asset {} was registered at topoheight {}, deleting", asset, registration_topoheight);
+                self.remove_from_tree(&self.assets, &key)?;
+                if let Some(cache) = &self.assets_cache {
+                    let mut cache = cache.lock().await;
+                    cache.pop(&asset);
+                }
+                deleted += 1;
+            }
+        }
+
+        self.decrease_counter(ASSETS_COUNT, deleted)?;
+        self.delete_assets_versions_above_topoheight(topoheight)
+    }
+
+    async fn get_asset_supply_at_maximum_topoheight(&self, asset: &Hash, topoheight: u64) -> Result<u64, BlockchainError> {
+        trace!("get asset {} supply at maximum topoheight {}", asset, topoheight);
+        match self.get_prefixed_version_at_maximum_topoheight(&self.assets_supply, asset.as_bytes(), topoheight)? {
+            Some((_, value)) => Ok(u64::from_bytes(&value)?),
+            None => Ok(0)
+        }
+    }
+
+    fn set_asset_supply_at_topoheight(&mut self, asset: &Hash, topoheight: u64, supply: u64) -> Result<(), BlockchainError> {
+        trace!("set asset {} supply to {} at topoheight {}", asset, supply, topoheight);
+        self.set_prefixed_version(&self.assets_supply, &self.assets_supply_changes, asset.as_bytes(), topoheight, &supply.to_be_bytes())
+    }
+
+    async fn get_asset_authority_at_maximum_topoheight(&self, asset: &Hash, topoheight: u64) -> Result<Option<PublicKey>, BlockchainError> {
+        trace!("get asset {} authority at maximum topoheight {}", asset, topoheight);
+        match self.get_prefixed_version_at_maximum_topoheight(&self.assets_authority, asset.as_bytes(), topoheight)? {
+            // an empty value means the authority was renounced
+            Some((_, value)) if !value.is_empty() => Ok(Some(PublicKey::from_bytes(&value)?)),
+            _ => Ok(None)
+        }
+    }
+
+    fn set_asset_authority_at_topoheight(&mut self, asset: &Hash, topoheight: u64, authority: Option<&PublicKey>) -> Result<(), BlockchainError> {
+        trace!("set asset {} authority at topoheight {}", asset, topoheight);
+        let value = match authority {
+            Some(key) => key.to_bytes(),
+            None => Vec::new()
+        };
+        self.set_prefixed_version(&self.assets_authority, &self.assets_authority_changes, asset.as_bytes(), topoheight, &value)
+    }
+
+    async fn get_multisig_at_maximum_topoheight(&self, key: &PublicKey, topoheight: u64) -> Result<Option<MultiSigSetup>, BlockchainError> {
+        trace!("get multisig of {} at maximum topoheight {}", key, topoheight);
+        match self.get_prefixed_version_at_maximum_topoheight(&self.multisig, key.as_bytes(), topoheight)? {
+            // an empty value means the multisig was removed
+            Some((_, value)) if !value.is_empty() => Ok(Some(MultiSigSetup::from_bytes(&value)?)),
+            _ => Ok(None)
+        }
+    }
+
+    fn set_multisig_at_topoheight(&mut self, key: &PublicKey, topoheight: u64, setup: Option<&MultiSigSetup>) -> Result<(), BlockchainError> {
+        trace!("set multisig of {} at topoheight {}", key, topoheight);
+        let value = match setup {
+            Some(setup) => setup.to_bytes(),
+            None => Vec::new()
+        };
+        self.set_prefixed_version(&self.multisig, &self.multisig_changes, key.as_bytes(), topoheight, &value)
+    }
+
+    async fn delete_multisig_above_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("delete multisig above topoheight {}", topoheight);
+        self.delete_prefixed_versions_above_topoheight(&self.multisig, &self.multisig_changes, topoheight)
+    }
+
+    async fn add_locked_balance(&mut self, key: &PublicKey, asset: &Hash, topoheight: u64, condition: &UnlockCondition, amount: u64) -> Result<(), BlockchainError> {
+        trace!("add locked balance {} of {} for {} at topoheight {} until {}", amount, asset, key, topoheight, condition);
+        let locked_key = self.get_locked_balance_key(key, asset, topoheight, condition);
+        let current: u64 = self.load_from_disk(&self.locked_balances, &locked_key).unwrap_or(0);
+        let amount = current.checked_add(amount).ok_or(BlockchainError::Overflow)?;
+        self.insert_into_tree(&self.locked_balances_changes, self.get_locked_balance_change_key(topoheight, &locked_key), [])?;
+        self.insert_into_tree(&self.locked_balances, locked_key, amount.to_be_bytes())
+    }
+
+    async fn get_locked_balances(&self, key: &PublicKey, asset: &Hash, maximum_topoheight: u64) -> Result<Vec<LockedBalance>, BlockchainError> {
+        trace!("get locked balances of {} for {} at maximum topoheight {}", asset, key, maximum_topoheight);
+        let mut prefix = [0; 64];
+        prefix[0..32].copy_from_slice(key.as_bytes());
+        prefix[32..64].copy_from_slice(asset.as_bytes());
+
+        // same conditions received at different topoheights are merged
+        let mut locked: Vec<LockedBalance> = Vec::new();
+        for el in self.locked_balances.scan_prefix(&prefix) {
+            let (key, value) = el?;
+            if u64::from_bytes(&key[64..72])? > maximum_topoheight {
+                continue;
+            }
+
+            let condition = UnlockCondition::from_bytes(&key[72..])?;
+            let amount = u64::from_bytes(&value)?;
+            match locked.iter_mut().find(|locked| *locked.get_unlock() == condition) {
+                Some(locked) => *locked = LockedBalance::new(condition, locked.get_amount().checked_add(amount).ok_or(BlockchainError::Overflow)?),
+                None => locked.push(LockedBalance::new(condition, amount))
+            };
+        }
+
+        Ok(locked)
+    }
+
+    async fn delete_locked_balances_above_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("delete locked balances above topoheight {}", topoheight);
+        // amounts can be locked until the top topoheight
+        let top_topoheight = self.get_top_topoheight_or_genesis()?;
+        for topo in topoheight + 1..=top_topoheight {
+            for el in self.locked_balances_changes.scan_prefix(&topo.to_be_bytes()) {
+                let (key, _) = el?;
+                self.remove_from_tree(&self.locked_balances, &key[8..])?;
+                self.remove_from_tree(&self.locked_balances_changes, &key)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // key is the owner and the nonce so saved txs are sorted by owner and nonce
+    // value is the time the tx was first seen followed by the tx
+    async fn add_mempool_tx(&mut self, tx: &Transaction, first_seen: u64) -> Result<(), BlockchainError> {
+        trace!("add mempool tx {} of {}", tx.get_nonce(), tx.get_owner());
+        let mut value = Vec::with_capacity(8 + tx.size());
+        value.extend_from_slice(&first_seen.to_be_bytes());
+        value.extend_from_slice(&tx.to_bytes());
+        self.insert_into_tree(&self.mempool, self.get_mempool_tx_key(tx.get_owner(), tx.get_nonce()), value)
+    }
+
+    async fn delete_mempool_tx(&mut self, owner: &PublicKey, nonce: u64) -> Result<(), BlockchainError> {
+        trace!("delete mempool tx {} of {}", nonce, owner);
+        self.remove_from_tree(&self.mempool, self.get_mempool_tx_key(owner, nonce))
+    }
+
+    async fn get_mempool_txs(&self) -> Result<Vec<(Transaction, u64)>, BlockchainError> {
+        trace!("get mempool txs");
+        let mut txs = Vec::new();
+        for el in self.mempool.iter() {
+            let (_, value) = el?;
+            let mut reader = Reader::new(&value);
+            let first_seen = reader.read_u64()?;
+            let tx = Transaction::read(&mut reader)?;
+            txs.push((tx, first_seen));
+        }
+
+        Ok(txs)
+    }
+
+    // we are forced to read from disk directly because cache may don't have all assets in memory
+    async fn get_assets(&self) -> Result<Vec<Hash>, BlockchainError> {
+        trace!("get assets");
+        let mut assets = Vec::new();
+        for e in self.assets.iter() {
+            let (key, _) = e?;
+            let mut reader = Reader::new(&key);
+            let hash = Hash::read(&mut reader)?;
+            assets.push(hash);
+        }
+
+        Ok(assets)
+    }
+
+    // count assets in storage
+    fn count_assets(&self) -> Result<u64, BlockchainError> {
+        trace!("count assets");
+        Ok(self.load_from_disk(&self.extra, ASSETS_COUNT).unwrap_or(0))
+    }
+
+    fn get_asset_data(&self, asset: &Hash) -> Result<AssetData, BlockchainError> {
+        trace!("get asset registration topoheight {}", asset);
+        self.load_from_disk(&self.assets, asset.as_bytes())
+    }
+
+    fn has_tx_blocks(&self, hash: &Hash) -> Result<bool, BlockchainError> {
+        trace!("has tx blocks {}", hash);
+        let contains = self.tx_blocks.contains_key(hash.as_bytes())?;
+        Ok(contains)
+    }
+
+    fn has_block_linked_to_tx(&self, tx: &Hash, block: &Hash) -> Result<bool, BlockchainError> {
+        trace!("has block {} linked to tx {}", block, tx);
+        Ok(self.has_tx_blocks(tx)? && self.get_blocks_for_tx(tx)?.contains(block))
+    }
+
+    fn get_blocks_for_tx(&self, hash: &Hash) -> Result<Tips, BlockchainError> {
+        trace!("get blocks for tx {}", hash);
+        self.load_from_disk(&self.tx_blocks, hash.as_bytes())
+    }
+
+    fn add_block_for_tx(&mut self, tx: &Hash, block: &Hash) -> Result<(), BlockchainError> {
+        trace!("add block {} for tx {}", block, tx);
+        let mut blocks = if self.has_tx_blocks(tx)? {
+            self.get_blocks_for_tx(tx)?
+        } else {
+            Tips::new()
+        };
+
+        if !blocks.contains(&block) {
+            blocks.insert(block.clone());
+            self.set_blocks_for_tx(tx, &blocks)?;
+        }
+
+        Ok(())
+    }
+
+    fn set_blocks_for_tx(&mut self, tx: &Hash, blocks: &HashSet<Hash>) -> Result<(), BlockchainError> {
+        trace!("set blocks ({}) for tx {} ", blocks.len(), tx);
+        self.insert_into_tree(&self.tx_blocks, tx.as_bytes(), blocks.to_bytes())?;
+        Ok(())
+    }
+
+    async fn has_balance_for(&self, key: &PublicKey, asset: &Hash) -> Result<bool, BlockchainError> {
+        trace!("has balance {} for {}", asset, key);
+        if !self.asset_exist(asset).await? {
+            return Err(BlockchainError::AssetNotFound(asset.clone()))
+        }
+
+        self.has_balance_internal(&self.get_balance_key_for(key, asset)).await
+    }
+
+    // Returns all balances that the key has
+    async fn get_assets_for(&self, key: &PublicKey) -> Result<Vec<Hash>, BlockchainError> {
+        let mut assets = Vec::new();
+        // Keys are stored like this: [public key (32 bytes)][asset hash (32 bytes)]
+        for el in self.balances.scan_prefix(key.as_bytes()) {
+            let (bytes, _) = el?;
+            let hash = Hash::from_bytes(&bytes[32..64])?;
+            assets.push(hash);
+        }
+        Ok(assets)
+    }
+
+    // returns the highest topoheight where a balance changes happened
+    async fn get_last_topoheight_for_balance(&self, key: &PublicKey, asset: &Hash) -> Result<u64, BlockchainError> {
+        trace!("get last topoheight for balance {} for {}", asset, key);
+        let key = self.get_balance_key_for(key, asset);
+        if !self.has_balance_internal(&key).await? {
+            return Ok(0)
+        }
+
+        self.get_cacheable_data_copiable(&self.balances, &None, &key).await
+    }
+
+    // set in storage the new top topoheight (the most up-to-date versioned balance)
+    fn set_last_topoheight_for_balance(&mut self, key: &PublicKey, asset: &Hash, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("set last topoheight to {} for balance {} for {}", topoheight, asset, key);
+        let key = self.get_balance_key_for(key, asset);
+        self.insert_into_tree(&self.balances, &key, &topoheight.to_be_bytes())?;
+        Ok(())
+    }
+
+    // get the balance at a specific topoheight
+    // if there is no balance change at this topoheight just return an error
+    async fn has_balance_at_exact_topoheight(&self, key: &PublicKey, asset: &Hash, topoheight: u64) -> Result<bool, BlockchainError> {
+        trace!("has balance {} for {} at exact topoheight {}", asset, key, topoheight);
+        // check first that this address has balance, if no returns
+        if !self.has_balance_for(key, asset).await? {
+            return Ok(false)
+        }
+
+        let key = self.get_versioned_balance_key(&key, &asset, topoheight);
+        self.contains_data::<_, ()>(&self.versioned_balances, &None, &key).await
+    }
+
+    // get the balance at a specific topoheight
+    // if there is no balance change at this topoheight just return an error
+    async fn get_balance_at_exact_topoheight(&self, key: &PublicKey, asset: &Hash, topoheight: u64) -> Result<VersionedBalance, BlockchainError> {
+        trace!("get balance {} for {} at exact topoheight {}", asset, key, topoheight);
+        // check first that this address has balance, if no returns
+        if !self.has_balance_at_exact_topoheight(key, asset, topoheight).await? {
+            return Err(BlockchainError::NoBalanceChanges(key.clone(), topoheight, asset.clone()))
+        }
+
+        let disk_key = self.get_versioned_balance_key(&key, &asset, topoheight);
+        self.get_cacheable_data_copiable(&self.versioned_balances, &None, &disk_key).await.map_err(|_| BlockchainError::NoBalanceChanges(key.clone(), topoheight, asset.clone()))
+    }
+
+    // delete the last topoheight registered for this key
+    // it can happens when rewinding chain and we don't have any changes (no transaction in/out) for this key
+    // because all versioned balances got deleted
+    fn delete_last_topoheight_for_balance(&mut self, key: &PublicKey, asset: &Hash) -> Result<(), BlockchainError> {
+        trace!("delete last topoheight balance {} for {}", asset, key);
+        let key = self.get_balance_key_for(key, asset);
+        self.remove_from_tree(&self.balances, &key)?;
+        Ok(())
+    }
+
+    // get the latest balance at maximum specified topoheight
+    // when a DAG re-ordering happens, we need to select the right balance and not the last one
+    // returns None if the key has no balances for this asset
+    // Maximum topoheight is inclusive
+    async fn get_balance_at_maximum_topoheight(&self, key: &PublicKey, asset: &Hash, topoheight: u64) -> Result<Option<(u64, VersionedBalance)>, BlockchainError> {
+        trace!("get balance {} for {} at maximum topoheight {}", asset, key, topoheight);
+        // check first that this address has balance for this asset, if no returns None
+        if !self.has_balance_for(key, asset).await? {
+            return Ok(None)
+        }
+
+        // Fast path: if the balance is at exact topoheight, return it
+        if self.has_balance_at_exact_topoheight(key, asset, topoheight).await? {
+            trace!("Balance version found at exact (maximum) topoheight {}", topoheight);
+            return Ok(Some((topoheight, self.get_balance_at_exact_topoheight(key, asset, topoheight).await?)))
+        }
+
+        let (topo, mut version) = self.get_last_balance(key, asset).await?;
+        trace!("Last version balance {} for {} is at topoheight {}", asset, key, topo);
+        // if it's the latest and its under the maximum topoheight
+        if topo <= topoheight {
+            trace!("Last version balance (valid) found at {} (maximum topoheight = {})", topo, topoheight);
+            return Ok(Some((topo, version)))
+        }
+
+        // otherwise, we have to go through the whole chain
+        while let Some(previous) = version.get_previous_topoheight() {
+            let previous_version = self.get_balance_at_exact_topoheight(key, asset, previous).await?;
+            trace!("previous version {}", previous);
+            if previous <= topoheight {
+                trace!("Highest version balance found at {} (maximum topoheight = {})", topo, topoheight);
+                return Ok(Some((previous, previous_version)))
+            }
+
+            if let Some(value) = previous_version.get_previous_topoheight() {
+                if value > previous {
+                    error!("FATAL ERROR: Previous topoheight ({}) should not be higher than current version ({})!", value, previous);
+                    return Err(BlockchainError::Unknown)
+                }
+            }
+            version = previous_version;
+        }
+
+        Ok(None)
+    }
+
+    // delete versioned balances for this topoheight
+    async fn delete_balance_at_topoheight(&mut self, key: &PublicKey, asset: &Hash, topoheight: u64) -> Result<VersionedBalance, BlockchainError> {
+        trace!("delete balance {} for {} at topoheight {}", asset, key, topoheight);
+        let disk_key = self.get_versioned_balance_key(&key, &asset, topoheight);
+        self.delete_cacheable_data(&self.versioned_balances, &None, &disk_key).await.map_err(|_| BlockchainError::NoBalanceChanges(key.clone(), topoheight, asset.clone()))
+    }
+
+    // returns a new versioned balance with already-set previous topoheight
+    // Topoheight is the new topoheight for the versioned balance,
+    // We create a new versioned balance by taking the previous version and setting it as previous topoheight
+    async fn get_new_versioned_balance(&self, key: &PublicKey, asset: &Hash, topoheight: u64) -> Result<VersionedBalance, BlockchainError> {
+        trace!("get new versioned balance {} for {} at {}", asset, key, topoheight);
+        if topoheight == 0 {
+            return Ok(VersionedBalance::new(0, None))
+        }
+
+        let version = match self.get_balance_at_maximum_topoheight(key, asset, topoheight - 1).await? {
+            Some((topo, mut version)) => {
+                trace!("new versioned balance (balance at maximum topoheight) topo: {}, previous: {:?}", topo, version.get_previous_topoheight());
+                // if its not at exact topoheight, then we set it as "previous topoheight"
+                if topo != topoheight {
+                    trace!("topo {} != topoheight {}, set topo {} as previous topoheight", topo, topoheight, topo);
+                    version.set_previous_topoheight(Some(topo));
+                }
+                version
+            },
+            None => VersionedBalance::new(0, None)
+        };
+
+        Ok(version)
+    }
+
+    // save a new versioned balance in storage and update the pointer
+    async fn set_balance_to(&mut self, key: &PublicKey, asset: &Hash, topoheight: u64, version: &VersionedBalance) -> Result<(), BlockchainError> {
+        trace!("set balance {} for {} to topoheight {}", asset, key, topoheight);
+        self.set_balance_at_topoheight(asset, topoheight, key, &version).await?;
+        self.set_last_topoheight_for_balance(key, asset, topoheight)?;
+        Ok(())
+    }
+
+    // get the last version of balance and returns topoheight
+    async fn get_last_balance(&self, key: &PublicKey, asset: &Hash) -> Result<(u64, VersionedBalance), BlockchainError> {
+        trace!("get last balance {} for {}", asset, key);
+        if !self.has_balance_for(key, asset).await? {
+            return Err(BlockchainError::NoBalance(key.clone()))
+        }
+
+        let topoheight = self.get_cacheable_data_copiable(&self.balances, &None, &self.get_balance_key_for(key, asset)).await?;
+        let version = self.get_balance_at_exact_topoheight(key, asset, topoheight).await?;
+        Ok((topoheight, version))
+    }
+
+    // save the asset balance at specific topoheight
+    async fn set_balance_at_topoheight(&mut self, asset: &Hash, topoheight: u64, key: &PublicKey, balance: &VersionedBalance) -> Result<(), BlockchainError> {
+        trace!("set balance {} at topoheight {} for {}", asset, topoheight, key);
+        self.update_state_tree(&balance_leaf_key(key, asset), balance.get_balance(), topoheight)?;
+        let key = self.get_versioned_balance_key(&key, &asset, topoheight);
+        self.insert_into_tree(&self.versioned_balances, key, balance.to_bytes())?;
+        Ok(())
+    }
+
+    async fn has_nonce(&self, key: &PublicKey) -> Result<bool, BlockchainError> {
+        trace!("has nonce {}", key);
+        let contains = self.nonces.contains_key(key.as_bytes())?;
+        Ok(contains)
+    }
+
+    async fn has_nonce_at_exact_topoheight(&self, key: &PublicKey, topoheight: u64) -> Result<bool, BlockchainError> {
+        trace!("has nonce {} at topoheight {}", key, topoheight);
+        let key = self.get_versioned_key(key, topoheight);
+        self.contains_data::<_, ()>(&self.versioned_nonces, &None, &key).await
+    }
+
+    async fn get_last_nonce(&self, key: &PublicKey) -> Result<(u64, VersionedNonce), BlockchainError> {
+        trace!("get last nonce {}", key);
+        if !self.has_nonce(key).await? {
+            return Err(BlockchainError::NoNonce(key.clone()))
+        }
+
+        let topoheight = self.load_from_disk(&self.nonces, key.as_bytes())?;
+        Ok((topoheight, self.get_nonce_at_exact_topoheight(key, topoheight).await?))
+    }
+
+    async fn get_nonce_at_exact_topoheight(&self, key: &PublicKey, topoheight: u64) -> Result<VersionedNonce, BlockchainError> {
+        trace!("get nonce at topoheight {} for {}", topoheight, key);
+
+        let key = self.get_versioned_key(key, topoheight);
+        self.load_from_disk(&self.versioned_nonces, &key)
+    }
+
+    // topoheight is inclusive bounds
+    async fn get_nonce_at_maximum_topoheight(&self, key: &PublicKey, topoheight: u64) -> Result<Option<(u64, VersionedNonce)>, BlockchainError> {
+        trace!("get nonce at maximum topoheight {} for {}", topoheight, key);
+        // check first that this address has nonce, if no returns None
+        if !self.has_nonce(key).await? {
+            return Ok(None)
+        }
+
+        let (topo, mut version) = self.get_last_nonce(key).await?;
+        trace!("Last version of nonce for {} is at topoheight {}", key, topo);
+        // if it's the latest and its under the maximum topoheight
+        if topo <= topoheight {
+            trace!("Last version nonce (valid) found at {} (maximum topoheight = {})", topo, topoheight);
+            return Ok(Some((topo, version)))
+        }
+
+        // otherwise, we have to go through the whole chain
+        while let Some(previous) = version.get_previous_topoheight() {
+            let previous_version = self.get_nonce_at_exact_topoheight(key, previous).await?;
+            trace!("previous nonce version is at {}", previous);
+            if previous <= topoheight {
+                trace!("Highest version nonce found at {} (maximum topoheight = {})", previous, topoheight);
+                return Ok(Some((previous, previous_version)))
+            }
+
+            if let Some(value) = previous_version.get_previous_topoheight() {
+                if value > previous {
+                    error!("FATAL ERROR: Previous topoheight ({}) should not be higher than current version ({})!", value, previous);
+                    return Err(BlockchainError::Unknown)
+                }
+            }
+            version = previous_version;
+        }
+
+        Ok(None)
+    }
+
+    async fn set_nonce_at_topoheight(&mut self, key: &PublicKey, nonce: u64, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("set nonce to {} for {} at topo {}", nonce, key, topoheight);
+        let previous_topoheight = if topoheight > 0 {
+            self.get_nonce_at_maximum_topoheight(key, topoheight - 1).await?.map(|(topo, _)| topo)
+        } else {
+            None
+        };
+
+        let versioned = VersionedNonce::new(nonce, previous_topoheight);
+        self.update_state_tree(&nonce_leaf_key(key), nonce, topoheight)?;
+        let disk_key = self.get_versioned_key(key, topoheight);
+        self.insert_into_tree(&self.versioned_nonces, &disk_key, versioned.to_bytes())?;
+
+        self.set_last_topoheight_for_nonce(key, topoheight)?;
+        Ok(())
+    }
+
+    fn set_last_topoheight_for_nonce(&mut self, key: &PublicKey, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("set last topoheight for nonce {} to {}", key, topoheight);
+        let is_new = !self.nonces.contains_key(key.as_bytes())?;
+        self.insert_into_tree(&self.nonces, key.as_bytes(), topoheight.to_be_bytes())?;
+        if is_new {
+            let count = self.count_accounts()? + 1;
+            self.insert_into_tree(&self.extra, ACCOUNTS_COUNT, &count.to_be_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    fn get_block_reward_at_topo_height(&self, topoheight: u64) -> Result<u64, BlockchainError> {
+        trace!("get block reward at topo height {}", topoheight);
+        Ok(self.load_from_disk(&self.rewards, &topoheight.to_be_bytes())?)
+    }
+
+    fn set_block_reward_at_topo_height(&mut self, topoheight: u64, reward: u64) -> Result<(), BlockchainError> {
+        trace!("set block reward to {} at topo height {}", reward, topoheight);
+        self.insert_into_tree(&self.rewards, topoheight.to_be_bytes(), &reward.to_be_bytes())?;
+        Ok(())
+    }
+
+    async fn get_transaction(&self, hash: &Hash) -> Result<Arc<Transaction>, BlockchainError> {
+        trace!("get transaction for hash {}", hash);
+        self.get_cacheable_arc_data(&self.transactions, &self.transactions_cache, hash).await
+    }
+
+    async fn get_transaction_size(&self, hash: &Hash) -> Result<usize, BlockchainError> {
+        let data = self.transactions.get(hash.as_bytes())?;
+        data.map(|data| data.len()).ok_or(BlockchainError::NotFoundOnDisk(DiskContext::LoadData))
+    }
+
+    async fn has_transaction(&self, hash: &Hash) -> Result<bool, BlockchainError> {
+        self.contains_data(&self.transactions, &self.transactions_cache, hash).await
+    }
+
+    fn count_transactions(&self) -> Result<u64, BlockchainError> {
+        trace!("count transactions");
+        Ok(self.load_from_disk(&self.extra, TXS_COUNT).unwrap_or(0))
+    }
+
+    async fn save_block(&mut self, block: Arc<BlockHeader>, txs: &Vec<Immutable<Transaction>>, difficulty: Difficulty, hash: Hash) -> Result<(), BlockchainError> {
+        debug!("Storing new {} with hash: {}, difficulty: {}", block, hash, difficulty);
+
+        // Store transactions
+        let mut txs_count = 0;
+        for (hash, tx) in block.get_transactions().iter().zip(txs) { // first save all txs, then save block
+            if !self.has_transaction(hash).await? {
+                self.insert_into_tree(&self.transactions, hash.as_bytes(), tx.to_bytes())?;
+                txs_count += 1;
+            }
+        }
+        let current_txs_count = self.count_transactions()?;
+        self.insert_into_tree(&self.extra, TXS_COUNT, &(current_txs_count + txs_count).to_be_bytes())?;
+
+        // Store block header
+        self.insert_into_tree(&self.blocks, hash.as_bytes(), block.to_bytes())?;
+        let blocks_count = self.count_blocks()? + 1;
+        self.insert_into_tree(&self.extra, BLOCKS_COUNT, &blocks_count.to_be_bytes())?;
+
+        // Store difficulty
+        self.insert_into_tree(&self.difficulty, hash.as_bytes(), &difficulty.to_be_bytes())?;
+
+        self.add_block_hash_at_height(hash.clone(), block.get_height()).await?;
+
+        if let Some(cache) = &self.blocks_cache {
+            cache.lock().await.put(hash, block);
+        }
+
+        Ok(())
+    }
+
+    async fn pop_blocks(&mut self, mut height: u64, mut topoheight: u64, count: u64) -> Result<(u64, u64, Vec<(Hash, Arc<Transaction>)>), BlockchainError> {
+        trace!("pop blocks from height: {}, topoheight: {}, count: {}", height, topoheight, count);
+        if height < count as u64 { // also prevent removing genesis block
+            return Err(BlockchainError::NotEnoughBlocks);
+        }
+
+        // search the lowest topo height available based on count + 1
+        // (last lowest topo height accepted)
+        let mut lowest_topo = topoheight - count;
+        trace!("Lowest topoheight for rewind: {}", lowest_topo);
+
+        let pruned_topoheight = self.get_pruned_topoheight()?.unwrap_or(0);
+        if pruned_topoheight != 0 {
+            let safety_pruned_topoheight = pruned_topoheight + PRUNE_SAFETY_LIMIT;
+            if lowest_topo <= safety_pruned_topoheight {
+                warn!("Pruned topoheight is {}, lowest topoheight is {}, rewind only until {}", pruned_topoheight, lowest_topo, safety_pruned_topoheight);
+                lowest_topo = safety_pruned_topoheight;
+            }
+        }
+
+        // new TIPS for chain
+        let mut tips = self.get_tips().await?;
+        // all txs to be rewinded
+        let mut txs = Vec::new();
+        let mut done = 0;
+        'main: loop {
+            // stop rewinding if its genesis block or if we reached the lowest topo
+            if topoheight <= lowest_topo || topoheight == 0 || height == 0 { // prevent removing genesis block
+                trace!("Done: {done}, count: {count}, height: {height}, topoheight: {topoheight}");
+                break 'main;
+            }
+
+            // Delete the hash at topoheight
+            let (hash, block, block_txs) = self.delete_block_at_topoheight(topoheight).await?;
+            txs.extend(block_txs);
+
+            // generate new tips
+            trace!("Removing {} from {} tips", hash, tips.len());
+            tips.remove(&hash);
+ 
+            for hash in block.get_tips() {
+                trace!("Adding {} to {} tips", hash, tips.len());
+                tips.insert(hash.clone());
+            }
+
+            topoheight -= 1;
+            // height of old block become new height
+            if block.get_height() < height {
+                height = block.get_height();
+            }
+            done += 1;
+        }
+
+        debug!("Blocks processed {}, new topoheight: {}, new height: {}, tips: {}", done, topoheight, height, tips.len());
+
+        // clean all assets
+        self.delete_assets_above_topoheight(topoheight).await?;
+        self.delete_prefixed_versions_above_topoheight(&self.multisig, &self.multisig_changes, topoheight)?;
+        self.delete_locked_balances_above_topoheight(topoheight).await?;
+
+        // now let's process nonces versions
+        // we set the new highest topoheight to the highest found under the new topoheight
+        for el in self.nonces.iter() {
+            let (key, value) = el?;
+            let highest_topoheight = u64::from_bytes(&value)?;
+            if highest_topoheight > topoheight {
+                self.remove_from_tree(&self.nonces, &key)?;
+                // find the first version which is under topoheight
+                let pkey = PublicKey::from_bytes(&key)?;
+                let mut version = self.get_nonce_at_exact_topoheight(&pkey, highest_topoheight).await?;
+                let mut found = false;
+                while let Some(previous_topoheight) = version.get_previous_topoheight() {
+                    if previous_topoheight <= topoheight {
+                        // we find the new highest version which is under new topoheight
+                        trace!("New highest version nonce for {} is at topoheight {}", pkey, previous_topoheight);
+                        self.insert_into_tree(&self.nonces, &key, &previous_topoheight.to_be_bytes())?;
+                        found = true;
+                        break;
+                    }
+
+                    // keep searching
+                    version = self.get_nonce_at_exact_topoheight(&pkey, previous_topoheight).await?;
+                }
+
+                // the account didn't exist before the new topoheight
+                if !found {
+                    self.decrease_counter(ACCOUNTS_COUNT, 1)?;
+                }
+            } else {
+                // nothing to do as its under the rewinded topoheight
+            }
+        }
+
+        // do balances too
+        for el in self.balances.iter() {
+            let (key, value) = el?;
+            let highest_topoheight = u64::from_bytes(&value)?;
+            if highest_topoheight > topoheight {
+                // find the first version which is under topoheight
+                let pkey = PublicKey::from_bytes(&key[0..32])?;
+                let asset = Hash::from_bytes(&key[32..64])?;
+                let mut delete = true;
+                let mut version = self.get_balance_at_exact_topoheight(&pkey, &asset, highest_topoheight).await?;
+                while let Some(previous_topoheight) = version.get_previous_topoheight() {
+                    if previous_topoheight <= topoheight {
+                        // we find the new highest version which is under new topoheight
+                        trace!("New highest version balance for {} is at topoheight {} with asset {}", pkey, previous_topoheight, asset);
+                        self.insert_into_tree(&self.balances, &key, &previous_topoheight.to_be_bytes())?;
+                        delete = false;
+                        break;
+                    }
+
+                    // keep searching
+                    version = self.get_balance_at_exact_topoheight(&pkey, &asset, previous_topoheight).await?;
+                }
+
+                if delete {
+                    self.remove_from_tree(&self.balances, &key)?;
+                }
+            } else {
+                // nothing to do as its under the rewinded topoheight
+            }
+        }
+
+        // now delete all versioned balances and nonces above the new topoheight
+        self.delete_versioned_balances_above_topoheight(topoheight).await?;
+        self.delete_versioned_nonces_above_topoheight(topoheight).await?;
+        self.delete_state_tree_above_topoheight(topoheight).await?;
+        self.delete_account_history_above_topoheight(topoheight).await?;
+
+        // Clear all caches to not have old data after rewind
+        self.clear_caches().await;
+
+        // store the new tips and topo topoheight
+        self.store_tips(&tips)?;
+        self.set_top_topoheight(topoheight)?;
+        self.set_top_height(height)?;
+
+        Ok((height, topoheight, txs))
+    }
+
+    fn has_blocks(&self) -> bool {
+        trace!("has blocks");
+        !self.blocks.is_empty().unwrap_or(true)
+    }
+
+    fn count_blocks(&self) -> Result<u64, BlockchainError> {
+        trace!("count blocks");
+        Ok(self.load_from_disk(&self.extra, BLOCKS_COUNT).unwrap_or(0))
+    }
+
+    async fn has_block(&self, hash: &Hash) -> Result<bool, BlockchainError> {
+        trace!("has block {}", hash);
+        self.contains_data(&self.blocks, &self.blocks_cache, hash).await
+    }
+
+    async fn get_block_header_at_topoheight(&self, topoheight: u64) -> Result<(Hash, Arc<BlockHeader>), BlockchainError> {
+        trace!("get block at topoheight: {}", topoheight);
+        let hash = self.get_hash_at_topo_height(topoheight).await?;
+        let block = self.get_block_header_by_hash(&hash).await?;
+        Ok((hash, block))
+    }
+
+    async fn get_block(&self, hash: &Hash) -> Result<Block, BlockchainError> {
+        trace!("get block {}", hash);
+        let block = self.get_block_header_by_hash(hash).await?;
+        let mut transactions = Vec::new();
+        for tx in block.get_transactions() {
+            let transaction = self.get_transaction(tx).await?;
+            transactions.push(Immutable::Arc(transaction));
+        }
+
+        let block = Block::new(Immutable::Arc(block), transactions);
+        Ok(block)
+    }
+
+    async fn get_top_block_hash(&self) -> Result<Hash, BlockchainError> {
+        trace!("get top block hash");
+        self.get_hash_at_topo_height(self.get_top_topoheight()?).await
+    }
+
+    fn get_top_topoheight(&self) -> Result<u64, BlockchainError> {
+        trace!("get top topoheight");
+        self.load_from_disk(&self.extra, TOP_TOPO_HEIGHT)
+    }
+
+    fn set_top_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("set new top topoheight at {}", topoheight);
+        self.insert_into_tree(&self.extra, TOP_TOPO_HEIGHT, &topoheight.to_be_bytes())?;
+        Ok(())
+    }
+
+    fn get_top_height(&self) -> Result<u64, BlockchainError> {
+        trace!("get top height");
+        self.load_from_disk(&self.extra, TOP_HEIGHT)
+    }
+
+    fn set_top_height(&mut self, height: u64) -> Result<(), BlockchainError> {
+        trace!("set new top height at {}", height);
+        self.insert_into_tree(&self.extra, TOP_HEIGHT, &height.to_be_bytes())?;
+        Ok(())
+    }
+
+    async fn get_top_block_header(&self) -> Result<(Arc<BlockHeader>, Hash), BlockchainError> {
+        trace!("get top block header");
+        let hash = self.get_top_block_hash().await?;
+        Ok((self.get_block_header_by_hash(&hash).await?, hash))
+    }
+
+    async fn get_top_block(&self) -> Result<Block, BlockchainError> {
+        trace!("get top block");
+        let (block, _) = self.get_top_block_header().await?;
+        let mut transactions = Vec::new();
+        for tx in block.get_transactions() {
+            let transaction = self.get_transaction(tx).await?;
+            transactions.push(Immutable::Arc(transaction));
+        }
+
+        let block = Block::new(Immutable::Arc(block), transactions);
+        Ok(block)
+    }
+
+    async fn get_tips(&self) -> Result<Tips, BlockchainError> {
+        trace!("get tips");
+        Ok(self.tips_cache.clone())
+    }
+
+    fn store_tips(&mut self, tips: &Tips) -> Result<(), BlockchainError> {
+        trace!("Saving {} Tips", tips.len());
+        self.insert_into_tree(&self.extra, TIPS, tips.to_bytes())?;
+        self.tips_cache = tips.clone();
+        Ok(())
+    }
+
+    async fn has_blocks_at_height(&self, height: u64) -> Result<bool, BlockchainError> {
+        trace!("get blocks at height {}", height);
+        Ok(self.blocks_at_height.contains_key(&height.to_be_bytes())?)
+    }
+
+    async fn set_blocks_at_height(&self, tips: Tips, height: u64) -> Result<(), BlockchainError> {
+        trace!("set {} blocks at height {}", tips.len(), height);
+        self.insert_into_tree(&self.blocks_at_height, height.to_be_bytes(), tips.to_bytes())?;
+        Ok(())
+    }
+
+    // returns all blocks hash at specified height
+    async fn get_blocks_at_height(&self, height: u64) -> Result<Tips, BlockchainError> {
+        trace!("get blocks at height {}", height);
+        self.load_from_disk(&self.blocks_at_height, &height.to_be_bytes())
+    }
+
+    async fn add_block_hash_at_height(&mut self, hash: Hash, height: u64) -> Result<(), BlockchainError> {
+        trace!("add block {} at height {}", hash, height);
+        let mut tips = if self.has_blocks_at_height(height).await? {
+            let hashes = self.get_blocks_at_height(height).await?;
+            trace!("Found {} blocks at this height", hashes.len());
+            hashes
+        } else {
+            trace!("No blocks found at this height");
+            Tips::new()
+        };
+
+        tips.insert(hash);
+        self.set_blocks_at_height(tips, height).await
+    }
+
+    async fn remove_block_hash_at_height(&self, hash: &Hash, height: u64) -> Result<(), BlockchainError> {
+        trace!("remove block {} at height {}", hash, height);
+        let mut tips = self.get_blocks_at_height(height).await?;
+        tips.remove(hash);
+
+        // Delete the height if there is no blocks present anymore
+        if tips.is_empty() {
+            self.remove_from_tree(&self.blocks_at_height, &height.to_be_bytes())?;
+        } else {
+            self.set_blocks_at_height(tips, height).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn set_topo_height_for_block(&mut self, hash: &Hash, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("set topo height for {} at {}", hash, topoheight);
+        self.insert_into_tree(&self.topo_by_hash, hash.as_bytes(), topoheight.to_bytes())?;
+        self.insert_into_tree(&self.hash_at_topo, topoheight.to_be_bytes(), hash.as_bytes())?;
+
+        // save in cache
+        if let Some(cache) = &self.topo_by_hash_cache {
+            let mut topo = cache.lock().await;
+            topo.put(hash.clone(), topoheight);
+        }
+
+        if let Some(cache) = &self.hash_at_topo_cache {
+            let mut hash_at_topo = cache.lock().await;
+            hash_at_topo.put(topoheight, hash.clone());
+        }
+
+        Ok(())
+    }
+
+    async fn is_block_topological_ordered(&self, hash: &Hash) -> bool {
+        trace!("is block topological ordered: {}", hash);
+        let topoheight = match self.get_topo_height_for_hash(&hash).await {
+            Ok(topoheight) => topoheight,
+            Err(e) => {
+                trace!("Error while checking if block {} is ordered: {}", hash, e);
+                return false
+            }
+        };
+
+        let hash_at_topo = match self.get_hash_at_topo_height(topoheight).await {
+            Ok(hash_at_topo) => hash_at_topo,
+            Err(e) => {
+                trace!("Error while checking if a block hash is ordered at topo {}: {}", topoheight, e);
+                return false
+            }
+        };
+        hash_at_topo == *hash
+    }
+
+    async fn get_topo_height_for_hash(&self, hash: &Hash) -> Result<u64, BlockchainError> {
+        trace!("get topoheight for hash: {}", hash);
+        self.get_cacheable_data_copiable(&self.topo_by_hash, &self.topo_by_hash_cache, &hash).await
+    }
+
+    async fn get_hash_at_topo_height(&self, topoheight: u64) -> Result<Hash, BlockchainError> {
+        trace!("get hash at topoheight: {}", topoheight);
+        let hash = if let Some(cache) = &self.hash_at_topo_cache {
+            let mut hash_at_topo = cache.lock().await;
+            if let Some(value) = hash_at_topo.get(&topoheight) {
+                return Ok(value.clone())
+            }
+            let hash: Hash = self.load_from_disk(&self.hash_at_topo, &topoheight.to_be_bytes())?;
+            hash_at_topo.put(topoheight, hash.clone());
+            hash
+        } else {
+            self.load_from_disk(&self.hash_at_topo, &topoheight.to_be_bytes())?
+        };
+
+        Ok(hash)
+    }
+
+    async fn get_supply_at_topo_height(&self, topoheight: u64) -> Result<u64, BlockchainError> {
+        trace!("get supply at topo height {}", topoheight);
+        self.load_from_disk(&self.supply, &topoheight.to_be_bytes())
+    }
+
+    fn set_supply_at_topo_height(&mut self, topoheight: u64, supply: u64) -> Result<(), BlockchainError> {
+        trace!("set supply at topo height {}", topoheight);
+        self.insert_into_tree(&self.supply, topoheight.to_be_bytes(), &supply.to_be_bytes())?;
+        Ok(())
+    }
+
+    async fn set_cumulative_difficulty_for_block_hash(&mut self, hash: &Hash, cumulative_difficulty: Difficulty) -> Result<(), BlockchainError> {
+        trace!("set cumulative difficulty for hash {}", hash);
+        self.insert_into_tree(&self.cumulative_difficulty, hash.as_bytes(), cumulative_difficulty.to_bytes())?;
+        Ok(())
+    }
+
+    // Returns the current size on disk in bytes
+    async fn get_size_on_disk(&self) -> Result<u64, BlockchainError> {
+        self.backend.size_on_disk()
+    }
+
+    fn start_batch(&mut self) -> Result<(), BlockchainError> {
+        trace!("start batch");
+        if self.batch || !self.journal.is_empty()? {
+            return Err(BlockchainError::BatchAlreadyInProgress)
+        }
+        self.batch = true;
+        Ok(())
+    }
+
+    fn commit_batch(&mut self) -> Result<(), BlockchainError> {
+        trace!("commit batch");
+        self.journal.clear()?;
+        self.batch = false;
+        Ok(())
+    }
+
+    async fn rollback_batch(&mut self) -> Result<(), BlockchainError> {
+        trace!("rollback batch");
+        if self.restore_journal()? > 0 {
+            self.backend.flush().await?;
+        }
+
+        // Clear all caches and reload metadata to not have data from the batch
+        self.clear_caches().await;
+        self.load_cached_metadata();
+
+        Ok(())
+    }
+
+    fn has_pending_batch(&self) -> Result<bool, BlockchainError> {
+        trace!("has pending batch");
+        Ok(!self.journal.is_empty()?)
+    }
+
+    fn get_db_version(&self) -> Result<u64, BlockchainError> {
+        trace!("get db version");
+        // databases created before schema versioning have no version
+        Ok(self.load_from_disk(&self.extra, DB_VERSION_KEY).unwrap_or(0))
+    }
+
+    async fn migrate_db(&mut self, from_version: Option<u64>, dry_run: bool) -> Result<Vec<MigrationReport>, BlockchainError> {
+        trace!("migrate db from {:?} (dry run: {})", from_version, dry_run);
+        let res = self.apply_migrations(from_version, dry_run);
+        if !dry_run {
+            // cached data may be outdated
+            self.clear_caches().await;
+            self.load_cached_metadata();
+            self.backend.flush().await?;
+        }
+        res
+    }
+
+    async fn check_db(&mut self, repair: bool) -> Result<Vec<IntegrityReport>, BlockchainError> {
+        trace!("check db (repair: {})", repair);
+        let checks = get_integrity_checks::<B>();
+        let total = checks.len();
+        let mut reports = Vec::with_capacity(total);
+        for (i, check) in checks.into_iter().enumerate() {
+            info!("Integrity check {}/{}: {}", i + 1, total, check.name);
+            let mut report = IntegrityReport::new(check.name);
+            if repair {
+                // derived trees are rewritten in their own batch
+                self.start_batch()?;
+                if let Err(e) = (check.run)(self, true, &mut report) {
+                    error!("Error while repairing database ({}): {}", check.name, e);
+                    self.restore_journal()?;
+                    return Err(e)
+                }
+                self.commit_batch()?;
+            } else {
+                (check.run)(self, false, &mut report)?;
+            }
+
+            info!("{} entries checked, {} errors found, {} repaired", report.checked, report.errors.len(), report.repaired);
+            reports.push(report);
+        }
+
+        if repair {
+            // cached data may be outdated
+            self.clear_caches().await;
+            self.backend.flush().await?;
+        }
+
+        Ok(reports)
+    }
+
+    async fn stop(&mut self) -> Result<(), BlockchainError> {
+        info!("Flushing database");
+        self.backend.flush().await?;
+        info!("Database flushed");
+        Ok(())
+    }
+}
\ No newline at end of file
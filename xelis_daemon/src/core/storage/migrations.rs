@@ -0,0 +1,70 @@
+use crate::core::error::BlockchainError;
+use super::{backend::Backend, KeyValueStorage};
+
+// Schema version of the database written by this version of the daemon
+// It must be equal to the version of the last migration registered
+pub const DB_VERSION: u64 = 4;
+
+// Summary of a migration applied (or checked in dry run mode)
+pub struct MigrationReport {
+    pub version: u64,
+    pub description: &'static str,
+    // number of entries changed, or that would be changed in dry run mode
+    pub changes: u64
+}
+
+// A migration upgrades the database from version - 1 to version
+// It must be idempotent as it can be applied again using the migrate_db command
+pub(super) struct Migration<B: Backend> {
+    pub version: u64,
+    pub description: &'static str,
+    // second parameter is the dry run mode, nothing should be written if set
+    pub apply: fn(&KeyValueStorage<B>, bool) -> Result<u64, BlockchainError>
+}
+
+// All migrations, ordered by version
+pub(super) fn get_migrations<B: Backend>() -> Vec<Migration<B>> {
+    vec![
+        Migration {
+            version: 1,
+            description: "Rebuild accounts, transactions, assets and blocks counters",
+            apply: rebuild_counters
+        },
+        Migration {
+            version: 2,
+            description: "Build the state tree from all versions of balances and nonces",
+            apply: rebuild_state_tree
+        },
+        Migration {
+            version: 3,
+            description: "Include the asset in the keys of versioned balances",
+            apply: migrate_versioned_balance_keys
+        },
+        Migration {
+            version: 4,
+            description: "Add the metadata of custom assets to the assets data",
+            apply: migrate_assets_metadata
+        }
+    ]
+}
+
+// Counters were not updated when blocks were popped or pruned
+fn rebuild_counters<B: Backend>(storage: &KeyValueStorage<B>, dry_run: bool) -> Result<u64, BlockchainError> {
+    storage.rebuild_counters(dry_run)
+}
+
+// State tree was added after the chain started
+fn rebuild_state_tree<B: Backend>(storage: &KeyValueStorage<B>, dry_run: bool) -> Result<u64, BlockchainError> {
+    storage.rebuild_state_tree(dry_run)
+}
+
+
+// Balances of different assets changed at the same topoheight were sharing the same versioned key
+fn migrate_versioned_balance_keys<B: Backend>(storage: &KeyValueStorage<B>, dry_run: bool) -> Result<u64, BlockchainError> {
+    storage.migrate_versioned_balance_keys(dry_run)
+}
+
+// Assets registered by a transaction have a name, ticker and maximum supply
+fn migrate_assets_metadata<B: Backend>(storage: &KeyValueStorage<B>, dry_run: bool) -> Result<u64, BlockchainError> {
+    storage.migrate_assets_metadata(dry_run)
+}
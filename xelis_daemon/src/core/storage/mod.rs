@@ -1,21 +1,67 @@
-mod sled;
-pub use self::sled::SledStorage;
+mod backend;
+mod key_value;
+mod migrations;
+mod integrity;
+#[cfg(test)]
+mod tests;
+
+pub use self::key_value::KeyValueStorage;
+pub use self::migrations::{MigrationReport, DB_VERSION};
+pub use self::integrity::IntegrityReport;
+pub use self::backend::{Backend, Tree, SledBackend, MemoryBackend};
+#[cfg(feature = "rocksdb")]
+pub use self::backend::RocksBackend;
 
 use std::{collections::HashSet, sync::Arc};
 use async_trait::async_trait;
 use indexmap::IndexSet;
 use xelis_common::{
-    crypto::{key::PublicKey, hash::Hash},
-    transaction::Transaction,
-    block::{Block, BlockHeader, Difficulty}, account::{VersionedBalance, VersionedNonce},
+    crypto::{key::PublicKey, hash::Hash, merkle::StateProof},
+    transaction::{Transaction, MultiSigSetup, UnlockCondition},
+    block::{Block, BlockHeader, Difficulty}, account::{VersionedBalance, VersionedNonce, LockedBalance},
     immutable::Immutable,
     network::Network, asset::{AssetData, AssetWithData},
+    api::daemon::AccountHistoryDirection
 };
 
 use crate::core::error::BlockchainError;
 
 pub type Tips = HashSet<Hash>;
 
+pub type SledStorage = KeyValueStorage<SledBackend>;
+// Storage fully kept in RAM, useful for tests and the simulator
+pub type MemoryStorage = KeyValueStorage<MemoryBackend>;
+#[cfg(feature = "rocksdb")]
+pub type RocksStorage = KeyValueStorage<RocksBackend>;
+
+// Database engine used by the daemon to store the chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(clap::ArgEnum)]
+pub enum StorageBackend {
+    Sled,
+    Rocksdb,
+    Memory
+}
+
+impl SledStorage {
+    pub fn new(dir_path: String, cache_size: Option<usize>, network: Network) -> Result<Self, BlockchainError> {
+        Self::with_backend(SledBackend::open(dir_path)?, cache_size, network)
+    }
+}
+
+impl MemoryStorage {
+    pub fn new(cache_size: Option<usize>, network: Network) -> Result<Self, BlockchainError> {
+        Self::with_backend(MemoryBackend::new(), cache_size, network)
+    }
+}
+
+#[cfg(feature = "rocksdb")]
+impl RocksStorage {
+    pub fn new(dir_path: String, cache_size: Option<usize>, network: Network) -> Result<Self, BlockchainError> {
+        Self::with_backend(RocksBackend::open(dir_path)?, cache_size, network)
+    }
+}
+
 // this trait is useful for P2p to check itself the validty of a chain
 #[async_trait]
 pub trait DifficultyProvider {
@@ -50,6 +96,27 @@ pub trait Storage: DifficultyProvider + Sync + Send + 'static {
     // same as above but for nonces
     async fn create_snapshot_nonces_at_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError>;
 
+    // root of the state tree (balances and nonces) at topoheight
+    async fn get_state_root_at_topoheight(&self, topoheight: u64) -> Result<Hash, BlockchainError>;
+    // check that the state tree is available at topoheight, it's not the case below a pruned or fast synced topoheight
+    async fn has_state_root_at_topoheight(&self, topoheight: u64) -> Result<bool, BlockchainError>;
+    // siblings of the path to the leaf if it is in the state tree at topoheight
+    async fn get_state_proof_at_topoheight(&self, leaf_key: &Hash, topoheight: u64) -> Result<Option<StateProof>, BlockchainError>;
+    // delete all state tree nodes written above topoheight
+    async fn delete_state_tree_above_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError>;
+    // delete all state tree nodes versions not needed anymore to compute the root at topoheight or above
+    async fn delete_state_tree_below_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError>;
+
+    // is the account history index built and maintained
+    fn is_account_history_indexed(&self) -> Result<bool, BlockchainError>;
+    // mark the index as complete, or delete all its entries
+    fn set_account_history_indexed(&mut self, indexed: bool) -> Result<(), BlockchainError>;
+    async fn add_account_history_entry(&mut self, key: &PublicKey, topoheight: u64, hash: &Hash, direction: AccountHistoryDirection) -> Result<(), BlockchainError>;
+    // entries between both topoheights (inclusive) from the newest to the oldest
+    async fn get_account_history_entries(&self, key: &PublicKey, minimum_topoheight: u64, maximum_topoheight: u64, skip: usize, maximum: usize) -> Result<Vec<(u64, Hash, AccountHistoryDirection)>, BlockchainError>;
+    async fn delete_account_history_above_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError>;
+    async fn delete_account_history_below_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError>;
+
     async fn get_partial_assets(&self, maximum: usize, skip: usize, minimum_topoheight: u64, maximum_topoheight: u64) -> Result<IndexSet<AssetWithData>, BlockchainError>;
     async fn get_partial_keys(&self, maximum: usize, skip: usize, minimum_topoheight: u64, maximum_topoheight: u64) -> Result<IndexSet<PublicKey>, BlockchainError>;
     async fn has_key_updated_in_range(&self, key: &PublicKey, minimum_topoheight: u64, maximum_topoheight: u64) -> Result<bool, BlockchainError>;
@@ -70,6 +137,33 @@ pub trait Storage: DifficultyProvider + Sync + Send + 'static {
 
     async fn asset_exist(&self, asset: &Hash) -> Result<bool, BlockchainError>;
     async fn add_asset(&mut self, asset: &Hash, data: AssetData) -> Result<(), BlockchainError>;
+    // delete all assets registered above topoheight
+    async fn delete_assets_above_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError>;
+    // supply of a custom asset at topoheight (inclusive), zero if nothing was created
+    async fn get_asset_supply_at_maximum_topoheight(&self, asset: &Hash, topoheight: u64) -> Result<u64, BlockchainError>;
+    fn set_asset_supply_at_topoheight(&mut self, asset: &Hash, topoheight: u64, supply: u64) -> Result<(), BlockchainError>;
+    // key allowed to mint a custom asset at topoheight (inclusive), none if renounced or never set
+    async fn get_asset_authority_at_maximum_topoheight(&self, asset: &Hash, topoheight: u64) -> Result<Option<PublicKey>, BlockchainError>;
+    fn set_asset_authority_at_topoheight(&mut self, asset: &Hash, topoheight: u64, authority: Option<&PublicKey>) -> Result<(), BlockchainError>;
+    // participants and threshold required by an account at topoheight (inclusive), none if removed or never set
+    async fn get_multisig_at_maximum_topoheight(&self, key: &PublicKey, topoheight: u64) -> Result<Option<MultiSigSetup>, BlockchainError>;
+    fn set_multisig_at_topoheight(&mut self, key: &PublicKey, topoheight: u64, setup: Option<&MultiSigSetup>) -> Result<(), BlockchainError>;
+    // delete all multisig changes above topoheight
+    async fn delete_multisig_above_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError>;
+    // lock an amount received at topoheight until the condition is reached, amounts with the same condition are added
+    async fn add_locked_balance(&mut self, key: &PublicKey, asset: &Hash, topoheight: u64, condition: &UnlockCondition, amount: u64) -> Result<(), BlockchainError>;
+    // all amounts locked for an account received at or below topoheight, with their condition even if already reached
+    async fn get_locked_balances(&self, key: &PublicKey, asset: &Hash, maximum_topoheight: u64) -> Result<Vec<LockedBalance>, BlockchainError>;
+    // delete all amounts locked above topoheight
+    async fn delete_locked_balances_above_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError>;
+
+    // save a tx of the mempool with the time it was first seen, it is kept across restarts
+    // a saved tx with the same owner and nonce is replaced
+    async fn add_mempool_tx(&mut self, tx: &Transaction, first_seen: u64) -> Result<(), BlockchainError>;
+    // delete the saved tx of the mempool with this owner and nonce
+    async fn delete_mempool_tx(&mut self, owner: &PublicKey, nonce: u64) -> Result<(), BlockchainError>;
+    // txs of the saved mempool with the time they were first seen, sorted by owner and nonce
+    async fn get_mempool_txs(&self) -> Result<Vec<(Transaction, u64)>, BlockchainError>;
     async fn get_assets(&self) -> Result<Vec<Hash>, BlockchainError>;
     fn count_assets(&self) -> Result<u64, BlockchainError>;
 
@@ -152,7 +246,24 @@ pub trait Storage: DifficultyProvider + Sync + Send + 'static {
 
     async fn get_size_on_disk(&self) -> Result<u64, BlockchainError>;
 
-    async fn stop(&mut self) -> Result<(), BlockchainError>;
+    // Start a batch: all changes made until it is committed can be rolled back,
+    // even after a crash using a new instance of the storage
+    fn start_batch(&mut self) -> Result<(), BlockchainError>;
+    // Apply definitively all the changes made since the batch started
+    fn commit_batch(&mut self) -> Result<(), BlockchainError>;
+    // Revert all the changes made since the batch started, or from an incomplete batch left on disk
+    async fn rollback_batch(&mut self) -> Result<(), BlockchainError>;
+    // Returns true if changes from a batch are not committed yet
+    fn has_pending_batch(&self) -> Result<bool, BlockchainError>;
 
-    //async fn execute_db_transaction<'a>(&mut self, transaction: DatabaseTransaction<'a, Self>) -> Result<(), BlockchainError>;
+    // Returns the schema version of the database
+    fn get_db_version(&self) -> Result<u64, BlockchainError>;
+    // Apply all migrations above from_version (current schema version by default)
+    // In dry run mode, nothing is written and only the changes are reported
+    async fn migrate_db(&mut self, from_version: Option<u64>, dry_run: bool) -> Result<Vec<MigrationReport>, BlockchainError>;
+    // Verify the consistency of all trees and report each inconsistency found
+    // In repair mode, derived data is rebuilt from the data it is computed from
+    async fn check_db(&mut self, repair: bool) -> Result<Vec<IntegrityReport>, BlockchainError>;
+
+    async fn stop(&mut self) -> Result<(), BlockchainError>;
 }
\ No newline at end of file
@@ -0,0 +1,568 @@
+// Conformance suite shared by all storage implementations
+// Each backend must behave exactly the same way for the same sequence of calls
+
+use std::{sync::Arc, path::PathBuf, time::Duration};
+use xelis_common::{
+    crypto::{
+        key::{KeyPair, PublicKey},
+        hash::{Hash, Hashable},
+        merkle::{balance_leaf_key, nonce_leaf_key, hash_leaf, verify_balance_proof, verify_nonce_proof, EMPTY_STATE_ROOT}
+    },
+    block::{BlockHeader, EXTRA_NONCE_SIZE},
+    account::{VersionedBalance, LockedBalance},
+    asset::AssetData,
+    network::Network,
+    transaction::{Transaction, TransactionType, MultiSigSetup, UnlockCondition},
+    config::XELIS_ASSET,
+    api::daemon::AccountHistoryDirection
+};
+use crate::core::error::BlockchainError;
+use super::{Storage, SledStorage, MemoryStorage, Tips, DB_VERSION};
+
+// Create a new empty directory for a test database
+fn temp_dir_path(name: &str) -> String {
+    let mut path: PathBuf = std::env::temp_dir();
+    path.push(format!("xelis-storage-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    path.to_string_lossy().into_owned()
+}
+
+fn create_block(height: u64, tips: Vec<Hash>) -> (Hash, Arc<BlockHeader>) {
+    let miner = KeyPair::new().get_public_key().clone();
+    let header = BlockHeader::new(0, height, height as u128, tips, [0u8; EXTRA_NONCE_SIZE], miner, Vec::new());
+    (header.hash(), Arc::new(header))
+}
+
+async fn test_network<S: Storage>(storage: &mut S) {
+    assert!(storage.has_network().unwrap());
+    assert_eq!(storage.get_network().unwrap(), Network::Dev);
+}
+
+async fn test_blocks<S: Storage>(storage: &mut S) {
+    assert!(!storage.has_blocks());
+    assert_eq!(storage.count_blocks().unwrap(), 0);
+
+    let (genesis_hash, genesis) = create_block(0, Vec::new());
+    storage.save_block(genesis.clone(), &Vec::new(), 1, genesis_hash.clone()).await.unwrap();
+    storage.set_topo_height_for_block(&genesis_hash, 0).await.unwrap();
+
+    let (hash, block) = create_block(1, vec![genesis_hash.clone()]);
+    storage.save_block(block.clone(), &Vec::new(), 2, hash.clone()).await.unwrap();
+    storage.set_topo_height_for_block(&hash, 1).await.unwrap();
+
+    assert!(storage.has_blocks());
+    assert_eq!(storage.count_blocks().unwrap(), 2);
+    assert!(storage.has_block(&hash).await.unwrap());
+    assert!(!storage.has_block(&Hash::new([1u8; 32])).await.unwrap());
+
+    assert_eq!(storage.get_block_header_by_hash(&hash).await.unwrap().hash(), hash);
+    assert_eq!(storage.get_block(&hash).await.unwrap().get_height(), 1);
+    assert_eq!(storage.get_difficulty_for_block_hash(&hash).await.unwrap(), 2);
+    assert_eq!(storage.get_height_for_block_hash(&hash).await.unwrap(), 1);
+    assert_eq!(*storage.get_past_blocks_for_block_hash(&hash).await.unwrap(), vec![genesis_hash.clone()]);
+
+    assert!(storage.has_blocks_at_height(1).await.unwrap());
+    assert!(!storage.has_blocks_at_height(2).await.unwrap());
+    assert!(storage.get_blocks_at_height(0).await.unwrap().contains(&genesis_hash));
+
+    assert!(storage.is_block_topological_ordered(&hash).await);
+    assert_eq!(storage.get_topo_height_for_hash(&hash).await.unwrap(), 1);
+    assert_eq!(storage.get_hash_at_topo_height(0).await.unwrap(), genesis_hash);
+    assert_eq!(storage.get_block_header_at_topoheight(1).await.unwrap().0, hash);
+
+    storage.set_cumulative_difficulty_for_block_hash(&hash, 3).await.unwrap();
+    assert_eq!(storage.get_cumulative_difficulty_for_block_hash(&hash).await.unwrap(), 3);
+
+    storage.set_top_height(1).unwrap();
+    storage.set_top_topoheight(1).unwrap();
+    assert_eq!(storage.get_top_height().unwrap(), 1);
+    assert_eq!(storage.get_top_topoheight().unwrap(), 1);
+    assert_eq!(storage.get_top_block_hash().await.unwrap(), hash);
+
+    let mut tips = Tips::new();
+    tips.insert(hash.clone());
+    storage.store_tips(&tips).unwrap();
+    assert_eq!(storage.get_tips().await.unwrap(), tips);
+
+    storage.set_block_reward_at_topo_height(1, 50).unwrap();
+    storage.set_supply_at_topo_height(1, 100).unwrap();
+    assert_eq!(storage.get_block_reward_at_topo_height(1).unwrap(), 50);
+    assert_eq!(storage.get_supply_at_topo_height(1).await.unwrap(), 100);
+}
+
+async fn test_assets<S: Storage>(storage: &mut S) {
+    assert!(!storage.asset_exist(&XELIS_ASSET).await.unwrap());
+    storage.add_asset(&XELIS_ASSET, AssetData::new(0, 8)).await.unwrap();
+    assert!(storage.asset_exist(&XELIS_ASSET).await.unwrap());
+    assert_eq!(storage.count_assets().unwrap(), 1);
+    assert_eq!(storage.get_assets().await.unwrap(), vec![XELIS_ASSET]);
+    assert_eq!(storage.get_asset_data(&XELIS_ASSET).unwrap().get_decimals(), 8);
+}
+
+async fn test_versioned_balances<S: Storage>(storage: &mut S) {
+    let key = KeyPair::new().get_public_key().clone();
+    assert!(!storage.has_balance_for(&key, &XELIS_ASSET).await.unwrap());
+
+    storage.set_balance_to(&key, &XELIS_ASSET, 1, &VersionedBalance::new(10, None)).await.unwrap();
+    let mut version = storage.get_new_versioned_balance(&key, &XELIS_ASSET, 3).await.unwrap();
+    assert_eq!(version.get_previous_topoheight(), Some(1));
+    version.set_balance(25);
+    storage.set_balance_to(&key, &XELIS_ASSET, 3, &version).await.unwrap();
+
+    assert!(storage.has_balance_for(&key, &XELIS_ASSET).await.unwrap());
+    assert_eq!(storage.get_assets_for(&key).await.unwrap(), vec![XELIS_ASSET]);
+    let (topoheight, last) = storage.get_last_balance(&key, &XELIS_ASSET).await.unwrap();
+    assert_eq!((topoheight, last.get_balance()), (3, 25));
+
+    let (topoheight, version) = storage.get_balance_at_maximum_topoheight(&key, &XELIS_ASSET, 2).await.unwrap().unwrap();
+    assert_eq!((topoheight, version.get_balance()), (1, 10));
+    assert!(storage.get_balance_at_maximum_topoheight(&key, &XELIS_ASSET, 0).await.unwrap().is_none());
+    assert_eq!(storage.get_balances(&XELIS_ASSET, [key.clone()].iter(), 3).await.unwrap(), vec![Some(25)]);
+
+    // another asset changed at the same topoheight must not overwrite the XELIS version
+    let asset = Hash::new([1; 32]);
+    storage.add_asset(&asset, AssetData::new(3, 8)).await.unwrap();
+    storage.set_balance_to(&key, &asset, 3, &VersionedBalance::new(7, None)).await.unwrap();
+    assert_eq!(storage.get_balance_at_exact_topoheight(&key, &asset, 3).await.unwrap().get_balance(), 7);
+    assert_eq!(storage.get_balance_at_exact_topoheight(&key, &XELIS_ASSET, 3).await.unwrap().get_balance(), 25);
+
+    storage.delete_versioned_balances_above_topoheight(2).await.unwrap();
+    assert!(!storage.has_balance_at_exact_topoheight(&key, &XELIS_ASSET, 3).await.unwrap());
+    assert!(storage.has_balance_at_exact_topoheight(&key, &XELIS_ASSET, 1).await.unwrap());
+}
+
+async fn test_assets_versions<S: Storage>(storage: &mut S) {
+    let asset = Hash::new([2; 32]);
+    let authority = KeyPair::new().get_public_key().clone();
+    storage.add_asset(&asset, AssetData::new(5, 8)).await.unwrap();
+    assert_eq!(storage.get_asset_supply_at_maximum_topoheight(&asset, 5).await.unwrap(), 0);
+
+    storage.set_asset_authority_at_topoheight(&asset, 5, Some(&authority)).unwrap();
+    storage.set_asset_supply_at_topoheight(&asset, 6, 100).unwrap();
+    storage.set_asset_supply_at_topoheight(&asset, 8, 150).unwrap();
+    storage.set_asset_authority_at_topoheight(&asset, 8, None).unwrap();
+
+    assert_eq!(storage.get_asset_supply_at_maximum_topoheight(&asset, 7).await.unwrap(), 100);
+    assert_eq!(storage.get_asset_supply_at_maximum_topoheight(&asset, 9).await.unwrap(), 150);
+    assert_eq!(storage.get_asset_authority_at_maximum_topoheight(&asset, 7).await.unwrap(), Some(authority.clone()));
+    assert_eq!(storage.get_asset_authority_at_maximum_topoheight(&asset, 8).await.unwrap(), None);
+
+    let top_topoheight = storage.get_top_topoheight().unwrap();
+    storage.set_top_topoheight(8).unwrap();
+    storage.delete_assets_above_topoheight(7).await.unwrap();
+    assert_eq!(storage.get_asset_supply_at_maximum_topoheight(&asset, 9).await.unwrap(), 100);
+    assert_eq!(storage.get_asset_authority_at_maximum_topoheight(&asset, 9).await.unwrap(), Some(authority));
+
+    storage.delete_assets_above_topoheight(4).await.unwrap();
+    assert!(!storage.asset_exist(&asset).await.unwrap());
+    assert_eq!(storage.get_asset_supply_at_maximum_topoheight(&asset, 9).await.unwrap(), 0);
+    storage.set_top_topoheight(top_topoheight).unwrap();
+}
+
+async fn test_multisig_versions<S: Storage>(storage: &mut S) {
+    let key = KeyPair::new().get_public_key().clone();
+    let setup = MultiSigSetup { participants: vec![KeyPair::new().get_public_key().clone(), KeyPair::new().get_public_key().clone()], threshold: 2 };
+    assert_eq!(storage.get_multisig_at_maximum_topoheight(&key, 10).await.unwrap(), None);
+
+    storage.set_multisig_at_topoheight(&key, 3, Some(&setup)).unwrap();
+    storage.set_multisig_at_topoheight(&key, 6, None).unwrap();
+    assert_eq!(storage.get_multisig_at_maximum_topoheight(&key, 2).await.unwrap(), None);
+    assert_eq!(storage.get_multisig_at_maximum_topoheight(&key, 5).await.unwrap(), Some(setup.clone()));
+    assert_eq!(storage.get_multisig_at_maximum_topoheight(&key, 6).await.unwrap(), None);
+
+    let top_topoheight = storage.get_top_topoheight().unwrap();
+    storage.set_top_topoheight(6).unwrap();
+    storage.delete_multisig_above_topoheight(5).await.unwrap();
+    assert_eq!(storage.get_multisig_at_maximum_topoheight(&key, 10).await.unwrap(), Some(setup));
+    storage.set_top_topoheight(top_topoheight).unwrap();
+}
+
+async fn test_locked_balances<S: Storage>(storage: &mut S) {
+    let key = KeyPair::new().get_public_key().clone();
+    let height = UnlockCondition::Topoheight(20);
+    let timestamp = UnlockCondition::Timestamp(5000);
+    storage.add_locked_balance(&key, &XELIS_ASSET, 3, &height, 100).await.unwrap();
+    storage.add_locked_balance(&key, &XELIS_ASSET, 5, &height, 50).await.unwrap();
+    storage.add_locked_balance(&key, &XELIS_ASSET, 5, &timestamp, 10).await.unwrap();
+    storage.add_locked_balance(&key, &Hash::new([1; 32]), 5, &height, 1).await.unwrap();
+
+    // same conditions are merged for an asset
+    let locked = storage.get_locked_balances(&key, &XELIS_ASSET, 10).await.unwrap();
+    assert_eq!(locked.len(), 2);
+    assert!(locked.contains(&LockedBalance::new(height.clone(), 150)));
+    assert!(locked.contains(&LockedBalance::new(timestamp.clone(), 10)));
+    assert_eq!(storage.get_locked_balances(&key, &XELIS_ASSET, 4).await.unwrap(), vec![LockedBalance::new(height.clone(), 100)]);
+
+    // only the topoheights up to the top one are deleted
+    let top_topoheight = storage.get_top_topoheight().unwrap();
+    storage.set_top_topoheight(5).unwrap();
+    storage.delete_locked_balances_above_topoheight(4).await.unwrap();
+    assert_eq!(storage.get_locked_balances(&key, &XELIS_ASSET, 10).await.unwrap(), vec![LockedBalance::new(height, 100)]);
+    assert!(storage.get_locked_balances(&key, &Hash::new([1; 32]), 10).await.unwrap().is_empty());
+    storage.set_top_topoheight(top_topoheight).unwrap();
+}
+
+async fn test_mempool_txs<S: Storage>(storage: &mut S) {
+    let keypair = KeyPair::new();
+    let tx = |nonce: u64| {
+        let data = TransactionType::Burn { asset: XELIS_ASSET, amount: 1 };
+        Arc::new(Transaction::new(keypair.get_public_key().clone(), data, 1000, nonce, keypair.sign(&nonce.to_be_bytes())))
+    };
+
+    // saved txs are sorted by nonce and keep the time they were first seen
+    storage.add_mempool_tx(&tx(1), 20).await.unwrap();
+    storage.add_mempool_tx(&tx(0), 10).await.unwrap();
+    let txs = storage.get_mempool_txs().await.unwrap();
+    assert_eq!(txs.iter().map(|(tx, first_seen)| (tx.get_nonce(), *first_seen)).collect::<Vec<_>>(), vec![(0, 10), (1, 20)]);
+
+    // a tx with the same nonce is replaced
+    let replacing = Arc::new(Transaction::new(keypair.get_public_key().clone(), TransactionType::Burn { asset: XELIS_ASSET, amount: 2 }, 1000, 1, keypair.sign(b"replacing")));
+    storage.add_mempool_tx(&replacing, 30).await.unwrap();
+    let txs = storage.get_mempool_txs().await.unwrap();
+    assert_eq!(txs.len(), 2);
+    assert_eq!(txs[1].0.hash(), replacing.hash());
+
+    // changes of a rolled back batch are not kept
+    storage.start_batch().unwrap();
+    storage.delete_mempool_tx(keypair.get_public_key(), 0).await.unwrap();
+    storage.add_mempool_tx(&tx(2), 40).await.unwrap();
+    storage.rollback_batch().await.unwrap();
+    assert_eq!(storage.get_mempool_txs().await.unwrap().len(), 2);
+
+    storage.delete_mempool_tx(keypair.get_public_key(), 0).await.unwrap();
+    storage.delete_mempool_tx(keypair.get_public_key(), 1).await.unwrap();
+    assert!(storage.get_mempool_txs().await.unwrap().is_empty());
+}
+
+async fn test_versioned_nonces<S: Storage>(storage: &mut S) {
+    let key = KeyPair::new().get_public_key().clone();
+    let accounts = storage.count_accounts().unwrap();
+    assert!(!storage.has_nonce(&key).await.unwrap());
+
+    storage.set_nonce_at_topoheight(&key, 1, 1).await.unwrap();
+    storage.set_nonce_at_topoheight(&key, 2, 4).await.unwrap();
+    assert_eq!(storage.count_accounts().unwrap(), accounts + 1);
+    assert!(storage.has_nonce(&key).await.unwrap());
+    assert!(storage.has_nonce_at_exact_topoheight(&key, 4).await.unwrap());
+
+    let (topoheight, last) = storage.get_last_nonce(&key).await.unwrap();
+    assert_eq!((topoheight, last.get_nonce(), last.get_previous_topoheight()), (4, 2, Some(1)));
+    let (topoheight, version) = storage.get_nonce_at_maximum_topoheight(&key, 3).await.unwrap().unwrap();
+    assert_eq!((topoheight, version.get_nonce()), (1, 1));
+}
+
+async fn test_state_tree<S: Storage>(storage: &mut S) {
+    let top_topoheight = storage.get_top_topoheight().unwrap();
+    let root = storage.get_state_root_at_topoheight(10).await.unwrap();
+
+    let key = KeyPair::new().get_public_key().clone();
+    storage.set_balance_to(&key, &XELIS_ASSET, 10, &VersionedBalance::new(100, None)).await.unwrap();
+    let balance_root = storage.get_state_root_at_topoheight(10).await.unwrap();
+    assert_ne!(balance_root, root);
+    // previous roots are kept
+    assert_eq!(storage.get_state_root_at_topoheight(9).await.unwrap(), root);
+
+    let proof = storage.get_state_proof_at_topoheight(&balance_leaf_key(&key, &XELIS_ASSET), 10).await.unwrap().unwrap();
+    assert!(verify_balance_proof(&balance_root, &key, &XELIS_ASSET, 100, &proof));
+    assert!(!verify_balance_proof(&balance_root, &key, &XELIS_ASSET, 101, &proof));
+    assert!(storage.get_state_proof_at_topoheight(&balance_leaf_key(&key, &XELIS_ASSET), 9).await.unwrap().is_none());
+    assert!(storage.get_state_proof_at_topoheight(&nonce_leaf_key(&key), 10).await.unwrap().is_none());
+
+    storage.set_nonce_at_topoheight(&key, 1, 11).await.unwrap();
+    let nonce_root = storage.get_state_root_at_topoheight(11).await.unwrap();
+    assert_ne!(nonce_root, balance_root);
+    // only the value is committed
+    storage.set_nonce_at_topoheight(&key, 1, 12).await.unwrap();
+    assert_eq!(storage.get_state_root_at_topoheight(12).await.unwrap(), nonce_root);
+
+    storage.set_top_topoheight(12).unwrap();
+    storage.delete_state_tree_above_topoheight(10).await.unwrap();
+    assert_eq!(storage.get_state_root_at_topoheight(12).await.unwrap(), balance_root);
+    storage.set_top_topoheight(top_topoheight).unwrap();
+}
+
+async fn test_account_history<S: Storage>(storage: &mut S) {
+    let top_topoheight = storage.get_top_topoheight().unwrap();
+    assert!(!storage.is_account_history_indexed().unwrap());
+    storage.set_account_history_indexed(true).unwrap();
+    assert!(storage.is_account_history_indexed().unwrap());
+
+    let key = KeyPair::new().get_public_key().clone();
+    let other = KeyPair::new().get_public_key().clone();
+    let hashes: Vec<Hash> = (1..=3u8).map(|i| Hash::new([i; 32])).collect();
+    storage.add_account_history_entry(&key, 1, &hashes[0], AccountHistoryDirection::Outgoing).await.unwrap();
+    storage.add_account_history_entry(&key, 2, &hashes[1], AccountHistoryDirection::Incoming).await.unwrap();
+    storage.add_account_history_entry(&other, 2, &hashes[1], AccountHistoryDirection::Outgoing).await.unwrap();
+    storage.add_account_history_entry(&key, 3, &hashes[2], AccountHistoryDirection::Outgoing).await.unwrap();
+    storage.add_account_history_entry(&key, 3, &hashes[2], AccountHistoryDirection::Incoming).await.unwrap();
+
+    // from the newest to the oldest
+    let entries = storage.get_account_history_entries(&key, 0, u64::MAX, 0, 10).await.unwrap();
+    assert_eq!(entries, vec![
+        (3, hashes[2].clone(), AccountHistoryDirection::Outgoing),
+        (3, hashes[2].clone(), AccountHistoryDirection::Incoming),
+        (2, hashes[1].clone(), AccountHistoryDirection::Incoming),
+        (1, hashes[0].clone(), AccountHistoryDirection::Outgoing)
+    ]);
+    assert_eq!(storage.get_account_history_entries(&key, 0, u64::MAX, 1, 2).await.unwrap(), entries[1..3]);
+    assert_eq!(storage.get_account_history_entries(&key, 2, 2, 0, 10).await.unwrap(), entries[2..3]);
+
+    storage.set_top_topoheight(3).unwrap();
+    storage.delete_account_history_above_topoheight(2).await.unwrap();
+    assert_eq!(storage.get_account_history_entries(&key, 0, u64::MAX, 0, 10).await.unwrap(), entries[2..]);
+    storage.delete_account_history_below_topoheight(2).await.unwrap();
+    assert_eq!(storage.get_account_history_entries(&key, 0, u64::MAX, 0, 10).await.unwrap(), entries[2..3]);
+    assert_eq!(storage.get_account_history_entries(&other, 0, u64::MAX, 0, 10).await.unwrap().len(), 1);
+
+    // disabling the index deletes it
+    storage.set_account_history_indexed(false).unwrap();
+    assert!(!storage.is_account_history_indexed().unwrap());
+    assert!(storage.get_account_history_entries(&other, 0, u64::MAX, 0, 10).await.unwrap().is_empty());
+    storage.set_top_topoheight(top_topoheight).unwrap();
+}
+
+async fn test_batch_rollback<S: Storage>(storage: &mut S) {
+    let supply = storage.get_supply_at_topo_height(1).await.unwrap();
+    let tips = storage.get_tips().await.unwrap();
+
+    storage.start_batch().unwrap();
+    assert!(matches!(storage.start_batch(), Err(BlockchainError::BatchAlreadyInProgress)));
+
+    let (hash, block) = create_block(2, tips.iter().cloned().collect());
+    storage.save_block(block, &Vec::new(), 1, hash.clone()).await.unwrap();
+    storage.set_supply_at_topo_height(1, supply + 1).unwrap();
+    let mut new_tips = Tips::new();
+    new_tips.insert(hash.clone());
+    storage.store_tips(&new_tips).unwrap();
+    assert!(storage.has_pending_batch().unwrap());
+
+    storage.rollback_batch().await.unwrap();
+    assert!(!storage.has_pending_batch().unwrap());
+    assert!(!storage.has_block(&hash).await.unwrap());
+    assert!(!storage.has_blocks_at_height(2).await.unwrap());
+    assert_eq!(storage.get_supply_at_topo_height(1).await.unwrap(), supply);
+    assert_eq!(storage.get_tips().await.unwrap(), tips);
+
+    // committed changes are kept
+    storage.start_batch().unwrap();
+    storage.set_supply_at_topo_height(1, supply + 1).unwrap();
+    storage.commit_batch().unwrap();
+    assert!(!storage.has_pending_batch().unwrap());
+    assert_eq!(storage.get_supply_at_topo_height(1).await.unwrap(), supply + 1);
+}
+
+// sled may still hold the lock of its files for a moment after being dropped
+fn is_lock_error(e: &BlockchainError) -> bool {
+    matches!(e, BlockchainError::DatabaseError(sled::Error::Io(e)) if e.to_string().contains("could not acquire lock"))
+}
+
+async fn open_storage<S: Storage, F: Fn() -> Result<S, BlockchainError>>(open: &F) -> S {
+    for _ in 0..50 {
+        match open() {
+            Ok(storage) => return storage,
+            Err(e) if is_lock_error(&e) => tokio::time::sleep(Duration::from_millis(100)).await,
+            Err(e) => panic!("Error while opening storage: {}", e)
+        }
+    }
+    panic!("Storage is still locked after being closed")
+}
+
+// Changes left by an interrupted batch are reverted after reopening the storage
+async fn test_batch_recovery<S: Storage, F: Fn() -> Result<S, BlockchainError>>(open: F) {
+    let mut storage = open_storage(&open).await;
+    storage.start_batch().unwrap();
+    storage.set_supply_at_topo_height(10, 1000).unwrap();
+    storage.stop().await.unwrap();
+    drop(storage);
+
+    let mut storage = open_storage(&open).await;
+    assert!(storage.has_pending_batch().unwrap());
+    storage.rollback_batch().await.unwrap();
+    assert!(storage.get_supply_at_topo_height(10).await.is_err());
+    storage.stop().await.unwrap();
+}
+
+async fn test_migrations<S: Storage>(storage: &mut S) {
+    assert_eq!(storage.get_db_version().unwrap(), DB_VERSION);
+    assert!(storage.migrate_db(None, false).await.unwrap().is_empty());
+    assert!(matches!(storage.migrate_db(Some(DB_VERSION + 1), false).await, Err(BlockchainError::InvalidMigrationVersion(_, _))));
+
+    // blocks counter is incremented again when a stored block is saved twice
+    let hash = storage.get_hash_at_topo_height(1).await.unwrap();
+    let block = storage.get_block_header_by_hash(&hash).await.unwrap();
+    storage.save_block(block, &Vec::new(), 1, hash).await.unwrap();
+    assert_eq!(storage.count_blocks().unwrap(), 3);
+
+    let reports = storage.migrate_db(Some(0), true).await.unwrap();
+    assert_eq!(reports.len() as u64, DB_VERSION);
+    assert_eq!(reports[0].changes, 1);
+    assert_eq!(storage.count_blocks().unwrap(), 3);
+
+    let reports = storage.migrate_db(Some(0), false).await.unwrap();
+    assert_eq!(reports[0].changes, 1);
+    assert_eq!(storage.count_blocks().unwrap(), 2);
+    assert_eq!(storage.get_db_version().unwrap(), DB_VERSION);
+    assert!(!storage.has_pending_batch().unwrap());
+}
+
+async fn test_integrity<S: Storage>(storage: &mut S) {
+    let reports = storage.check_db(false).await.unwrap();
+    assert!(reports.iter().all(|report| report.is_valid()));
+
+    // derived data is rebuilt in repair mode
+    let tx = Hash::new([2u8; 32]);
+    let genesis_hash = storage.get_hash_at_topo_height(0).await.unwrap();
+    storage.add_block_for_tx(&tx, &genesis_hash).unwrap();
+    storage.remove_block_hash_at_height(&genesis_hash, 0).await.unwrap();
+    // other inconsistencies are only reported
+    // the broken nonce versions also prevent to verify the state root
+    let key = KeyPair::new().get_public_key().clone();
+    storage.set_nonce_at_topoheight(&key, 1, 1).await.unwrap();
+    storage.set_last_topoheight_for_nonce(&key, 5).unwrap();
+
+    let reports = storage.check_db(true).await.unwrap();
+    let errors: usize = reports.iter().map(|report| report.errors.len()).sum();
+    let repaired: u64 = reports.iter().map(|report| report.repaired).sum();
+    assert_eq!((errors, repaired), (5, 2));
+    assert!(!storage.has_tx_blocks(&tx).unwrap());
+    assert!(storage.get_blocks_at_height(0).await.unwrap().contains(&genesis_hash));
+    assert!(!storage.has_pending_batch().unwrap());
+
+    let reports = storage.check_db(false).await.unwrap();
+    let errors: usize = reports.iter().map(|report| report.errors.len()).sum();
+    assert_eq!(errors, 3);
+
+    // state tree is rebuilt once the versions are valid again
+    storage.set_last_topoheight_for_nonce(&key, 1).unwrap();
+    storage.delete_state_tree_above_topoheight(0).await.unwrap();
+    let reports = storage.check_db(true).await.unwrap();
+    let errors: usize = reports.iter().map(|report| report.errors.len()).sum();
+    assert_eq!(errors, 1);
+    assert!(storage.check_db(false).await.unwrap().iter().all(|report| report.is_valid()));
+}
+
+async fn run_conformance_suite<S: Storage>(storage: &mut S) {
+    test_network(storage).await;
+    test_blocks(storage).await;
+    test_assets(storage).await;
+    test_versioned_balances(storage).await;
+    test_assets_versions(storage).await;
+    test_multisig_versions(storage).await;
+    test_locked_balances(storage).await;
+    test_mempool_txs(storage).await;
+    test_versioned_nonces(storage).await;
+    test_state_tree(storage).await;
+    test_account_history(storage).await;
+    test_batch_rollback(storage).await;
+}
+
+// Same as above, but also check that data is still available after reopening the storage
+async fn run_persistent_conformance_suite<S: Storage, F: Fn() -> Result<S, BlockchainError>>(open: F) {
+    {
+        let mut storage = open_storage(&open).await;
+        run_conformance_suite(&mut storage).await;
+        storage.stop().await.unwrap();
+    }
+
+    {
+        let storage = open_storage(&open).await;
+        assert_eq!(storage.count_blocks().unwrap(), 2);
+        assert_eq!(storage.get_top_topoheight().unwrap(), 1);
+        assert!(storage.asset_exist(&XELIS_ASSET).await.unwrap());
+    }
+
+    test_batch_recovery(open).await;
+}
+
+#[tokio::test]
+async fn test_sled_storage() {
+    let dir_path = temp_dir_path("sled");
+    run_persistent_conformance_suite(|| SledStorage::new(dir_path.clone(), Some(16), Network::Dev)).await;
+    let _ = std::fs::remove_dir_all(&dir_path);
+}
+
+#[cfg(feature = "rocksdb")]
+#[tokio::test]
+async fn test_rocksdb_storage() {
+    let dir_path = temp_dir_path("rocksdb");
+    run_persistent_conformance_suite(|| super::RocksStorage::new(dir_path.clone(), Some(16), Network::Dev)).await;
+    let _ = std::fs::remove_dir_all(&dir_path);
+}
+
+#[tokio::test]
+async fn test_memory_storage() {
+    let mut storage = MemoryStorage::new(Some(16), Network::Dev).unwrap();
+    run_conformance_suite(&mut storage).await;
+}
+
+#[tokio::test]
+async fn test_memory_storage_migrations() {
+    let mut storage = MemoryStorage::new(Some(16), Network::Dev).unwrap();
+    test_blocks(&mut storage).await;
+    test_migrations(&mut storage).await;
+}
+
+#[tokio::test]
+async fn test_memory_storage_integrity() {
+    let mut storage = MemoryStorage::new(Some(16), Network::Dev).unwrap();
+    test_blocks(&mut storage).await;
+    test_integrity(&mut storage).await;
+}
+
+#[tokio::test]
+async fn test_memory_storage_without_cache() {
+    let mut storage = MemoryStorage::new(None, Network::Dev).unwrap();
+    run_conformance_suite(&mut storage).await;
+}
+
+#[tokio::test]
+async fn test_state_root_is_independent_of_history() {
+    let keys: Vec<PublicKey> = (0..20).map(|_| KeyPair::new().get_public_key().clone()).collect();
+
+    let mut storage = MemoryStorage::new(None, Network::Dev).unwrap();
+    assert_eq!(storage.get_state_root_at_topoheight(0).await.unwrap(), EMPTY_STATE_ROOT);
+    storage.set_nonce_at_topoheight(&keys[0], 5, 0).await.unwrap();
+    assert_eq!(storage.get_state_root_at_topoheight(0).await.unwrap(), hash_leaf(&nonce_leaf_key(&keys[0]), 5));
+    for (i, key) in keys.iter().enumerate() {
+        storage.set_nonce_at_topoheight(key, i as u64, 1).await.unwrap();
+    }
+
+    // same final values, written in another order with intermediate versions
+    let mut other = MemoryStorage::new(None, Network::Dev).unwrap();
+    for (i, key) in keys.iter().enumerate().rev() {
+        other.set_nonce_at_topoheight(key, 100, 1).await.unwrap();
+        other.set_nonce_at_topoheight(key, i as u64, 2).await.unwrap();
+    }
+    assert_eq!(storage.get_state_root_at_topoheight(1).await.unwrap(), other.get_state_root_at_topoheight(2).await.unwrap());
+    assert_ne!(other.get_state_root_at_topoheight(1).await.unwrap(), other.get_state_root_at_topoheight(2).await.unwrap());
+
+    let root = other.get_state_root_at_topoheight(2).await.unwrap();
+    for (i, key) in keys.iter().enumerate() {
+        let proof = other.get_state_proof_at_topoheight(&nonce_leaf_key(key), 2).await.unwrap().unwrap();
+        assert!(verify_nonce_proof(&root, key, i as u64, &proof));
+        assert!(!verify_nonce_proof(&root, key, 100, &proof));
+    }
+}
+
+#[tokio::test]
+async fn test_memory_storage_state_tree_rebuild() {
+    let mut storage = MemoryStorage::new(None, Network::Dev).unwrap();
+    storage.add_asset(&XELIS_ASSET, AssetData::new(0, 8)).await.unwrap();
+    let key = KeyPair::new().get_public_key().clone();
+    let mut roots = Vec::new();
+    for topoheight in 0..5 {
+        let mut version = storage.get_new_versioned_balance(&key, &XELIS_ASSET, topoheight).await.unwrap();
+        version.set_balance(topoheight * 10);
+        storage.set_balance_to(&key, &XELIS_ASSET, topoheight, &version).await.unwrap();
+        storage.set_nonce_at_topoheight(&key, topoheight, topoheight).await.unwrap();
+        roots.push(storage.get_state_root_at_topoheight(topoheight).await.unwrap());
+    }
+
+    // state tree is built by the second migration
+    let reports = storage.migrate_db(Some(1), false).await.unwrap();
+    assert_eq!(reports.len() as u64, DB_VERSION - 1);
+    assert_eq!(reports[0].changes, 10);
+    for (topoheight, root) in roots.into_iter().enumerate() {
+        assert_eq!(storage.get_state_root_at_topoheight(topoheight as u64).await.unwrap(), root);
+    }
+}
@@ -0,0 +1,164 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+use super::mempool::has_higher_fee_rate;
+
+// a TX which can be selected for a block
+// size is the space it takes in the block
+pub struct TxCandidate<T> {
+    item: T,
+    fee: u64,
+    size: usize
+}
+
+impl<T> TxCandidate<T> {
+    pub fn new(item: T, fee: u64, size: usize) -> Self {
+        Self {
+            item,
+            fee,
+            size
+        }
+    }
+}
+
+// best prefix of the remaining TXs of an owner
+struct PackageScore {
+    owner: usize,
+    // index of its first TX in the package of the owner
+    start: usize,
+    len: usize,
+    fee: u64,
+    size: usize
+}
+
+impl PartialEq for PackageScore {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for PackageScore {}
+
+impl PartialOrd for PackageScore {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PackageScore {
+    // higher fee rate first, then lower owner index to stay deterministic
+    fn cmp(&self, other: &Self) -> Ordering {
+        if has_higher_fee_rate(self.fee, self.size, other.fee, other.size) {
+            Ordering::Greater
+        } else if has_higher_fee_rate(other.fee, other.size, self.fee, self.size) {
+            Ordering::Less
+        } else {
+            other.owner.cmp(&self.owner)
+        }
+    }
+}
+
+// search the prefix of txs fitting in max_size with the highest fee rate
+// a TX paying high fees also makes its parents worth including
+// on equal fee rate, the longest prefix is kept
+fn find_best_prefix<T>(owner: usize, start: usize, txs: &[TxCandidate<T>], max_size: usize) -> Option<PackageScore> {
+    let mut best: Option<PackageScore> = None;
+    let mut fee = 0u64;
+    let mut size = 0;
+    for (i, tx) in txs.iter().enumerate() {
+        fee = fee.saturating_add(tx.fee);
+        size += tx.size;
+        if size > max_size {
+            break;
+        }
+
+        if best.as_ref().is_none_or(|best| !has_higher_fee_rate(best.fee, best.size, fee, size)) {
+            best = Some(PackageScore { owner, start, len: i + 1, fee, size });
+        }
+    }
+    best
+}
+
+// select the TXs to include in a block without exceeding max_size
+// each package contains the TXs of an owner ordered by nonce, a TX is never selected without its parents
+// packages are selected by their fee rate, and smaller ones are tried once the bigger ones don't fit anymore
+// the selected TXs are returned in the order they must be included
+pub fn select_txs<T>(packages: Vec<Vec<TxCandidate<T>>>, max_size: usize) -> Vec<T> {
+    let mut remaining_size = max_size;
+    let mut heap = BinaryHeap::with_capacity(packages.len());
+    for (owner, txs) in packages.iter().enumerate() {
+        if let Some(score) = find_best_prefix(owner, 0, txs, remaining_size) {
+            heap.push(score);
+        }
+    }
+
+    let mut selected: Vec<(usize, usize)> = Vec::new();
+    while let Some(score) = heap.pop() {
+        // its prefix didn't fit anymore, search a smaller one
+        if score.size > remaining_size {
+            if let Some(score) = find_best_prefix(score.owner, score.start, &packages[score.owner][score.start..], remaining_size) {
+                heap.push(score);
+            }
+            continue;
+        }
+
+        // the best prefix fitting in more space is still the best one fitting in the remaining space
+        remaining_size -= score.size;
+        for i in score.start..score.start + score.len {
+            selected.push((score.owner, i));
+        }
+
+        let start = score.start + score.len;
+        if let Some(score) = find_best_prefix(score.owner, start, &packages[score.owner][start..], remaining_size) {
+            heap.push(score);
+        }
+    }
+
+    let mut packages: Vec<Vec<Option<T>>> = packages.into_iter()
+        .map(|txs| txs.into_iter().map(|tx| Some(tx.item)).collect())
+        .collect();
+
+    selected.into_iter()
+        .filter_map(|(owner, i)| packages[owner][i].take())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(owner: u8, txs: &[(u64, usize)]) -> Vec<TxCandidate<(u8, usize)>> {
+        txs.iter().enumerate().map(|(nonce, (fee, size))| TxCandidate::new((owner, nonce), *fee, *size)).collect()
+    }
+
+    #[test]
+    fn test_select_by_fee_rate() {
+        // fee * size would select the biggest TX first
+        let packages = vec![package(0, &[(1000, 1000)]), package(1, &[(500, 100)])];
+        assert_eq!(select_txs(packages, 2000), vec![(1, 0), (0, 0)]);
+    }
+
+    #[test]
+    fn test_child_pays_for_parent() {
+        // the parent pays less than the other owner, but its child makes the package worth it
+        let packages = vec![package(0, &[(100, 100)]), package(1, &[(10, 100), (1000, 100)])];
+        assert_eq!(select_txs(packages, 200), vec![(1, 0), (1, 1)]);
+
+        // a TX is never selected without its parent
+        let packages = vec![package(0, &[(100, 100)]), package(1, &[(10, 100), (1000, 100)])];
+        assert_eq!(select_txs(packages, 100), vec![(0, 0)]);
+    }
+
+    #[test]
+    fn test_nonce_order_kept() {
+        let packages = vec![package(0, &[(100, 100), (50, 100), (10, 100)]), package(1, &[(60, 100)])];
+        assert_eq!(select_txs(packages, 1000), vec![(0, 0), (1, 0), (0, 1), (0, 2)]);
+    }
+
+    #[test]
+    fn test_fill_remaining_space() {
+        // the best package doesn't fit anymore, smaller ones are still included
+        let packages = vec![package(0, &[(1000, 600)]), package(1, &[(900, 600)]), package(2, &[(100, 300), (10, 300)])];
+        assert_eq!(select_txs(packages, 1000), vec![(0, 0), (2, 0)]);
+
+        assert!(select_txs(vec![package(0, &[(1000, 600)])], 500).is_empty());
+    }
+}
@@ -10,15 +10,17 @@ use p2p::P2pServer;
 use rpc::{getwork_server::SharedGetWorkServer, rpc::get_block_response_for_hash};
 use xelis_common::{
     prompt::{Prompt, command::{CommandManager, CommandError, Command, CommandHandler}, PromptError, argument::{ArgumentManager, Arg, ArgType}, LogLevel, self, ShareablePrompt},
-    config::{VERSION, XELIS_ASSET}, utils::{format_hashrate, set_network_to, format_xelis, format_coin, format_difficulty}, async_handler, crypto::{address::Address, hash::Hashable}, network::Network, transaction::Transaction, serializer::Serializer, block::Difficulty
+    config::{VERSION, XELIS_ASSET}, utils::{format_hashrate, set_network_to, format_xelis, format_coin, format_difficulty}, async_handler, crypto::{address::Address, hash::Hashable}, network::Network, transaction::Transaction, serializer::Serializer
 };
 use crate::{
     core::{
         blockchain::{Config, Blockchain, get_block_reward},
-        storage::{Storage, SledStorage}
+        storage::{Storage, SledStorage, MemoryStorage, StorageBackend, DB_VERSION}
     },
-    config::{BLOCK_TIME_MILLIS, MILLIS_PER_SECOND}
+    config::MILLIS_PER_SECOND
 };
+#[cfg(feature = "rocksdb")]
+use crate::core::storage::RocksStorage;
 use std::{sync::Arc, net::IpAddr};
 use std::time::Duration;
 use clap::Parser;
@@ -58,23 +60,71 @@ async fn main() -> Result<()> {
     set_network_to(config.network);
 
     let blockchain_config = config.nested;
-    let storage = {
-        let use_cache = if blockchain_config.cache_size > 0 {
-            Some(blockchain_config.cache_size)
-        } else {
-            None
-        };
+    let use_cache = if blockchain_config.cache_size > 0 {
+        Some(blockchain_config.cache_size)
+    } else {
+        None
+    };
 
-        let dir_path = if let Some(path) = blockchain_config.dir_path.as_ref() {
-            path.clone()
-        } else {
-            config.network.to_string().to_lowercase()
-        };
-        SledStorage::new(dir_path, use_cache, config.network)?
+    let dir_path = if let Some(path) = blockchain_config.dir_path.as_ref() {
+        path.clone()
+    } else {
+        config.network.to_string().to_lowercase()
     };
 
-    let blockchain = Blockchain::new(blockchain_config, config.network, storage).await?;
-    if let Err(e) = run_prompt(prompt, blockchain.clone(), config.network).await {
+    match blockchain_config.storage_backend {
+        StorageBackend::Sled => {
+            let storage = SledStorage::new(dir_path, use_cache, config.network)?;
+            start_node(blockchain_config, config.network, storage, prompt).await
+        },
+        StorageBackend::Memory => {
+            warn!("Using in-memory storage, chain will be lost at exit");
+            let storage = MemoryStorage::new(use_cache, config.network)?;
+            start_node(blockchain_config, config.network, storage, prompt).await
+        },
+        #[cfg(feature = "rocksdb")]
+        StorageBackend::Rocksdb => {
+            let storage = RocksStorage::new(dir_path, use_cache, config.network)?;
+            start_node(blockchain_config, config.network, storage, prompt).await
+        },
+        #[cfg(not(feature = "rocksdb"))]
+        backend => Err(core::error::BlockchainError::StorageBackendNotAvailable(backend).into())
+    }
+}
+
+async fn start_node<S: Storage>(config: Config, network: Network, storage: S, prompt: ShareablePrompt) -> Result<()> {
+    let import_chain = config.import_chain.clone();
+    let trusted_import = config.trusted_import;
+    let export_chain = config.export_chain.clone();
+    let load_snapshot = config.load_snapshot.clone();
+    let create_snapshot = config.create_snapshot.clone();
+    let blockchain = Blockchain::new(config, network, storage).await?;
+
+    if let Some(path) = load_snapshot {
+        if let Err(e) = blockchain.load_snapshot(&path).await {
+            error!("Error while loading snapshot from {}: {}", path, e);
+        }
+    }
+
+    if let Some(path) = import_chain {
+        if let Err(e) = blockchain.import_chain(&path, trusted_import).await {
+            error!("Error while importing chain from {}: {}", path, e);
+        }
+    }
+
+    if let Some(path) = export_chain {
+        if let Err(e) = blockchain.export_chain(&path).await {
+            error!("Error while exporting chain to {}: {}", path, e);
+        }
+    }
+
+    if let Some(path) = create_snapshot {
+        if let Err(e) = blockchain.create_snapshot(&path).await {
+            error!("Error while creating snapshot to {}: {}", path, e);
+        }
+    }
+
+    if let Err(e) = run_prompt(prompt, blockchain.clone(), network).await {
         error!("Error while running prompt: {}", e);
     }
 
@@ -101,6 +151,12 @@ async fn run_prompt<S: Storage>(prompt: ShareablePrompt, blockchain: Arc<Blockch
     command_manager.add_command(Command::with_optional_arguments("blacklist", "View blacklist or add a peer address in it", vec![Arg::new("address", ArgType::String)], CommandHandler::Async(async_handler!(blacklist))))?;
     command_manager.add_command(Command::with_optional_arguments("whitelist", "View whitelist or add a peer address in it", vec![Arg::new("address", ArgType::String)], CommandHandler::Async(async_handler!(whitelist))))?;
     command_manager.add_command(Command::new("verify_chain", "Check chain supply/balances", CommandHandler::Async(async_handler!(verify_chain))))?;
+    command_manager.add_command(Command::with_required_arguments("export_chain", "Export all blocks to a chain file", vec![Arg::new("path", ArgType::String)], CommandHandler::Async(async_handler!(export_chain))))?;
+    command_manager.add_command(Command::with_arguments("import_chain", "Import all blocks from a chain file", vec![Arg::new("path", ArgType::String)], vec![Arg::new("trusted", ArgType::Bool)], CommandHandler::Async(async_handler!(import_chain))))?;
+    command_manager.add_command(Command::with_required_arguments("create_snapshot", "Write the chain state at the stable topoheight to a snapshot file", vec![Arg::new("path", ArgType::String)], CommandHandler::Async(async_handler!(create_snapshot))))?;
+    command_manager.add_command(Command::with_required_arguments("load_snapshot", "Boot a new chain from a snapshot file", vec![Arg::new("path", ArgType::String)], CommandHandler::Async(async_handler!(load_snapshot))))?;
+    command_manager.add_command(Command::with_optional_arguments("check_db", "Check consistency of all database trees (and rebuild derived ones)", vec![Arg::new("repair", ArgType::Bool)], CommandHandler::Async(async_handler!(check_db))))?;
+    command_manager.add_command(Command::with_optional_arguments("migrate_db", "Apply pending database migrations (or again from a version)", vec![Arg::new("dry_run", ArgType::Bool), Arg::new("from_version", ArgType::Number)], CommandHandler::Async(async_handler!(migrate_db))))?;
 
     // Don't keep the lock for ever
     let (p2p, getwork) = {
@@ -131,7 +187,7 @@ async fn run_prompt<S: Storage>(prompt: ShareablePrompt, blockchain: Arc<Blockch
             mempool.size()
         };
 
-        let network_hashrate = (blockchain.get_difficulty() / (BLOCK_TIME_MILLIS / MILLIS_PER_SECOND) as Difficulty) as f64;
+        let network_hashrate = blockchain.get_difficulty() as f64 / (blockchain.get_block_time_target() as f64 / MILLIS_PER_SECOND as f64);
 
         Ok(
             build_prompt_message(
@@ -203,7 +259,7 @@ async fn verify_chain<S: Storage>(manager: &CommandManager<Arc<Blockchain<S>>>,
     let blockchain = lock.as_ref().ok_or(CommandError::NoData)?;
 
     let storage = blockchain.get_storage().read().await;
-    let mut expected_supply = 0;
+    let mut expected_supply = blockchain.get_chain_spec().get_premine_supply();
     for topo in 0..=blockchain.get_topo_height() {
         let hash_at_topo = storage.get_hash_at_topo_height(topo).await.context("Error while retrieving hash at topo")?;
         let block_reward = blockchain.get_block_reward(&*storage, &hash_at_topo, expected_supply).await.context("Error while calculating block reward")?;
@@ -421,6 +477,146 @@ async fn prune_chain<S: Storage>(manager: &CommandManager<Arc<Blockchain<S>>>, m
     Ok(())
 }
 
+async fn export_chain<S: Storage>(manager: &CommandManager<Arc<Blockchain<S>>>, mut arguments: ArgumentManager) -> Result<(), CommandError> {
+    let path = arguments.get_value("path")?.to_string_value()?;
+    let lock = manager.get_data().lock()?;
+    let blockchain = lock.as_ref().ok_or(CommandError::NoData)?;
+    manager.message(format!("Exporting chain to {}...", path));
+    match blockchain.export_chain(&path).await {
+        Ok(count) => manager.message(format!("{} blocks have been exported", count)),
+        Err(e) => manager.error(format!("Error while exporting chain: {}", e))
+    };
+    Ok(())
+}
+
+async fn import_chain<S: Storage>(manager: &CommandManager<Arc<Blockchain<S>>>, mut arguments: ArgumentManager) -> Result<(), CommandError> {
+    let path = arguments.get_value("path")?.to_string_value()?;
+    let trusted = if arguments.has_argument("trusted") {
+        arguments.get_value("trusted")?.to_bool()?
+    } else {
+        false
+    };
+
+    let lock = manager.get_data().lock()?;
+    let blockchain = lock.as_ref().ok_or(CommandError::NoData)?;
+    manager.message(format!("Importing chain from {}...", path));
+    match blockchain.import_chain(&path, trusted).await {
+        Ok((imported, skipped)) => manager.message(format!("{} blocks have been imported ({} already in chain)", imported, skipped)),
+        Err(e) => manager.error(format!("Error while importing chain: {}", e))
+    };
+    Ok(())
+}
+
+async fn create_snapshot<S: Storage>(manager: &CommandManager<Arc<Blockchain<S>>>, mut arguments: ArgumentManager) -> Result<(), CommandError> {
+    let path = arguments.get_value("path")?.to_string_value()?;
+    let lock = manager.get_data().lock()?;
+    let blockchain = lock.as_ref().ok_or(CommandError::NoData)?;
+    manager.message(format!("Creating snapshot to {}...", path));
+    match blockchain.create_snapshot(&path).await {
+        Ok((header, state_hash)) => manager.message(format!("Snapshot created at topoheight {}, top hash: {}, state hash: {}", header.get_topoheight(), header.get_top_hash(), state_hash)),
+        Err(e) => manager.error(format!("Error while creating snapshot: {}", e))
+    };
+    Ok(())
+}
+
+async fn load_snapshot<S: Storage>(manager: &CommandManager<Arc<Blockchain<S>>>, mut arguments: ArgumentManager) -> Result<(), CommandError> {
+    let path = arguments.get_value("path")?.to_string_value()?;
+    let lock = manager.get_data().lock()?;
+    let blockchain = lock.as_ref().ok_or(CommandError::NoData)?;
+    manager.message(format!("Loading snapshot from {}...", path));
+    match blockchain.load_snapshot(&path).await {
+        Ok((header, state_hash)) => manager.message(format!("Snapshot loaded at topoheight {}, top hash: {}, state hash: {}", header.get_topoheight(), header.get_top_hash(), state_hash)),
+        Err(e) => manager.error(format!("Error while loading snapshot: {}", e))
+    };
+    Ok(())
+}
+
+async fn migrate_db<S: Storage>(manager: &CommandManager<Arc<Blockchain<S>>>, mut arguments: ArgumentManager) -> Result<(), CommandError> {
+    let dry_run = if arguments.has_argument("dry_run") {
+        arguments.get_value("dry_run")?.to_bool()?
+    } else {
+        false
+    };
+
+    let from_version = if arguments.has_argument("from_version") {
+        Some(arguments.get_value("from_version")?.to_number()?)
+    } else {
+        None
+    };
+
+    let lock = manager.get_data().lock()?;
+    let blockchain = lock.as_ref().ok_or(CommandError::NoData)?;
+    let mut storage = blockchain.get_storage().write().await;
+    let version = storage.get_db_version().context("Error while retrieving database version")?;
+    manager.message(format!("Database schema version: {} (latest: {})", version, DB_VERSION));
+
+    let reports = match storage.migrate_db(from_version, dry_run).await {
+        Ok(reports) => reports,
+        Err(e) => {
+            manager.error(format!("Error while migrating database: {}", e));
+            return Ok(())
+        }
+    };
+
+    if reports.is_empty() {
+        manager.message("No migration to apply");
+        return Ok(())
+    }
+
+    for report in reports {
+        if dry_run {
+            manager.message(format!("Migration {} ({}) would change {} entries", report.version, report.description, report.changes));
+        } else {
+            manager.message(format!("Migration {} ({}) changed {} entries", report.version, report.description, report.changes));
+        }
+    }
+
+    if !dry_run {
+        manager.message(format!("Database is now at version {}", storage.get_db_version().context("Error while retrieving database version")?));
+    }
+    Ok(())
+}
+
+async fn check_db<S: Storage>(manager: &CommandManager<Arc<Blockchain<S>>>, mut arguments: ArgumentManager) -> Result<(), CommandError> {
+    let repair = if arguments.has_argument("repair") {
+        arguments.get_value("repair")?.to_bool()?
+    } else {
+        false
+    };
+
+    let lock = manager.get_data().lock()?;
+    let blockchain = lock.as_ref().ok_or(CommandError::NoData)?;
+    manager.message(format!("Checking database{}...", if repair { " and repairing derived data" } else { "" }));
+    let reports = match blockchain.check_db(repair).await {
+        Ok(reports) => reports,
+        Err(e) => {
+            manager.error(format!("Error while checking database: {}", e));
+            return Ok(())
+        }
+    };
+
+    let mut errors = 0;
+    for report in reports {
+        if report.is_valid() {
+            manager.message(format!("{}: {} entries checked, no error", report.name, report.checked));
+            continue;
+        }
+
+        manager.error(format!("{}: {} entries checked, {} errors ({} repaired)", report.name, report.checked, report.errors.len(), report.repaired));
+        for error in &report.errors {
+            manager.error(format!("- {}", error));
+        }
+        errors += report.errors.len() - report.repaired as usize;
+    }
+
+    if errors == 0 {
+        manager.message("Database is consistent");
+    } else {
+        manager.error(format!("{} errors can't be repaired automatically", errors));
+    }
+    Ok(())
+}
+
 async fn status<S: Storage>(manager: &CommandManager<Arc<Blockchain<S>>>, _: ArgumentManager) -> Result<(), CommandError> {
     let lock = manager.get_data().lock()?;
     let blockchain = lock.as_ref().ok_or(CommandError::NoData)?;
@@ -439,12 +635,12 @@ async fn status<S: Storage>(manager: &CommandManager<Arc<Blockchain<S>>>, _: Arg
     manager.message(format!("Stable Height: {}", stableheight));
     manager.message(format!("Topo Height: {}", topoheight));
     manager.message(format!("Difficulty: {}", format_difficulty(difficulty)));
-    manager.message(format!("Network Hashrate: {}", format_hashrate((difficulty / (BLOCK_TIME_MILLIS / MILLIS_PER_SECOND) as Difficulty) as f64)));
+    manager.message(format!("Network Hashrate: {}", format_hashrate(difficulty as f64 / (blockchain.get_block_time_target() as f64 / MILLIS_PER_SECOND as f64))));
     manager.message(format!("Top block hash: {}", top_block_hash));
     manager.message(format!("Average Block Time: {:.2}s", avg_block_time as f64 / MILLIS_PER_SECOND as f64));
-    manager.message(format!("Target Block Time: {:.2}s", BLOCK_TIME_MILLIS as f64 / MILLIS_PER_SECOND as f64));
+    manager.message(format!("Target Block Time: {:.2}s", blockchain.get_block_time_target() as f64 / MILLIS_PER_SECOND as f64));
     manager.message(format!("Current Supply: {} XELIS", format_xelis(supply)));
-    manager.message(format!("Current Block Reward: {} XELIS", format_xelis(get_block_reward(supply))));
+    manager.message(format!("Current Block Reward: {} XELIS", format_xelis(get_block_reward(supply, blockchain.get_block_time_target()))));
 
     manager.message(format!("Tips ({}):", tips.len()));
     for hash in tips {
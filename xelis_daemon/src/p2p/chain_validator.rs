@@ -72,10 +72,13 @@ impl<S: Storage> ChainValidator<S> {
             }
         }
 
-        let pow_hash = header.get_pow_hash();
-        trace!("POW hash: {}", pow_hash);
-        let difficulty = self.blockchain.verify_proof_of_work(self, &pow_hash, &tips).await?;
-        let cumulative_difficulty = 0;
+        let difficulty = self.blockchain.verify_proof_of_work(self, &header).await?;
+        // work of its heaviest tip plus its own, only used to select the best tip in the difficulty of next blocks
+        let mut cumulative_difficulty = 0;
+        for tip in tips {
+            cumulative_difficulty = cumulative_difficulty.max(self.get_cumulative_difficulty_for_block_hash(tip).await?);
+        }
+        let cumulative_difficulty = cumulative_difficulty + difficulty;
 
         let hash = Arc::new(hash);
         self.blocks.insert(hash.clone(), Data { header: Arc::new(header), difficulty, cumulative_difficulty });
@@ -84,6 +87,26 @@ impl<S: Storage> ChainValidator<S> {
         Ok(())
     }
 
+    // check that the block reaches the ancestor through the blocks inserted
+    pub fn is_descendant_of(&self, hash: &Hash, ancestor: &Hash) -> bool {
+        let mut queue = vec![hash];
+        let mut visited = HashSet::new();
+        while let Some(hash) = queue.pop() {
+            if hash == ancestor {
+                return true
+            }
+
+            if let Some(data) = self.blocks.get(hash) {
+                for tip in data.header.get_tips() {
+                    if visited.insert(tip) {
+                        queue.push(tip);
+                    }
+                }
+            }
+        }
+        false
+    }
+
     // retrieve the whole chain order maintained internally
     pub fn get_order(&mut self) -> Vec<Arc<Hash>> {
         let order = std::mem::replace(&mut self.order, Vec::new());
@@ -53,6 +53,8 @@ pub enum P2pError {
     InvalidNetwork,
     #[error("Invalid network ID")]
     InvalidNetworkID,
+    #[error("Peer has a different hard forks schedule {}", _0)]
+    HardForksMismatch(Hash),
     #[error("Peer id {} is already used!", _0)]
     PeerIdAlreadyUsed(u64),
     #[error("Peer already connected: {}", _0)]
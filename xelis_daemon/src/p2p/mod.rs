@@ -12,7 +12,7 @@ use xelis_common::{
     config::VERSION,
     serializer::Serializer,
     crypto::hash::{Hashable, Hash},
-    block::{BlockHeader, Block, Difficulty},
+    block::{BlockHeader, Block, Difficulty, STATE_ROOT_BLOCK_VERSION},
     utils::get_current_time,
     immutable::Immutable,
     api::daemon::{NotifyEvent, PeerPeerDisconnectedEvent, Direction}
@@ -20,7 +20,7 @@ use xelis_common::{
 use crate::{
     core::{
         blockchain::Blockchain,
-        storage::Storage,
+        storage::{Storage, DifficultyProvider},
         error::BlockchainError
     },
     p2p::{
@@ -40,7 +40,7 @@ use crate::{
     config::{
         NETWORK_ID, SEED_NODES, MAX_BLOCK_SIZE, CHAIN_SYNC_DELAY, P2P_PING_DELAY, CHAIN_SYNC_REQUEST_MAX_BLOCKS,
         P2P_PING_PEER_LIST_DELAY, P2P_PING_PEER_LIST_LIMIT, STABLE_LIMIT, PEER_FAIL_LIMIT,
-        CHAIN_SYNC_TOP_BLOCKS, GENESIS_BLOCK_HASH, PRUNE_SAFETY_LIMIT, P2P_EXTEND_PEERLIST_DELAY,
+        CHAIN_SYNC_TOP_BLOCKS, PRUNE_SAFETY_LIMIT, P2P_EXTEND_PEERLIST_DELAY,
         TIPS_LIMIT, PEER_TIMEOUT_INIT_CONNECTION, CHAIN_SYNC_DEFAULT_RESPONSE_BLOCKS
     },
     rpc::rpc::get_peer_entry
@@ -322,11 +322,17 @@ impl<S: Storage> P2pServer<S> {
             return Err(P2pError::PeerIdAlreadyUsed(handshake.get_peer_id()));
         }
 
-        if *handshake.get_block_genesis_hash() != *GENESIS_BLOCK_HASH {
+        if handshake.get_block_genesis_hash() != self.blockchain.get_genesis_hash() {
             debug!("Invalid genesis block hash {}", handshake.get_block_genesis_hash());
             return Err(P2pError::InvalidHandshake)
         }
 
+        // peer must follow the same hard forks, at the same heights
+        if handshake.get_hard_forks_hash() != self.blockchain.get_hard_forks_hash() {
+            warn!("Peer {} has a hard forks schedule {} different from ours {}, one of us must update its node", connection, handshake.get_hard_forks_hash(), self.blockchain.get_hard_forks_hash());
+            return Err(P2pError::HardForksMismatch(handshake.get_hard_forks_hash().clone()))
+        }
+
         if let Some(pruned_topoheight) = handshake.get_pruned_topoheight() {
             let topoheight = handshake.get_topoheight();
             if *pruned_topoheight > topoheight {
@@ -346,7 +352,7 @@ impl<S: Storage> P2pServer<S> {
         let topoheight = self.blockchain.get_topo_height();
         let pruned_topoheight = storage.get_pruned_topoheight()?;
         let cumulative_difficulty = storage.get_cumulative_difficulty_for_block_hash(&top_hash).await.unwrap_or(0);
-        Ok(Handshake::new(VERSION.to_owned(), *self.blockchain.get_network(), self.get_tag().clone(), NETWORK_ID, self.get_peer_id(), self.bind_address.port(), get_current_time(), topoheight, block.get_height(), pruned_topoheight, top_hash, GENESIS_BLOCK_HASH.clone(), cumulative_difficulty))
+        Ok(Handshake::new(VERSION.to_owned(), *self.blockchain.get_network(), self.get_tag().clone(), NETWORK_ID, self.get_peer_id(), self.bind_address.port(), get_current_time(), topoheight, block.get_height(), pruned_topoheight, top_hash, self.blockchain.get_genesis_hash().clone(), cumulative_difficulty, self.blockchain.get_hard_forks_hash().clone()))
     }
 
     // this function handle all new connections
@@ -442,7 +448,7 @@ impl<S: Storage> P2pServer<S> {
             match storage.get_top_block_hash().await {
                 Err(e) => {
                     error!("Couldn't get the top block hash from storage for generic ping packet: {}", e);
-                    (0, GENESIS_BLOCK_HASH.clone(), pruned_topoheight)
+                    (0, self.blockchain.get_genesis_hash().clone(), pruned_topoheight)
                 },
                 Ok(hash) => (storage.get_cumulative_difficulty_for_block_hash(&hash).await.unwrap_or(0), hash, pruned_topoheight)
             }
@@ -1432,6 +1438,9 @@ impl<S: Storage> P2pServer<S> {
             // then, verify if it's a priority node, otherwise, check if we are connected to a priority node so only him can rewind us
             && (peer.is_priority() || !self.is_connected_to_a_synced_priority_node().await)
         {
+            // even a priority node can't rewind us below a checkpoint
+            self.blockchain.verify_reorg_above_checkpoints(common_point.get_topoheight() + 1)?;
+
             // check that if we can trust him
             if peer.is_priority() {
                 warn!("Rewinding chain without checking because {} is a priority node (pop count: {})", peer, pop_count);
@@ -1732,8 +1741,14 @@ impl<S: Storage> P2pServer<S> {
         let response = match request {
             StepRequest::ChainInfo(blocks) => {
                 let common_point = self.find_common_point(&*storage, blocks).await?;
-                let tips = storage.get_tips().await?;
-                let (hash, height) = self.blockchain.find_common_base(&storage, &tips).await?;
+                // stable point is the common base of the top block tips, so the top block commits its state root
+                let (top_block, _) = storage.get_top_block_header().await?;
+                let (hash, height) = if top_block.get_tips().is_empty() {
+                    let tips = storage.get_tips().await?;
+                    self.blockchain.find_common_base(&storage, &tips).await?
+                } else {
+                    self.blockchain.find_common_base(&storage, top_block.get_tips()).await?
+                };
                 let stable_topo = storage.get_topo_height_for_hash(&hash).await?;
                 StepResponse::ChainInfo(common_point, stable_topo, height, hash)
             },
@@ -1750,19 +1765,30 @@ impl<S: Storage> P2pServer<S> {
                 } else {
                     None
                 };
-                StepResponse::Assets(assets, page)
+
+                let mut states = Vec::with_capacity(assets.len());
+                for asset in assets.iter() {
+                    states.push(self.blockchain.get_asset_state(&*storage, asset.get_asset(), max).await?);
+                }
+                StepResponse::Assets(assets, states, page)
             },
             StepRequest::Balances(topoheight, asset, keys) => {
                 let balances = storage.get_balances(&asset, keys.iter(), topoheight).await?;
-                StepResponse::Balances(balances)
+                let mut locked = Vec::with_capacity(keys.len());
+                for key in keys.iter() {
+                    locked.push(self.blockchain.get_pending_locked_balances(&*storage, key, &asset, topoheight).await?);
+                }
+                StepResponse::Balances(balances, locked)
             },
             StepRequest::Nonces(topoheight, keys) => {
                 let mut nonces = Vec::with_capacity(keys.len());
+                let mut multisigs = Vec::with_capacity(keys.len());
                 for key in keys.iter() {
                     let nonce = storage.get_nonce_at_maximum_topoheight(key, topoheight).await?.map(|(_, v)| v.get_nonce()).unwrap_or(0);
                     nonces.push(nonce);
+                    multisigs.push(storage.get_multisig_at_maximum_topoheight(key, topoheight).await?);
                 }
-                StepResponse::Nonces(nonces)
+                StepResponse::Nonces(nonces, multisigs)
             },
             StepRequest::Keys(min, max, page) => {
                 if min > max {
@@ -1799,6 +1825,39 @@ impl<S: Storage> P2pServer<S> {
                 }
                 StepResponse::BlocksMetadata(blocks)
             },
+            StepRequest::StateRoot(topoheight) => {
+                // search from the top the block committing the state root at this topoheight
+                // it is sent after all the blocks of its past above this topoheight, so its PoW and ancestry can be verified
+                let hash = storage.get_hash_at_topo_height(topoheight).await?;
+                let mut headers = Vec::new();
+                for topo in (topoheight + 1..=self.blockchain.get_topo_height()).rev().take(PRUNE_SAFETY_LIMIT as usize) {
+                    let (block_hash, block) = storage.get_block_header_at_topoheight(topo).await?;
+                    if block.get_version() < STATE_ROOT_BLOCK_VERSION || block.get_tips().is_empty() {
+                        continue;
+                    }
+
+                    let (base_hash, _) = self.blockchain.find_common_base(&storage, block.get_tips()).await?;
+                    if base_hash == hash {
+                        let mut blocks = vec![(topo, block.as_ref().clone())];
+                        let mut queue = vec![block_hash];
+                        let mut visited = HashSet::new();
+                        while let Some(block_hash) = queue.pop() {
+                            for tip in storage.get_past_blocks_for_block_hash(&block_hash).await?.iter() {
+                                let tip_topoheight = storage.get_topo_height_for_hash(tip).await?;
+                                if tip_topoheight > topoheight && visited.insert(tip.clone()) {
+                                    blocks.push((tip_topoheight, storage.get_block_header_by_hash(tip).await?.as_ref().clone()));
+                                    queue.push(tip.clone());
+                                }
+                            }
+                        }
+
+                        blocks.sort_by_key(|(topoheight, _)| *topoheight);
+                        headers = blocks.into_iter().map(|(_, header)| header).collect();
+                        break;
+                    }
+                }
+                StepResponse::StateRoot(headers)
+            },
         };
         peer.send_packet(Packet::BootstrapChainResponse(BootstrapChainResponse::new(response))).await?;
         Ok(())
@@ -1852,6 +1911,10 @@ impl<S: Storage> P2pServer<S> {
         let mut top_topoheight: u64 = 0;
         let mut top_height: u64 = 0;
         let mut top_block_hash: Option<Hash> = None;
+        // lowest topoheight of the blocks saved, chain is pruned below it
+        let mut lowest_topoheight = 0;
+        // work advertised by the peer when we selected it, it must be backed by the blocks verified
+        let peer_cumulative_difficulty = peer.get_cumulative_difficulty();
 
         let mut all_assets = HashSet::new();
         loop {
@@ -1884,6 +1947,7 @@ impl<S: Storage> P2pServer<S> {
                             } else {
                                 our_topoheight - common_point.get_topoheight()
                             };
+                            self.blockchain.verify_reorg_above_checkpoints(our_topoheight - pop_count + 1)?;
                             our_topoheight = self.blockchain.rewind_chain_for_storage(&mut *storage, pop_count).await?;
                             debug!("New topoheight after rewind is now {}", our_topoheight);
                         }
@@ -1892,6 +1956,7 @@ impl<S: Storage> P2pServer<S> {
                         return Err(BlockchainError::Unknown)
                     }
 
+                    self.blockchain.verify_checkpoint(topoheight, &hash)?;
                     top_topoheight = topoheight;
                     top_height = height;
                     top_block_hash = Some(hash);
@@ -1900,12 +1965,13 @@ impl<S: Storage> P2pServer<S> {
                     Some(StepRequest::Assets(our_topoheight, topoheight, None))
                 },
                 // fetch all assets from peer
-                StepResponse::Assets(assets, next_page) => {
+                StepResponse::Assets(assets, states, next_page) => {
                     let mut storage = self.blockchain.get_storage().write().await;
-                    for asset in assets {
+                    for (asset, state) in assets.into_iter().zip(states) {
                         let (asset, data) = asset.consume();
                         debug!("Saving asset {} at topoheight {}", asset, stable_topoheight);
                         storage.add_asset(&asset, data).await?;
+                        Blockchain::set_asset_state(&mut *storage, &asset, stable_topoheight, &state)?;
                         all_assets.insert(asset);
                     }
 
@@ -1919,7 +1985,7 @@ impl<S: Storage> P2pServer<S> {
                 // fetch all new accounts
                 StepResponse::Keys(keys, next_page) => {
                     debug!("Requesting nonces for keys");
-                    let StepResponse::Nonces(nonces) = peer.request_boostrap_chain(StepRequest::Nonces(stable_topoheight, Cow::Borrowed(&keys))).await? else {
+                    let StepResponse::Nonces(nonces, multisigs) = peer.request_boostrap_chain(StepRequest::Nonces(stable_topoheight, Cow::Borrowed(&keys))).await? else {
                         // shouldn't happen
                         error!("Received an invalid StepResponse (how ?) while fetching nonces");
                         return Err(P2pError::InvalidPacket.into())
@@ -1928,9 +1994,12 @@ impl<S: Storage> P2pServer<S> {
                     {
                         let mut storage = self.blockchain.get_storage().write().await;
                         // save all nonces
-                        for (key, nonce) in keys.iter().zip(nonces) {
+                        for ((key, nonce), multisig) in keys.iter().zip(nonces).zip(multisigs) {
                             debug!("Saving nonce {} for {}", nonce, key);
                             storage.set_nonce_at_topoheight(key, nonce, stable_topoheight).await?;
+                            if let Some(setup) = &multisig {
+                                storage.set_multisig_at_topoheight(key, stable_topoheight, Some(setup))?;
+                            }
                         }
                     }
 
@@ -1938,7 +2007,7 @@ impl<S: Storage> P2pServer<S> {
                     // otherwise in really long time, it may consume lot of memory
                     for asset in &all_assets {
                         debug!("Request balances for asset {}", asset);
-                        let StepResponse::Balances(balances) = peer.request_boostrap_chain(StepRequest::Balances(stable_topoheight, Cow::Borrowed(&asset), Cow::Borrowed(&keys))).await? else {
+                        let StepResponse::Balances(balances, locked) = peer.request_boostrap_chain(StepRequest::Balances(stable_topoheight, Cow::Borrowed(&asset), Cow::Borrowed(&keys))).await? else {
                             // shouldn't happen
                             error!("Received an invalid StepResponse (how ?) while fetching balances");
                             return Err(P2pError::InvalidPacket.into())
@@ -1946,7 +2015,7 @@ impl<S: Storage> P2pServer<S> {
 
                         // save all balances for this asset
                         let mut storage = self.blockchain.get_storage().write().await;
-                        for (key, balance) in keys.iter().zip(balances) {
+                        for ((key, balance), locked) in keys.iter().zip(balances).zip(locked) {
                             // check that the account have balance for this asset
                             if let Some(balance) = balance {
                                 debug!("Saving balance {} for key {} at topoheight {}", balance, key, stable_topoheight);
@@ -1955,6 +2024,10 @@ impl<S: Storage> P2pServer<S> {
                                 storage.set_balance_to(key, &asset, stable_topoheight, &versioned_balance).await?;
                                 storage.set_last_topoheight_for_balance(key, &asset, stable_topoheight)?;
                             }
+
+                            for locked in locked {
+                                storage.add_locked_balance(key, &asset, stable_topoheight, locked.get_unlock(), locked.get_amount()).await?;
+                            }
                         }
                     }
 
@@ -1966,7 +2039,8 @@ impl<S: Storage> P2pServer<S> {
                     }
                 },
                 StepResponse::BlocksMetadata(blocks) => {
-                    let mut lowest_topoheight = stable_topoheight;
+                    lowest_topoheight = stable_topoheight;
+                    let mut saved = Vec::with_capacity(blocks.len());
                     for (i, metadata) in blocks.into_iter().enumerate() {
                         // check that we don't already have this block in storage
                         if self.blockchain.has_block(&metadata.hash).await? {
@@ -1980,6 +2054,9 @@ impl<S: Storage> P2pServer<S> {
                             return Err(P2pError::InvalidPacket.into())
                         };
 
+                        // its difficulty can't be computed without its past, but its PoW must reach it
+                        self.blockchain.verify_proof_of_work_at_difficulty(&header, metadata.difficulty)?;
+
                         let mut txs = Vec::with_capacity(header.get_txs_hashes().len());
                         debug!("Retrieving {} txs for block {}", header.get_txs_count(), hash);
                         for tx_hash in header.get_txs_hashes() {
@@ -1997,6 +2074,8 @@ impl<S: Storage> P2pServer<S> {
                             txs.push(tx);
                         }
 
+                        self.blockchain.verify_checkpoint(lowest_topoheight, &hash)?;
+
                         // link its TX to the block
                         let mut storage = self.blockchain.get_storage().write().await;
                         for tx_hash in header.get_txs_hashes() {
@@ -2011,7 +2090,93 @@ impl<S: Storage> P2pServer<S> {
                         storage.set_cumulative_difficulty_for_block_hash(&hash, metadata.cumulative_difficulty).await?;
 
                         // save the block with its transactions, difficulty
-                        storage.save_block(Arc::new(header), &txs, metadata.difficulty, hash).await?;
+                        let header = Arc::new(header);
+                        storage.save_block(header.clone(), &txs, metadata.difficulty, hash.clone()).await?;
+                        saved.push((hash, header, metadata.difficulty));
+                    }
+
+                    // the difficulty of a block is computed from its tips and their own tips, so it is verified once they are saved
+                    {
+                        let storage = self.blockchain.get_storage().read().await;
+                        'blocks: for (hash, header, difficulty) in saved {
+                            for tip in header.get_tips() {
+                                if !storage.has_block(tip).await? {
+                                    continue 'blocks;
+                                }
+
+                                for parent in storage.get_past_blocks_for_block_hash(tip).await?.iter() {
+                                    if !storage.has_block(parent).await? {
+                                        continue 'blocks;
+                                    }
+                                }
+                            }
+
+                            let expected = self.blockchain.get_difficulty_at_tips(&*storage, header.get_tips()).await?;
+                            if expected != difficulty {
+                                error!("Block {} from {} has a difficulty {} while {} is expected", hash, peer, difficulty, expected);
+                                return Err(BlockchainError::InvalidDifficulty)
+                            }
+                        }
+                    }
+
+                    Some(StepRequest::StateRoot(stable_topoheight))
+                },
+                // verify the state we built against the state root committed in a block header above it
+                // the block and its past until the stable block are verified with the difficulty computed from the saved blocks
+                StepResponse::StateRoot(headers) => {
+                    let stable_hash = top_block_hash.as_ref().expect("Expected stable block hash for fast sync");
+                    let our_root = {
+                        let storage = self.blockchain.get_storage().read().await;
+                        storage.get_state_root_at_topoheight(stable_topoheight).await?
+                    };
+                    match headers.last().map(|header| header.hash()) {
+                        Some(hash) => {
+                            if headers.len() > PRUNE_SAFETY_LIMIT as usize {
+                                error!("{} sent {} blocks to verify the state root at topoheight {}", peer, headers.len(), stable_topoheight);
+                                return Err(P2pError::InvalidPacket.into())
+                            }
+
+                            let root = headers.last().and_then(|header| header.get_state_root()).cloned().ok_or(BlockchainError::InvalidBlockStateRoot)?;
+                            // its cumulative difficulty is the one of the stable block, its base, plus the difficulty of each block of its past above it
+                            let mut cumulative_difficulty = {
+                                let storage = self.blockchain.get_storage().read().await;
+                                storage.get_cumulative_difficulty_for_block_hash(stable_hash).await?
+                            };
+                            let mut chain_validator = ChainValidator::new(self.blockchain.clone());
+                            for header in headers {
+                                if header.get_height() <= top_height {
+                                    error!("Block above the stable block sent by {} is at height {}, below the stable height {}", peer, header.get_height(), top_height);
+                                    return Err(P2pError::InvalidPacket.into())
+                                }
+                                let block_hash = header.hash();
+                                chain_validator.insert_block(block_hash.clone(), header).await?;
+                                cumulative_difficulty += chain_validator.get_difficulty_for_block_hash(&block_hash).await?;
+                            }
+
+                            if !chain_validator.is_descendant_of(&hash, stable_hash) {
+                                error!("Block {} from {} doesn't descend from the stable block {}", hash, peer, stable_hash);
+                                return Err(BlockchainError::StateRootBlockNotDescendant(hash, stable_hash.clone()))
+                            }
+
+                            if cumulative_difficulty < peer_cumulative_difficulty {
+                                error!("Block {} from {} has a cumulative difficulty {} below the {} advertised", hash, peer, cumulative_difficulty, peer_cumulative_difficulty);
+                                return Err(BlockchainError::InvalidCumulativeDifficulty(hash))
+                            }
+
+                            if root != our_root {
+                                error!("State root at topoheight {} is {} while block {} from {} commits {}", stable_topoheight, our_root, hash, peer, root);
+                                return Err(BlockchainError::StateRootMismatch(root, our_root))
+                            }
+
+                            info!("State root {} at topoheight {} verified with block {} from {}", root, stable_topoheight, hash, peer);
+                        },
+                        None if self.blockchain.get_version_at_height(top_height + 1) >= STATE_ROOT_BLOCK_VERSION => {
+                            error!("{} didn't send the block committing the state root at topoheight {}", peer, stable_topoheight);
+                            return Err(P2pError::InvalidPacket.into())
+                        },
+                        None => {
+                            warn!("State at topoheight {} from {} can't be verified before the state root hard fork, {} must be trusted", stable_topoheight, peer, peer);
+                        }
                     }
 
                     let mut storage = self.blockchain.get_storage().write().await;
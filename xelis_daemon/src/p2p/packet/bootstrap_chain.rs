@@ -4,8 +4,10 @@ use log::debug;
 use xelis_common::{
     crypto::{hash::Hash, key::PublicKey},
     serializer::{Serializer, ReaderError, Reader, Writer},
-    block::Difficulty,
-    asset::AssetWithData
+    block::{BlockHeader, Difficulty},
+    asset::AssetWithData,
+    account::LockedBalance,
+    transaction::MultiSigSetup
 };
 use super::chain::{BlockId, CommonPoint};
 use crate::config::CHAIN_SYNC_REQUEST_MAX_BLOCKS;
@@ -55,6 +57,37 @@ impl Serializer for BlockMetadata {
     }
 }
 
+// Supply and mint authority of an asset, they can't be computed from blocks in a fast sync
+#[derive(Debug)]
+pub struct AssetState {
+    pub supply: u64,
+    pub authority: Option<PublicKey>
+}
+
+impl Serializer for AssetState {
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let supply = reader.read_u64()?;
+        let authority = if reader.read_bool()? {
+            Some(PublicKey::read(reader)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            supply,
+            authority
+        })
+    }
+
+    fn write(&self, writer: &mut Writer) {
+        writer.write_u64(&self.supply);
+        writer.write_bool(self.authority.is_some());
+        if let Some(key) = &self.authority {
+            key.write(writer);
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Ord, PartialOrd)]
 pub enum StepKind {
     ChainInfo,
@@ -62,7 +95,8 @@ pub enum StepKind {
     Keys,
     Balances,
     Nonces,
-    BlocksMetadata
+    BlocksMetadata,
+    StateRoot
 }
 
 impl StepKind {
@@ -73,7 +107,8 @@ impl StepKind {
             Self::Keys => Self::Balances,
             Self::Balances => Self::Nonces,
             Self::Nonces => Self::BlocksMetadata,
-            Self::BlocksMetadata => return None
+            Self::BlocksMetadata => Self::StateRoot,
+            Self::StateRoot => return None
         })
     }
 }
@@ -90,6 +125,8 @@ pub enum StepRequest<'a> {
     Balances(u64, Cow<'a, Hash>, Cow<'a, IndexSet<PublicKey>>),
     // Max topoheight, Accounts
     Nonces(u64, Cow<'a, IndexSet<PublicKey>>),
+    // Request the state root at topoheight
+    StateRoot(u64),
     // Request blocks metadata starting topoheight
     BlocksMetadata(u64)
 }
@@ -102,6 +139,7 @@ impl<'a> StepRequest<'a> {
             Self::Keys(_, _, _) => StepKind::Keys,
             Self::Balances(_, _, _) => StepKind::Balances,
             Self::Nonces(_, _) => StepKind::Nonces,
+            Self::StateRoot(_) => StepKind::StateRoot,
             Self::BlocksMetadata(_) => StepKind::BlocksMetadata
         }
     }
@@ -113,6 +151,7 @@ impl<'a> StepRequest<'a> {
             Self::Keys(_, topo, _) => topo,
             Self::Balances(topo, _, _) => topo,
             Self::Nonces(topo, _) => topo,
+            Self::StateRoot(topo) => topo,
             Self::BlocksMetadata(topo) => topo
         })
     }
@@ -182,6 +221,9 @@ impl Serializer for StepRequest<'_> {
             5 => {
                 Self::BlocksMetadata(reader.read_u64()?)
             },
+            6 => {
+                Self::StateRoot(reader.read_u64()?)
+            },
             id => {
                 debug!("Received invalid value for StepResponse: {}", id);
                 return Err(ReaderError::InvalidValue)
@@ -225,6 +267,10 @@ impl Serializer for StepRequest<'_> {
                 writer.write_u8(5);
                 writer.write_u64(topoheight);
             },
+            Self::StateRoot(topoheight) => {
+                writer.write_u8(6);
+                writer.write_u64(topoheight);
+            },
         };
     }
 }
@@ -232,21 +278,23 @@ impl Serializer for StepRequest<'_> {
 #[derive(Debug)]
 pub enum StepResponse {
     ChainInfo(Option<CommonPoint>, u64, u64, Hash), // common point, topoheight of stable hash, stable height, stable hash
-    Assets(IndexSet<AssetWithData>, Option<u64>), // Set of assets, pagination
+    Assets(IndexSet<AssetWithData>, Vec<AssetState>, Option<u64>), // Set of assets, state of each asset, pagination
     Keys(IndexSet<PublicKey>, Option<u64>), // Set of keys, pagination
-    Balances(Vec<Option<u64>>), // Balances requested
-    Nonces(Vec<u64>), // Nonces for requested accounts
+    Balances(Vec<Option<u64>>, Vec<Vec<LockedBalance>>), // Balances requested, amounts still locked in each one
+    Nonces(Vec<u64>, Vec<Option<MultiSigSetup>>), // Nonces and multisig setup for requested accounts
     BlocksMetadata(Vec<BlockMetadata>), // top blocks metadata
+    StateRoot(Vec<BlockHeader>), // block committing the state root at requested topoheight, after all its past blocks above it
 }
 
 impl StepResponse {
     pub fn kind(&self) -> StepKind {
         match self {
             Self::ChainInfo(_, _, _, _) => StepKind::ChainInfo,
-            Self::Assets(_, _) => StepKind::Assets,
+            Self::Assets(_, _, _) => StepKind::Assets,
             Self::Keys(_, _) => StepKind::Keys,
-            Self::Balances(_) => StepKind::Balances,
-            Self::Nonces(_) => StepKind::Nonces,
+            Self::Balances(_, _) => StepKind::Balances,
+            Self::Nonces(_, _) => StepKind::Nonces,
+            Self::StateRoot(_) => StepKind::StateRoot,
             Self::BlocksMetadata(_) => StepKind::BlocksMetadata
         }
     }
@@ -265,6 +313,12 @@ impl Serializer for StepResponse {
             },
             1 => {
                 let assets = IndexSet::<AssetWithData>::read(reader)?;
+                let states = Vec::<AssetState>::read(reader)?;
+                if states.len() != assets.len() {
+                    debug!("Invalid assets states count in Step Response");
+                    return Err(ReaderError::InvalidSize)
+                }
+
                 let page = Option::read(reader)?;
                 if let Some(page_number) = &page {
                     if *page_number == 0 {
@@ -272,7 +326,7 @@ impl Serializer for StepResponse {
                         return Err(ReaderError::InvalidValue)
                     }
                 }
-                Self::Assets(assets, page)
+                Self::Assets(assets, states, page)
             },
             2 => {
                 let keys = IndexSet::<PublicKey>::read(reader)?;
@@ -286,14 +340,29 @@ impl Serializer for StepResponse {
                 Self::Keys(keys, page)
             },
             3 => {
-                Self::Balances(Vec::<Option<u64>>::read(reader)?)
+                let balances = Vec::<Option<u64>>::read(reader)?;
+                let locked = Vec::<Vec<LockedBalance>>::read(reader)?;
+                if locked.len() != balances.len() {
+                    debug!("Invalid locked balances count in Step Response");
+                    return Err(ReaderError::InvalidSize)
+                }
+                Self::Balances(balances, locked)
             },
             4 => {
-                Self::Nonces(Vec::<u64>::read(reader)?)
+                let nonces = Vec::<u64>::read(reader)?;
+                let multisigs = Vec::<Option<MultiSigSetup>>::read(reader)?;
+                if multisigs.len() != nonces.len() {
+                    debug!("Invalid multisig count in Step Response");
+                    return Err(ReaderError::InvalidSize)
+                }
+                Self::Nonces(nonces, multisigs)
             },
             5 => {
                 Self::BlocksMetadata(Vec::<BlockMetadata>::read(reader)?)
             },
+            6 => {
+                Self::StateRoot(Vec::<BlockHeader>::read(reader)?)
+            },
             id => {
                 debug!("Received invalid value for StepResponse: {}", id);
                 return Err(ReaderError::InvalidValue)
@@ -310,9 +379,10 @@ impl Serializer for StepResponse {
                 writer.write_u64(stable_height);
                 writer.write_hash(hash);
             },
-            Self::Assets(assets, page) => {
+            Self::Assets(assets, states, page) => {
                 writer.write_u8(1);
                 assets.write(writer);
+                states.write(writer);
                 page.write(writer);
             },
             Self::Keys(keys, page) => {
@@ -320,17 +390,23 @@ impl Serializer for StepResponse {
                 keys.write(writer);
                 page.write(writer);
             },
-            Self::Balances(balances) => {
+            Self::Balances(balances, locked) => {
                 writer.write_u8(3);
                 balances.write(writer);
+                locked.write(writer);
             },
-            Self::Nonces(nonces) => {
+            Self::Nonces(nonces, multisigs) => {
                 writer.write_u8(4);
                 nonces.write(writer);
+                multisigs.write(writer);
             },
             Self::BlocksMetadata(blocks) => {
                 writer.write_u8(5);
                 blocks.write(writer);
+            },
+            Self::StateRoot(headers) => {
+                writer.write_u8(6);
+                headers.write(writer);
             }
         };
     }
@@ -4,6 +4,7 @@ use xelis_common::{
     crypto::hash::Hash, network::Network, block::Difficulty
 };
 
+use crate::config::P2P_PROTOCOL_VERSION;
 use crate::p2p::peer_list::SharedPeerList;
 use crate::p2p::connection::Connection;
 use crate::p2p::peer::Peer;
@@ -29,12 +30,13 @@ pub struct Handshake {
     top_hash: Hash, // current block top hash
     genesis_hash: Hash, // genesis hash
     cumulative_difficulty: Difficulty,
+    hard_forks_hash: Hash, // hash of its hard forks schedule
 } // Server reply with his own list of peers, but we remove all already known by requester for the response.
 
 impl Handshake {
     pub const MAX_LEN: usize = 16;
 
-    pub fn new(version: String, network: Network, node_tag: Option<String>, network_id: [u8; 16], peer_id: u64, local_port: u16, utc_time: u64, topoheight: u64, height: u64, pruned_topoheight: Option<u64>, top_hash: Hash, genesis_hash: Hash, cumulative_difficulty: Difficulty) -> Self {
+    pub fn new(version: String, network: Network, node_tag: Option<String>, network_id: [u8; 16], peer_id: u64, local_port: u16, utc_time: u64, topoheight: u64, height: u64, pruned_topoheight: Option<u64>, top_hash: Hash, genesis_hash: Hash, cumulative_difficulty: Difficulty, hard_forks_hash: Hash) -> Self {
         debug_assert!(version.len() > 0 && version.len() <= Handshake::MAX_LEN); // version cannot be greater than 16 chars
         if let Some(node_tag) = &node_tag {
             debug_assert!(node_tag.len() > 0 && node_tag.len() <= Handshake::MAX_LEN); // node tag cannot be greater than 16 chars
@@ -53,7 +55,8 @@ impl Handshake {
             pruned_topoheight,
             top_hash,
             genesis_hash,
-            cumulative_difficulty
+            cumulative_difficulty,
+            hard_forks_hash
         }
     }
 
@@ -105,11 +108,18 @@ impl Handshake {
     pub fn get_pruned_topoheight(&self) -> &Option<u64> {
         &self.pruned_topoheight
     }
+
+    pub fn get_hard_forks_hash(&self) -> &Hash {
+        &self.hard_forks_hash
+    }
 }
 
 impl Serializer for Handshake {
-    // 1 + MAX(16) + 1 + MAX(16) + 16 + 8 + 8 + 8 + 32 + 1 + 24 * 16
+    // 1 + 1 + MAX(16) + 1 + MAX(16) + 16 + 8 + 8 + 8 + 32 + 1 + 24 * 16 + 32
     fn write(&self, writer: &mut Writer) {
+        // P2P protocol version, the rest of the packet depends on it
+        writer.write_u8(P2P_PROTOCOL_VERSION);
+
         // daemon version
         writer.write_string(&self.version);
 
@@ -129,6 +139,7 @@ impl Serializer for Handshake {
         writer.write_hash(&self.top_hash); // Block Top Hash (32 bytes)
         writer.write_hash(&self.genesis_hash); // Genesis Hash
         self.cumulative_difficulty.write(writer); // Cumulative Difficulty
+        writer.write_hash(&self.hard_forks_hash); // Hard Forks Hash
     }
 
     fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
@@ -136,6 +147,13 @@ impl Serializer for Handshake {
         // we must verify the correct size each time we want to read from the data sent by the client
         // if we don't verify each time, it can create a panic error and crash the node
 
+        // P2P protocol version
+        let protocol_version = reader.read_u8()?;
+        if protocol_version != P2P_PROTOCOL_VERSION {
+            debug!("Peer uses P2P protocol version {} while {} is required", protocol_version, P2P_PROTOCOL_VERSION);
+            return Err(ReaderError::InvalidValue)
+        }
+
         // Daemon version
         let version = reader.read_string()?;
         if version.len() == 0 || version.len() > Handshake::MAX_LEN {
@@ -169,8 +187,9 @@ impl Serializer for Handshake {
         let top_hash = reader.read_hash()?;
         let genesis_hash = reader.read_hash()?;
         let cumulative_difficulty = Difficulty::read(reader)?;
+        let hard_forks_hash = reader.read_hash()?;
 
-        Ok(Handshake::new(version, network, node_tag, network_id, peer_id, local_port, utc_time, topoheight, height, pruned_topoheight, top_hash, genesis_hash, cumulative_difficulty))
+        Ok(Handshake::new(version, network, node_tag, network_id, peer_id, local_port, utc_time, topoheight, height, pruned_topoheight, top_hash, genesis_hash, cumulative_difficulty, hard_forks_hash))
     }
 }
 
@@ -183,6 +202,6 @@ impl Display for Handshake {
         } else {
             &NO_NODE_TAG
         };
-        write!(f, "Handshake[version: {}, node tag: {}, network_id: {}, peer_id: {}, utc_time: {}, block_height: {}, block_top_hash: {}]", self.get_version(), node_tag, hex::encode(self.get_network_id()), self.get_peer_id(), self.get_utc_time(), self.get_block_height(), self.get_block_top_hash())
+        write!(f, "Handshake[version: {}, node tag: {}, network_id: {}, peer_id: {}, utc_time: {}, block_height: {}, block_top_hash: {}, hard_forks_hash: {}]", self.get_version(), node_tag, hex::encode(self.get_network_id()), self.get_peer_id(), self.get_utc_time(), self.get_block_height(), self.get_block_top_hash(), self.get_hard_forks_hash())
     }
 }
\ No newline at end of file
@@ -177,10 +177,10 @@ impl<S: Storage> GetWorkServer<S> {
     // retrieve last mining job and set random extra nonce and miner public key
     // then, send it
     async fn send_new_job(self: Arc<Self>, addr: Addr<GetWorkWebSocketHandler<S>>, key: PublicKey) -> Result<(), InternalRpcError> {
-        let (mut job, height, difficulty) = {
+        let (mut job, height, difficulty, version) = {
             let mut hash = self.last_header_hash.lock().await;
             let mut mining_jobs = self.mining_jobs.lock().await;
-            let (job, height, difficulty);
+            let (job, height, difficulty, version);
             if let Some(hash) = hash.as_ref() {
                 let (header, diff) = mining_jobs.peek(hash).ok_or_else(|| {
                     error!("No mining job found! How is it possible ?");
@@ -188,6 +188,7 @@ impl<S: Storage> GetWorkServer<S> {
                 })?;
                 job = BlockMiner::new(header.get_work_hash(), get_current_timestamp());
                 height = header.height;
+                version = header.version;
                 difficulty = *diff;
             } else {
                 // generate a mining job
@@ -197,13 +198,14 @@ impl<S: Storage> GetWorkServer<S> {
 
                 job = BlockMiner::new(header.get_work_hash(), get_current_timestamp());
                 height = header.height;
+                version = header.version;
 
                 // save the mining job, and set it as last job
                 *hash = Some(job.header_work_hash.clone());
                 mining_jobs.put(job.header_work_hash.clone(), (header, difficulty));
             }
 
-            (job, height, difficulty)
+            (job, height, difficulty, version)
         };
 
         // set miner key and random extra nonce
@@ -211,7 +213,7 @@ impl<S: Storage> GetWorkServer<S> {
         OsRng.fill_bytes(&mut job.extra_nonce);
 
         debug!("Sending job to new miner");
-        addr.send(Response::NewJob(GetBlockTemplateResult { template: job.to_hex(), height, difficulty })).await.context("error while sending block template")??;
+        addr.send(Response::NewJob(GetBlockTemplateResult { template: job.to_hex(), height, difficulty, version })).await.context("error while sending block template")??;
         Ok(())
     }
 
@@ -358,6 +360,7 @@ impl<S: Storage> GetWorkServer<S> {
 
         let mut job = BlockMiner::new(header.get_work_hash(), header.timestamp);
         let height = header.height;
+        let version = header.version;
 
         // save the header used for job in cache
         {
@@ -382,7 +385,7 @@ impl<S: Storage> GetWorkServer<S> {
             // New task for each miner in case a miner is slow
             // we don't want to wait for him
             tokio::spawn(async move {
-                match addr.send(Response::NewJob(GetBlockTemplateResult { template, height, difficulty })).await {
+                match addr.send(Response::NewJob(GetBlockTemplateResult { template, height, difficulty, version })).await {
                     Ok(request) => {
                         if let Err(e) = request {
                             warn!("Error while sending new job to addr {:?}: {}", addr, e);
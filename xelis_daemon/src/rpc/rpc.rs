@@ -1,4 +1,4 @@
-use crate::{core::{blockchain::{Blockchain, get_block_reward}, storage::Storage, error::BlockchainError, mempool::Mempool}, p2p::peer::Peer, config::{DEV_FEES, MAXIMUM_SUPPLY}};
+use crate::{core::{blockchain::{Blockchain, get_block_reward}, storage::Storage, error::BlockchainError, mempool::Mempool}, p2p::peer::Peer, config::MAXIMUM_SUPPLY};
 use super::{InternalRpcError, ApiError};
 use anyhow::Context as AnyContext;
 use human_bytes::human_bytes;
@@ -22,6 +22,8 @@ use xelis_common::{
         GetBalanceAtTopoHeightParams,
         GetLastBalanceResult,
         GetInfoResult,
+        EstimateFeeResult,
+        HardForkInfo,
         GetTopBlockParams,
         GetTransactionsParams,
         TransactionResponse,
@@ -32,25 +34,37 @@ use xelis_common::{
         HasNonceResult,
         HasNonceParams,
         GetAssetParams,
+        GetAssetSupplyParams,
+        GetAssetSupplyResult,
+        GetMultisigParams,
+        GetMultisigResult,
+        GetLockedBalanceResult,
         GetAccountHistoryParams,
         AccountHistoryEntry,
         AccountHistoryType,
         GetAccountAssetsParams,
         PeerEntry,
         IsTxExecutedInBlockParams,
-        SizeOnDiskResult
+        SizeOnDiskResult,
+        GetStateRootParams,
+        GetStateRootResult,
+        GetBalanceProofParams,
+        GetBalanceProofResult,
+        GetNonceProofParams,
+        GetNonceProofResult,
+        GetAccountTransactionsParams,
+        AccountTransactionEntry
     }, DataHash},
     async_handler,
     serializer::Serializer,
     transaction::{Transaction, TransactionType},
-    crypto::hash::Hash,
+    crypto::{hash::Hash, merkle::{balance_leaf_key, nonce_leaf_key}},
     block::{BlockHeader, Block},
     config::{XELIS_ASSET, VERSION},
     immutable::Immutable,
     rpc_server::{RPCHandler, parse_params},
     context::Context
 };
-use crate::config::BLOCK_TIME_MILLIS;
 use std::{sync::Arc, borrow::Cow};
 use log::{info, debug, trace};
 
@@ -174,12 +188,15 @@ pub fn register_methods<S: Storage>(handler: &mut RPCHandler<Arc<Blockchain<S>>>
     handler.register_method("get_top_block", async_handler!(get_top_block::<S>));
     handler.register_method("submit_block", async_handler!(submit_block::<S>));
     handler.register_method("get_last_balance", async_handler!(get_last_balance::<S>));
+    handler.register_method("get_locked_balance", async_handler!(get_locked_balance::<S>));
     handler.register_method("get_balance_at_topoheight", async_handler!(get_balance_at_topoheight::<S>));
     handler.register_method("get_info", async_handler!(get_info::<S>));
     handler.register_method("get_nonce", async_handler!(get_nonce::<S>));
     handler.register_method("has_nonce", async_handler!(has_nonce::<S>));
     handler.register_method("get_asset", async_handler!(get_asset::<S>));
     handler.register_method("get_assets", async_handler!(get_assets::<S>));
+    handler.register_method("get_asset_supply", async_handler!(get_asset_supply::<S>));
+    handler.register_method("get_multisig", async_handler!(get_multisig::<S>));
     handler.register_method("count_assets", async_handler!(count_assets::<S>));
     handler.register_method("count_accounts", async_handler!(count_accounts::<S>));
     handler.register_method("count_transactions", async_handler!(count_transactions::<S>));
@@ -194,11 +211,16 @@ pub fn register_methods<S: Storage>(handler: &mut RPCHandler<Arc<Blockchain<S>>>
     handler.register_method("get_blocks_range_by_height", async_handler!(get_blocks_range_by_height::<S>));
     handler.register_method("get_transactions", async_handler!(get_transactions::<S>));
     handler.register_method("get_account_history", async_handler!(get_account_history::<S>));
+    handler.register_method("get_account_transactions", async_handler!(get_account_transactions::<S>));
     handler.register_method("get_account_assets", async_handler!(get_account_assets::<S>));
     handler.register_method("get_accounts", async_handler!(get_accounts::<S>));
     handler.register_method("is_tx_executed_in_block", async_handler!(is_tx_executed_in_block::<S>));
     handler.register_method("get_dev_fee_thresholds", async_handler!(get_dev_fee_thresholds::<S>));
     handler.register_method("get_size_on_disk", async_handler!(get_size_on_disk::<S>));
+    handler.register_method("get_state_root", async_handler!(get_state_root::<S>));
+    handler.register_method("get_balance_proof", async_handler!(get_balance_proof::<S>));
+    handler.register_method("get_nonce_proof", async_handler!(get_nonce_proof::<S>));
+    handler.register_method("estimate_fee", async_handler!(estimate_fee::<S>));
 }
 
 async fn version<S: Storage>(_: Context, body: Value) -> Result<Value, InternalRpcError> {
@@ -270,7 +292,8 @@ async fn get_block_template<S: Storage>(context: Context, body: Value) -> Result
     let block = blockchain.get_block_template_for_storage(&storage, params.address.into_owned().to_public_key()).await.context("Error while retrieving block template")?;
     let difficulty = blockchain.get_difficulty_at_tips(&*storage, block.get_tips()).await.context("Error while retrieving difficulty at tips")?;
     let height = block.height;
-    Ok(json!(GetBlockTemplateResult { template: block.to_hex(), height, difficulty }))
+    let version = block.version;
+    Ok(json!(GetBlockTemplateResult { template: block.to_hex(), height, difficulty, version }))
 }
 
 async fn submit_block<S: Storage>(context: Context, body: Value) -> Result<Value, InternalRpcError> {
@@ -298,6 +321,24 @@ async fn get_last_balance<S: Storage>(context: Context, body: Value) -> Result<V
     }))
 }
 
+async fn get_locked_balance<S: Storage>(context: Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: GetBalanceParams = parse_params(body)?;
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    if params.address.is_mainnet() != blockchain.get_network().is_mainnet() {
+        return Err(InternalRpcError::AnyError(BlockchainError::InvalidNetwork.into()))
+    }
+
+    let storage = blockchain.get_storage().read().await;
+    let topoheight = blockchain.get_topo_height();
+    let entries = blockchain.get_pending_locked_balances(&storage, params.address.get_public_key(), &params.asset, topoheight).await.context("Error while retrieving locked balance")?;
+    let locked = entries.iter().map(|locked| locked.get_amount()).sum();
+    Ok(json!(GetLockedBalanceResult {
+        topoheight,
+        locked,
+        entries
+    }))
+}
+
 async fn get_info<S: Storage>(context: Context, body: Value) -> Result<Value, InternalRpcError> {
     if body != Value::Null {
         return Err(InternalRpcError::UnexpectedParams)
@@ -315,11 +356,23 @@ async fn get_info<S: Storage>(context: Context, body: Value) -> Result<Value, In
         (top_block_hash, supply, pruned_topoheight, average_block_time)
     };
     let difficulty = blockchain.get_difficulty();
-    let block_time_target = BLOCK_TIME_MILLIS;
-    let block_reward = get_block_reward(circulating_supply);
-    let mempool_size = blockchain.get_mempool_size().await;
+    let block_time_target = blockchain.get_block_time_target();
+    let block_reward = get_block_reward(circulating_supply, block_time_target);
+    let (mempool_size, mempool_bytes, mempool_min_fee_rate) = {
+        let mempool = blockchain.get_mempool().read().await;
+        (mempool.size(), mempool.get_bytes(), mempool.get_min_fee_rate())
+    };
     let version = VERSION.into();
     let network = *blockchain.get_network();
+    let block_version = blockchain.get_version_at_height(height + 1);
+    let hard_forks = blockchain.get_hard_forks().iter().map(|hard_fork| HardForkInfo {
+        height: hard_fork.height,
+        version: hard_fork.version,
+        tx_version: hard_fork.tx_version,
+        features: hard_fork.features.iter().map(|feature| feature.to_string()).collect(),
+        changelog: hard_fork.changelog.to_owned(),
+        activated: hard_fork.height <= height + 1
+    }).collect();
 
     Ok(json!(GetInfoResult {
         height,
@@ -334,8 +387,13 @@ async fn get_info<S: Storage>(context: Context, body: Value) -> Result<Value, In
         average_block_time,
         block_reward,
         mempool_size,
+        mempool_bytes,
+        mempool_min_fee_rate,
+        rbf_min_fee_bump: blockchain.get_rbf_min_fee_bump(),
         version,
-        network
+        network,
+        block_version,
+        hard_forks
     }))
 }
 
@@ -400,6 +458,57 @@ async fn get_asset<S: Storage>(context: Context, body: Value) -> Result<Value, I
     Ok(json!(asset))
 }
 
+async fn get_asset_supply<S: Storage>(context: Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: GetAssetSupplyParams = parse_params(body)?;
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    let current = blockchain.get_topo_height();
+    let topoheight = params.topoheight.unwrap_or(current);
+    if topoheight > current {
+        return Err(InternalRpcError::UnexpectedParams).context("Topoheight cannot be greater than current chain topoheight")?
+    }
+
+    let storage = blockchain.get_storage().read().await;
+    let data = storage.get_asset_data(&params.asset).context("Asset was not found")?;
+    if data.get_topoheight() > topoheight {
+        return Err(InternalRpcError::UnexpectedParams).context("Asset was not registered at this topoheight")?
+    }
+
+    // native asset supply is tracked for each block
+    let (supply, max_supply) = if params.asset == XELIS_ASSET {
+        (storage.get_supply_at_topo_height(topoheight).await.context("Error while retrieving supply")?, Some(MAXIMUM_SUPPLY))
+    } else {
+        let supply = storage.get_asset_supply_at_maximum_topoheight(&params.asset, topoheight).await.context("Error while retrieving asset supply")?;
+        (supply, data.get_metadata().map(|metadata| metadata.get_max_supply()))
+    };
+
+    let mint_authority = storage.get_asset_authority_at_maximum_topoheight(&params.asset, topoheight).await.context("Error while retrieving asset authority")?
+        .map(|key| key.to_address(blockchain.get_network().is_mainnet()));
+    Ok(json!(GetAssetSupplyResult { topoheight, supply, max_supply, mint_authority }))
+}
+
+async fn get_multisig<S: Storage>(context: Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: GetMultisigParams = parse_params(body)?;
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    let mainnet = blockchain.get_network().is_mainnet();
+    if params.address.is_mainnet() != mainnet {
+        return Err(InternalRpcError::AnyError(BlockchainError::InvalidNetwork.into()))
+    }
+
+    let current = blockchain.get_topo_height();
+    let topoheight = params.topoheight.unwrap_or(current);
+    if topoheight > current {
+        return Err(InternalRpcError::UnexpectedParams).context("Topoheight cannot be greater than current chain topoheight")?
+    }
+
+    let storage = blockchain.get_storage().read().await;
+    let setup = storage.get_multisig_at_maximum_topoheight(params.address.get_public_key(), topoheight).await
+        .context("Error while retrieving multisig for account")?
+        .context("Account has no multisig")?;
+
+    let participants = setup.participants.into_iter().map(|key| key.to_address(mainnet)).collect();
+    Ok(json!(GetMultisigResult { topoheight, participants, threshold: setup.threshold }))
+}
+
 const MAX_ASSETS: usize = 100;
 
 async fn get_assets<S: Storage>(context: Context, body: Value) -> Result<Value, InternalRpcError> {
@@ -787,6 +896,41 @@ async fn get_account_history<S: Storage>(context: Context, body: Value) -> Resul
     Ok(json!(history))
 }
 
+const MAX_ACCOUNT_TRANSACTIONS: usize = 100;
+
+async fn get_account_transactions<S: Storage>(context: Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: GetAccountTransactionsParams = parse_params(body)?;
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    if !blockchain.is_account_history_indexed() {
+        return Err(InternalRpcError::CustomStr("Account history index is not enabled on this node"))
+    }
+
+    if params.address.is_mainnet() != blockchain.get_network().is_mainnet() {
+        return Err(InternalRpcError::AnyError(BlockchainError::InvalidNetwork.into()))
+    }
+
+    let maximum = if let Some(maximum) = params.maximum {
+        if maximum > MAX_ACCOUNT_TRANSACTIONS {
+            return Err(InternalRpcError::InvalidRequest).context(format!("Maximum transactions requested cannot be greater than {}", MAX_ACCOUNT_TRANSACTIONS))?
+        }
+        maximum
+    } else {
+        MAX_ACCOUNT_TRANSACTIONS
+    };
+    let skip = params.skip.unwrap_or(0);
+    let minimum_topoheight = params.minimum_topoheight.unwrap_or(0);
+    let maximum_topoheight = params.maximum_topoheight.unwrap_or(u64::MAX);
+
+    let storage = blockchain.get_storage().read().await;
+    let entries = storage.get_account_history_entries(params.address.get_public_key(), minimum_topoheight, maximum_topoheight, skip, maximum).await
+        .context("Error while retrieving account transactions")?;
+    let transactions: Vec<AccountTransactionEntry> = entries.into_iter()
+        .map(|(topoheight, hash, direction)| AccountTransactionEntry { topoheight, hash, direction })
+        .collect();
+
+    Ok(json!(transactions))
+}
+
 async fn get_account_assets<S: Storage>(context: Context, body: Value) -> Result<Value, InternalRpcError> {
     let params: GetAccountAssetsParams = parse_params(body)?;
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
@@ -852,12 +996,29 @@ async fn is_tx_executed_in_block<S: Storage>(context: Context, body: Value) -> R
 }
 
 // Get the configured dev fees
-async fn get_dev_fee_thresholds<S: Storage>(_: Context, body: Value) -> Result<Value, InternalRpcError> {
+async fn get_dev_fee_thresholds<S: Storage>(context: Context, body: Value) -> Result<Value, InternalRpcError> {
+    if body != Value::Null {
+        return Err(InternalRpcError::UnexpectedParams)
+    }
+
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    Ok(json!(blockchain.get_dev_fees()))
+}
+
+// Estimate the fee rates to be included within 1, 3 and 10 blocks
+async fn estimate_fee<S: Storage>(context: Context, body: Value) -> Result<Value, InternalRpcError> {
     if body != Value::Null {
         return Err(InternalRpcError::UnexpectedParams)
     }
 
-    Ok(json!(DEV_FEES))
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    let storage = blockchain.get_storage().read().await;
+    let topoheight = blockchain.get_topo_height();
+    let estimates = blockchain.estimate_fee_rates(&storage).await.context("Error while estimating fee rates")?;
+    Ok(json!(EstimateFeeResult {
+        topoheight,
+        estimates
+    }))
 }
 
 // Get the configured dev fees
@@ -874,4 +1035,92 @@ async fn get_size_on_disk<S: Storage>(context: Context, body: Value) -> Result<V
         size_bytes,
         size_formatted
     }))
-}
\ No newline at end of file
+}
+
+async fn get_state_root<S: Storage>(context: Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: GetStateRootParams = parse_params(body)?;
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    let current = blockchain.get_topo_height();
+    let topoheight = params.topoheight.unwrap_or(current);
+    if topoheight > current {
+        return Err(InternalRpcError::UnexpectedParams).context("Topoheight cannot be greater than current chain topoheight")?
+    }
+
+    let storage = blockchain.get_storage().read().await;
+    let root = storage.get_state_root_at_topoheight(topoheight).await.context("Error while retrieving state root")?;
+    Ok(json!(GetStateRootResult { topoheight, root }))
+}
+
+// topoheight of the state to prove, the one committed by the block header if set, otherwise the requested one
+async fn get_state_proof_topoheight<S: Storage>(blockchain: &Blockchain<S>, storage: &S, topoheight: Option<u64>, block_hash: Option<&Hash>) -> Result<u64, InternalRpcError> {
+    if let Some(hash) = block_hash {
+        let header = storage.get_block_header_by_hash(hash).await.context("Error while retrieving block header")?;
+        if header.get_state_root().is_none() {
+            return Err(InternalRpcError::UnexpectedParams).context("Block header doesn't commit a state root")?
+        }
+
+        let topoheight = blockchain.get_tips_base_topoheight(storage, header.get_tips()).await.context("Error while retrieving the base of block tips")?;
+        return Ok(topoheight)
+    }
+
+    let current = blockchain.get_topo_height();
+    let topoheight = topoheight.unwrap_or(current);
+    if topoheight > current {
+        return Err(InternalRpcError::UnexpectedParams).context("Topoheight cannot be greater than current chain topoheight")?
+    }
+    Ok(topoheight)
+}
+
+async fn get_balance_proof<S: Storage>(context: Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: GetBalanceProofParams = parse_params(body)?;
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    if params.address.is_mainnet() != blockchain.get_network().is_mainnet() {
+        return Err(InternalRpcError::AnyError(BlockchainError::InvalidNetwork.into()))
+    }
+
+    let key = params.address.get_public_key();
+    let storage = blockchain.get_storage().read().await;
+    let topoheight = get_state_proof_topoheight(blockchain, &storage, params.topoheight, params.block_hash.as_deref()).await?;
+    let root = storage.get_state_root_at_topoheight(topoheight).await.context("Error while retrieving state root")?;
+    let (version_topoheight, balance) = storage.get_balance_at_maximum_topoheight(key, &params.asset, topoheight).await
+        .context("Error while retrieving balance")?
+        .context("No balance found for this account and asset")?;
+    let proof = storage.get_state_proof_at_topoheight(&balance_leaf_key(key, &params.asset), topoheight).await
+        .context("Error while retrieving balance proof")?
+        .context("Balance was not found in the state tree")?;
+
+    Ok(json!(GetBalanceProofResult {
+        topoheight,
+        root,
+        version_topoheight,
+        balance,
+        proof
+    }))
+}
+
+async fn get_nonce_proof<S: Storage>(context: Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: GetNonceProofParams = parse_params(body)?;
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    if params.address.is_mainnet() != blockchain.get_network().is_mainnet() {
+        return Err(InternalRpcError::AnyError(BlockchainError::InvalidNetwork.into()))
+    }
+
+    let key = params.address.get_public_key();
+    let storage = blockchain.get_storage().read().await;
+    let topoheight = get_state_proof_topoheight(blockchain, &storage, params.topoheight, params.block_hash.as_deref()).await?;
+    let root = storage.get_state_root_at_topoheight(topoheight).await.context("Error while retrieving state root")?;
+    let (version_topoheight, nonce) = storage.get_nonce_at_maximum_topoheight(key, topoheight).await
+        .context("Error while retrieving nonce")?
+        .context("No nonce found for this account")?;
+    let proof = storage.get_state_proof_at_topoheight(&nonce_leaf_key(key), topoheight).await
+        .context("Error while retrieving nonce proof")?
+        .context("Nonce was not found in the state tree")?;
+
+    Ok(json!(GetNonceProofResult {
+        topoheight,
+        root,
+        version_topoheight,
+        nonce,
+        proof
+    }))
+}
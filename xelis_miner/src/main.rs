@@ -8,7 +8,7 @@ use serde::{Serialize, Deserialize};
 use tokio::{sync::{broadcast, mpsc, Mutex}, select, time::Instant};
 use tokio_tungstenite::{connect_async, tungstenite::Message};
 use xelis_common::{
-    block::{BlockMiner, BLOCK_WORK_SIZE, Difficulty},
+    block::{BlockMiner, BLOCK_WORK_SIZE, Difficulty, PowAlgorithm, get_pow_algorithm},
     serializer::Serializer,
     difficulty::check_difficulty,
     config::VERSION,
@@ -55,7 +55,7 @@ pub struct MinerConfig {
 
 #[derive(Clone)]
 enum ThreadNotification<'a> {
-    NewJob(BlockMiner<'a>, Difficulty, u64), // block work, difficulty, height
+    NewJob(BlockMiner<'a>, Difficulty, u64, &'static dyn PowAlgorithm), // block work, difficulty, height, PoW algorithm
     WebSocketClosed, // WebSocket connection has been closed
     Exit // all threads must stop
 }
@@ -257,9 +257,10 @@ async fn handle_websocket_message(message: Result<Message, tokio_tungstenite::tu
                 SocketMessage::NewJob(job) => {
                     info!("New job received from daemon: difficulty = {} and height = {}", job.difficulty, job.height);
                     let block = BlockMiner::from_hex(job.template).context("Error while decoding new job received from daemon")?;
+                    let algorithm = get_pow_algorithm(job.version).with_context(|| format!("No PoW algorithm found for block version {}", job.version))?;
                     CURRENT_HEIGHT.store(job.height, Ordering::SeqCst);
 
-                    if let Err(e) = job_sender.send(ThreadNotification::NewJob(block, job.difficulty, job.height)) {
+                    if let Err(e) = job_sender.send(ThreadNotification::NewJob(block, job.difficulty, job.height, algorithm)) {
                         error!("Error while sending new job to threads: {}", e);
                     }
                 },
@@ -319,7 +320,7 @@ fn start_thread(id: u8, mut job_receiver: broadcast::Receiver<ThreadNotification
                     info!("Exiting Mining Thread #{}...", id);
                     break 'main;
                 },
-                ThreadNotification::NewJob(new_job, expected_difficulty, height) => {
+                ThreadNotification::NewJob(new_job, expected_difficulty, height, algorithm) => {
                     debug!("Mining Thread #{} received a new job", id);
                     job = new_job;
                     // set thread id in extra nonce for more work spread between threads
@@ -327,7 +328,7 @@ fn start_thread(id: u8, mut job_receiver: broadcast::Receiver<ThreadNotification
                     job.extra_nonce[job.extra_nonce.len() - 1] = id;
 
                     // Solve block
-                    hash = job.get_pow_hash();
+                    hash = job.get_pow_hash(algorithm);
                     while !match check_difficulty(&hash, expected_difficulty) {
                         Ok(value) => value,
                         Err(e) => {
@@ -344,7 +345,7 @@ fn start_thread(id: u8, mut job_receiver: broadcast::Receiver<ThreadNotification
                         }
 
                         job.nonce += 1;
-                        hash = job.get_pow_hash();
+                        hash = job.get_pow_hash(algorithm);
                         job.timestamp = get_current_timestamp();
                         HASHRATE_COUNTER.fetch_add(1, Ordering::Relaxed);
                     }
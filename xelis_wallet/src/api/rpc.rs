@@ -177,10 +177,12 @@ async fn build_transaction(context: Context, body: Value) -> Result<Value, Inter
         return Err(InternalRpcError::CustomStr("Invalid params, should either be broadcasted, or returned in hex format"))
     }
 
-    // create the TX
+    // create the TX, the fee target and locked balances are requested to the daemon before locking the storage
+    let fee = wallet.resolve_fee_builder(params.fee.unwrap_or(FeeBuilder::Multiplier(1f64))).await?;
+    let locked = wallet.get_locked_balances(&params.tx_type).await?;
     let tx = {
         let storage = wallet.get_storage().read().await;
-        wallet.create_transaction(&storage, params.tx_type, params.fee.unwrap_or(FeeBuilder::Multiplier(1f64)))?
+        wallet.create_transaction(&storage, params.tx_type, fee, &locked, params.valid_until_topoheight)?
     };
 
     // if requested, broadcast the TX ourself
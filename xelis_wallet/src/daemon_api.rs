@@ -1,7 +1,7 @@
 use std::borrow::Cow;
 
 use anyhow::{Context, Result};
-use xelis_common::{json_rpc::JsonRPCClient, api::daemon::{GetLastBalanceResult, GetBalanceAtTopoHeightParams, GetBalanceParams, GetInfoResult, SubmitTransactionParams, BlockResponse, GetBlockAtTopoHeightParams, GetTransactionParams, GetNonceParams, GetNonceResult, GetAssetsParams, IsTxExecutedInBlockParams}, account::VersionedBalance, crypto::{address::Address, hash::Hash}, transaction::Transaction, serializer::Serializer, block::{BlockHeader, Block}, asset::AssetWithData};
+use xelis_common::{json_rpc::JsonRPCClient, api::daemon::{GetLastBalanceResult, GetLockedBalanceResult, GetBalanceAtTopoHeightParams, GetBalanceParams, GetInfoResult, SubmitTransactionParams, BlockResponse, GetBlockAtTopoHeightParams, GetTransactionParams, GetNonceParams, GetNonceResult, GetAssetsParams, IsTxExecutedInBlockParams, GetBalanceProofParams, GetBalanceProofResult, GetNonceProofParams, GetNonceProofResult, EstimateFeeResult}, account::VersionedBalance, crypto::{address::Address, hash::Hash}, transaction::Transaction, serializer::Serializer, block::{BlockHeader, Block}, asset::AssetWithData};
 
 pub struct DaemonAPI {
     client: JsonRPCClient,
@@ -28,6 +28,11 @@ impl DaemonAPI {
         Ok(info)
     }
 
+    pub async fn estimate_fee(&self) -> Result<EstimateFeeResult> {
+        let result = self.client.call("estimate_fee").await.context("Error while estimating fee rates")?;
+        Ok(result)
+    }
+
     pub async fn count_assets(&self) -> Result<usize> {
         let count = self.client.call("count_assets").await?;
         Ok(count)
@@ -51,6 +56,14 @@ impl DaemonAPI {
         Ok(balance)
     }
 
+    pub async fn get_locked_balance(&self, address: &Address, asset: &Hash) -> Result<GetLockedBalanceResult> {
+        let locked = self.client.call_with("get_locked_balance", &GetBalanceParams {
+            address: Cow::Borrowed(address),
+            asset: Cow::Borrowed(asset),
+        }).await.context("Error while retrieving locked balance")?;
+        Ok(locked)
+    }
+
     pub async fn get_balance_at_topoheight(&self, address: &Address, asset: &Hash, topoheight: u64) -> Result<VersionedBalance> {
         let balance = self.client.call_with("get_balance_at_topoheight", &GetBalanceAtTopoHeightParams {
             topoheight,
@@ -98,6 +111,27 @@ impl DaemonAPI {
         Ok(nonce)
     }
 
+    // proof against the state root committed in the header of this block
+    pub async fn get_balance_proof(&self, address: &Address, asset: &Hash, block_hash: &Hash) -> Result<GetBalanceProofResult> {
+        let proof = self.client.call_with("get_balance_proof", &GetBalanceProofParams {
+            address: Cow::Borrowed(address),
+            asset: Cow::Borrowed(asset),
+            topoheight: None,
+            block_hash: Some(Cow::Borrowed(block_hash))
+        }).await.context("Error while retrieving balance proof")?;
+        Ok(proof)
+    }
+
+    // proof against the state root committed in the header of this block
+    pub async fn get_nonce_proof(&self, address: &Address, block_hash: &Hash) -> Result<GetNonceProofResult> {
+        let proof = self.client.call_with("get_nonce_proof", &GetNonceProofParams {
+            address: Cow::Borrowed(address),
+            topoheight: None,
+            block_hash: Some(Cow::Borrowed(block_hash))
+        }).await.context(format!("Error while fetching nonce proof from address {}", address))?;
+        Ok(proof)
+    }
+
     pub async fn is_tx_executed_in_block(&self, tx_hash: &Hash, block_hash: &Hash) -> Result<bool> {
         let is_executed = self.client.call_with("is_tx_executed_in_block", &IsTxExecutedInBlockParams {
             tx_hash: Cow::Borrowed(tx_hash),
@@ -7,7 +7,7 @@ use log::{error, info};
 use clap::Parser;
 use xelis_common::{config::{
     VERSION, XELIS_ASSET, COIN_DECIMALS
-}, prompt::{Prompt, command::{CommandManager, Command, CommandHandler, CommandError}, argument::{Arg, ArgType, ArgumentManager}, LogLevel, self, ShareablePrompt, PromptError}, async_handler, crypto::{address::{Address, AddressType}, hash::Hashable}, transaction::{TransactionType, Transaction}, utils::{format_xelis, set_network_to, get_network, format_coin}, serializer::Serializer, network::Network, api::wallet::FeeBuilder};
+}, prompt::{Prompt, command::{CommandManager, Command, CommandHandler, CommandError}, argument::{Arg, ArgType, ArgumentManager}, LogLevel, self, ShareablePrompt, PromptError}, async_handler, crypto::{address::{Address, AddressType}, hash::{Hash, Hashable}}, transaction::{TransactionType, Transaction, UnlockCondition}, utils::{format_xelis, set_network_to, get_network, format_coin}, serializer::Serializer, network::Network, api::wallet::FeeBuilder};
 use xelis_wallet::{
     wallet::Wallet,
     config::DEFAULT_DAEMON_ADDRESS
@@ -193,6 +193,7 @@ async fn setup_wallet_command_manager(wallet: Arc<Wallet>, command_manager: &Com
     command_manager.add_command(Command::new("change_password", "Set a new password to open your wallet", CommandHandler::Async(async_handler!(change_password))))?;
     command_manager.add_command(Command::with_optional_arguments("transfer", "Send asset to a specified address", vec![Arg::new("asset", ArgType::Hash)], CommandHandler::Async(async_handler!(transfer))))?;
     command_manager.add_command(Command::with_required_arguments("burn", "Burn amount of asset", vec![Arg::new("asset", ArgType::Hash), Arg::new("amount", ArgType::Number)], CommandHandler::Async(async_handler!(burn))))?;
+    command_manager.add_command(Command::with_required_arguments("bump_fee", "Replace a pending transaction by the same one with higher fees", vec![Arg::new("hash", ArgType::Hash)], CommandHandler::Async(async_handler!(bump_fee))))?;
     command_manager.add_command(Command::new("display_address", "Show your wallet address", CommandHandler::Async(async_handler!(display_address))))?;
     command_manager.add_command(Command::with_optional_arguments("balance", "List all non-zero balances or show the selected one", vec![Arg::new("asset", ArgType::Hash)], CommandHandler::Async(async_handler!(balance))))?;
     command_manager.add_command(Command::with_optional_arguments("history", "Show all your transactions", vec![Arg::new("page", ArgType::Number)], CommandHandler::Async(async_handler!(history))))?;
@@ -452,7 +453,22 @@ async fn transfer(manager: &CommandManager<Arc<Wallet>>, _: ArgumentManager) ->
     ).await.context("Error while reading amount")?;
 
     let amount = (float_amount * 10u32.pow(decimals as u32) as f64) as u64;
-    manager.message(format!("Sending {} of {} to {}", format_coin(amount, decimals), asset, address.to_string()));
+
+    // funds can be locked for the receiver until a topoheight
+    let unlock = prompt.read_input(
+        prompt::colorize_str(Color::Green, "Lock until topoheight (empty for none): "),
+        false
+    ).await.context("Error while reading unlock topoheight")?;
+    let unlock = if unlock.is_empty() {
+        None
+    } else {
+        Some(UnlockCondition::Topoheight(unlock.parse().context("Invalid unlock topoheight")?))
+    };
+
+    match &unlock {
+        Some(unlock) => manager.message(format!("Sending {} of {} to {} locked until {}", format_coin(amount, decimals), asset, address.to_string(), unlock)),
+        None => manager.message(format!("Sending {} of {} to {}", format_coin(amount, decimals), asset, address.to_string()))
+    };
 
     if !prompt.ask_confirmation().await.context("Error while confirming action")? {
         manager.message("Transaction has been aborted");
@@ -467,10 +483,16 @@ async fn transfer(manager: &CommandManager<Arc<Wallet>>, _: ArgumentManager) ->
         AddressType::Data(data) => Some(data)
     };
 
+    let transfer = {
+        let storage = wallet.get_storage().read().await;
+        wallet.create_transfer(&storage, asset, key, extra_data, amount, unlock)?
+    };
+
+    let transaction_type = TransactionType::Transfer(vec![transfer]);
+    let locked = wallet.get_locked_balances(&transaction_type).await.context("Error while retrieving locked balances")?;
     let tx = {
         let storage = wallet.get_storage().read().await;
-        let transfer = wallet.create_transfer(&storage, asset, key, extra_data, amount)?;
-        wallet.create_transaction(&storage, TransactionType::Transfer(vec![transfer]), FeeBuilder::Multiplier(1f64))?
+        wallet.create_transaction(&storage, transaction_type, FeeBuilder::Multiplier(1f64), &locked, None)?
     };
 
     broadcast_tx(wallet, manager, tx).await;
@@ -482,18 +504,30 @@ async fn burn(manager: &CommandManager<Arc<Wallet>>, mut arguments: ArgumentMana
     let asset = arguments.get_value("asset")?.to_hash()?;
     let lock = manager.get_data().lock()?;
     let wallet = lock.as_ref().ok_or(CommandError::NoData)?;
+    let transaction_type = TransactionType::Burn { asset: asset.clone(), amount };
+    let locked = wallet.get_locked_balances(&transaction_type).await.context("Error while retrieving locked balances")?;
     let tx = {
         let storage = wallet.get_storage().read().await;
         let decimals = storage.get_asset_decimals(&asset).unwrap_or(COIN_DECIMALS);
 
         manager.message(format!("Burning {} of {}", format_coin(amount, decimals), asset));
-        wallet.create_transaction(&storage, TransactionType::Burn { asset, amount }, FeeBuilder::Multiplier(1f64))?
+        wallet.create_transaction(&storage, transaction_type, FeeBuilder::Multiplier(1f64), &locked, None)?
     };
 
     broadcast_tx(wallet, manager, tx).await;
     Ok(())
 }
 
+// Rebuild a pending transaction with higher fees and resubmit it
+async fn bump_fee(manager: &CommandManager<Arc<Wallet>>, mut arguments: ArgumentManager) -> Result<(), CommandError> {
+    let hash = arguments.get_value("hash")?.to_hash()?;
+    let lock = manager.get_data().lock()?;
+    let wallet = lock.as_ref().ok_or(CommandError::NoData)?;
+    let tx = wallet.bump_fee(&hash).await.context("Couldn't bump fee of transaction")?;
+    manager.message(format!("Transaction {} replaced by {} with fees {}", hash, tx.hash(), format_xelis(tx.get_fee())));
+    Ok(())
+}
+
 // Show current wallet address
 async fn display_address(manager: &CommandManager<Arc<Wallet>>, _: ArgumentManager) -> Result<(), CommandError> {
     let lock = manager.get_data().lock()?;
@@ -512,12 +546,12 @@ async fn balance(manager: &CommandManager<Arc<Wallet>>, mut arguments: ArgumentM
         let asset = arguments.get_value("asset")?.to_hash()?;
         let balance = storage.get_balance_for(&asset).unwrap_or(0);
         let decimals = storage.get_asset_decimals(&asset).unwrap_or(0);
-        manager.message(format!("Balance for asset {}: {}", asset, format_coin(balance, decimals)));
+        display_balance(manager, wallet, &asset, balance, decimals).await;
     } else {
         for (asset, decimals) in storage.get_assets_with_decimals()? {
             let balance = storage.get_balance_for(&asset).unwrap_or(0);
             if balance > 0 {
-                manager.message(format!("Balance for asset {}: {}", asset, format_coin(balance, decimals)));
+                display_balance(manager, wallet, &asset, balance, decimals).await;
             }
         }
     }
@@ -525,6 +559,17 @@ async fn balance(manager: &CommandManager<Arc<Wallet>>, mut arguments: ArgumentM
     Ok(())
 }
 
+// Show the balance of an asset with its pending locked part when the daemon can tell it
+async fn display_balance(manager: &CommandManager<Arc<Wallet>>, wallet: &Arc<Wallet>, asset: &Hash, balance: u64, decimals: u8) {
+    match wallet.get_locked_balance(asset).await {
+        Ok(locked) if locked > 0 => {
+            let spendable = balance.saturating_sub(locked);
+            manager.message(format!("Balance for asset {}: {} (spendable: {}, locked: {})", asset, format_coin(balance, decimals), format_coin(spendable, decimals), format_coin(locked, decimals)));
+        },
+        _ => manager.message(format!("Balance for asset {}: {}", asset, format_coin(balance, decimals)))
+    };
+}
+
 // Show all transactions
 const TXS_PER_PAGE: usize = 10;
 async fn history(manager: &CommandManager<Arc<Wallet>>, mut arguments: ArgumentManager) -> Result<(), CommandError> {
@@ -3,7 +3,7 @@ use thiserror::Error;
 use anyhow::Error;
 use log::{debug, error, info, warn};
 use tokio::{task::JoinHandle, sync::Mutex, time::interval};
-use xelis_common::{crypto::{hash::Hash, address::Address}, block::Block, transaction::TransactionType, account::VersionedBalance, asset::AssetWithData, serializer::Serializer};
+use xelis_common::{crypto::{hash::{Hash, Hashable}, address::Address, merkle::{verify_balance_proof, verify_nonce_proof}}, block::{Block, BlockHeader}, config::MINIMUM_DIFFICULTY, difficulty::check_difficulty, network::Network, transaction::TransactionType, account::VersionedBalance, asset::AssetWithData, serializer::Serializer};
 
 use crate::{daemon_api::DaemonAPI, wallet::Wallet, entry::{EntryData, Transfer, TransactionEntry}};
 
@@ -24,10 +24,24 @@ pub enum NetworkError {
     NotRunning,
     #[error(transparent)]
     TaskError(#[from] tokio::task::JoinError),
+    #[error("invalid state proof for balance of asset {}", _0)]
+    InvalidBalanceProof(Hash),
+    #[error("invalid state proof for nonce")]
+    InvalidNonceProof,
+    #[error("invalid block header {}", _0)]
+    InvalidBlockHeader(Hash),
     #[error(transparent)]
     DaemonAPIError(#[from] Error)
 }
 
+// state root committed in a block header verified by the wallet
+struct CommittedState {
+    block_hash: Hash,
+    // topoheight of the block
+    topoheight: u64,
+    root: Hash
+}
+
 pub struct NetworkHandler {
     // tokio task
     task: Mutex<Option<JoinHandle<Result<(), Error>>>>,
@@ -41,6 +55,8 @@ pub struct NetworkHandler {
 
 // how many assets we get by request
 const MAX_ASSETS: usize = 64;
+// how many blocks above a balance version are searched for one committing it in their state root
+const MAX_STATE_ROOT_SEARCH: u64 = 64;
 
 impl NetworkHandler {
     pub async fn new<S: ToString>(wallet: Arc<Wallet>, daemon_address: S) -> Result<SharedNetworkHandler, Error> {
@@ -99,42 +115,172 @@ impl NetworkHandler {
         self.is_paused.load(Ordering::SeqCst)
     }
 
-    async fn get_versioned_balance_and_topoheight(&self, address: &Address, asset: &Hash, current_topoheight: Option<u64>) -> Result<Option<(u64, VersionedBalance)>, Error> {
+    // retrieve the block header at this topoheight and verify its hash and PoW ourself
+    // PoW can't be below the minimum difficulty of the network, whatever the daemon reports
+    async fn get_verified_header(&self, topoheight: u64) -> Result<(Hash, BlockHeader), Error> {
+        let response = self.api.get_block_at_topoheight(topoheight).await?;
+        let block_hash = response.data.hash.into_owned();
+        let header = response.data.data.into_owned();
+        if header.hash() != block_hash {
+            return Err(NetworkError::InvalidBlockHeader(block_hash).into())
+        }
+
+        // dev network minimum difficulty can be changed in its chain spec
+        let difficulty = match self.wallet.get_network() {
+            Network::Dev => response.difficulty,
+            _ => response.difficulty.max(MINIMUM_DIFFICULTY)
+        };
+
+        let algorithm = header.get_pow_algorithm().ok_or_else(|| NetworkError::InvalidBlockHeader(block_hash.clone()))?;
+        if !check_difficulty(&header.get_pow_hash(algorithm), difficulty)? {
+            return Err(NetworkError::InvalidBlockHeader(block_hash).into())
+        }
+
+        Ok((block_hash, header))
+    }
+
+    // returns the state root committed in the verified block header at this topoheight, None if the block was mined before the state root commitment
+    // block version can't go back below the highest one already verified, so a daemon can't skip the verification
+    async fn get_committed_state(&self, topoheight: u64) -> Result<Option<CommittedState>, Error> {
+        let (block_hash, header) = self.get_verified_header(topoheight).await?;
+        {
+            let mut storage = self.wallet.get_storage().write().await;
+            let version = storage.get_block_version().unwrap_or(0);
+            if header.get_version() < version {
+                error!("Block {} at topoheight {} has version {} while version {} was already verified", block_hash, topoheight, header.get_version(), version);
+                return Err(NetworkError::InvalidBlockHeader(block_hash).into())
+            }
+
+            if header.get_version() > version {
+                storage.set_block_version(header.get_version())?;
+            }
+        }
+
+        Ok(header.get_state_root().map(|root| CommittedState {
+            root: root.clone(),
+            topoheight,
+            block_hash
+        }))
+    }
+
+    // a previous version of a balance is proven against the state root committed by a block above it
+    // blocks are searched until one commits a state at or above its topoheight
+    async fn verify_balance_at_topoheight(&self, address: &Address, asset: &Hash, topoheight: u64, balance: &VersionedBalance, state: &CommittedState) -> Result<(), Error> {
+        // the block verified for this sync commits a state below this version
+        if topoheight >= state.topoheight {
+            debug!("Balance of asset {} at topoheight {} is not committed yet", asset, topoheight);
+            return Ok(())
+        }
+
+        for topo in topoheight + 1..=(topoheight + MAX_STATE_ROOT_SEARCH).min(state.topoheight) {
+            let (block_hash, header) = self.get_verified_header(topo).await?;
+            let Some(root) = header.get_state_root() else {
+                // balances before the state root commitment can't be verified
+                return Ok(())
+            };
+
+            let res = self.api.get_balance_proof(address, asset, &block_hash).await?;
+            if !verify_balance_proof(root, address.get_public_key(), asset, res.balance.get_balance(), &res.proof) {
+                return Err(NetworkError::InvalidBalanceProof(asset.clone()).into())
+            }
+
+            // state committed is below this version
+            if res.version_topoheight < topoheight {
+                continue;
+            }
+
+            // this version was replaced before being committed, its next version is verified instead
+            if res.version_topoheight > topoheight {
+                debug!("Balance of asset {} at topoheight {} was replaced at topoheight {} before being committed", asset, topoheight, res.version_topoheight);
+                return Ok(())
+            }
+
+            if res.balance.get_balance() != balance.get_balance() {
+                return Err(NetworkError::InvalidBalanceProof(asset.clone()).into())
+            }
+            return Ok(())
+        }
+
+        // only blocks above the one verified for this sync can commit it
+        if topoheight + MAX_STATE_ROOT_SEARCH >= state.topoheight {
+            debug!("Balance of asset {} at topoheight {} is not committed yet", asset, topoheight);
+            return Ok(())
+        }
+
+        error!("No block committing the balance of asset {} at topoheight {} found", asset, topoheight);
+        Err(NetworkError::InvalidBalanceProof(asset.clone()).into())
+    }
+
+    async fn get_versioned_balance_and_topoheight(&self, address: &Address, asset: &Hash, current_topoheight: Option<u64>, state: Option<&CommittedState>) -> Result<Option<(u64, VersionedBalance)>, Error> {
         let (topoheight, balance) = match &current_topoheight {
-            Some(topoheight) => (*topoheight, self.api.get_balance_at_topoheight(address, asset, *topoheight).await?),
-            None => { // try to get last balance
-                let res = match self.api.get_last_balance(&address, asset).await {
+            Some(topoheight) => {
+                let balance = self.api.get_balance_at_topoheight(address, asset, *topoheight).await?;
+                if let Some(state) = state {
+                    self.verify_balance_at_topoheight(address, asset, *topoheight, &balance, state).await?;
+                }
+                (*topoheight, balance)
+            },
+            None => { // sync from the last balance, but only save the one proven against the committed state root
+                let last = match self.api.get_last_balance(address, asset).await {
                     Ok(res) => res,
                     Err(e) => { // balance doesn't exist on chain for this asset
                         debug!("Error while getting last balance: {}", e);
                         return Ok(None)
                     }
                 };
-                let balance = res.balance;
-
-                // Inform the change of the balance
-                #[cfg(feature = "api_server")]
-                {
-                    if let Some(api_server) = self.wallet.get_api_server().lock().await.as_ref() {
-                        api_server.notify_event(&NotifyEvent::BalanceChanged, &BalanceChanged {
-                            asset: Cow::Borrowed(&asset),
-                            balance: balance.get_balance()
-                        }).await;
+
+                let balance = match state {
+                    Some(state) => match self.api.get_balance_proof(address, asset, &state.block_hash).await {
+                        Ok(res) => {
+                            if !verify_balance_proof(&state.root, address.get_public_key(), asset, res.balance.get_balance(), &res.proof) {
+                                return Err(NetworkError::InvalidBalanceProof(asset.clone()).into())
+                            }
+
+                            // a last balance already committed must be the proven one
+                            if last.topoheight <= res.topoheight && (last.topoheight != res.version_topoheight || last.balance.get_balance() != res.balance.get_balance()) {
+                                return Err(NetworkError::InvalidBalanceProof(asset.clone()).into())
+                            }
+                            Some(res.balance.get_balance())
+                        },
+                        // a balance created after the committed state has no proof yet, it will be saved on a next sync
+                        Err(e) if last.balance.get_previous_topoheight().is_none() => {
+                            debug!("Balance of asset {} created at topoheight {} is not committed yet: {}", asset, last.topoheight, e);
+                            None
+                        },
+                        Err(e) => {
+                            error!("Error while getting balance proof for asset {}: {}", asset, e);
+                            return Err(NetworkError::InvalidBalanceProof(asset.clone()).into())
+                        }
+                    },
+                    // no state root committed in the block header, balance can't be verified
+                    None => Some(last.balance.get_balance())
+                };
+
+                if let Some(balance) = balance {
+                    // Inform the change of the balance
+                    #[cfg(feature = "api_server")]
+                    {
+                        if let Some(api_server) = self.wallet.get_api_server().lock().await.as_ref() {
+                            api_server.notify_event(&NotifyEvent::BalanceChanged, &BalanceChanged {
+                                asset: Cow::Borrowed(&asset),
+                                balance
+                            }).await;
+                        }
                     }
-                }
 
-                // lets write the final balance
-                let mut storage = self.wallet.get_storage().write().await;
-                storage.set_balance_for(asset, balance.get_balance())?;
+                    // lets write the final balance
+                    let mut storage = self.wallet.get_storage().write().await;
+                    storage.set_balance_for(asset, balance)?;
+                }
 
-                (res.topoheight, balance)
+                (last.topoheight, last.balance)
             }
         };
         Ok(Some((topoheight, balance)))
     }
 
-    async fn get_balance_and_transactions(&self, address: &Address, asset: &Hash, min_topoheight: u64, current_topoheight: Option<u64>) -> Result<(), Error> {
-        let mut res = self.get_versioned_balance_and_topoheight(address, asset, current_topoheight).await?;
+    async fn get_balance_and_transactions(&self, address: &Address, asset: &Hash, min_topoheight: u64, current_topoheight: Option<u64>, state: Option<&CommittedState>) -> Result<(), Error> {
+        let mut res = self.get_versioned_balance_and_topoheight(address, asset, current_topoheight, state).await?;
         while let Some((topoheight, balance)) = res.take() {
             // don't sync already synced blocks
             if min_topoheight > topoheight {
@@ -234,7 +380,7 @@ impl NetworkHandler {
             }
 
             if let Some(previous_topo) = balance.get_previous_topoheight() {
-                res = self.get_versioned_balance_and_topoheight(address, asset, Some(previous_topo)).await?;
+                res = self.get_versioned_balance_and_topoheight(address, asset, Some(previous_topo), state).await?;
             }
         }
 
@@ -320,6 +466,12 @@ impl NetworkHandler {
     }
 
     async fn sync_new_blocks(&self, address: &Address, current_topoheight: u64, network_topoheight: u64) -> Result<(), Error> {
+        // balances and nonce are verified against the state root committed in the top block header
+        let state = self.get_committed_state(network_topoheight).await?;
+        if state.is_none() {
+            debug!("Block at topoheight {} doesn't commit a state root, balances and nonce can't be verified", network_topoheight);
+        }
+
         let mut assets = {
             let storage = self.wallet.get_storage().read().await;
             storage.get_assets()?
@@ -357,7 +509,29 @@ impl NetworkHandler {
 
         // Retrieve the highest nonce (in one call, in case of assets/txs not tracked correctly)
         {
-            let nonce = self.api.get_last_nonce(&address).await.map(|v| v.version.get_nonce()).unwrap_or(0);
+            let (nonce, is_new) = match self.api.get_last_nonce(address).await {
+                Ok(res) => (res.version.get_nonce(), res.version.get_previous_topoheight().is_none()),
+                // no nonce on chain yet
+                Err(_) => (0, true)
+            };
+
+            // the last nonce is kept to build new transactions, but it can't be lower than the committed one
+            if let Some(state) = &state {
+                match self.api.get_nonce_proof(address, &state.block_hash).await {
+                    Ok(res) => {
+                        let committed = res.nonce.get_nonce();
+                        if !verify_nonce_proof(&state.root, address.get_public_key(), committed, &res.proof) || nonce < committed {
+                            return Err(NetworkError::InvalidNonceProof.into())
+                        }
+                    },
+                    // a nonce created after the committed state has no proof yet
+                    Err(e) if is_new => debug!("Nonce is not committed yet: {}", e),
+                    Err(e) => {
+                        error!("Error while getting nonce proof: {}", e);
+                        return Err(NetworkError::InvalidNonceProof.into())
+                    }
+                }
+            }
             debug!("New nonce found is {}", nonce);
             let mut storage = self.wallet.get_storage().write().await;
             storage.set_nonce(nonce)?;
@@ -366,7 +540,7 @@ impl NetworkHandler {
         // get balance and transactions for each asset
         for asset in assets {
             debug!("calling get balances and transactions {}", current_topoheight);
-            if let Err(e) = self.get_balance_and_transactions(&address, &asset, current_topoheight, None).await {
+            if let Err(e) = self.get_balance_and_transactions(&address, &asset, current_topoheight, None, state.as_ref()).await {
                 error!("Error while syncing balance for asset {}: {}", asset, e);
             }
         }
@@ -24,6 +24,8 @@ const KEY_PAIR: &[u8] = b"KPAIR";
 const TOPOHEIGHT_KEY: &[u8] = b"TOPH";
 // represent the daemon top block hash
 const TOP_BLOCK_HASH_KEY: &[u8] = b"TOPBH";
+// represent the highest block version verified by the wallet
+const BLOCK_VERSION_KEY: &[u8] = b"BVER";
 const NETWORK: &[u8] = b"NET";
 
 // Use this struct to get access to non-encrypted keys (such as salt for KDF and encrypted master key)
@@ -436,6 +438,14 @@ impl EncryptedStorage {
         self.load_from_disk(&self.extra, TOP_BLOCK_HASH_KEY)
     }
 
+    pub fn set_block_version(&mut self, version: u8) -> Result<()> {
+        self.save_to_disk(&self.extra, BLOCK_VERSION_KEY, &[version])
+    }
+
+    pub fn get_block_version(&self) -> Result<u8> {
+        self.load_from_disk(&self.extra, BLOCK_VERSION_KEY)
+    }
+
     pub fn get_public_storage(&self) -> &Storage {
         &self.inner
     }
@@ -1,7 +1,8 @@
 use std::collections::HashMap;
 
 use xelis_common::{
-    transaction::{Transaction, TransactionType, EXTRA_DATA_LIMIT_SIZE},
+    transaction::{Transaction, TransactionType, UnsignedTransaction, EXTRA_DATA_LIMIT_SIZE, VALID_UNTIL_TX_VERSION},
+    config::{XELIS_ASSET, ASSET_REGISTRATION_FEE},
     utils::calculate_tx_fee,
     serializer::{Writer, Serializer},
     crypto::{key::{SIGNATURE_LENGTH, PublicKey, KeyPair}, hash::{Hash, hash}}, api::wallet::FeeBuilder
@@ -9,26 +10,58 @@ use xelis_common::{
 
 use crate::wallet::WalletError;
 
+// total amount spent per asset by a transaction, without its fees
+pub fn total_spent(data: &TransactionType) -> HashMap<&Hash, u64> {
+    let mut total_spent = HashMap::new();
+    match data {
+        TransactionType::Burn { asset, amount } => {
+            total_spent.insert(asset, *amount);
+        },
+        TransactionType::CallContract(call) => {
+            for (asset, amount) in &call.assets {
+                total_spent.insert(asset, *amount);
+            }
+        },
+        TransactionType::Transfer(txs) => {
+            for tx in txs {
+                let current = total_spent.entry(&tx.asset).or_insert(0);
+                *current += tx.amount; 
+            }
+        },
+        TransactionType::DeployContract(_) => {},
+        TransactionType::CreateAsset { .. } => {
+            total_spent.insert(&XELIS_ASSET, ASSET_REGISTRATION_FEE);
+        },
+        TransactionType::MintAsset { .. } | TransactionType::SetAssetAuthority { .. } | TransactionType::SetMultisig(_) => {}
+    }
+
+    total_spent
+}
+
 pub struct TransactionBuilder {
     owner: PublicKey,
     data: TransactionType,
     nonce: u64,
     fee_builder: FeeBuilder,
+    valid_until_topoheight: Option<u64>
 }
 
 impl TransactionBuilder {
-    pub fn new(owner: PublicKey, data: TransactionType, nonce: u64, fee_builder: FeeBuilder) -> Self {
+    pub fn new(owner: PublicKey, data: TransactionType, nonce: u64, fee_builder: FeeBuilder, valid_until_topoheight: Option<u64>) -> Self {
         Self {
             owner,
             data,
             nonce,
-            fee_builder
+            fee_builder,
+            valid_until_topoheight
         }
     }
 
     fn serialize(&self) -> Writer {
         let mut writer = Writer::new();
-        writer.write_u8(0);
+        // an expiring transaction requires a newer version
+        let version = if self.valid_until_topoheight.is_some() { VALID_UNTIL_TX_VERSION } else { 0 };
+        writer.write_u8(version);
         self.owner.write(&mut writer);
         self.data.write(&mut writer);
         writer
@@ -37,7 +70,9 @@ impl TransactionBuilder {
     fn verify_fees_internal(&self, calculated_fees: u64) -> Result<u64, WalletError> {
         let provided_fees = match &self.fee_builder {
             FeeBuilder::Multiplier(multiplier) => (calculated_fees as f64  * multiplier) as u64,
-            FeeBuilder::Value(value) => *value
+            FeeBuilder::Value(value) => *value,
+            // must be resolved by the wallet through the daemon
+            FeeBuilder::Target(_) => return Err(WalletError::UnresolvedFeeTarget)
         };
 
         if provided_fees < calculated_fees {
@@ -48,26 +83,7 @@ impl TransactionBuilder {
     }
 
     pub fn total_spent(&self) -> HashMap<&Hash, u64> {
-        let mut total_spent = HashMap::new();
-        match &self.data {
-            TransactionType::Burn { asset, amount } => {
-                total_spent.insert(asset, *amount);
-            },
-            TransactionType::CallContract(call) => {
-                for (asset, amount) in &call.assets {
-                    total_spent.insert(asset, *amount);
-                }
-            },
-            TransactionType::Transfer(txs) => {
-                for tx in txs {
-                    let current = total_spent.entry(&tx.asset).or_insert(0);
-                    *current += tx.amount; 
-                }
-            },
-            TransactionType::DeployContract(_) => {}
-        }
-
-        total_spent
+        total_spent(&self.data)
     }
 
     pub fn total_extra_data_size(&self) -> usize {
@@ -82,23 +98,44 @@ impl TransactionBuilder {
         total_size
     }
 
+    // size of the fields written after the nonce when an expiry is set
+    // option flag and value for the expiry, then an empty option for the multisig
+    fn valid_until_size(&self) -> usize {
+        if self.valid_until_topoheight.is_some() {
+            1 + 8 + 1
+        } else {
+            0
+        }
+    }
+
     fn estimate_fees_internal(&self, writer: &Writer) -> u64 {
         // 8 represent the field 'fee' in bytes size
-        let total_bytes = SIGNATURE_LENGTH + 8 + writer.total_write();
+        let total_bytes = SIGNATURE_LENGTH + 8 + writer.total_write() + self.valid_until_size();
         let calculated_fees = calculate_tx_fee(total_bytes);
         calculated_fees
     }
 
+    // a multisig transaction also contains the signatures of threshold participants
+    fn estimate_multisig_fees_internal(&self, writer: &Writer, threshold: u8) -> u64 {
+        // option flag and count, then participant index and signature for each one
+        let multisig_bytes = 2 + threshold as usize * (1 + SIGNATURE_LENGTH);
+        // expiry option flag and value
+        let valid_until_bytes = if self.valid_until_topoheight.is_some() { 1 + 8 } else { 0 };
+        let total_bytes = SIGNATURE_LENGTH + 8 + writer.total_write() + multisig_bytes + valid_until_bytes;
+        calculate_tx_fee(total_bytes)
+    }
+
     pub fn estimate_fees(&self) -> u64 {
         let writer = self.serialize();
         self.estimate_fees_internal(&writer)
     }
 
-    pub fn build(self, keypair: &KeyPair) -> Result<Transaction, WalletError> {
-        if *keypair.get_public_key() != self.owner {
-            return Err(WalletError::InvalidKeyPair)
-        }
+    pub fn estimate_multisig_fees(&self, threshold: u8) -> u64 {
+        let writer = self.serialize();
+        self.estimate_multisig_fees_internal(&writer, threshold)
+    }
 
+    fn verify(&self) -> Result<(), WalletError> {
         if let TransactionType::Transfer(txs) = &self.data {
             if txs.len() == 0 {
                 return Err(WalletError::ExpectedOneTx)
@@ -120,13 +157,31 @@ impl TransactionBuilder {
             return Err(WalletError::ExtraDataTooBig(EXTRA_DATA_LIMIT_SIZE, extra_data_size))
         }
 
+        Ok(())
+    }
+
+    pub fn build(self, keypair: &KeyPair) -> Result<Transaction, WalletError> {
+        if *keypair.get_public_key() != self.owner {
+            return Err(WalletError::InvalidKeyPair)
+        }
+
+        self.verify()?;
+
         let mut writer = self.serialize();
         let fee = self.verify_fees_internal(self.estimate_fees_internal(&writer))?;
         writer.write_u64(&fee);
         writer.write_u64(&self.nonce);
+        if let Some(valid_until) = self.valid_until_topoheight {
+            Some(valid_until).write(&mut writer);
+            // no multisig signatures
+            writer.write_bool(false);
+        }
 
         let signature = keypair.sign(hash(writer.as_bytes()).as_bytes());
-        let tx = Transaction::new(self.owner, self.data, fee, self.nonce, signature);
+        let tx = match self.valid_until_topoheight {
+            Some(valid_until) => Transaction::with_valid_until_topoheight(self.owner, self.data, fee, self.nonce, valid_until, signature),
+            None => Transaction::new(self.owner, self.data, fee, self.nonce, signature)
+        };
 
         if !tx.verify_signature() {
             return Err(WalletError::InvalidSignature)
@@ -134,4 +189,14 @@ impl TransactionBuilder {
 
         Ok(tx)
     }
-}
\ No newline at end of file
+
+    // build a transaction for a multisig account, it must be signed by threshold participants
+    // through UnsignedTransaction::sign_multisig before being finalized by the owner
+    pub fn build_unsigned(self, threshold: u8) -> Result<UnsignedTransaction, WalletError> {
+        self.verify()?;
+
+        let writer = self.serialize();
+        let fee = self.verify_fees_internal(self.estimate_multisig_fees_internal(&writer, threshold))?;
+        Ok(UnsignedTransaction::new(self.owner, self.data, fee, self.nonce, self.valid_until_topoheight))
+    }
+}
@@ -6,22 +6,22 @@ use serde_json::{Value, json};
 use tokio::sync::{Mutex, RwLock};
 use xelis_common::api::DataElement;
 use xelis_common::api::wallet::FeeBuilder;
-use xelis_common::config::{XELIS_ASSET, COIN_DECIMALS};
+use xelis_common::config::{XELIS_ASSET, COIN_DECIMALS, FEE_PER_KB};
 use xelis_common::crypto::address::Address;
 use xelis_common::crypto::hash::Hash;
 use xelis_common::crypto::key::{KeyPair, PublicKey};
 use xelis_common::rpc_server::{RpcRequest, InternalRpcError, RpcResponseError};
-use xelis_common::utils::{format_xelis, format_coin};
+use xelis_common::utils::{format_xelis, format_coin, calculate_replacement_fee};
 use xelis_common::network::Network;
 use xelis_common::serializer::{Serializer, Writer};
-use xelis_common::transaction::{TransactionType, Transfer, Transaction, EXTRA_DATA_LIMIT_SIZE};
+use xelis_common::transaction::{TransactionType, Transfer, Transaction, UnsignedTransaction, UnlockCondition, EXTRA_DATA_LIMIT_SIZE};
 use crate::api::XSWDNodeMethodHandler;
 use crate::cipher::Cipher;
 use crate::config::{PASSWORD_ALGORITHM, PASSWORD_HASH_SIZE, SALT_SIZE};
 use crate::mnemonics;
 use crate::network_handler::{NetworkHandler, SharedNetworkHandler, NetworkError};
 use crate::storage::{EncryptedStorage, Storage};
-use crate::transaction_builder::TransactionBuilder;
+use crate::transaction_builder::{TransactionBuilder, total_spent};
 use chacha20poly1305::{aead::OsRng, Error as CryptoError};
 use rand::RngCore;
 use thiserror::Error;
@@ -88,6 +88,16 @@ pub enum WalletError {
     ExtraDataTooBig(usize, usize),
     #[error("Wallet is not in online mode")]
     NotOnlineMode,
+    #[error("Transaction {} was not sent by this wallet", _0)]
+    TxNotOwned(Hash),
+    #[error("Transaction {} is not pending anymore", _0)]
+    TxNotPending(Hash),
+    #[error("Transaction {} requires the signatures of the multisig participants", _0)]
+    TxRequiresMultiSig(Hash),
+    #[error("Daemon did not return any fee estimate")]
+    NoFeeEstimate,
+    #[error("Fee target must be resolved through the daemon before building the transaction")]
+    UnresolvedFeeTarget,
     #[error("Wallet is already in online mode")]
     AlreadyOnlineMode,
     #[error("Asset is already present on disk")]
@@ -352,7 +362,8 @@ impl Wallet {
     // create a transfer from the wallet to the given address to send the given amount of the given asset
     // and include extra data if present
     // TODO encrypt all the extra data for the receiver
-    pub fn create_transfer(&self, storage: &EncryptedStorage, asset: Hash, key: PublicKey, extra_data: Option<DataElement>, amount: u64) -> Result<Transfer, Error> {
+    // funds can be locked for the receiver until the unlock condition is reached
+    pub fn create_transfer(&self, storage: &EncryptedStorage, asset: Hash, key: PublicKey, extra_data: Option<DataElement>, amount: u64, unlock: Option<UnlockCondition>) -> Result<Transfer, Error> {
         let balance = storage.get_balance_for(&asset).unwrap_or(0);
         // check if we have enough funds for this asset
         if amount > balance {
@@ -383,22 +394,87 @@ impl Wallet {
             amount,
             asset,
             to: key,
-            extra_data
+            extra_data,
+            unlock
         };
         Ok(transfer)
     }
 
     // create the final transaction with calculated fees and signature
     // also check that we have enough funds for the transaction
-    pub fn create_transaction(&self, storage: &EncryptedStorage, transaction_type: TransactionType, fee: FeeBuilder) -> Result<Transaction, Error> {
+    // an expiring transaction can be safely rebuilt once the chain is above valid_until_topoheight
+    // a fee target must be resolved before using resolve_fee_builder
+    // locked balances are fetched using get_locked_balances and can't be spent
+    pub fn create_transaction(&self, storage: &EncryptedStorage, transaction_type: TransactionType, fee: FeeBuilder, locked: &HashMap<Hash, u64>, valid_until_topoheight: Option<u64>) -> Result<Transaction, Error> {
         let nonce = storage.get_nonce().unwrap_or(0);
-        let builder = TransactionBuilder::new(self.keypair.get_public_key().clone(), transaction_type, nonce, fee);
+        let builder = TransactionBuilder::new(self.keypair.get_public_key().clone(), transaction_type, nonce, fee, valid_until_topoheight);
+        self.verify_funds(storage, &builder, builder.estimate_fees(), locked)?;
+
+        Ok(builder.build(&self.keypair)?)
+    }
+
+    // create a transaction for this multisig account, to be signed by threshold participants
+    // the returned transaction can be exchanged serialized and combined with the other partially signed ones
+    pub fn create_unsigned_transaction(&self, storage: &EncryptedStorage, transaction_type: TransactionType, fee: FeeBuilder, threshold: u8, locked: &HashMap<Hash, u64>, valid_until_topoheight: Option<u64>) -> Result<UnsignedTransaction, Error> {
+        let nonce = storage.get_nonce().unwrap_or(0);
+        let builder = TransactionBuilder::new(self.keypair.get_public_key().clone(), transaction_type, nonce, fee, valid_until_topoheight);
+        self.verify_funds(storage, &builder, builder.estimate_multisig_fees(threshold), locked)?;
+
+        Ok(builder.build_unsigned(threshold)?)
+    }
+
+    // sign as the owner a transaction which collected the signatures of its participants
+    pub fn finalize_unsigned_transaction(&self, transaction: UnsignedTransaction) -> Result<Transaction, WalletError> {
+        if transaction.get_owner() != self.keypair.get_public_key() {
+            return Err(WalletError::InvalidKeyPair)
+        }
+
+        let tx = transaction.finalize(&self.keypair);
+        if !tx.verify_signature() {
+            return Err(WalletError::InvalidSignature)
+        }
+
+        Ok(tx)
+    }
+
+    // fee rate in atomic units per byte estimated by the daemon to be included within this count of blocks
+    // the estimate with the highest target not above the requested one is used, or the fastest one
+    pub async fn estimate_fee_rate(&self, blocks: u64) -> Result<f64, Error> {
+        let network_handler = self.network_handler.lock().await;
+        let api = network_handler.as_ref().ok_or(WalletError::NotOnlineMode)?.get_api();
+        let result = api.estimate_fee().await?;
+        let estimate = result.estimates.iter()
+            .filter(|estimate| estimate.blocks <= blocks)
+            .max_by_key(|estimate| estimate.blocks)
+            .or_else(|| result.estimates.iter().min_by_key(|estimate| estimate.blocks))
+            .ok_or(WalletError::NoFeeEstimate)?;
+
+        Ok(estimate.fee_rate)
+    }
+
+    // replace a fee target by a multiplier of the minimum fee, which is paid per started KB so the estimated fee rate is always reached
+    // it requests the daemon, so call it before locking the storage to create the transaction
+    pub async fn resolve_fee_builder(&self, fee: FeeBuilder) -> Result<FeeBuilder, Error> {
+        match fee {
+            FeeBuilder::Target(blocks) => {
+                let fee_rate = self.estimate_fee_rate(blocks).await?;
+                let multiplier = (fee_rate * 1024f64 / FEE_PER_KB as f64).max(1f64);
+                debug!("Estimated fee multiplier for a target of {} blocks: {}", blocks, multiplier);
+                Ok(FeeBuilder::Multiplier(multiplier))
+            },
+            fee => Ok(fee)
+        }
+    }
+
+    // verify that we have enough balance for every assets spent and the fees
+    // the locked part of a balance is not spendable
+    fn verify_funds(&self, storage: &EncryptedStorage, builder: &TransactionBuilder, fees: u64, locked: &HashMap<Hash, u64>) -> Result<(), Error> {
         let assets_spent: HashMap<&Hash, u64> = builder.total_spent();
 
         // check that we have enough balance for every assets spent
         for (asset, amount) in &assets_spent {
             let asset: &Hash = *asset;
-            let balance = storage.get_balance_for(asset).unwrap_or(0);
+            let balance = storage.get_balance_for(asset).unwrap_or(0).saturating_sub(*locked.get(asset).unwrap_or(&0));
             if balance < *amount {
                 let decimals = storage.get_asset_decimals(asset).unwrap_or(COIN_DECIMALS);
                 return Err(WalletError::NotEnoughFunds(balance, *amount, decimals, asset.clone()).into())
@@ -406,13 +482,86 @@ impl Wallet {
         }
 
         // now we have to check that we have enough funds for spent + fees
-        let total_native_spent = assets_spent.get(&XELIS_ASSET).unwrap_or(&0) +  builder.estimate_fees();
-        let native_balance = storage.get_balance_for(&XELIS_ASSET).unwrap_or(0);
+        let total_native_spent = assets_spent.get(&XELIS_ASSET).unwrap_or(&0) + fees;
+        let native_balance = storage.get_balance_for(&XELIS_ASSET).unwrap_or(0).saturating_sub(*locked.get(&XELIS_ASSET).unwrap_or(&0));
         if total_native_spent > native_balance {
             return Err(WalletError::NotEnoughFundsForFee(native_balance, total_native_spent).into())
         }
 
-        Ok(builder.build(&self.keypair)?)
+        Ok(())
+    }
+
+    // amount of an asset received through locked transfers that can't be spent yet
+    // it is included in the balance and requires the online mode
+    pub async fn get_locked_balance(&self, asset: &Hash) -> Result<u64, WalletError> {
+        let network_handler = self.network_handler.lock().await;
+        if let Some(network_handler) = network_handler.as_ref() {
+            let result = network_handler.get_api().get_locked_balance(&self.get_address(), asset).await?;
+            Ok(result.locked)
+        } else {
+            Err(WalletError::NotOnlineMode)
+        }
+    }
+
+    // locked balances of the assets spent by a transaction and of the native asset for its fees
+    // nothing is locked for the wallet in offline mode, call it before locking the storage
+    pub async fn get_locked_balances(&self, transaction_type: &TransactionType) -> Result<HashMap<Hash, u64>, Error> {
+        let mut locked = HashMap::new();
+        if !self.is_online().await {
+            return Ok(locked)
+        }
+
+        let mut assets: Vec<&Hash> = total_spent(transaction_type).into_keys().collect();
+        if !assets.contains(&&XELIS_ASSET) {
+            assets.push(&XELIS_ASSET);
+        }
+
+        for asset in assets {
+            let amount = self.get_locked_balance(asset).await?;
+            locked.insert(asset.clone(), amount);
+        }
+
+        Ok(locked)
+    }
+
+    // rebuild a pending transaction with the same nonce and enough fees to replace it in mempool
+    // the new transaction is submitted to the network and returned
+    pub async fn bump_fee(&self, hash: &Hash) -> Result<Transaction, Error> {
+        let (tx, min_fee_bump) = {
+            let network_handler = self.network_handler.lock().await;
+            let api = network_handler.as_ref().ok_or(WalletError::NotOnlineMode)?.get_api();
+            let tx = api.get_transaction(hash).await?;
+            if tx.get_owner() != self.keypair.get_public_key() {
+                return Err(WalletError::TxNotOwned(hash.clone()).into())
+            }
+
+            if tx.get_multisig().is_some() {
+                return Err(WalletError::TxRequiresMultiSig(hash.clone()).into())
+            }
+
+            // its nonce is already used on chain
+            let nonce = api.get_last_nonce(&self.get_address()).await?.version.get_nonce();
+            if tx.get_nonce() < nonce {
+                return Err(WalletError::TxNotPending(hash.clone()).into())
+            }
+
+            // the replacement must follow the policy of the daemon it is submitted to
+            let min_fee_bump = api.get_info().await?.rbf_min_fee_bump;
+            (tx, min_fee_bump)
+        };
+
+        let fee = calculate_replacement_fee(tx.get_fee(), min_fee_bump);
+        debug!("Bumping fee of TX {} from {} to {}", hash, format_xelis(tx.get_fee()), format_xelis(fee));
+        let builder = TransactionBuilder::new(self.keypair.get_public_key().clone(), tx.get_data().clone(), tx.get_nonce(), FeeBuilder::Value(fee), tx.get_valid_until_topoheight());
+        let locked = self.get_locked_balances(tx.get_data()).await?;
+        {
+            let storage = self.storage.read().await;
+            self.verify_funds(&storage, &builder, fee, &locked)?;
+        }
+
+        let replacement = builder.build(&self.keypair)?;
+        self.submit_transaction(&replacement).await?;
+        Ok(replacement)
     }
 
     // submit a transaction to the network through the connection to daemon
@@ -422,7 +571,9 @@ impl Wallet {
         if let Some(network_handler) = network_handler.as_ref() {
             network_handler.get_api().submit_transaction(transaction).await?;
             let mut storage = self.storage.write().await;
-            storage.set_nonce(transaction.get_nonce() + 1)?;
+            // a replacement TX doesn't move the nonce backward
+            let nonce = storage.get_nonce().unwrap_or(0).max(transaction.get_nonce() + 1);
+            storage.set_nonce(nonce)?;
             Ok(())
         } else {
             Err(WalletError::NotOnlineMode)